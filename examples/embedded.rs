@@ -0,0 +1,49 @@
+//! Serves the httpbox endpoints below `/httpbox` of a hyper server of its
+//! own, answering everything else itself
+use httpbox::http::Body;
+use hyper::body::Incoming;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use tokio::net::TcpListener;
+use tower::ServiceExt;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let app = httpbox::app();
+    let listener = TcpListener::bind(("127.0.0.1", 3000)).await?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let app = app.clone();
+        let service = service_fn(move |mut req: Request<Incoming>| {
+            let app = app.clone();
+            async move {
+                let path = req.uri().path();
+                let rest = match path.strip_prefix("/httpbox") {
+                    Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                        rest.to_owned()
+                    }
+                    _ => {
+                        return Ok(Response::new(Body::from(
+                            "Try /httpbox/get\n",
+                        )))
+                    }
+                };
+                let query = req
+                    .uri()
+                    .query()
+                    .map_or_else(String::new, |query| format!("?{}", query));
+                let rest = if rest.is_empty() { "/".into() } else { rest };
+                *req.uri_mut() = format!("{}{}", rest, query).parse()?;
+                app.oneshot(req).await
+            }
+        });
+
+        tokio::spawn(async move {
+            let _ = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
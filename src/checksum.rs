@@ -0,0 +1,116 @@
+//! Digests of bodies, for clients to check what they received
+//!
+//! CRC32C is implemented here, being too small to bring in a crate for.
+use serde_derive::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// The reflected Castagnoli polynomial
+const CRC32C_POLYNOMIAL: u32 = 0x82f6_3b78;
+
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ CRC32C_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Sha256,
+    Crc32c,
+}
+
+impl Algorithm {
+    /// The name in the HTTP Digest Algorithm Values registry
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha-256",
+            Self::Crc32c => "crc32c",
+        }
+    }
+
+    pub fn hasher(&self) -> Hasher {
+        match self {
+            Self::Sha256 => Hasher::Sha256(Sha256::new()),
+            Self::Crc32c => Hasher::Crc32c(!0),
+        }
+    }
+}
+
+/// A digest computed a chunk at a time
+#[derive(Clone)]
+pub enum Hasher {
+    Sha256(Sha256),
+    Crc32c(u32),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(hash) => hash.update(data),
+            Self::Crc32c(crc) => {
+                for byte in data {
+                    let index = (*crc ^ u32::from(*byte)) as u8;
+                    *crc = (*crc >> 8) ^ CRC32C_TABLE[usize::from(index)];
+                }
+            }
+        }
+    }
+
+    /// The digest of everything passed to `update`, a CRC32C being four
+    /// big-endian bytes
+    pub fn finish(self) -> Vec<u8> {
+        match self {
+            Self::Sha256(hash) => hash.finalize().to_vec(),
+            Self::Crc32c(crc) => (!crc).to_be_bytes().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn digest(algorithm: Algorithm, chunks: &[&[u8]]) -> Vec<u8> {
+        let mut hasher = algorithm.hasher();
+        for chunk in chunks {
+            hasher.update(chunk);
+        }
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(
+            digest(Algorithm::Crc32c, &[b"123456789"]),
+            0xe306_9283_u32.to_be_bytes()
+        );
+        assert_eq!(
+            digest(Algorithm::Crc32c, &[b"1234", b"56789"]),
+            digest(Algorithm::Crc32c, &[b"123456789"])
+        );
+        assert_eq!(digest(Algorithm::Crc32c, &[]), [0; 4]);
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            digest(Algorithm::Sha256, &[b"a", b"bc"]),
+            Sha256::digest(b"abc").to_vec()
+        );
+    }
+}
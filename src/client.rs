@@ -0,0 +1,202 @@
+//! Outbound HTTP requests made on behalf of a client, which keep off the
+//! network of the server unless told otherwise
+use crate::http::Body;
+use anyhow::{anyhow, bail};
+use hyper::header;
+use hyper::http::request::Builder;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use lazy_static::lazy_static;
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{
+    Certificate, ClientConfig, RootCertStore, ServerName,
+};
+use tokio_rustls::TlsConnector;
+use url::Url;
+
+const USER_AGENT: &str = concat!("httpbox/", env!("CARGO_PKG_VERSION"));
+
+lazy_static! {
+    static ref TLS: TlsConnector = {
+        let mut roots = RootCertStore::empty();
+        for cert in rustls_native_certs::load_native_certs().unwrap_or_default()
+        {
+            let _ = roots.add(&Certificate(cert.0));
+        }
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    };
+}
+
+/// The host resolved to no public address, and private ones are not
+/// allowed
+#[derive(Debug)]
+pub struct NotPublic(String);
+
+impl fmt::Display for NotPublic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is not a public address", self.0)
+    }
+}
+
+impl std::error::Error for NotPublic {}
+
+/// Whether `ip` is reachable from the internet, and not on the network of
+/// the server
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            let shared = a == 100 && (b & 0xc0) == 64;
+            let protocol = a == 192 && b == 0 && c == 0;
+            let benchmarking = a == 198 && (b & 0xfe) == 18;
+            let reserved = (a & 0xf0) == 240;
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || shared
+                || protocol
+                || benchmarking
+                || reserved)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let segments = ip.segments();
+                let first = segments[0];
+                let unique_local = (first & 0xfe00) == 0xfc00;
+                let link_local = (first & 0xffc0) == 0xfe80;
+                let site_local = (first & 0xffc0) == 0xfec0;
+                // Both lead on to an IPv4 address, which may be anything
+                let nat64 = segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0];
+                let six_to_four = first == 0x2002;
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || unique_local
+                    || link_local
+                    || site_local
+                    || nat64
+                    || six_to_four)
+            }
+        },
+    }
+}
+
+/// The host of `url`, without the brackets around an IPv6 address
+pub fn host(url: &Url) -> Option<&str> {
+    url.host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+}
+
+/// A request for `url`, naming its host and this server as the agent
+pub fn request(url: &Url) -> anyhow::Result<Builder> {
+    let host = url.host_str().ok_or_else(|| anyhow!("no host"))?;
+    let authority = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_owned(),
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_owned(),
+    };
+    Ok(Request::builder()
+        .uri(path)
+        .header(header::HOST, authority)
+        .header(header::USER_AGENT, USER_AGENT))
+}
+
+async fn handshake<I>(
+    io: I,
+    req: Request<Body>,
+) -> anyhow::Result<Response<Body>>
+where
+    I: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(io)).await?;
+    tokio::spawn(connection);
+    Ok(sender.send_request(req).await?.map(Body::new))
+}
+
+/// Connects to an address the host of `url` resolves to, which has to be
+/// public unless `private` is allowed, and sends `req` over HTTP/1.1
+///
+/// Connecting to the address that was checked leaves no room for the name
+/// to resolve to another one in between.
+pub async fn send(
+    url: &Url,
+    req: Request<Body>,
+    private: bool,
+) -> anyhow::Result<Response<Body>> {
+    let host = host(url).ok_or_else(|| anyhow!("no host"))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addr = tokio::net::lookup_host((host, port))
+        .await?
+        .find(|addr: &SocketAddr| private || is_public(addr.ip()))
+        .ok_or_else(|| NotPublic(host.to_owned()))?;
+
+    let stream = TcpStream::connect(addr).await?;
+    match url.scheme() {
+        "https" => {
+            let name = ServerName::try_from(host)?;
+            handshake(TLS.connect(name, stream).await?, req).await
+        }
+        "http" => handshake(stream, req).await,
+        scheme => bail!("unsupported scheme {}", scheme),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["93.184.216.34", "2606:2800:220:1::1"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "192.168.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "192.0.0.8",
+            "198.19.0.1",
+            "240.0.0.1",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "fec0::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::7f00:1",
+            "2002:7f00:1::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_request() {
+        let url = Url::parse("http://[::1]:8080/a?b=c").unwrap();
+        let req = request(&url).unwrap().body(()).unwrap();
+
+        assert_eq!(req.uri(), "/a?b=c");
+        assert_eq!(req.headers()["host"], "[::1]:8080");
+        assert_eq!(host(&url), Some("::1"));
+    }
+}
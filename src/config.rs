@@ -0,0 +1,1121 @@
+//! Settings from the command line, the environment and a TOML config file
+//!
+//! Flags and environment variables win over the config file, which wins
+//! over the defaults. Every field is optional so the layers can be merged
+//! before the defaults are filled in by the accessors.
+use crate::http::{Cidr, ClientKey, ForwardingHeader, Limit, TrustedProxies};
+use crate::jwt::KeySet;
+use crate::middleware::{Chaos, ContinueMode, Cors, ExpectContinue, Faults};
+use crate::server::{self, TlsConfig};
+use clap::{Parser, ValueEnum};
+use clap_complete::Shell;
+use hyper::header::HeaderName;
+use hyper::Method;
+use serde_derive::Deserialize;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DEFAULT_HOST: &str = "0.0.0.0";
+pub const DEFAULT_PORT: u16 = 3000;
+pub const DEFAULT_ADMIN_HOST: &str = "127.0.0.1";
+pub const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(10);
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DEFAULT_HMAC_SECRET: &str = "httpbox";
+
+/// A set of endpoints that can be switched on or off together
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Group {
+    /// /ip, /user-agent and /headers
+    Inspection,
+    /// /get, /post, /patch, /put and /delete
+    Methods,
+    Anything,
+    Status,
+    Auth,
+    /// Response headers and caching
+    Response,
+    Redirects,
+    Cookies,
+    /// Generated, delayed and streamed data
+    Dynamic,
+    /// Broken connections and responses, like /abort, /reset, /malformed
+    /// and /slow-headers
+    Chaos,
+    /// The pre-encoded gzip, deflate and brotli bodies
+    Compression,
+    Images,
+    /// /html, /xml, /json, /robots.txt, /deny and /encoding/utf8
+    Fixtures,
+    /// Request bins at /bin, capturing requests for later inspection
+    Bins,
+    /// Requests to other servers, at /callback and /fetch
+    Outbound,
+    Websocket,
+    /// Prometheus metrics at /metrics
+    Metrics,
+    /// Health and readiness probes at /healthz and /readyz
+    Health,
+    /// Runtime controls, at /admin/health and /admin/ready
+    Admin,
+}
+
+impl Group {
+    pub const ALL: [Group; 19] = [
+        Self::Inspection,
+        Self::Methods,
+        Self::Anything,
+        Self::Status,
+        Self::Auth,
+        Self::Response,
+        Self::Redirects,
+        Self::Cookies,
+        Self::Dynamic,
+        Self::Chaos,
+        Self::Compression,
+        Self::Images,
+        Self::Fixtures,
+        Self::Bins,
+        Self::Outbound,
+        Self::Websocket,
+        Self::Metrics,
+        Self::Health,
+        Self::Admin,
+    ];
+
+    /// The name of the group, as given to `--endpoints` and tagging its routes
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Inspection => "inspection",
+            Self::Methods => "methods",
+            Self::Anything => "anything",
+            Self::Status => "status",
+            Self::Auth => "auth",
+            Self::Response => "response",
+            Self::Redirects => "redirects",
+            Self::Cookies => "cookies",
+            Self::Dynamic => "dynamic",
+            Self::Chaos => "chaos",
+            Self::Compression => "compression",
+            Self::Images => "images",
+            Self::Fixtures => "fixtures",
+            Self::Bins => "bins",
+            Self::Outbound => "outbound",
+            Self::Websocket => "websocket",
+            Self::Metrics => "metrics",
+            Self::Health => "health",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+/// How log events are written to stdout
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// One line per event
+    #[default]
+    Text,
+    /// Multiple lines per event, for reading by humans
+    Pretty,
+    /// One JSON object per event
+    Json,
+}
+
+/// How errors without a body of their own are rendered
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorFormat {
+    /// The message, if any, as plain text
+    #[default]
+    Text,
+    /// A `{status, error, message}` object
+    Json,
+    /// A minimal page
+    Html,
+}
+
+#[derive(Parser, Clone, Debug, Default, Deserialize)]
+#[command(author, version, about, long_about = None, disable_help_flag = true)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    #[arg(
+        long,
+        env = "HTTPBOX_CONFIG",
+        help = "TOML file to read settings from"
+    )]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        env,
+        help = "Host address to listen on [default: 0.0.0.0]"
+    )]
+    pub host: Option<String>,
+
+    #[arg(short, long, env, help = "Port to listen on [default: 3000]")]
+    pub port: Option<u16>,
+
+    #[arg(long, env, help = "Number of threads to process requests")]
+    pub threads: Option<NonZeroUsize>,
+
+    #[arg(
+        long,
+        env,
+        help = "Maximum request body size in bytes [default: 10485760]"
+    )]
+    pub max_body_size: Option<usize>,
+
+    #[arg(
+        long,
+        env,
+        help = "Longest delay in seconds /delay and /drip wait [default: 10]"
+    )]
+    pub max_delay: Option<u64>,
+
+    #[arg(
+        long,
+        env,
+        help = "Seconds a handler gets to respond before a 504, 0 for no \
+                limit [default: 30]"
+    )]
+    pub request_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        env,
+        help = "Seconds a streamed response may go without sending data, 0 \
+                for no limit [default: 60]"
+    )]
+    pub idle_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        env,
+        value_enum,
+        help = "How to answer requests expecting 100 Continue [default: send]"
+    )]
+    pub expect_continue: Option<ContinueMode>,
+
+    #[arg(
+        long,
+        env,
+        help = "Seconds to hold back 100 Continue for, before answering the \
+                way expect-continue says"
+    )]
+    pub continue_delay: Option<f64>,
+
+    #[arg(
+        long,
+        env,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Allow cross-origin requests and answer CORS preflights \
+                [default: true]"
+    )]
+    pub cors: Option<bool>,
+
+    #[arg(
+        long,
+        env,
+        value_delimiter = ',',
+        help = "Origins allowed to make cross-origin requests [default: any]"
+    )]
+    pub cors_origins: Vec<String>,
+
+    #[arg(
+        long,
+        env,
+        value_delimiter = ',',
+        help = "Methods allowed in cross-origin requests [default: those of \
+                the route]"
+    )]
+    pub cors_methods: Vec<String>,
+
+    #[arg(
+        long,
+        env,
+        value_delimiter = ',',
+        help = "Headers allowed in cross-origin requests [default: those \
+                asked for]"
+    )]
+    pub cors_headers: Vec<String>,
+
+    #[arg(
+        long,
+        env,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Allow cross-origin requests with credentials"
+    )]
+    pub cors_credentials: Option<bool>,
+
+    #[arg(long, env, help = "Seconds browsers may cache a CORS preflight")]
+    pub cors_max_age: Option<u64>,
+
+    #[arg(
+        long,
+        env,
+        help = "Requests handled at once before answering 503 [default: no \
+                limit]"
+    )]
+    pub max_concurrency: Option<NonZeroUsize>,
+
+    #[arg(
+        long,
+        env,
+        help = "Requests per second each client may make [default: no limit]"
+    )]
+    pub rate_limit: Option<f64>,
+
+    #[arg(
+        long,
+        env,
+        help = "Requests a client may make at once under the rate limit \
+                [default: the rate, rounded up]"
+    )]
+    pub rate_limit_burst: Option<u32>,
+
+    #[arg(
+        long,
+        env,
+        help = "Header telling clients apart for the rate limit [default: \
+                the client address]"
+    )]
+    pub rate_limit_key: Option<String>,
+
+    #[arg(
+        long,
+        env,
+        value_delimiter = ',',
+        help = "Proxy addresses or CIDRs trusted to report the client address"
+    )]
+    pub trusted_proxies: Vec<Cidr>,
+
+    #[arg(
+        long,
+        env,
+        value_enum,
+        help = "Header the trusted proxies report the client address in \
+                [default: x-forwarded-for]"
+    )]
+    pub trusted_header: Option<ForwardingHeader>,
+
+    #[arg(
+        long,
+        env,
+        help = "Faults to inject, like latency=0.2,delay=1,error=0.1,\
+                reset=0.05,truncate=0.05"
+    )]
+    pub chaos: Option<Faults>,
+
+    #[arg(
+        long,
+        env,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Let requests choose their faults with an X-Chaos header"
+    )]
+    pub chaos_header: Option<bool>,
+
+    #[arg(long, env, help = "PEM certificate chain to serve HTTPS with")]
+    pub tls_cert: Option<PathBuf>,
+
+    #[arg(long, env, help = "PEM private key for the HTTPS certificate")]
+    pub tls_key: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env,
+        help = "PEM certificates of the CAs to ask HTTPS clients for \
+                certificates of, still taking clients without one"
+    )]
+    pub tls_client_ca: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env,
+        help = "JSON Web Key Set file to check /jwt signatures against, \
+                reread for every request"
+    )]
+    pub jwt_keys: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env,
+        help = "Secret to check /hmac signatures with [default: httpbox]"
+    )]
+    pub hmac_secret: Option<String>,
+
+    #[arg(
+        long,
+        env,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Accept cleartext HTTP/2, with prior knowledge or Upgrade: h2c"
+    )]
+    pub h2c: Option<bool>,
+
+    #[cfg(feature = "http3")]
+    #[arg(
+        long,
+        env,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Also serve HTTP/3 over UDP on the same port (experimental)"
+    )]
+    pub http3: Option<bool>,
+
+    #[cfg(feature = "grpc")]
+    #[arg(
+        long,
+        env,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Also serve the gRPC echo and reflection services, which take \
+                HTTP/2 over TLS or h2c"
+    )]
+    pub grpc: Option<bool>,
+
+    #[arg(
+        long,
+        env,
+        help = "Also listen on a Unix domain socket at this path"
+    )]
+    pub unix_socket: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Only listen on the Unix socket, not on TCP"
+    )]
+    pub unix_only: Option<bool>,
+
+    #[arg(
+        long,
+        env,
+        help = "Seconds in-flight requests get to finish on shutdown \
+                [default: 30]"
+    )]
+    pub drain_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        env,
+        help = "Seconds an HTTP/1.x connection may sit idle between \
+                requests before it is closed, 0 to close it after every \
+                response [default: no limit]"
+    )]
+    pub keep_alive_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        env,
+        help = "Port of a separate listener with runtime controls, over \
+                plaintext HTTP [default: none]"
+    )]
+    pub admin_port: Option<u16>,
+
+    #[arg(
+        long,
+        env,
+        help = "Host address the admin listener listens on [default: \
+                127.0.0.1]"
+    )]
+    pub admin_host: Option<String>,
+
+    #[arg(
+        long,
+        env,
+        value_enum,
+        value_delimiter = ',',
+        help = "Endpoint groups to serve [default: all of them]"
+    )]
+    pub endpoints: Vec<Group>,
+
+    #[arg(
+        long,
+        env,
+        value_enum,
+        value_delimiter = ',',
+        help = "Endpoint groups not to serve, even if given to endpoints, \
+                like chaos and outbound on a public instance"
+    )]
+    pub disable_endpoints: Vec<Group>,
+
+    #[arg(long, env, help = "Directory whose files are served below /static")]
+    pub static_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "List the entries of static directories without an index.html"
+    )]
+    pub static_listing: Option<bool>,
+
+    #[arg(
+        long,
+        env,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Serve a Swagger UI page of /openapi.json at /docs, which \
+                loads its scripts from unpkg.com"
+    )]
+    pub swagger_ui: Option<bool>,
+
+    #[arg(
+        long,
+        env,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Let /callback and /fetch call loopback and private network \
+                addresses"
+    )]
+    pub outbound_private: Option<bool>,
+
+    #[arg(
+        long,
+        env,
+        value_delimiter = ',',
+        help = "Hosts /fetch may get, *.example.com matching subdomains \
+                [default: any]"
+    )]
+    pub fetch_allow: Vec<String>,
+
+    #[arg(
+        long,
+        env,
+        value_delimiter = ',',
+        help = "Hosts /fetch may not get, even if allowed"
+    )]
+    pub fetch_deny: Vec<String>,
+
+    #[arg(
+        long,
+        env,
+        value_enum,
+        help = "How to write log events [default: text]"
+    )]
+    pub log_format: Option<LogFormat>,
+
+    #[arg(
+        long,
+        env,
+        value_enum,
+        help = "How to render errors [default: text]"
+    )]
+    pub error_format: Option<ErrorFormat>,
+
+    #[arg(long)]
+    #[serde(skip)]
+    pub completions: Option<Shell>,
+
+    #[arg(long, action = clap::ArgAction::Help, help = "Print help information")]
+    #[serde(skip)]
+    pub help: (),
+}
+
+impl Config {
+    /// Parses the command line and environment, layered over the config
+    /// file they point to
+    pub fn load() -> anyhow::Result<Self> {
+        let config = Self::parse();
+        let config = match config.config.clone() {
+            Some(path) => config.or(Self::from_file(&path)?),
+            None => config,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Cannot read {}: {}", path.display(), e)
+        })?;
+        Self::from_toml(&contents)
+            .map_err(|e| anyhow::anyhow!("Invalid {}: {}", path.display(), e))
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Fills in whatever is unset here from `other`
+    pub fn or(self, other: Self) -> Self {
+        fn or_vec<T>(vec: Vec<T>, other: Vec<T>) -> Vec<T> {
+            if vec.is_empty() {
+                other
+            } else {
+                vec
+            }
+        }
+
+        Self {
+            config: self.config.or(other.config),
+            host: self.host.or(other.host),
+            port: self.port.or(other.port),
+            threads: self.threads.or(other.threads),
+            max_body_size: self.max_body_size.or(other.max_body_size),
+            max_delay: self.max_delay.or(other.max_delay),
+            request_timeout: self.request_timeout.or(other.request_timeout),
+            idle_timeout: self.idle_timeout.or(other.idle_timeout),
+            expect_continue: self.expect_continue.or(other.expect_continue),
+            continue_delay: self.continue_delay.or(other.continue_delay),
+            cors: self.cors.or(other.cors),
+            cors_origins: or_vec(self.cors_origins, other.cors_origins),
+            cors_methods: or_vec(self.cors_methods, other.cors_methods),
+            cors_headers: or_vec(self.cors_headers, other.cors_headers),
+            cors_credentials: self.cors_credentials.or(other.cors_credentials),
+            cors_max_age: self.cors_max_age.or(other.cors_max_age),
+            max_concurrency: self.max_concurrency.or(other.max_concurrency),
+            rate_limit: self.rate_limit.or(other.rate_limit),
+            rate_limit_burst: self.rate_limit_burst.or(other.rate_limit_burst),
+            rate_limit_key: self.rate_limit_key.or(other.rate_limit_key),
+            trusted_proxies: or_vec(
+                self.trusted_proxies,
+                other.trusted_proxies,
+            ),
+            trusted_header: self.trusted_header.or(other.trusted_header),
+            chaos: self.chaos.or(other.chaos),
+            chaos_header: self.chaos_header.or(other.chaos_header),
+            tls_cert: self.tls_cert.or(other.tls_cert),
+            tls_key: self.tls_key.or(other.tls_key),
+            tls_client_ca: self.tls_client_ca.or(other.tls_client_ca),
+            jwt_keys: self.jwt_keys.or(other.jwt_keys),
+            hmac_secret: self.hmac_secret.or(other.hmac_secret),
+            h2c: self.h2c.or(other.h2c),
+            #[cfg(feature = "http3")]
+            http3: self.http3.or(other.http3),
+            #[cfg(feature = "grpc")]
+            grpc: self.grpc.or(other.grpc),
+            unix_socket: self.unix_socket.or(other.unix_socket),
+            unix_only: self.unix_only.or(other.unix_only),
+            drain_timeout: self.drain_timeout.or(other.drain_timeout),
+            keep_alive_timeout: self
+                .keep_alive_timeout
+                .or(other.keep_alive_timeout),
+            admin_port: self.admin_port.or(other.admin_port),
+            admin_host: self.admin_host.or(other.admin_host),
+            endpoints: or_vec(self.endpoints, other.endpoints),
+            disable_endpoints: or_vec(
+                self.disable_endpoints,
+                other.disable_endpoints,
+            ),
+            static_dir: self.static_dir.or(other.static_dir),
+            static_listing: self.static_listing.or(other.static_listing),
+            swagger_ui: self.swagger_ui.or(other.swagger_ui),
+            outbound_private: self.outbound_private.or(other.outbound_private),
+            fetch_allow: or_vec(self.fetch_allow, other.fetch_allow),
+            fetch_deny: or_vec(self.fetch_deny, other.fetch_deny),
+            log_format: self.log_format.or(other.log_format),
+            error_format: self.error_format.or(other.error_format),
+            completions: self.completions.or(other.completions),
+            help: (),
+        }
+    }
+
+    /// Checks the settings that only make sense together, which clap can't
+    /// do once the config file is involved
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            anyhow::bail!("tls-cert and tls-key have to be given together");
+        }
+        if self.tls_client_ca.is_some() && self.tls_cert.is_none() {
+            anyhow::bail!("tls-client-ca requires a TLS certificate");
+        }
+        self.jwt_keys()?;
+        if self.unix_only() && self.unix_socket.is_none() {
+            anyhow::bail!("unix-only requires a unix-socket");
+        }
+        if self.static_listing() && self.static_dir.is_none() {
+            anyhow::bail!("static-listing requires a static-dir");
+        }
+        if let Some(dir) = &self.static_dir {
+            if !dir.is_dir() {
+                anyhow::bail!(
+                    "static-dir {} is not a directory",
+                    dir.display()
+                );
+            }
+        }
+        for method in &self.cors_methods {
+            method.parse::<Method>().map_err(|_| {
+                anyhow::anyhow!("cors-methods has an invalid method {}", method)
+            })?;
+        }
+        for header in &self.cors_headers {
+            header.parse::<HeaderName>().map_err(|_| {
+                anyhow::anyhow!("cors-headers has an invalid header {}", header)
+            })?;
+        }
+        if self
+            .rate_limit
+            .is_some_and(|rate| !(rate > 0.0 && rate.is_finite()))
+        {
+            anyhow::bail!("rate-limit has to be a positive number");
+        }
+        if self
+            .continue_delay
+            .is_some_and(|delay| !(delay >= 0.0 && delay.is_finite()))
+        {
+            anyhow::bail!("continue-delay has to be a non-negative number");
+        }
+        if self.rate_limit_burst == Some(0) {
+            anyhow::bail!("rate-limit-burst has to be at least 1");
+        }
+        if let Some(key) = &self.rate_limit_key {
+            key.parse::<HeaderName>().map_err(|_| {
+                anyhow::anyhow!("rate-limit-key is not a header name")
+            })?;
+        }
+        if self.admin_host.is_some() && self.admin_port.is_none() {
+            anyhow::bail!("admin-host requires an admin-port");
+        }
+        if self.admin_port.is_some_and(|port| port == self.port()) {
+            anyhow::bail!("admin-port has to differ from the port");
+        }
+        #[cfg(feature = "grpc")]
+        if self.grpc() && !self.h2c() && self.tls_cert.is_none() {
+            anyhow::bail!("grpc requires h2c or a TLS certificate");
+        }
+        #[cfg(feature = "http3")]
+        if self.http3() && self.tls_cert.is_none() {
+            anyhow::bail!("http3 requires a TLS certificate");
+        }
+        Ok(())
+    }
+
+    pub fn host(&self) -> &str {
+        self.host.as_deref().unwrap_or(DEFAULT_HOST)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_PORT)
+    }
+
+    pub fn addr(&self) -> anyhow::Result<SocketAddr> {
+        resolve(self.host(), self.port())
+    }
+
+    pub fn admin(&self) -> bool {
+        self.admin_port.is_some()
+    }
+
+    /// Where the admin listener listens, if there is one
+    pub fn admin_addr(&self) -> anyhow::Result<Option<SocketAddr>> {
+        let host = self.admin_host.as_deref().unwrap_or(DEFAULT_ADMIN_HOST);
+        self.admin_port.map(|port| resolve(host, port)).transpose()
+    }
+
+    pub fn threads(&self) -> NonZeroUsize {
+        self.threads.unwrap_or_else(crate::num_cpus::num_cpus)
+    }
+
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE)
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+            .map_or(DEFAULT_MAX_DELAY, Duration::from_secs)
+    }
+
+    /// `None` when there is no limit
+    pub fn request_timeout(&self) -> Option<Duration> {
+        timeout(self.request_timeout, DEFAULT_REQUEST_TIMEOUT)
+    }
+
+    /// `None` when there is no limit
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        timeout(self.idle_timeout, DEFAULT_IDLE_TIMEOUT)
+    }
+
+    /// How to answer requests expecting `100 Continue`, unless hyper's
+    /// default of sending it once the body is read will do
+    pub fn expect_continue(&self) -> Option<ExpectContinue> {
+        let mode = self.expect_continue.unwrap_or_default();
+        let delay = self.continue_delay.unwrap_or_default();
+        (mode != ContinueMode::Send || delay > 0.0)
+            .then(|| ExpectContinue::new(mode, Duration::from_secs_f64(delay)))
+    }
+
+    /// The CORS policy, unless cross-origin requests are switched off
+    pub fn cors(&self) -> Option<Cors> {
+        if !self.cors.unwrap_or(true) {
+            return None;
+        }
+        Some(
+            Cors::new()
+                .origins(self.cors_origins.clone())
+                .methods(
+                    self.cors_methods
+                        .iter()
+                        .filter_map(|method| method.parse().ok())
+                        .collect(),
+                )
+                .headers(
+                    self.cors_headers
+                        .iter()
+                        .filter_map(|header| header.parse().ok())
+                        .collect(),
+                )
+                .credentials(self.cors_credentials.unwrap_or_default())
+                .max_age(self.cors_max_age.map(Duration::from_secs)),
+        )
+    }
+
+    pub fn max_concurrency(&self) -> Option<usize> {
+        self.max_concurrency.map(NonZeroUsize::get)
+    }
+
+    pub fn rate_limit(&self) -> Option<Limit> {
+        let rate = self.rate_limit?;
+        let burst = self.rate_limit_burst.unwrap_or(rate.ceil() as u32);
+        Some(Limit::new(rate, burst))
+    }
+
+    pub fn rate_limit_key(&self) -> ClientKey {
+        self.rate_limit_key
+            .as_deref()
+            .and_then(|key| key.parse().ok())
+            .map_or(ClientKey::Addr, ClientKey::Header)
+    }
+
+    pub fn trusted_proxies(&self) -> TrustedProxies {
+        TrustedProxies::new(self.trusted_proxies.clone())
+            .header(self.trusted_header.unwrap_or_default())
+    }
+
+    /// The failure injection, if any is configured or allowed
+    pub fn chaos(&self) -> Option<Chaos> {
+        let from_header = self.chaos_header.unwrap_or_default();
+        (self.chaos.is_some() || from_header)
+            .then(|| Chaos::new(self.chaos).header(from_header))
+    }
+
+    pub fn tls(&self) -> Option<TlsConfig> {
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Some(TlsConfig {
+                cert: cert.clone(),
+                key: key.clone(),
+                client_ca: self.tls_client_ca.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The keys of the `jwt-keys` file, read afresh so they can be rotated
+    /// without a restart
+    pub fn jwt_keys(&self) -> anyhow::Result<Option<KeySet>> {
+        self.jwt_keys.as_deref().map(KeySet::from_file).transpose()
+    }
+
+    pub fn hmac_secret(&self) -> &str {
+        self.hmac_secret.as_deref().unwrap_or(DEFAULT_HMAC_SECRET)
+    }
+
+    pub fn h2c(&self) -> bool {
+        self.h2c.unwrap_or_default()
+    }
+
+    #[cfg(feature = "http3")]
+    pub fn http3(&self) -> bool {
+        self.http3.unwrap_or_default()
+    }
+
+    #[cfg(feature = "grpc")]
+    pub fn grpc(&self) -> bool {
+        self.grpc.unwrap_or_default()
+    }
+
+    /// The port HTTP/3 is advertised on, if it is served at all
+    pub fn http3_port(&self) -> Option<u16> {
+        #[cfg(feature = "http3")]
+        return self.http3().then_some(self.port());
+        #[cfg(not(feature = "http3"))]
+        None
+    }
+
+    pub fn unix_only(&self) -> bool {
+        self.unix_only.unwrap_or_default()
+    }
+
+    pub fn drain_timeout(&self) -> Duration {
+        self.drain_timeout
+            .map_or(server::DRAIN_TIMEOUT, Duration::from_secs)
+    }
+
+    pub fn keep_alive_timeout(&self) -> Option<Duration> {
+        self.keep_alive_timeout.map(Duration::from_secs)
+    }
+
+    pub fn log_format(&self) -> LogFormat {
+        self.log_format.unwrap_or_default()
+    }
+
+    pub fn error_format(&self) -> ErrorFormat {
+        self.error_format.unwrap_or_default()
+    }
+
+    pub fn static_dir(&self) -> Option<&Path> {
+        self.static_dir.as_deref()
+    }
+
+    pub fn static_listing(&self) -> bool {
+        self.static_listing.unwrap_or_default()
+    }
+
+    pub fn swagger_ui(&self) -> bool {
+        self.swagger_ui.unwrap_or_default()
+    }
+
+    pub fn outbound_private(&self) -> bool {
+        self.outbound_private.unwrap_or_default()
+    }
+
+    /// Whether /fetch may get `host`, which has to be allowed, if any hosts
+    /// are, and not denied
+    ///
+    /// Names are compared without case or the dot that may end one.
+    pub fn fetch_allowed(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let matches = |pattern: &String| {
+            let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+            match pattern.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+                None => host == pattern,
+            }
+        };
+        (self.fetch_allow.is_empty() || self.fetch_allow.iter().any(matches))
+            && !self.fetch_deny.iter().any(matches)
+    }
+
+    pub fn enabled(&self, group: Group) -> bool {
+        (self.endpoints.is_empty() || self.endpoints.contains(&group))
+            && !self.disable_endpoints.contains(&group)
+    }
+
+    pub fn server_options(&self) -> server::Options {
+        server::Options {
+            tls: self.tls(),
+            h2c: self.h2c(),
+            #[cfg(feature = "http3")]
+            http3: self.http3(),
+            unix_socket: self.unix_socket.clone(),
+            unix_only: self.unix_only(),
+            drain_timeout: self.drain_timeout(),
+            keep_alive: self.keep_alive_timeout(),
+        }
+    }
+}
+
+/// Whole seconds where zero switches the timeout off
+fn timeout(seconds: Option<u64>, default: Duration) -> Option<Duration> {
+    match seconds {
+        Some(0) => None,
+        Some(seconds) => Some(Duration::from_secs(seconds)),
+        None => Some(default),
+    }
+}
+
+fn resolve(host: &str, port: u16) -> anyhow::Result<SocketAddr> {
+    (host, port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|iter| iter.last())
+        .ok_or_else(|| {
+            anyhow::anyhow!("Invalid listening address: {}:{}", host, port)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Config {
+        Config::try_parse_from(
+            std::iter::once("httpbox").chain(args.iter().copied()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::default();
+        assert_eq!(config.port(), DEFAULT_PORT);
+        assert_eq!(config.max_delay(), DEFAULT_MAX_DELAY);
+        assert_eq!(config.drain_timeout(), server::DRAIN_TIMEOUT);
+        assert_eq!(config.request_timeout(), Some(DEFAULT_REQUEST_TIMEOUT));
+        assert_eq!(config.idle_timeout(), Some(DEFAULT_IDLE_TIMEOUT));
+        assert!(config.cors().is_some());
+        assert_eq!(config.max_concurrency(), None);
+        assert_eq!(config.rate_limit(), None);
+        assert_eq!(config.rate_limit_key(), ClientKey::Addr);
+        assert_eq!(config.error_format(), ErrorFormat::Text);
+        assert!(config.tls().is_none());
+        assert!(Group::ALL.iter().all(|group| config.enabled(*group)));
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r#"
+            port = 8080
+            max-delay = 3
+            request-timeout = 0
+            idle-timeout = 5
+            trusted-proxies = ["10.0.0.0/8"]
+            trusted-header = "forwarded"
+            h2c = true
+            endpoints = ["methods", "dynamic"]
+            log-format = "json"
+            error-format = "html"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.port(), 8080);
+        assert_eq!(config.max_delay(), Duration::from_secs(3));
+        assert_eq!(config.request_timeout(), None);
+        assert_eq!(config.idle_timeout(), Some(Duration::from_secs(5)));
+        assert_eq!(
+            config.trusted_proxies(),
+            TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()])
+                .header(ForwardingHeader::Forwarded)
+        );
+        assert!(config.h2c());
+        assert!(config.enabled(Group::Dynamic));
+        assert!(!config.enabled(Group::Auth));
+        assert_eq!(config.log_format(), LogFormat::Json);
+        assert_eq!(config.error_format(), ErrorFormat::Html);
+    }
+
+    #[test]
+    fn test_from_toml_unknown_field() {
+        assert!(Config::from_toml("prot = 8080").is_err());
+    }
+
+    #[test]
+    fn test_flags_override_file() {
+        let file = Config::from_toml("port = 8080\nhost = \"::1\"").unwrap();
+        let config =
+            parse(&["--port", "9090", "--h2c", "--endpoints", "auth,status"])
+                .or(file);
+
+        assert_eq!(config.port(), 9090);
+        assert_eq!(config.host(), "::1");
+        assert!(config.h2c());
+        assert_eq!(config.endpoints, vec![Group::Auth, Group::Status]);
+    }
+
+    #[test]
+    fn test_disable_endpoints() {
+        let config = parse(&["--disable-endpoints", "chaos,outbound"]);
+
+        assert!(config.enabled(Group::Methods));
+        assert!(!config.enabled(Group::Chaos));
+        assert!(!config.enabled(Group::Outbound));
+
+        let config =
+            parse(&["--endpoints", "auth,chaos", "--disable-endpoints=chaos"]);
+        assert!(config.enabled(Group::Auth));
+        assert!(!config.enabled(Group::Chaos));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(parse(&["--tls-cert", "cert.pem"]).validate().is_err());
+        assert!(parse(&["--tls-client-ca", "ca.pem"]).validate().is_err());
+        assert!(parse(&["--unix-only"]).validate().is_err());
+        assert!(parse(&["--unix-only", "--unix-socket", "httpbox.sock"])
+            .validate()
+            .is_ok());
+        assert!(parse(&["--rate-limit", "0"]).validate().is_err());
+        assert!(parse(&["--cors-methods", "GET,P O S T"])
+            .validate()
+            .is_err());
+        assert!(parse(&["--rate-limit-key", "not a header"])
+            .validate()
+            .is_err());
+        assert!(parse(&["--admin-port", "3000"]).validate().is_err());
+        assert!(parse(&["--admin-host", "::1"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_admin_addr() {
+        assert_eq!(Config::default().admin_addr().unwrap(), None);
+        assert_eq!(
+            parse(&["--admin-port", "9000"]).admin_addr().unwrap(),
+            Some("127.0.0.1:9000".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_cors() {
+        assert!(parse(&["--cors=false"]).cors().is_none());
+        assert!(Config::from_toml("cors = false").unwrap().cors().is_none());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let config =
+            parse(&["--rate-limit", "2.5", "--rate-limit-key", "X-Key"]);
+        assert_eq!(config.rate_limit(), Some(Limit::new(2.5, 3)));
+        assert_eq!(
+            config.rate_limit_key(),
+            ClientKey::Header(HeaderName::from_static("x-key"))
+        );
+
+        let config =
+            parse(&["--rate-limit", "2.5", "--rate-limit-burst", "10"]);
+        assert_eq!(config.rate_limit(), Some(Limit::new(2.5, 10)));
+    }
+
+    #[test]
+    fn test_fetch_allowed() {
+        assert!(Config::default().fetch_allowed("example.com"));
+
+        let config = parse(&[
+            "--fetch-allow",
+            "example.com,*.example.org",
+            "--fetch-deny",
+            "private.example.org",
+        ]);
+        assert!(config.fetch_allowed("example.com"));
+        assert!(!config.fetch_allowed("www.example.com"));
+        assert!(config.fetch_allowed("www.EXAMPLE.org"));
+        assert!(!config.fetch_allowed("example.org"));
+        assert!(!config.fetch_allowed("badexample.org"));
+        assert!(!config.fetch_allowed("private.example.org"));
+        assert!(!config.fetch_allowed("private.example.org."));
+        assert!(!config.fetch_allowed("PRIVATE.Example.ORG"));
+        assert!(config.fetch_allowed("example.com."));
+        assert!(!config.fetch_allowed(".example.org"));
+    }
+}
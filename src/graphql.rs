@@ -0,0 +1,907 @@
+//! Parsing GraphQL executable documents, the operations and fragments
+//! clients send
+//!
+//! Only the syntax is checked, there being no schema to validate the
+//! documents against.
+use serde_json::{Map, Number, Value as Json};
+use std::fmt;
+
+/// Lists, objects and selection sets nest at most this deep, recursion
+/// bounding the parser's stack
+pub const MAX_DEPTH: usize = 64;
+
+/// Where a document stops making sense, counting from 1 like the
+/// `locations` of GraphQL errors
+#[derive(Debug, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}:{})", self.message, self.line, self.column)
+    }
+}
+
+type Result<T> = std::result::Result<T, SyntaxError>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperationKind {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+impl OperationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Query => "query",
+            Self::Mutation => "mutation",
+            Self::Subscription => "subscription",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Variable(String),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Null,
+    Enum(String),
+    List(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The value as JSON, with the variables filled in and enum values as
+    /// strings
+    pub fn to_json(&self, variables: &Map<String, Json>) -> Json {
+        match self {
+            Self::Variable(name) => {
+                variables.get(name).cloned().unwrap_or(Json::Null)
+            }
+            Self::Int(i) => (*i).into(),
+            Self::Float(f) => {
+                Number::from_f64(*f).map_or(Json::Null, Json::Number)
+            }
+            Self::String(s) | Self::Enum(s) => s.clone().into(),
+            Self::Boolean(b) => (*b).into(),
+            Self::Null => Json::Null,
+            Self::List(values) => {
+                values.iter().map(|v| v.to_json(variables)).collect()
+            }
+            Self::Object(fields) => Json::Object(
+                fields
+                    .iter()
+                    .map(|(name, v)| (name.clone(), v.to_json(variables)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Directive {
+    pub name: String,
+    pub arguments: Vec<(String, Value)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub alias: Option<String>,
+    pub name: String,
+    pub arguments: Vec<(String, Value)>,
+    pub directives: Vec<Directive>,
+    pub selections: Vec<Selection>,
+}
+
+impl Field {
+    /// The key of the field in the response
+    pub fn response_key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selection {
+    Field(Field),
+    FragmentSpread {
+        name: String,
+        directives: Vec<Directive>,
+    },
+    InlineFragment {
+        type_condition: Option<String>,
+        directives: Vec<Directive>,
+        selections: Vec<Selection>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariableDefinition {
+    pub name: String,
+    /// Such as `[ID!]!`
+    pub ty: String,
+    pub default: Option<Value>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operation {
+    pub kind: OperationKind,
+    pub name: Option<String>,
+    pub variables: Vec<VariableDefinition>,
+    pub directives: Vec<Directive>,
+    pub selections: Vec<Selection>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fragment {
+    pub name: String,
+    pub type_condition: String,
+    pub directives: Vec<Directive>,
+    pub selections: Vec<Selection>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    pub operations: Vec<Operation>,
+    pub fragments: Vec<Fragment>,
+}
+
+impl Document {
+    pub fn fragment(&self, name: &str) -> Option<&Fragment> {
+        self.fragments.iter().find(|fragment| fragment.name == name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// One of `!$&()[]{}:=@|`, or `.` for `...`
+    Punct(char),
+    Name(String),
+    Int(String),
+    Float(String),
+    String(String),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Punct('.') => f.write_str("\"...\""),
+            Self::Punct(c) => write!(f, "\"{}\"", c),
+            Self::Name(name) => write!(f, "Name \"{}\"", name),
+            Self::Int(i) | Self::Float(i) => write!(f, "Number \"{}\"", i),
+            Self::String(_) => f.write_str("String"),
+            Self::End => f.write_str("<EOF>"),
+        }
+    }
+}
+
+/// The position of byte `pos` of `source`
+fn error(source: &str, pos: usize, message: String) -> SyntaxError {
+    let before = &source[..pos];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    SyntaxError {
+        message,
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c == '_' || c.is_ascii_alphabetic()
+}
+
+fn is_name_continue(c: char) -> bool {
+    c == '_' || c.is_ascii_alphanumeric()
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, pos: usize, message: impl Into<String>) -> SyntaxError {
+        error(self.source, pos, message.into())
+    }
+
+    fn skip_ignored(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\n' | '\r' | ',' | '\u{feff}' => {
+                    self.bump();
+                }
+                '#' => {
+                    while !matches!(self.peek(), None | Some('\n' | '\r')) {
+                        self.bump();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// The next token and where it starts
+    fn next(&mut self) -> Result<(usize, Token)> {
+        self.skip_ignored();
+        let start = self.pos;
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Ok((start, Token::End)),
+        };
+        let token = match c {
+            '!' | '$' | '&' | '(' | ')' | '[' | ']' | '{' | '}' | ':' | '='
+            | '@' | '|' => {
+                self.bump();
+                Token::Punct(c)
+            }
+            '.' if self.rest().starts_with("...") => {
+                self.pos += 3;
+                Token::Punct('.')
+            }
+            c if is_name_start(c) => {
+                while self.peek().is_some_and(is_name_continue) {
+                    self.bump();
+                }
+                Token::Name(self.source[start..self.pos].to_owned())
+            }
+            '-' | '0'..='9' => self.number(start)?,
+            '"' if self.rest().starts_with("\"\"\"") => {
+                self.pos += 3;
+                Token::String(self.block_string(start)?)
+            }
+            '"' => {
+                self.bump();
+                Token::String(self.string(start)?)
+            }
+            c => {
+                return Err(self
+                    .error(start, format!("Unexpected character \"{}\"", c)))
+            }
+        };
+        Ok((start, token))
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+        }
+        self.pos - start
+    }
+
+    fn number(&mut self, start: usize) -> Result<Token> {
+        if self.peek() == Some('-') {
+            self.bump();
+        }
+        let int_start = self.pos;
+        match self.digits() {
+            0 => return Err(self.error(start, "Invalid number")),
+            n if n > 1 && self.source[int_start..].starts_with('0') => {
+                return Err(self.error(start, "Invalid number, unexpected 0"))
+            }
+            _ => {}
+        }
+        let mut float = false;
+        if self.peek() == Some('.') {
+            self.bump();
+            float = true;
+            if self.digits() == 0 {
+                return Err(self.error(start, "Invalid number"));
+            }
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.bump();
+            float = true;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.bump();
+            }
+            if self.digits() == 0 {
+                return Err(self.error(start, "Invalid number"));
+            }
+        }
+        if self.peek().is_some_and(|c| c == '.' || is_name_start(c)) {
+            return Err(self.error(self.pos, "Invalid number"));
+        }
+        let number = self.source[start..self.pos].to_owned();
+        Ok(if float {
+            Token::Float(number)
+        } else {
+            Token::Int(number)
+        })
+    }
+
+    fn string(&mut self, start: usize) -> Result<String> {
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n' | '\r') => {
+                    return Err(self.error(start, "Unterminated string"))
+                }
+                Some('"') => return Ok(value),
+                Some('\\') => {
+                    let escape = self.pos;
+                    value.push(match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode(escape)?,
+                        _ => {
+                            return Err(
+                                self.error(escape, "Invalid escape sequence")
+                            )
+                        }
+                    });
+                }
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    /// The four hex digits of a `\u` escape
+    fn code_unit(&mut self) -> Option<u16> {
+        let unit = u16::from_str_radix(self.rest().get(..4)?, 16).ok()?;
+        self.pos += 4;
+        Some(unit)
+    }
+
+    /// The character of a `\uXXXX` escape, joining surrogate pairs
+    fn unicode(&mut self, escape: usize) -> Result<char> {
+        let units = match self.code_unit() {
+            Some(high @ 0xd800..=0xdbff) if self.rest().starts_with("\\u") => {
+                self.pos += 2;
+                vec![high, self.code_unit().unwrap_or(0)]
+            }
+            Some(unit) => vec![unit],
+            None => vec![],
+        };
+        match char::decode_utf16(units).collect::<Vec<_>>()[..] {
+            [Ok(c)] => Ok(c),
+            _ => Err(self.error(escape, "Invalid Unicode escape sequence")),
+        }
+    }
+
+    /// A `"""` string, its common indentation and blank first and last
+    /// lines removed
+    fn block_string(&mut self, start: usize) -> Result<String> {
+        let mut raw = String::new();
+        loop {
+            if self.rest().starts_with("\"\"\"") {
+                self.pos += 3;
+                break;
+            }
+            if self.rest().starts_with("\\\"\"\"") {
+                self.pos += 4;
+                raw.push_str("\"\"\"");
+                continue;
+            }
+            match self.bump() {
+                Some(c) => raw.push(c),
+                None => return Err(self.error(start, "Unterminated string")),
+            }
+        }
+
+        let lines = raw
+            .split("\r\n")
+            .flat_map(|line| line.split(['\n', '\r']))
+            .collect::<Vec<_>>();
+        let indent = lines
+            .iter()
+            .skip(1)
+            .filter_map(|line| {
+                let trimmed = line.trim_start_matches([' ', '\t']);
+                Some(line.len() - trimmed.len()).filter(|_| !trimmed.is_empty())
+            })
+            .min()
+            .unwrap_or(0);
+        let mut lines = lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| match i {
+                0 => line,
+                _ => line.get(indent..).unwrap_or(""),
+            })
+            .collect::<Vec<_>>();
+        let blank = |line: &&str| line.trim_matches([' ', '\t']).is_empty();
+        while lines.first().is_some_and(blank) {
+            lines.remove(0);
+        }
+        while lines.last().is_some_and(blank) {
+            lines.pop();
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    token: Token,
+    start: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Result<Self> {
+        let mut lexer = Lexer { source, pos: 0 };
+        let (start, token) = lexer.next()?;
+        Ok(Self {
+            lexer,
+            token,
+            start,
+        })
+    }
+
+    fn advance(&mut self) -> Result<Token> {
+        let (start, token) = self.lexer.next()?;
+        self.start = start;
+        Ok(std::mem::replace(&mut self.token, token))
+    }
+
+    fn unexpected<T>(&self) -> Result<T> {
+        Err(error(
+            self.lexer.source,
+            self.start,
+            format!("Unexpected {}", self.token),
+        ))
+    }
+
+    fn is(&self, c: char) -> bool {
+        self.token == Token::Punct(c)
+    }
+
+    fn is_name(&self, name: &str) -> bool {
+        matches!(&self.token, Token::Name(found) if found == name)
+    }
+
+    fn eat(&mut self, c: char) -> Result<bool> {
+        let is = self.is(c);
+        if is {
+            self.advance()?;
+        }
+        Ok(is)
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c)? {
+            Ok(())
+        } else {
+            Err(error(
+                self.lexer.source,
+                self.start,
+                format!("Expected \"{}\", found {}", c, self.token),
+            ))
+        }
+    }
+
+    /// The depth one level below `depth`, failing past `MAX_DEPTH`
+    fn nested(&self, depth: usize) -> Result<usize> {
+        if depth >= MAX_DEPTH {
+            return Err(error(
+                self.lexer.source,
+                self.start,
+                format!("Nested over {} deep", MAX_DEPTH),
+            ));
+        }
+        Ok(depth + 1)
+    }
+
+    fn name(&mut self) -> Result<String> {
+        match &self.token {
+            Token::Name(_) => match self.advance()? {
+                Token::Name(name) => Ok(name),
+                _ => unreachable!(),
+            },
+            _ => Err(error(
+                self.lexer.source,
+                self.start,
+                format!("Expected Name, found {}", self.token),
+            )),
+        }
+    }
+
+    fn document(mut self) -> Result<Document> {
+        let mut document = Document::default();
+        loop {
+            if self.is('{') {
+                document.operations.push(Operation {
+                    kind: OperationKind::Query,
+                    name: None,
+                    variables: vec![],
+                    directives: vec![],
+                    selections: self.selection_set(0)?,
+                });
+                continue;
+            }
+            let kind = match &self.token {
+                Token::End if !document.operations.is_empty() => break,
+                Token::Name(name) => match name.as_str() {
+                    "query" => OperationKind::Query,
+                    "mutation" => OperationKind::Mutation,
+                    "subscription" => OperationKind::Subscription,
+                    "fragment" => {
+                        self.advance()?;
+                        let fragment = self.fragment()?;
+                        document.fragments.push(fragment);
+                        continue;
+                    }
+                    _ => return self.unexpected(),
+                },
+                _ => return self.unexpected(),
+            };
+            self.advance()?;
+            let name = match self.token {
+                Token::Name(_) => Some(self.name()?),
+                _ => None,
+            };
+            let variables = self.variable_definitions()?;
+            document.operations.push(Operation {
+                kind,
+                name,
+                variables,
+                directives: self.directives(false, 0)?,
+                selections: self.selection_set(0)?,
+            });
+        }
+        Ok(document)
+    }
+
+    fn fragment(&mut self) -> Result<Fragment> {
+        if self.is_name("on") {
+            return self.unexpected();
+        }
+        let name = self.name()?;
+        if !self.is_name("on") {
+            return self.unexpected();
+        }
+        self.advance()?;
+        Ok(Fragment {
+            name,
+            type_condition: self.name()?,
+            directives: self.directives(false, 0)?,
+            selections: self.selection_set(0)?,
+        })
+    }
+
+    fn variable_definitions(&mut self) -> Result<Vec<VariableDefinition>> {
+        let mut definitions = vec![];
+        if !self.eat('(')? {
+            return Ok(definitions);
+        }
+        loop {
+            self.expect('$')?;
+            let name = self.name()?;
+            self.expect(':')?;
+            let ty = self.ty(0)?;
+            let default = match self.eat('=')? {
+                true => Some(self.value(true, 0)?),
+                false => None,
+            };
+            self.directives(true, 0)?;
+            definitions.push(VariableDefinition { name, ty, default });
+            if self.eat(')')? {
+                return Ok(definitions);
+            }
+        }
+    }
+
+    fn ty(&mut self, depth: usize) -> Result<String> {
+        let mut ty = if self.is('[') {
+            let depth = self.nested(depth)?;
+            self.advance()?;
+            let ty = format!("[{}]", self.ty(depth)?);
+            self.expect(']')?;
+            ty
+        } else {
+            self.name()?
+        };
+        if self.eat('!')? {
+            ty.push('!');
+        }
+        Ok(ty)
+    }
+
+    fn directives(
+        &mut self,
+        constant: bool,
+        depth: usize,
+    ) -> Result<Vec<Directive>> {
+        let mut directives = vec![];
+        while self.eat('@')? {
+            directives.push(Directive {
+                name: self.name()?,
+                arguments: self.arguments(constant, depth)?,
+            });
+        }
+        Ok(directives)
+    }
+
+    fn arguments(
+        &mut self,
+        constant: bool,
+        depth: usize,
+    ) -> Result<Vec<(String, Value)>> {
+        let mut arguments = vec![];
+        if !self.eat('(')? {
+            return Ok(arguments);
+        }
+        loop {
+            let name = self.name()?;
+            self.expect(':')?;
+            arguments.push((name, self.value(constant, depth)?));
+            if self.eat(')')? {
+                return Ok(arguments);
+            }
+        }
+    }
+
+    fn value(&mut self, constant: bool, depth: usize) -> Result<Value> {
+        if self.is('$') && !constant {
+            self.advance()?;
+            return Ok(Value::Variable(self.name()?));
+        }
+        if self.is('[') {
+            let depth = self.nested(depth)?;
+            self.advance()?;
+            let mut values = vec![];
+            while !self.eat(']')? {
+                values.push(self.value(constant, depth)?);
+            }
+            return Ok(Value::List(values));
+        }
+        if self.is('{') {
+            let depth = self.nested(depth)?;
+            self.advance()?;
+            let mut fields = vec![];
+            while !self.eat('}')? {
+                let name = self.name()?;
+                self.expect(':')?;
+                fields.push((name, self.value(constant, depth)?));
+            }
+            return Ok(Value::Object(fields));
+        }
+        let value = match &self.token {
+            Token::Int(i) => match i.parse() {
+                Ok(i) => Value::Int(i),
+                Err(_) => Value::Float(i.parse().unwrap_or(f64::INFINITY)),
+            },
+            Token::Float(f) => Value::Float(f.parse().unwrap_or(f64::NAN)),
+            Token::String(s) => Value::String(s.clone()),
+            Token::Name(name) => match name.as_str() {
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                "null" => Value::Null,
+                _ => Value::Enum(name.clone()),
+            },
+            _ => return self.unexpected(),
+        };
+        self.advance()?;
+        Ok(value)
+    }
+
+    fn selection_set(&mut self, depth: usize) -> Result<Vec<Selection>> {
+        let depth = self.nested(depth)?;
+        self.expect('{')?;
+        let mut selections = vec![self.selection(depth)?];
+        while !self.eat('}')? {
+            selections.push(self.selection(depth)?);
+        }
+        Ok(selections)
+    }
+
+    fn selection(&mut self, depth: usize) -> Result<Selection> {
+        if self.eat('.')? {
+            if matches!(self.token, Token::Name(_)) && !self.is_name("on") {
+                return Ok(Selection::FragmentSpread {
+                    name: self.name()?,
+                    directives: self.directives(false, depth)?,
+                });
+            }
+            let type_condition = match self.is_name("on") {
+                true => {
+                    self.advance()?;
+                    Some(self.name()?)
+                }
+                false => None,
+            };
+            return Ok(Selection::InlineFragment {
+                type_condition,
+                directives: self.directives(false, depth)?,
+                selections: self.selection_set(depth)?,
+            });
+        }
+
+        let mut name = self.name()?;
+        let mut alias = None;
+        if self.eat(':')? {
+            alias = Some(name);
+            name = self.name()?;
+        }
+        Ok(Selection::Field(Field {
+            alias,
+            name,
+            arguments: self.arguments(false, depth)?,
+            directives: self.directives(false, depth)?,
+            selections: match self.is('{') {
+                true => self.selection_set(depth)?,
+                false => vec![],
+            },
+        }))
+    }
+}
+
+/// Parses an executable document, which has to have an operation
+pub fn parse(source: &str) -> Result<Document> {
+    Parser::new(source)?.document()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn field(selection: &Selection) -> &Field {
+        match selection {
+            Selection::Field(field) => field,
+            _ => panic!("not a field"),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let document = parse(
+            r#"
+            query Hero($episode: Episode = JEDI, $ids: [ID!]!) @cached {
+              hero(episode: $episode) {
+                name, friends: friendsConnection(first: 2.5e1) {
+                  ...Names
+                  ... on Droid @include(if: true) { primaryFunction }
+                }
+              }
+            }
+            fragment Names on Character { name # the name
+            }
+            "#,
+        )
+        .unwrap();
+
+        let operation = &document.operations[0];
+        assert_eq!(operation.kind, OperationKind::Query);
+        assert_eq!(operation.name.as_deref(), Some("Hero"));
+        assert_eq!(
+            operation.variables[1],
+            VariableDefinition {
+                name: "ids".to_owned(),
+                ty: "[ID!]!".to_owned(),
+                default: None,
+            }
+        );
+        assert_eq!(
+            operation.variables[0].default,
+            Some(Value::Enum("JEDI".to_owned()))
+        );
+        assert_eq!(operation.directives[0].name, "cached");
+
+        let hero = field(&operation.selections[0]);
+        assert_eq!(
+            hero.arguments,
+            vec![("episode".to_owned(), Value::Variable("episode".to_owned()))]
+        );
+        let friends = field(&hero.selections[1]);
+        assert_eq!(friends.response_key(), "friends");
+        assert_eq!(friends.arguments[0].1, Value::Float(25.0));
+        assert!(matches!(
+            &friends.selections[0],
+            Selection::FragmentSpread { name, .. } if name == "Names"
+        ));
+        assert!(matches!(
+            &friends.selections[1],
+            Selection::InlineFragment { type_condition: Some(ty), .. }
+                if ty == "Droid"
+        ));
+        assert_eq!(
+            document.fragment("Names").unwrap().type_condition,
+            "Character"
+        );
+    }
+
+    #[test]
+    fn test_parse_shorthand_and_values() {
+        let document = parse(
+            r#"{ a(s: "xé\n", b: """
+                  block
+                    string
+                """, l: [1 -2 null], o: {k: false}) }"#,
+        )
+        .unwrap();
+        let a = field(&document.operations[0].selections[0]);
+
+        assert_eq!(a.arguments[0].1, Value::String("xé\n".to_owned()));
+        assert_eq!(
+            a.arguments[1].1,
+            Value::String("block\n  string".to_owned())
+        );
+        assert_eq!(
+            a.arguments[2].1.to_json(&Map::new()),
+            serde_json::json!([1, -2, null])
+        );
+        assert_eq!(
+            a.arguments[3].1.to_json(&Map::new()),
+            serde_json::json!({"k": false})
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert_eq!(
+            parse("{\n  a(b: ) }"),
+            Err(SyntaxError {
+                message: "Unexpected \")\"".to_owned(),
+                line: 2,
+                column: 8,
+            })
+        );
+        assert_eq!(parse("").unwrap_err().message, "Unexpected <EOF>");
+        assert_eq!(parse("{ a }}").unwrap_err().message, "Unexpected \"}\"");
+        assert_eq!(
+            parse("type Query { a: Int }").unwrap_err().message,
+            "Unexpected Name \"type\""
+        );
+        assert_eq!(
+            parse("{ a(b: 01) }").unwrap_err().message,
+            "Invalid number, unexpected 0"
+        );
+        assert_eq!(
+            parse("{ a(b: \"open) }").unwrap_err().message,
+            "Unterminated string"
+        );
+        assert!(parse("query ($a: Int = $b) { a }").is_err());
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let nested = |open: &str, close: &str, depth| {
+            format!("{}{}", open.repeat(depth), close.repeat(depth))
+        };
+        let message = format!("Nested over {} deep", MAX_DEPTH);
+
+        // The selection set counts towards the depth of its arguments
+        let list = |depth| format!("{{ a(x: {}) }}", nested("[", "]", depth));
+        assert!(parse(&list(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(parse(&list(MAX_DEPTH)).unwrap_err().message, message);
+        assert_eq!(parse(&list(20_000)).unwrap_err().message, message);
+
+        let selections = |depth| nested("{ a ", "}", depth);
+        assert!(parse(&selections(MAX_DEPTH)).is_ok());
+        assert_eq!(parse(&selections(20_000)).unwrap_err().message, message);
+
+        let fragments = format!("{{ {} }}", nested("... { a ", "}", 20_000));
+        assert_eq!(parse(&fragments).unwrap_err().message, message);
+        let objects = format!("{{ a(x: {}) }}", nested("{b: ", "}", 20_000));
+        assert_eq!(parse(&objects).unwrap_err().message, message);
+        let ty = format!("query ($a: {}) {{ a }}", nested("[", "]", 20_000));
+        assert_eq!(parse(&ty).unwrap_err().message, message);
+    }
+}
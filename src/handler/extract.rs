@@ -0,0 +1,248 @@
+//! Handlers taking the parts of the request they need as arguments
+use super::Handler;
+use crate::http::{bad_request, Body, Bytes, Error, Request, Result};
+use crate::openapi::Parameter;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+/// A part of the request that a handler can take as an argument
+#[async_trait]
+pub trait FromRequest: Sized {
+    async fn from_request(
+        req: &mut Request,
+    ) -> std::result::Result<Self, Error>;
+
+    /// The query parameters taken, see `Handler::parameters`
+    fn parameters() -> Vec<Parameter> {
+        vec![]
+    }
+}
+
+/// The only parameter of the route, failing with 400 if it doesn't parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T: FromStr> FromRequest for Path<T> {
+    async fn from_request(
+        req: &mut Request,
+    ) -> std::result::Result<Self, Error> {
+        let mut values = req.path_params().values();
+        match (values.next(), values.next()) {
+            (Some(value), None) => {
+                value.parse().map(Path).map_err(|_| bad_request())
+            }
+            _ => Err(bad_request()),
+        }
+    }
+}
+
+/// All parameters of the route, deserialized by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned> FromRequest for Params<T> {
+    async fn from_request(
+        req: &mut Request,
+    ) -> std::result::Result<Self, Error> {
+        req.params().map(Params).ok_or_else(bad_request)
+    }
+}
+
+/// The query string, failing with 400 if it doesn't deserialize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned> FromRequest for Query<T> {
+    async fn from_request(
+        req: &mut Request,
+    ) -> std::result::Result<Self, Error> {
+        req.query()
+            .map(Query)
+            .map_err(|e| Error::bad_request(format!("invalid query: {}", e)))
+    }
+
+    fn parameters() -> Vec<Parameter> {
+        crate::openapi::parameters::<T>()
+    }
+}
+
+/// A JSON body, see `Request::json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned> FromRequest for Json<T> {
+    async fn from_request(
+        req: &mut Request,
+    ) -> std::result::Result<Self, Error> {
+        req.json().await.map(Json)
+    }
+}
+
+/// A form body, see `Request::form`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Form<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned> FromRequest for Form<T> {
+    async fn from_request(
+        req: &mut Request,
+    ) -> std::result::Result<Self, Error> {
+        req.form().await.map(Form)
+    }
+}
+
+/// The whole body, within the route's limit
+#[async_trait]
+impl FromRequest for Bytes {
+    async fn from_request(
+        req: &mut Request,
+    ) -> std::result::Result<Self, Error> {
+        req.bytes().await
+    }
+}
+
+/// The body as it streams in, leaving nothing for later arguments
+#[async_trait]
+impl FromRequest for Body {
+    async fn from_request(
+        req: &mut Request,
+    ) -> std::result::Result<Self, Error> {
+        Ok(req.body())
+    }
+}
+
+/// A handler from a function of extractors, see `extract`
+pub struct Extract<F, A> {
+    f: F,
+    args: PhantomData<fn() -> A>,
+}
+
+/// Installs `f` as a handler, extracting its arguments in order
+///
+/// ```ignore
+/// async fn bytes(Path(n): Path<u32>, Query(query): Query<Params>) -> Result
+/// Router::builder().install(extract(bytes), route(path!("bytes" / [n: u32])))
+/// ```
+pub fn extract<F, A>(f: F) -> Extract<F, A>
+where
+    Extract<F, A>: Handler,
+{
+    Extract {
+        f,
+        args: PhantomData,
+    }
+}
+
+macro_rules! extract_handler {
+    ($($arg:ident),+) => {
+        #[async_trait]
+        impl<F, Fut, $($arg),+> Handler for Extract<F, ($($arg,)+)>
+        where
+            F: Fn($($arg),+) -> Fut + Send + Sync,
+            Fut: Future<Output = Result> + Send + 'static,
+            $($arg: FromRequest + Send + 'static,)+
+        {
+            #[allow(non_snake_case)]
+            async fn handle(&self, mut req: Request) -> Result {
+                $(let $arg = $arg::from_request(&mut req).await?;)+
+                (self.f)($($arg),+).await
+            }
+
+            /// The extracting function, rather than its wrapper
+            fn name(&self) -> &'static str {
+                std::any::type_name::<F>()
+            }
+
+            fn parameters(&self) -> Vec<Parameter> {
+                let mut parameters = vec![];
+                $(parameters.extend($arg::parameters());)+
+                parameters
+            }
+        }
+    };
+}
+
+extract_handler!(A1);
+extract_handler!(A1, A2);
+extract_handler!(A1, A2, A3);
+extract_handler!(A1, A2, A3, A4);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{ok, StatusCode};
+    use crate::test::*;
+    use serde_derive::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Page {
+        page: u32,
+    }
+
+    async fn paged(Path(n): Path<u32>, Query(query): Query<Page>) -> Result {
+        ok(format!("{}/{}", n, query.page))
+    }
+
+    #[tokio::test]
+    async fn test_extract() {
+        let res = request()
+            .param("n", "4")
+            .path("/?page=2")
+            .handle(extract(paged))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.read_body_utf8().await.unwrap(), "4/2");
+    }
+
+    #[tokio::test]
+    async fn test_extract_rejected() {
+        let bad_param = request()
+            .param("n", "four")
+            .path("/?page=2")
+            .handle(extract(paged));
+        let bad_query = request()
+            .param("n", "4")
+            .path("/?page=two")
+            .handle(extract(paged));
+
+        assert_eq!(bad_param.await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(bad_query.await.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[derive(Deserialize)]
+    struct Credentials {
+        user: String,
+        passwd: String,
+    }
+
+    #[tokio::test]
+    async fn test_extract_params_and_body() {
+        let handler = |Params(credentials): Params<Credentials>,
+                       body: Bytes| async move {
+            ok(format!(
+                "{}:{} {}",
+                credentials.user,
+                credentials.passwd,
+                String::from_utf8_lossy(&body)
+            ))
+        };
+        let res = request()
+            .param("user", "user")
+            .param("passwd", "secret")
+            .body("data")
+            .handle(extract(handler))
+            .await
+            .unwrap();
+
+        assert_eq!(res.read_body_utf8().await.unwrap(), "user:secret data");
+    }
+}
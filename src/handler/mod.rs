@@ -0,0 +1,40 @@
+mod extract;
+
+pub use self::extract::*;
+
+use crate::http::{Request, Result};
+use crate::openapi::Parameter;
+use async_trait::async_trait;
+use std::future::Future;
+
+#[async_trait]
+pub trait Handler: Send {
+    async fn handle(&self, req: Request) -> Result;
+
+    /// What the handler is called in route listings, its type by default
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Describes the endpoint, for routes installed without a description
+    fn describe(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The query parameters the handler takes, for the OpenAPI document
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![]
+    }
+}
+
+#[async_trait]
+impl<F, Fut: 'static> Handler for F
+where
+    Fut: Future<Output = Result> + Send,
+    F: Fn(Request) -> Fut + Send + Sync,
+{
+    #[inline]
+    async fn handle(&self, req: Request) -> Result {
+        self(req).await
+    }
+}
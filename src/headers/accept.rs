@@ -0,0 +1,95 @@
+use crate::headers::{Error, Header, HeaderName, HeaderValue};
+use hyper::http::header;
+use std::iter;
+use std::str::FromStr;
+
+/// A q-valued list header, kept as sent since negotiation borrows from it,
+/// see `crate::http::negotiation`
+macro_rules! qualified_list {
+    ($(#[$doc:meta])* $name:ident, $header:ident) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct $name(String);
+
+        impl $name {
+            /// Every value, joined as if sent on one line
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Header for $name {
+            fn name() -> &'static HeaderName {
+                &header::$header
+            }
+
+            fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+            where
+                I: Iterator<Item = &'i HeaderValue>,
+            {
+                values
+                    .map(|value| value.to_str().map_err(|_| Error::invalid()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|values| $name(values.join(",")))
+            }
+
+            fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+                if let Ok(value) = HeaderValue::from_str(&self.0) {
+                    values.extend(iter::once(value))
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(list: &str) -> Result<Self, Self::Err> {
+                HeaderValue::from_str(list)
+                    .map(|_| $name(list.to_owned()))
+                    .map_err(|_| Error::invalid())
+            }
+        }
+    };
+}
+
+qualified_list!(
+    /// The media types a client takes, like `text/html;q=0.9, */*;q=0.1`
+    Accept,
+    ACCEPT
+);
+qualified_list!(
+    /// The content codings a client decodes, like `gzip, br;q=0.5`
+    AcceptEncoding,
+    ACCEPT_ENCODING
+);
+qualified_list!(
+    /// The languages a client reads, like `de-CH, en;q=0.5`
+    AcceptLanguage,
+    ACCEPT_LANGUAGE
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headers::HeaderMapExt;
+    use crate::test::headers::encode;
+    use hyper::http::HeaderMap;
+
+    #[test]
+    fn test_decode_joins_lines() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::ACCEPT, "text/html".parse().unwrap());
+        headers.append(header::ACCEPT, "*/*;q=0.1".parse().unwrap());
+
+        let accept = headers.typed_get::<Accept>().unwrap();
+        assert_eq!(accept.as_str(), "text/html,*/*;q=0.1");
+        assert!(headers.typed_get::<AcceptLanguage>().is_none());
+    }
+
+    #[test]
+    fn test_encode() {
+        let accept = "gzip, br;q=0.5".parse::<AcceptEncoding>().unwrap();
+        assert_eq!(encode(accept), "gzip, br;q=0.5");
+        assert!("de\n".parse::<AcceptLanguage>().is_err());
+    }
+}
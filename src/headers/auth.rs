@@ -1,3 +1,4 @@
+use super::digest::DigestChallenge;
 use crate::headers::{Error, Header, HeaderName, HeaderValue};
 use hyper::http::header;
 use std::fmt;
@@ -5,14 +6,61 @@ use std::iter;
 use std::str::FromStr;
 
 const BASIC_REALM_PREAMBLE: &str = "Basic realm=";
+const BEARER: &str = "Bearer";
 static WWW_AUTHENTICATE: &HeaderName = &header::WWW_AUTHENTICATE;
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct WWWAuthenticate(BasicRealm);
+pub struct WWWAuthenticate(Challenge);
 
 impl WWWAuthenticate {
     pub fn basic_realm(realm: &str) -> Self {
-        WWWAuthenticate(BasicRealm(realm.to_owned()))
+        WWWAuthenticate(Challenge::Basic(BasicRealm(realm.to_owned())))
+    }
+
+    pub fn bearer() -> Self {
+        WWWAuthenticate(Challenge::Bearer)
+    }
+
+    pub fn digest(challenge: DigestChallenge) -> Self {
+        WWWAuthenticate(Challenge::Digest(challenge))
+    }
+
+    #[cfg(test)]
+    pub fn digest_challenge(&self) -> Option<&DigestChallenge> {
+        match &self.0 {
+            Challenge::Digest(challenge) => Some(challenge),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Challenge {
+    Basic(BasicRealm),
+    Bearer,
+    Digest(DigestChallenge),
+}
+
+impl From<&Challenge> for HeaderValue {
+    fn from(challenge: &Challenge) -> Self {
+        match challenge {
+            Challenge::Basic(realm) => realm.into(),
+            Challenge::Bearer => HeaderValue::from_static(BEARER),
+            Challenge::Digest(digest) => format!("{}", digest).parse().unwrap(),
+        }
+    }
+}
+
+impl FromStr for Challenge {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Challenge, Error> {
+        if s == BEARER || s.starts_with("Bearer ") {
+            return Ok(Challenge::Bearer);
+        }
+        s.parse()
+            .map(Challenge::Basic)
+            .or_else(|_| s.parse().map(Challenge::Digest))
     }
 }
 
@@ -51,7 +99,12 @@ impl fmt::Display for BasicRealm {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.write_str(BASIC_REALM_PREAMBLE)?;
         f.write_str("\"")?;
-        f.write_str(&self.0)?;
+        for c in self.0.chars() {
+            if c == '"' || c == '\\' {
+                f.write_str("\\")?;
+            }
+            write!(f, "{}", c)?;
+        }
         f.write_str("\"")?;
         Ok(())
     }
@@ -61,13 +114,21 @@ impl FromStr for BasicRealm {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<BasicRealm, Error> {
-        if s.starts_with(BASIC_REALM_PREAMBLE) {
-            Ok(BasicRealm(
-                s[BASIC_REALM_PREAMBLE.len() + 1..s.len() - 1].to_owned(),
-            ))
-        } else {
-            Err(Error::invalid())
+        let quoted = s
+            .strip_prefix(BASIC_REALM_PREAMBLE)
+            .and_then(|rest| rest.strip_prefix('"')?.strip_suffix('"'))
+            .ok_or_else(Error::invalid)?;
+
+        let mut realm = String::with_capacity(quoted.len());
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => realm.push(chars.next().ok_or_else(Error::invalid)?),
+                '"' => return Err(Error::invalid()),
+                c => realm.push(c),
+            }
         }
+        Ok(BasicRealm(realm))
     }
 }
 
@@ -101,6 +162,20 @@ mod test {
             .is_err())
     }
 
+    #[test]
+    fn test_parse_err_basic_realm_unquoted() {
+        assert!("Basic realm=".parse::<BasicRealm>().is_err());
+        assert!("Basic realm=\"".parse::<BasicRealm>().is_err());
+        assert!("Basic realm=Test".parse::<BasicRealm>().is_err());
+    }
+
+    #[test]
+    fn test_basic_realm_escaping() {
+        let realm = BasicRealm(String::from("Say \"hi\""));
+        assert_eq!(format!("{}", realm), "Basic realm=\"Say \\\"hi\\\"\"");
+        assert_eq!(format!("{}", realm).parse::<BasicRealm>().unwrap(), realm);
+    }
+
     #[test]
     fn test_encode_www_authenticate() {
         assert_eq!(
@@ -123,6 +198,22 @@ mod test {
         assert_eq!(header, WWWAuthenticate::basic_realm("Test Realm"))
     }
 
+    #[test]
+    fn test_www_authenticate_bearer() {
+        assert_eq!(
+            encode(WWWAuthenticate::bearer()).to_str().unwrap(),
+            "Bearer"
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            WWWAuthenticate::name(),
+            "Bearer realm=\"example\"".parse().unwrap(),
+        );
+        let header = headers.typed_get::<WWWAuthenticate>().unwrap();
+        assert_eq!(header, WWWAuthenticate::bearer())
+    }
+
     #[test]
     fn test_decode_www_authenticate_invalid() {
         let mut headers = HeaderMap::new();
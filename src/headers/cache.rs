@@ -0,0 +1,155 @@
+use crate::headers::{Error, Header, HeaderName, HeaderValue, Vary};
+use hyper::http::header;
+use std::iter;
+use std::time::Duration;
+
+static SURROGATE_CONTROL: HeaderName =
+    HeaderName::from_static("surrogate-control");
+
+/// Seconds from the `name=seconds` directive among `directives`
+fn directive(directives: &str, name: &str) -> Option<Duration> {
+    directives
+        .split(',')
+        .filter_map(|directive| directive.trim().split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .and_then(|(_, seconds)| seconds.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+fn first_str<'i, I>(values: &mut I) -> Result<&'i str, Error>
+where
+    I: Iterator<Item = &'i HeaderValue>,
+{
+    values
+        .next()
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(Error::invalid)
+}
+
+/// `Cache-Control` with the RFC 5861 extensions, which `CacheControl`
+/// drops, letting caches serve a stale response while they revalidate it
+/// and in place of an error
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaleCacheControl {
+    pub max_age: Duration,
+    pub stale_while_revalidate: Duration,
+    pub stale_if_error: Duration,
+}
+
+impl Header for StaleCacheControl {
+    fn name() -> &'static HeaderName {
+        &header::CACHE_CONTROL
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let directives = first_str(values)?;
+        let get = |name| directive(directives, name).ok_or_else(Error::invalid);
+        Ok(Self {
+            max_age: get("max-age")?,
+            stale_while_revalidate: get("stale-while-revalidate")?,
+            stale_if_error: get("stale-if-error")?,
+        })
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        let value = format!(
+            "max-age={}, stale-while-revalidate={}, stale-if-error={}",
+            self.max_age.as_secs(),
+            self.stale_while_revalidate.as_secs(),
+            self.stale_if_error.as_secs()
+        );
+        values.extend(iter::once(value.parse().unwrap()))
+    }
+}
+
+/// How long a CDN may keep a response, which it strips before passing the
+/// response on, so that browsers go by `Cache-Control`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SurrogateControl {
+    pub max_age: Duration,
+}
+
+impl Header for SurrogateControl {
+    fn name() -> &'static HeaderName {
+        &SURROGATE_CONTROL
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        directive(first_str(values)?, "max-age")
+            .map(|max_age| Self { max_age })
+            .ok_or_else(Error::invalid)
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        let value = format!("max-age={}", self.max_age.as_secs());
+        values.extend(iter::once(value.parse().unwrap()))
+    }
+}
+
+/// `Vary` on `names`, which `Vary` itself can only be decoded into
+pub fn vary_on<'a, I>(names: I) -> Vary
+where
+    I: IntoIterator<Item = &'a HeaderName>,
+{
+    let names = names.into_iter().map(HeaderName::as_str);
+    let value = names.collect::<Vec<_>>().join(", ");
+    match HeaderValue::from_str(&value) {
+        Ok(value) if !value.is_empty() => Vary::decode(&mut iter::once(&value))
+            .unwrap_or_else(|_| Vary::any()),
+        _ => Vary::any(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headers::HeaderMapExt;
+    use crate::test::headers::encode;
+    use hyper::http::HeaderMap;
+
+    #[test]
+    fn test_stale_cache_control() {
+        let stale = StaleCacheControl {
+            max_age: Duration::from_secs(1),
+            stale_while_revalidate: Duration::from_secs(2),
+            stale_if_error: Duration::from_secs(3),
+        };
+        let value = encode(stale.clone());
+        assert_eq!(
+            value,
+            "max-age=1, stale-while-revalidate=2, stale-if-error=3"
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CACHE_CONTROL, value);
+        assert_eq!(headers.typed_get::<StaleCacheControl>(), Some(stale));
+    }
+
+    #[test]
+    fn test_surrogate_control() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            &SURROGATE_CONTROL,
+            "no-store, max-age=60".parse().unwrap(),
+        );
+
+        let surrogate = headers.typed_get::<SurrogateControl>().unwrap();
+        assert_eq!(surrogate.max_age, Duration::from_secs(60));
+        assert_eq!(encode(surrogate), "max-age=60");
+    }
+
+    #[test]
+    fn test_vary_on() {
+        let names = [header::ACCEPT, header::ACCEPT_LANGUAGE];
+        let vary = vary_on(&names);
+
+        assert_eq!(encode(vary), "accept, accept-language");
+        assert!(vary_on(&[]).is_any());
+    }
+}
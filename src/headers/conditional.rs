@@ -0,0 +1,158 @@
+use crate::headers::{
+    ETag, HeaderMapExt, IfMatch, IfModifiedSince, IfNoneMatch,
+    IfUnmodifiedSince,
+};
+use hyper::http::{HeaderMap, Method};
+use std::time::SystemTime;
+
+/// The outcome of evaluating conditional request headers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precondition {
+    /// Respond as usual
+    Passed,
+    /// The client's copy is current, respond with 304
+    NotModified,
+    /// Respond with 412
+    Failed,
+}
+
+fn if_match(headers: &HeaderMap, etag: Option<&ETag>) -> Option<bool> {
+    let header = headers.typed_get::<IfMatch>()?;
+    Some(match etag {
+        Some(etag) => header.precondition_passes(etag),
+        None => header.is_any(),
+    })
+}
+
+fn if_none_match(headers: &HeaderMap, etag: Option<&ETag>) -> Option<bool> {
+    let header = headers.typed_get::<IfNoneMatch>()?;
+    Some(match etag {
+        Some(etag) => header.precondition_passes(etag),
+        None => header != IfNoneMatch::any(),
+    })
+}
+
+/// Evaluates `If-Match`, `If-Unmodified-Since`, `If-None-Match` and
+/// `If-Modified-Since` against the current representation, in the order of
+/// RFC 7232 section 6
+pub fn evaluate_preconditions(
+    headers: &HeaderMap,
+    method: &Method,
+    etag: Option<&ETag>,
+    last_modified: Option<SystemTime>,
+) -> Precondition {
+    let safe = method == Method::GET || method == Method::HEAD;
+
+    match if_match(headers, etag) {
+        Some(false) => return Precondition::Failed,
+        Some(true) => {}
+        None => {
+            let unmodified = headers
+                .typed_get::<IfUnmodifiedSince>()
+                .zip(last_modified)
+                .map(|(header, time)| header.precondition_passes(time));
+            if unmodified == Some(false) {
+                return Precondition::Failed;
+            }
+        }
+    }
+
+    match if_none_match(headers, etag) {
+        Some(false) if safe => return Precondition::NotModified,
+        Some(false) => return Precondition::Failed,
+        Some(true) => {}
+        None if safe => {
+            let modified = headers
+                .typed_get::<IfModifiedSince>()
+                .zip(last_modified)
+                .map(|(header, time)| header.is_modified(time));
+            if modified == Some(false) {
+                return Precondition::NotModified;
+            }
+        }
+        None => {}
+    }
+
+    Precondition::Passed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn etag() -> ETag {
+        "\"abc\"".parse().unwrap()
+    }
+
+    fn headers(name: &'static str, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    fn evaluate(headers: &HeaderMap, method: Method) -> Precondition {
+        let last_modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        evaluate_preconditions(
+            headers,
+            &method,
+            Some(&etag()),
+            Some(last_modified),
+        )
+    }
+
+    #[test]
+    fn test_no_conditions() {
+        assert_eq!(
+            evaluate(&HeaderMap::new(), Method::GET),
+            Precondition::Passed
+        );
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let matching = headers("if-none-match", "\"xyz\", W/\"abc\"");
+        assert_eq!(evaluate(&matching, Method::GET), Precondition::NotModified);
+        assert_eq!(evaluate(&matching, Method::PUT), Precondition::Failed);
+
+        let other = headers("if-none-match", "\"xyz\"");
+        assert_eq!(evaluate(&other, Method::GET), Precondition::Passed);
+    }
+
+    #[test]
+    fn test_if_match() {
+        let matching = headers("if-match", "\"abc\"");
+        assert_eq!(evaluate(&matching, Method::PUT), Precondition::Passed);
+
+        let other = headers("if-match", "\"xyz\"");
+        assert_eq!(evaluate(&other, Method::PUT), Precondition::Failed);
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let after =
+            headers("if-modified-since", "Thu, 01 Jan 1970 01:00:00 GMT");
+        assert_eq!(evaluate(&after, Method::GET), Precondition::NotModified);
+
+        let before =
+            headers("if-modified-since", "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(evaluate(&before, Method::GET), Precondition::Passed);
+    }
+
+    #[test]
+    fn test_if_none_match_overrides_if_modified_since() {
+        let mut headers = headers("if-none-match", "\"xyz\"");
+        headers.insert(
+            "if-modified-since",
+            "Thu, 01 Jan 1970 01:00:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(evaluate(&headers, Method::GET), Precondition::Passed);
+    }
+
+    #[test]
+    fn test_if_unmodified_since() {
+        let before =
+            headers("if-unmodified-since", "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(evaluate(&before, Method::PUT), Precondition::Failed);
+    }
+}
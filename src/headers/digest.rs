@@ -0,0 +1,374 @@
+use crate::headers::authorization::Credentials;
+use crate::headers::{Error, HeaderValue};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+const DIGEST_PREAMBLE: &str = "Digest ";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Sha256 => "SHA-256",
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        if s.eq_ignore_ascii_case("MD5") {
+            Ok(Self::Md5)
+        } else if s.eq_ignore_ascii_case("SHA-256") {
+            Ok(Self::Sha256)
+        } else {
+            Err(Error::invalid())
+        }
+    }
+}
+
+/// The quality of protection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Qop {
+    Auth,
+    /// Also covers the request body
+    AuthInt,
+}
+
+impl Qop {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auth => "auth",
+            Self::AuthInt => "auth-int",
+        }
+    }
+}
+
+impl FromStr for Qop {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "auth" => Ok(Self::Auth),
+            "auth-int" => Ok(Self::AuthInt),
+            _ => Err(Error::invalid()),
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parses a comma separated list of `name=value` auth parameters, where
+/// values are either tokens or quoted strings
+fn parse_params(s: &str) -> Result<HashMap<String, String>, Error> {
+    let mut params = HashMap::new();
+    let mut chars = s.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        if chars.peek().is_none() {
+            return Ok(params);
+        }
+
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=') {
+            name.push(c);
+        }
+        if chars.next() != Some('=') {
+            return Err(Error::invalid());
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next().ok_or_else(Error::invalid)? {
+                    '"' => break,
+                    '\\' => {
+                        value.push(chars.next().ok_or_else(Error::invalid)?)
+                    }
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                value.push(c);
+            }
+            value.truncate(value.trim_end().len());
+        }
+
+        params.insert(name.trim().to_ascii_lowercase(), value);
+    }
+}
+
+fn digest_params(s: &str) -> Result<HashMap<String, String>, Error> {
+    let params = s
+        .get(..DIGEST_PREAMBLE.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(DIGEST_PREAMBLE))
+        .map(|_| &s[DIGEST_PREAMBLE.len()..])
+        .ok_or_else(Error::invalid)?;
+    parse_params(params)
+}
+
+/// The `WWW-Authenticate` challenge for Digest authentication
+#[derive(Clone, Debug, PartialEq)]
+pub struct DigestChallenge {
+    pub realm: String,
+    pub qop: Qop,
+    pub algorithm: Algorithm,
+    pub nonce: String,
+    pub opaque: String,
+    /// The credentials were right but the nonce has expired
+    pub stale: bool,
+}
+
+impl fmt::Display for DigestChallenge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}realm={}, qop=\"{}\", algorithm={}, nonce={}, opaque={}",
+            DIGEST_PREAMBLE,
+            quote(&self.realm),
+            self.qop.as_str(),
+            self.algorithm.as_str(),
+            quote(&self.nonce),
+            quote(&self.opaque)
+        )?;
+        if self.stale {
+            f.write_str(", stale=true")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DigestChallenge {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut params = digest_params(s)?;
+        let mut take = |name| params.remove(name).ok_or_else(Error::invalid);
+
+        Ok(Self {
+            realm: take("realm")?,
+            qop: take("qop")?.parse()?,
+            algorithm: take("algorithm")
+                .map_or(Ok(Algorithm::default()), |a| a.parse())?,
+            nonce: take("nonce")?,
+            opaque: take("opaque")?,
+            stale: take("stale")
+                .is_ok_and(|stale| stale.eq_ignore_ascii_case("true")),
+        })
+    }
+}
+
+/// The `Authorization` credentials for Digest authentication
+#[derive(Clone, Debug, PartialEq)]
+pub struct Digest {
+    pub username: String,
+    pub realm: String,
+    pub uri: String,
+    pub algorithm: Algorithm,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    pub qop: Option<Qop>,
+    /// The nonce count, sent as 8 hex digits
+    pub nc: Option<u32>,
+    pub cnonce: Option<String>,
+    pub response: String,
+}
+
+impl FromStr for Digest {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut params = digest_params(s)?;
+        let mut take = |name| params.remove(name).ok_or_else(Error::invalid);
+
+        Ok(Self {
+            username: take("username")?,
+            realm: take("realm")?,
+            uri: take("uri")?,
+            algorithm: take("algorithm")
+                .map_or(Ok(Algorithm::default()), |a| a.parse())?,
+            nonce: take("nonce")?,
+            opaque: take("opaque").ok(),
+            qop: take("qop").ok().map(|qop| qop.parse()).transpose()?,
+            nc: take("nc")
+                .ok()
+                .map(|nc| u32::from_str_radix(&nc, 16))
+                .transpose()
+                .map_err(|_| Error::invalid())?,
+            cnonce: take("cnonce").ok(),
+            response: take("response")?,
+        })
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}username={}, realm={}, uri={}, algorithm={}, nonce={}",
+            DIGEST_PREAMBLE,
+            quote(&self.username),
+            quote(&self.realm),
+            quote(&self.uri),
+            self.algorithm.as_str(),
+            quote(&self.nonce)
+        )?;
+        if let Some(opaque) = &self.opaque {
+            write!(f, ", opaque={}", quote(opaque))?;
+        }
+        if let Some(qop) = self.qop {
+            write!(f, ", qop={}", qop.as_str())?;
+        }
+        if let Some(nc) = self.nc {
+            write!(f, ", nc={:08x}", nc)?;
+        }
+        if let Some(cnonce) = &self.cnonce {
+            write!(f, ", cnonce={}", quote(cnonce))?;
+        }
+        write!(f, ", response={}", quote(&self.response))
+    }
+}
+
+impl Credentials for Digest {
+    const SCHEME: &'static str = "Digest";
+
+    fn decode(value: &HeaderValue) -> Option<Self> {
+        value.to_str().ok()?.parse().ok()
+    }
+
+    fn encode(&self) -> HeaderValue {
+        self.to_string().parse().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headers::{Authorization, HeaderMapExt};
+    use hyper::http::HeaderMap;
+
+    fn challenge() -> DigestChallenge {
+        DigestChallenge {
+            realm: "Test Realm".to_owned(),
+            qop: Qop::Auth,
+            algorithm: Algorithm::Sha256,
+            nonce: "abc".to_owned(),
+            opaque: "def".to_owned(),
+            stale: false,
+        }
+    }
+
+    fn digest() -> Digest {
+        Digest {
+            username: "Mufasa".to_owned(),
+            realm: "Test Realm".to_owned(),
+            uri: "/dir/index.html".to_owned(),
+            algorithm: Algorithm::Md5,
+            nonce: "abc".to_owned(),
+            opaque: Some("def".to_owned()),
+            qop: Some(Qop::Auth),
+            nc: Some(1),
+            cnonce: Some("0a4f113b".to_owned()),
+            response: "6629fae49393a05397450978507c4ef1".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let params =
+            parse_params(r#"a=token, B="quoted, \"escaped\"",c = 1 "#).unwrap();
+        assert_eq!(params["a"], "token");
+        assert_eq!(params["b"], r#"quoted, "escaped""#);
+        assert_eq!(params["c"], "1");
+    }
+
+    #[test]
+    fn test_parse_params_unterminated() {
+        assert!(parse_params(r#"a="open"#).is_err());
+        assert!(parse_params("a").is_err());
+    }
+
+    #[test]
+    fn test_encode_challenge() {
+        assert_eq!(
+            format!("{}", challenge()),
+            "Digest realm=\"Test Realm\", qop=\"auth\", algorithm=SHA-256, \
+             nonce=\"abc\", opaque=\"def\""
+        );
+
+        let stale = DigestChallenge {
+            stale: true,
+            ..challenge()
+        };
+        assert!(format!("{}", stale).ends_with(", stale=true"));
+    }
+
+    #[test]
+    fn test_challenge_roundtrip() {
+        let stale = DigestChallenge {
+            stale: true,
+            ..challenge()
+        };
+        assert_eq!(
+            format!("{}", stale).parse::<DigestChallenge>().unwrap(),
+            stale
+        );
+    }
+
+    #[test]
+    fn test_decode_authorization() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "authorization",
+            "Digest username=\"Mufasa\", realm=\"Test Realm\", \
+             uri=\"/dir/index.html\", nonce=\"abc\", opaque=\"def\", \
+             qop=auth, nc=00000001, cnonce=\"0a4f113b\", \
+             response=\"6629fae49393a05397450978507c4ef1\""
+                .parse()
+                .unwrap(),
+        );
+
+        let header = headers.typed_get::<Authorization<Digest>>().unwrap();
+        assert_eq!(header.0, digest());
+    }
+
+    #[test]
+    fn test_authorization_roundtrip() {
+        let mut headers = HeaderMap::new();
+        headers.typed_insert(Authorization(digest()));
+
+        let header = headers.typed_get::<Authorization<Digest>>().unwrap();
+        assert_eq!(header.0, digest());
+    }
+
+    #[test]
+    fn test_decode_authorization_missing_response() {
+        assert!("Digest username=\"Mufasa\", realm=\"r\", uri=\"/\", \
+                 nonce=\"abc\""
+            .parse::<Digest>()
+            .is_err());
+    }
+}
@@ -29,6 +29,14 @@ impl XForwardedFor {
     pub fn ip_addr(&self) -> IpAddr {
         self.client
     }
+
+    /// Every hop, from the client to the proxy nearest to us
+    pub fn chain(&self) -> Vec<Option<IpAddr>> {
+        iter::once(self.client)
+            .chain(self.proxies.iter().copied())
+            .map(Some)
+            .collect()
+    }
 }
 
 impl Header for XForwardedFor {
@@ -76,9 +84,82 @@ impl From<&XForwardedFor> for HeaderValue {
     }
 }
 
+/// The `for` nodes of the standard `Forwarded` header (RFC 7239)
+///
+/// A node is `None` when a proxy didn't disclose the address, as with
+/// `unknown` or an obfuscated identifier.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Forwarded {
+    pub nodes: Vec<Option<IpAddr>>,
+}
+
+impl Forwarded {
+    /// Every hop, from the client to the proxy nearest to us
+    pub fn chain(&self) -> Vec<Option<IpAddr>> {
+        self.nodes.clone()
+    }
+}
+
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim_matches('"');
+    if let Some(bracketed) = node.strip_prefix('[') {
+        let (ip, _) = bracketed.split_once(']')?;
+        return ip.parse().ok();
+    }
+    node.parse().ok().or_else(|| {
+        let (ip, _port) = node.rsplit_once(':')?;
+        ip.parse().ok()
+    })
+}
+
+impl Header for Forwarded {
+    fn name() -> &'static HeaderName {
+        &hyper::header::FORWARDED
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+    where
+        Self: Sized,
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let mut nodes = vec![];
+        for value in values {
+            let value = value.to_str().map_err(|_| Error::invalid())?;
+            for element in value.split(',') {
+                let node = element.split(';').find_map(|pair| {
+                    let (name, node) = pair.trim().split_once('=')?;
+                    Some(node).filter(|_| name.eq_ignore_ascii_case("for"))
+                });
+                if let Some(node) = node {
+                    nodes.push(parse_node(node));
+                }
+            }
+        }
+
+        if nodes.is_empty() {
+            return Err(Error::invalid());
+        }
+        Ok(Forwarded { nodes })
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        let value = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                Some(IpAddr::V6(ip)) => format!("for=\"[{}]\"", ip),
+                Some(ip) => format!("for={}", ip),
+                None => "for=unknown".to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        values.extend(iter::once(value.parse().unwrap()))
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::XForwardedFor;
+    use super::{Forwarded, XForwardedFor};
     use crate::headers::{Header, HeaderMapExt};
     use crate::test::headers::encode;
     use hyper::http::HeaderMap;
@@ -172,4 +253,51 @@ mod test {
         assert_eq!(location.client, ip_addr);
         assert_eq!(location.proxies.first(), Some(&proxy));
     }
+
+    #[test]
+    fn test_decode_forwarded() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            Forwarded::name(),
+            "for=192.0.2.60;proto=http;by=203.0.113.43, \
+             For=\"[2001:db8:cafe::17]:4711\", for=unknown, \
+             for=198.51.100.17:80"
+                .parse()
+                .unwrap(),
+        );
+
+        let forwarded = headers.typed_get::<Forwarded>().unwrap();
+        assert_eq!(
+            forwarded.nodes,
+            vec![
+                Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 60))),
+                Some("2001:db8:cafe::17".parse().unwrap()),
+                None,
+                Some(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 17))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forwarded_roundtrip() {
+        let forwarded = Forwarded {
+            nodes: vec![Some("::1".parse().unwrap()), None],
+        };
+        assert_eq!(
+            encode(forwarded.clone()).to_str().unwrap(),
+            "for=\"[::1]\", for=unknown"
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.typed_insert(forwarded.clone());
+        assert_eq!(headers.typed_get::<Forwarded>().unwrap(), forwarded);
+    }
+
+    #[test]
+    fn test_decode_forwarded_without_for() {
+        let mut headers = HeaderMap::new();
+        headers.insert(Forwarded::name(), "proto=https".parse().unwrap());
+
+        assert!(headers.typed_get::<Forwarded>().is_none());
+    }
 }
@@ -0,0 +1,72 @@
+use crate::headers::{Error, Header, HeaderName, HeaderValue};
+use std::iter;
+use std::time::Duration;
+
+static KEEP_ALIVE: HeaderName = HeaderName::from_static("keep-alive");
+
+/// How long an idle HTTP/1.x connection is kept open, and for how many
+/// more requests
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeepAlive {
+    pub timeout: Duration,
+    pub max: Option<u64>,
+}
+
+impl Header for KeepAlive {
+    fn name() -> &'static HeaderName {
+        &KEEP_ALIVE
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let value = values
+            .next()
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(Error::invalid)?;
+        let (mut timeout, mut max) = (None, None);
+        for param in value.split(',') {
+            match param.trim().split_once('=') {
+                Some(("timeout", seconds)) => {
+                    timeout = seconds.parse().ok().map(Duration::from_secs)
+                }
+                Some(("max", n)) => max = n.parse().ok(),
+                _ => {}
+            }
+        }
+        timeout
+            .map(|timeout| Self { timeout, max })
+            .ok_or_else(Error::invalid)
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        let mut value = format!("timeout={}", self.timeout.as_secs());
+        if let Some(max) = self.max {
+            value.push_str(&format!(", max={}", max));
+        }
+        values.extend(iter::once(value.parse().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headers::HeaderMapExt;
+    use crate::test::headers::encode;
+    use hyper::http::HeaderMap;
+
+    #[test]
+    fn test_keep_alive() {
+        let keep_alive = KeepAlive {
+            timeout: Duration::from_secs(5),
+            max: Some(100),
+        };
+        let value = encode(keep_alive.clone());
+        assert_eq!(value, "timeout=5, max=100");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(&KEEP_ALIVE, value);
+        assert_eq!(headers.typed_get::<KeepAlive>(), Some(keep_alive));
+    }
+}
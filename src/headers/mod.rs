@@ -1,14 +1,29 @@
+mod accept;
 mod auth;
+mod cache;
+mod conditional;
 mod cookie;
+mod digest;
 mod ip;
+mod keep_alive;
 mod location;
+mod repr_digest;
 
+pub use self::accept::*;
 pub use self::auth::*;
+pub use self::cache::*;
+pub use self::conditional::*;
+// The globs re-export whatever the modules gain beyond the explicit names
+#[allow(unused_imports)]
 pub use self::cookie::*;
 pub use self::cookie::{Cookie, SetCookie}; // Needed to de-conflict glob import from headers;
+pub use self::digest::*;
 pub use self::ip::*;
+pub use self::keep_alive::KeepAlive;
 pub use self::location::Location; // Needed to de-conflict glob import from headers;
+#[allow(unused_imports)]
 pub use self::location::*;
+pub use self::repr_digest::ReprDigest;
 pub use headers::*;
 
 pub mod authorization {
@@ -0,0 +1,84 @@
+use crate::checksum::Algorithm;
+use crate::headers::{Error, Header, HeaderName, HeaderValue};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::iter;
+
+static REPR_DIGEST: HeaderName = HeaderName::from_static("repr-digest");
+
+/// The digest of a representation, before any content coding, per RFC 9530
+///
+/// Only the first digest of an algorithm we know is decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReprDigest {
+    pub algorithm: Algorithm,
+    pub digest: Vec<u8>,
+}
+
+impl ReprDigest {
+    /// The value as a structured field dictionary, such as
+    /// `sha-256=:base64:`
+    fn value(&self) -> String {
+        format!(
+            "{}=:{}:",
+            self.algorithm.as_str(),
+            STANDARD.encode(&self.digest)
+        )
+    }
+}
+
+impl Header for ReprDigest {
+    fn name() -> &'static HeaderName {
+        &REPR_DIGEST
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        values
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|member| member.trim().split_once('='))
+            .find_map(|(name, digest)| {
+                let algorithm = [Algorithm::Sha256, Algorithm::Crc32c]
+                    .iter()
+                    .copied()
+                    .find(|algorithm| algorithm.as_str() == name)?;
+                let digest = digest.strip_prefix(':')?.strip_suffix(':')?;
+                Some(Self {
+                    algorithm,
+                    digest: STANDARD.decode(digest).ok()?,
+                })
+            })
+            .ok_or_else(Error::invalid)
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        values.extend(iter::once(self.value().parse().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headers::HeaderMapExt;
+    use crate::test::headers::encode;
+    use hyper::http::HeaderMap;
+
+    #[test]
+    fn test_repr_digest() {
+        let digest = ReprDigest {
+            algorithm: Algorithm::Crc32c,
+            digest: vec![0xe3, 0x06, 0x92, 0x83],
+        };
+        assert_eq!(encode(digest.clone()), "crc32c=:4waSgw==:");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            &REPR_DIGEST,
+            "md5=:AAAA:, crc32c=:4waSgw==:".parse().unwrap(),
+        );
+        assert_eq!(headers.typed_get::<ReprDigest>(), Some(digest));
+    }
+}
@@ -0,0 +1,127 @@
+//! Letting a handler cut off the connection its request came in on
+use super::{Bytes, Request};
+use futures::prelude::*;
+use futures::stream::BoxStream;
+use hyper::Version;
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Bytes the connection writes as they come, in place of the response
+#[derive(Clone)]
+pub struct Replacement(Arc<Mutex<Option<BoxStream<'static, Bytes>>>>);
+
+impl Replacement {
+    pub fn new(bytes: Bytes) -> Self {
+        Self::stream(stream::once(future::ready(bytes)))
+    }
+
+    pub fn stream(bytes: impl Stream<Item = Bytes> + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Some(bytes.boxed()))))
+    }
+
+    /// The bytes, for the only connection to write them
+    pub fn take(&self) -> Option<BoxStream<'static, Bytes>> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+impl fmt::Debug for Replacement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Replacement")
+    }
+}
+
+/// How a connection is cut off
+#[derive(Clone, Debug)]
+pub enum Abort {
+    /// Closed without a word, as if the server had crashed
+    Close,
+    /// Reset, so the peer sees `ECONNRESET` on TCP
+    Reset,
+    /// Closed after writing these bytes as they are, in place of whatever
+    /// the server would have written next, for responses hyper refuses to
+    /// write
+    Replace(Replacement),
+}
+
+/// Shared between a connection and the requests on it, which refuses to
+/// read or write anything more once a handler has aborted it
+#[derive(Clone, Debug, Default)]
+pub struct Aborting(Arc<OnceLock<Abort>>);
+
+impl Aborting {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only the first abort counts
+    pub fn abort(&self, how: Abort) {
+        let _ = self.0.set(how);
+    }
+
+    pub fn get(&self) -> Option<&Abort> {
+        self.0.get()
+    }
+}
+
+impl Request {
+    /// Cuts off the connection of the request, which takes effect the next
+    /// time the server reads from or writes to it, and which takes every
+    /// other request on an HTTP/2 connection with it
+    ///
+    /// Returns whether there is a connection to cut, which there is not
+    /// outside a server or over HTTP/3.
+    pub fn abort(&self, how: Abort) -> bool {
+        match self.extensions().get::<Aborting>() {
+            Some(aborting) => {
+                aborting.abort(how);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Has the connection write `replacement` in place of the response,
+    /// then close
+    ///
+    /// Returns whether it will, which only HTTP/1.x connections can, as
+    /// HTTP/2 and HTTP/3 frame responses themselves.
+    pub fn replace_response(&self, replacement: Replacement) -> bool {
+        matches!(self.version(), Version::HTTP_10 | Version::HTTP_11)
+            && self.abort(Abort::Replace(replacement))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    #[test]
+    fn test_abort() {
+        let aborting = Aborting::new();
+        let req = request().extension(aborting.clone()).build();
+
+        assert!(aborting.get().is_none());
+        assert!(req.abort(Abort::Reset));
+        assert!(req.abort(Abort::Close));
+        assert!(matches!(aborting.get(), Some(Abort::Reset)));
+    }
+
+    #[test]
+    fn test_abort_without_server() {
+        assert!(!request().build().abort(Abort::Close));
+    }
+
+    #[test]
+    fn test_replace_response_http2() {
+        let aborting = Aborting::new();
+        let req = request()
+            .version(Version::HTTP_2)
+            .extension(aborting.clone())
+            .build();
+
+        assert!(!req.replace_response(Replacement::new(Bytes::new())));
+        assert!(aborting.get().is_none());
+    }
+}
@@ -0,0 +1,340 @@
+//! The body of requests and responses, whatever produces it
+use futures::prelude::*;
+use futures::stream::BoxStream;
+use http_body_util::{BodyExt, Empty, Full, StreamBody};
+use hyper::body::{Body as HttpBody, Bytes, Frame, SizeHint};
+use hyper::HeaderMap;
+use std::any::Any;
+use std::borrow::Cow;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use sync_wrapper::SyncStream;
+use tokio::sync::{mpsc, oneshot};
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Any body, boxed
+pub type BoxBody = http_body_util::combinators::BoxBody<Bytes, BoxError>;
+
+/// Chunks queued on a channel body before sending waits for them to go out
+const CHANNEL_CAPACITY: usize = 1;
+
+/// A body of requests and responses, and a stream of its chunks
+///
+/// Trailers come after the chunks, from `trailers` once the stream has
+/// ended.
+pub struct Body {
+    inner: BoxBody,
+    /// Trailers polled while looking for the next chunk
+    trailers: Option<HeaderMap>,
+}
+
+impl Body {
+    /// Boxes `body`, unless it is a `Body` already
+    pub fn new<B>(body: B) -> Self
+    where
+        B: HttpBody<Data = Bytes> + Send + Sync + 'static,
+        B::Error: Into<BoxError>,
+    {
+        let mut body = Some(body);
+        if let Some(body) =
+            (&mut body as &mut dyn Any).downcast_mut::<Option<Self>>()
+        {
+            return body.take().unwrap_or_default();
+        }
+        let body = body.expect("only a Body is taken");
+        Self {
+            inner: body.map_err(Into::into).boxed(),
+            trailers: None,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(Empty::new())
+    }
+
+    /// A body of the chunks of `stream`, which ends it when it fails
+    pub fn wrap_stream<S, O, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<BoxError> + 'static,
+    {
+        Self::wrap_frames(stream.map_ok(|chunk| Frame::data(chunk.into())))
+    }
+
+    /// A body of the frames of `stream`, trailers included, which ends it
+    /// when it fails
+    pub fn wrap_frames<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Frame<Bytes>, E>> + Send + 'static,
+        E: Into<BoxError> + 'static,
+    {
+        let frames = stream.err_into::<BoxError>().fuse();
+        Self::new(StreamBody::new(SyncStream::new(frames)))
+    }
+
+    /// A body of the chunks of this one run through `f`, followed by its
+    /// trailers
+    pub fn map_chunks<F, S>(self, f: F) -> Self
+    where
+        F: FnOnce(BoxStream<'static, Result<Bytes, BoxError>>) -> S,
+        S: Stream<Item = Result<Bytes, BoxError>> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let chunks =
+            stream::unfold((self, Some(tx)), |(mut body, tx)| async move {
+                if let Some(chunk) = body.data().await {
+                    return Some((chunk, (body, tx)));
+                }
+                match (body.trailers().await, tx) {
+                    (Ok(Some(trailers)), Some(tx)) => {
+                        let _ = tx.send(trailers);
+                        None
+                    }
+                    (Err(e), _) => Some((Err(e), (body, None))),
+                    _ => None,
+                }
+            });
+        let trailers = rx.into_stream().filter_map(|trailers| {
+            future::ready(trailers.ok().map(|t| Ok(Frame::trailers(t))))
+        });
+        Self::wrap_frames(f(chunks.boxed()).map_ok(Frame::data).chain(trailers))
+    }
+
+    /// A body sent chunk by chunk, and its trailers, through the sender
+    pub fn channel() -> (Sender, Self) {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let frames = stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|frame| (frame, rx))
+        })
+        .fuse();
+        (
+            Sender(tx),
+            Self::new(StreamBody::new(SyncStream::new(frames))),
+        )
+    }
+
+    /// The next chunk, if the body has more
+    pub async fn data(&mut self) -> Option<Result<Bytes, BoxError>> {
+        futures::StreamExt::next(self).await
+    }
+
+    /// The trailers, once the chunks have been read
+    pub async fn trailers(&mut self) -> Result<Option<HeaderMap>, BoxError> {
+        while self.trailers.is_none() {
+            match self.inner.frame().await.transpose()? {
+                Some(frame) => self.trailers = frame.into_trailers().ok(),
+                None => return Ok(None),
+            }
+        }
+        Ok(self.trailers.take())
+    }
+
+    /// The chunks of the body, as a boxed stream
+    pub fn into_stream(self) -> BoxStream<'static, Result<Bytes, BoxError>> {
+        futures::StreamExt::boxed(self)
+    }
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl std::fmt::Debug for Body {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Body").finish_non_exhaustive()
+    }
+}
+
+impl HttpBody for Body {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, BoxError>>> {
+        if let Some(trailers) = self.trailers.take() {
+            return Poll::Ready(Some(Ok(Frame::trailers(trailers))));
+        }
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.trailers.is_none() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Stream for Body {
+    type Item = Result<Bytes, BoxError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        if self.trailers.is_some() {
+            return Poll::Ready(None);
+        }
+        loop {
+            let frame = match ready!(Pin::new(&mut self.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+            match frame.into_data() {
+                Ok(data) if data.is_empty() => continue,
+                Ok(data) => return Poll::Ready(Some(Ok(data))),
+                Err(frame) => {
+                    if let Ok(trailers) = frame.into_trailers() {
+                        self.trailers = Some(trailers);
+                        return Poll::Ready(None);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl From<Bytes> for Body {
+    fn from(bytes: Bytes) -> Self {
+        Self::new(Full::new(bytes))
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytes::from(bytes).into()
+    }
+}
+
+impl From<&'static [u8]> for Body {
+    fn from(bytes: &'static [u8]) -> Self {
+        Bytes::from_static(bytes).into()
+    }
+}
+
+impl From<String> for Body {
+    fn from(text: String) -> Self {
+        Bytes::from(text).into()
+    }
+}
+
+impl From<&'static str> for Body {
+    fn from(text: &'static str) -> Self {
+        Bytes::from_static(text.as_bytes()).into()
+    }
+}
+
+impl From<Cow<'static, str>> for Body {
+    fn from(text: Cow<'static, str>) -> Self {
+        match text {
+            Cow::Borrowed(text) => text.into(),
+            Cow::Owned(text) => text.into(),
+        }
+    }
+}
+
+impl From<Cow<'static, [u8]>> for Body {
+    fn from(bytes: Cow<'static, [u8]>) -> Self {
+        match bytes {
+            Cow::Borrowed(bytes) => bytes.into(),
+            Cow::Owned(bytes) => bytes.into(),
+        }
+    }
+}
+
+/// The sending half of `Body::channel`, which ends the body when dropped
+pub struct Sender(mpsc::Sender<Result<Frame<Bytes>, BoxError>>);
+
+impl Sender {
+    /// Sends a chunk, once the previous ones have been taken, handing it
+    /// back if the body is gone
+    pub async fn send_data(&mut self, chunk: Bytes) -> Result<(), Bytes> {
+        match self.0.send(Ok(Frame::data(chunk))).await {
+            Err(mpsc::error::SendError(Ok(frame))) => {
+                Err(frame.into_data().unwrap_or_default())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends the trailers, which end the body
+    pub async fn send_trailers(
+        &mut self,
+        trailers: HeaderMap,
+    ) -> Result<(), HeaderMap> {
+        match self.0.send(Ok(Frame::trailers(trailers))).await {
+            Err(mpsc::error::SendError(Ok(frame))) => {
+                Err(frame.into_trailers().unwrap_or_default())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Ends the body with an error, so that it doesn't pass for complete
+    pub fn abort(self) {
+        let _ = self.0.try_send(Err("body aborted".into()));
+    }
+}
+
+/// All of `body`, once it has been read
+pub async fn to_bytes<B: HttpBody>(body: B) -> Result<Bytes, B::Error> {
+    Ok(body.collect().await?.to_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_chunks_then_trailers() {
+        let (mut sender, mut body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from("a")).await.unwrap();
+            sender.send_data(Bytes::from("b")).await.unwrap();
+            let mut trailers = HeaderMap::new();
+            trailers.insert("x-done", "1".parse().unwrap());
+            sender.send_trailers(trailers).await.unwrap();
+        });
+
+        assert_eq!(body.data().await.unwrap().unwrap(), "a");
+        assert_eq!(body.data().await.unwrap().unwrap(), "b");
+        assert!(body.data().await.is_none());
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["x-done"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_wrap_stream() {
+        let chunks = stream::iter(vec![Ok::<_, std::io::Error>("a"), Ok("b")]);
+        let body = Body::wrap_stream(chunks);
+
+        assert_eq!(to_bytes(body).await.unwrap(), "ab");
+    }
+
+    #[tokio::test]
+    async fn test_map_chunks_keeps_trailers() {
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from("a")).await.unwrap();
+            let mut trailers = HeaderMap::new();
+            trailers.insert("x-done", "1".parse().unwrap());
+            sender.send_trailers(trailers).await.unwrap();
+        });
+        let mut body = body.map_chunks(|chunks| {
+            chunks.map_ok(|chunk| Bytes::from(chunk.to_ascii_uppercase()))
+        });
+
+        assert_eq!(body.data().await.unwrap().unwrap(), "A");
+        assert!(body.data().await.is_none());
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["x-done"], "1");
+    }
+}
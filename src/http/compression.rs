@@ -0,0 +1,128 @@
+use super::{Body, Response};
+use async_compression::tokio::bufread::{
+    BrotliEncoder, GzipEncoder, ZlibEncoder,
+};
+use futures::prelude::*;
+use hyper::header::{HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH};
+use std::io;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    /// The zlib format, which is what HTTP calls `deflate`
+    Deflate,
+    Brotli,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Brotli => "br",
+        }
+    }
+}
+
+/// Passes the trailers of `body` on after the compressed chunks
+pub fn compress_body(body: Body, encoding: Encoding) -> Body {
+    body.map_chunks(move |chunks| {
+        let reader = StreamReader::new(chunks.map_err(io::Error::other));
+        let compressed = match encoding {
+            Encoding::Gzip => {
+                ReaderStream::new(GzipEncoder::new(reader)).boxed()
+            }
+            Encoding::Deflate => {
+                ReaderStream::new(ZlibEncoder::new(reader)).boxed()
+            }
+            Encoding::Brotli => {
+                ReaderStream::new(BrotliEncoder::new(reader)).boxed()
+            }
+        };
+        compressed.err_into()
+    })
+}
+
+/// Streams the response body through the encoder, fixing up its headers
+pub fn compress(res: Response, encoding: Encoding) -> Response {
+    let (mut parts, body) = res.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(
+        CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.as_str()),
+    );
+
+    Response::from_parts(parts, compress_body(body, encoding))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use async_compression::tokio::bufread::{
+        BrotliDecoder, GzipDecoder, ZlibDecoder,
+    };
+    use tokio::io::{AsyncRead, AsyncReadExt};
+
+    pub(crate) async fn decompress(data: &[u8], encoding: Encoding) -> Vec<u8> {
+        let mut reader: Box<dyn AsyncRead + Unpin> = match encoding {
+            Encoding::Gzip => Box::new(GzipDecoder::new(data)),
+            Encoding::Deflate => Box::new(ZlibDecoder::new(data)),
+            Encoding::Brotli => Box::new(BrotliDecoder::new(data)),
+        };
+        let mut output = vec![];
+        reader.read_to_end(&mut output).await.unwrap();
+        output
+    }
+
+    async fn roundtrip(encoding: Encoding) {
+        let res = hyper::Response::builder()
+            .header(CONTENT_LENGTH, "5")
+            .body(Body::from("hello"))
+            .unwrap();
+        let res = compress(res, encoding);
+
+        assert_eq!(
+            res.headers().get(CONTENT_ENCODING).unwrap(),
+            encoding.as_str()
+        );
+        assert!(res.headers().get(CONTENT_LENGTH).is_none());
+        let body = crate::http::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(decompress(&body, encoding).await, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_keeps_trailers() {
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data("hello".into()).await.unwrap();
+            let mut trailers = hyper::HeaderMap::new();
+            trailers.insert("x-done", HeaderValue::from_static("1"));
+            sender.send_trailers(trailers).await.unwrap();
+        });
+        let mut body = compress_body(body, Encoding::Gzip);
+
+        let mut data = vec![];
+        while let Some(chunk) = body.data().await {
+            data.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(decompress(&data, Encoding::Gzip).await, b"hello");
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["x-done"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_gzip() {
+        roundtrip(Encoding::Gzip).await
+    }
+
+    #[tokio::test]
+    async fn test_deflate() {
+        roundtrip(Encoding::Deflate).await
+    }
+
+    #[tokio::test]
+    async fn test_brotli() {
+        roundtrip(Encoding::Brotli).await
+    }
+}
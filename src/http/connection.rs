@@ -0,0 +1,81 @@
+//! What is known about the connection a request came in on
+use super::{Bytes, PeerCredentials, Request};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Numbers the connections of the server, from 1
+static CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+
+/// The outcome of the TLS handshake of a connection
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tls {
+    /// Such as `TLSv1_3`
+    pub version: Option<&'static str>,
+    /// Such as `TLS13_AES_128_GCM_SHA256`, unknown for HTTP/3
+    pub cipher: Option<&'static str>,
+    /// The protocol agreed on through ALPN
+    pub alpn: Option<String>,
+    /// The name the client asked for through SNI
+    pub server_name: Option<String>,
+    /// The DER of the certificate the client presented, if it was asked for
+    /// one
+    pub client_certificate: Option<Bytes>,
+}
+
+/// A connection, as the acceptor saw it, and the number of the request on
+/// it
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    pub peer_addr: Option<SocketAddr>,
+    pub local_addr: Option<SocketAddr>,
+    pub credentials: Option<PeerCredentials>,
+    pub tls: Option<Tls>,
+    /// Requests numbered so far, shared by every copy of the info
+    requests: Arc<AtomicU64>,
+    /// The request it was handed to, from 1, or 0 before
+    pub request: u64,
+}
+
+impl ConnectionInfo {
+    /// A new connection, numbered after the last one
+    pub fn new() -> Self {
+        Self {
+            id: CONNECTIONS.fetch_add(1, Ordering::Relaxed) + 1,
+            ..Self::default()
+        }
+    }
+
+    /// The info for the next request on the connection
+    pub fn next_request(&self) -> Self {
+        Self {
+            request: self.requests.fetch_add(1, Ordering::Relaxed) + 1,
+            ..self.clone()
+        }
+    }
+}
+
+impl Request {
+    /// The connection the request came in on, outside of tests
+    pub fn connection_info(&self) -> Option<&ConnectionInfo> {
+        self.extensions().get::<ConnectionInfo>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_numbering() {
+        let (first, second) = (ConnectionInfo::new(), ConnectionInfo::new());
+        assert!(second.id > first.id);
+
+        assert_eq!(first.next_request().request, 1);
+        let reused = first.clone().next_request();
+        assert_eq!(reused.request, 2);
+        assert_eq!(reused.id, first.id);
+        assert_eq!(second.next_request().request, 1);
+    }
+}
@@ -0,0 +1,97 @@
+//! Stopping work for a client that has gone away
+use super::{client_disconnected, Error, Request};
+use futures::prelude::*;
+use tokio_util::sync::CancellationToken;
+
+/// Signalled when the connection of a request reaches its end, fails or is
+/// closed, so whatever is still being done for its client can stop
+#[derive(Clone, Debug, Default)]
+pub struct Disconnected(CancellationToken);
+
+impl Disconnected {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn disconnect(&self) {
+        self.0.cancel()
+    }
+
+    pub async fn wait(&self) {
+        self.0.cancelled().await
+    }
+}
+
+impl Request {
+    /// Resolves once the client goes away, or never outside a server or
+    /// over HTTP/3
+    ///
+    /// A connection is only known to be gone once the server reads from or
+    /// writes to it, which hyper does for HTTP/1.x and HTTP/2 while a
+    /// response is pending.
+    pub fn on_disconnect(&self) -> impl Future<Output = ()> + Send + 'static {
+        let disconnected = self.extensions().get::<Disconnected>().cloned();
+        async move {
+            match disconnected {
+                Some(disconnected) => disconnected.wait().await,
+                None => future::pending().await,
+            }
+        }
+    }
+
+    /// Waits for `future`, unless the client goes away first
+    pub async fn unless_disconnected<F: Future>(
+        &self,
+        future: F,
+    ) -> Result<F::Output, Error> {
+        tokio::select! {
+            output = future => Ok(output),
+            _ = self.on_disconnect() => Err(client_disconnected()),
+        }
+    }
+
+    /// Ends `stream` as soon as the client goes away
+    pub fn until_disconnect<S: Stream>(
+        &self,
+        stream: S,
+    ) -> impl Stream<Item = S::Item> {
+        stream.take_until(self.on_disconnect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_until_disconnect() {
+        let disconnected = Disconnected::new();
+        let req = request().extension(disconnected.clone()).build();
+
+        let mut stream = Box::pin(req.until_disconnect(stream::repeat(1)));
+        assert_eq!(stream.next().await, Some(1));
+        disconnected.disconnect();
+        assert_eq!(stream.next().await, None);
+        req.on_disconnect().await;
+    }
+
+    #[tokio::test]
+    async fn test_unless_disconnected() {
+        let disconnected = Disconnected::new();
+        let req = request().extension(disconnected.clone()).build();
+
+        assert_eq!(req.unless_disconnected(async { 1 }).await.unwrap(), 1);
+        disconnected.disconnect();
+        let pending = req.unless_disconnected(future::pending::<()>()).await;
+        assert!(matches!(pending, Err(Error::Disconnected)));
+    }
+
+    #[tokio::test]
+    async fn test_until_disconnect_without_server() {
+        let req = request().build();
+
+        let stream = req.until_disconnect(stream::iter(vec![1, 2]));
+        assert_eq!(stream.collect::<Vec<_>>().await, vec![1, 2]);
+    }
+}
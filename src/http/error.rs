@@ -1,16 +1,61 @@
-use super::Response;
+use super::{Body, Response, StatusCode};
+use crate::config::ErrorFormat;
+use crate::headers::{ContentType, HeaderMapExt};
+use serde_derive::Serialize;
+use std::borrow::Cow;
+use std::sync::Arc;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     HyperError(hyper::http::Error),
-    Failure(Response),
+    /// Fails with a response prepared up front
+    Failure(Box<Response>),
+    /// An optional message tells the client what was wrong
+    BadRequest(Cow<'static, str>),
+    NotFound,
+    Timeout,
+    /// The client went away before the response was ready, logged as
+    /// nginx's 499 Client Closed Request
+    Disconnected,
+    /// Logged, but not shown to the client
+    Internal(anyhow::Error),
 }
 
 impl Error {
-    pub async fn into_result(self) -> hyper::http::Result<Response> {
+    pub fn bad_request(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::BadRequest(message.into())
+    }
+
+    pub fn internal(source: impl Into<anyhow::Error>) -> Self {
+        Self::Internal(source.into())
+    }
+
+    pub fn status(&self) -> StatusCode {
         match self {
+            Self::HyperError(_) | Self::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Self::Failure(res) => res.status(),
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            Self::Disconnected => StatusCode::from_u16(499).unwrap(),
+        }
+    }
+
+    /// What the client is told beyond the status
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Self::BadRequest(message) if !message.is_empty() => Some(message),
+            _ => None,
+        }
+    }
+
+    pub async fn into_result(self) -> hyper::http::Result<Response> {
+        match ErrorRenderer::default().render(self) {
             Self::HyperError(e) => Err(e),
-            Self::Failure(res) => Ok(res),
+            error => Ok(error.into_response()),
         }
     }
 }
@@ -18,7 +63,7 @@ impl Error {
 impl From<hyper::http::Result<Response>> for Error {
     fn from(result: hyper::http::Result<Response>) -> Self {
         match result {
-            Ok(res) => Self::Failure(res),
+            Ok(res) => Self::Failure(Box::new(res)),
             Err(e) => Self::HyperError(e),
         }
     }
@@ -29,3 +74,219 @@ impl From<hyper::http::Error> for Error {
         Error::HyperError(error)
     }
 }
+
+/// What can be sent as a response
+pub trait IntoResponse {
+    fn into_response(self) -> Response;
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+impl IntoResponse for Error {
+    /// Renders the error as plain text, see `ErrorRenderer` for others
+    fn into_response(self) -> Response {
+        match self {
+            Self::Failure(res) => *res,
+            error => render_text(&error),
+        }
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for std::result::Result<T, Error> {
+    fn into_response(self) -> Response {
+        match self {
+            Ok(res) => res.into_response(),
+            Err(error) => error.into_response(),
+        }
+    }
+}
+
+fn with_status(status: StatusCode, body: Body) -> Response {
+    let mut res = Response::new(body);
+    *res.status_mut() = status;
+    res
+}
+
+/// The message alone, if any
+pub fn render_text(error: &Error) -> Response {
+    let mut res = with_status(
+        error.status(),
+        error.message().unwrap_or_default().to_owned().into(),
+    );
+    if error.message().is_some() {
+        res.headers_mut().typed_insert(ContentType::text());
+    }
+    res
+}
+
+#[derive(Serialize)]
+struct Problem<'a> {
+    status: u16,
+    error: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+}
+
+pub fn render_json(error: &Error) -> Response {
+    let status = error.status();
+    let problem = Problem {
+        status: status.as_u16(),
+        error: status.canonical_reason().unwrap_or_default(),
+        message: error.message(),
+    };
+    let body = serde_json::to_vec_pretty(&problem).unwrap_or_default();
+    let mut res = with_status(status, body.into());
+    res.headers_mut().typed_insert(ContentType::json());
+    res
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn render_html(error: &Error) -> Response {
+    let status = error.status();
+    let title = format!(
+        "{} {}",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    );
+    let message = error
+        .message()
+        .map(|message| format!("<p>{}</p>", escape_html(message)))
+        .unwrap_or_default();
+    let body = format!(
+        "<!DOCTYPE html>\n<title>{0}</title>\n<h1>{0}</h1>\n{1}",
+        title, message
+    );
+    let mut res = with_status(status, body.into());
+    res.headers_mut().typed_insert(ContentType::html());
+    res
+}
+
+/// Turns the errors that don't come with a response into one, see
+/// `RouterBuilder::errors`
+#[derive(Clone)]
+pub struct ErrorRenderer(Arc<dyn Fn(&Error) -> Response + Send + Sync>);
+
+impl ErrorRenderer {
+    pub fn new<F>(render: F) -> Self
+    where
+        F: Fn(&Error) -> Response + Send + Sync + 'static,
+    {
+        Self(Arc::new(render))
+    }
+
+    /// Gives errors without a response one, leaving the others be
+    pub fn render(&self, error: Error) -> Error {
+        match error {
+            Error::HyperError(_) | Error::Failure(_) => error,
+            error => {
+                if let Error::Internal(source) = &error {
+                    tracing::error!(error = %format!("{:#}", source), "internal error");
+                }
+                Error::Failure(Box::new(self.response(&error)))
+            }
+        }
+    }
+
+    /// The response for `error`, without logging internal errors
+    pub fn response(&self, error: &Error) -> Response {
+        (self.0)(error)
+    }
+}
+
+impl Default for ErrorRenderer {
+    fn default() -> Self {
+        ErrorFormat::default().into()
+    }
+}
+
+impl From<ErrorFormat> for ErrorRenderer {
+    fn from(format: ErrorFormat) -> Self {
+        match format {
+            ErrorFormat::Text => Self::new(render_text),
+            ErrorFormat::Json => Self::new(render_json),
+            ErrorFormat::Html => Self::new(render_html),
+        }
+    }
+}
+
+impl std::fmt::Debug for ErrorRenderer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ErrorRenderer").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_render_text() {
+        let res = Error::bad_request("missing n").into_response();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.read_body_utf8().await.unwrap(), "missing n");
+
+        let res = Error::NotFound.into_response();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert!(res.read_body().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_render_json() {
+        let res = render_json(&Error::bad_request("missing n"));
+
+        assert_eq!(
+            res.read_body_json().await.unwrap(),
+            serde_json::json!({
+                "status": 400,
+                "error": "Bad Request",
+                "message": "missing n",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_html() {
+        let res = render_html(&Error::bad_request("<n> is missing"));
+        let body = res.read_body_utf8().await.unwrap();
+
+        assert!(body.contains("<h1>400 Bad Request</h1>"));
+        assert!(body.contains("<p>&lt;n&gt; is missing</p>"));
+    }
+
+    #[tokio::test]
+    async fn test_internal_not_shown() {
+        let error = Error::internal(anyhow::anyhow!("secret"));
+        let res = ErrorRenderer::from(ErrorFormat::Json)
+            .render(error)
+            .into_response();
+        let body = res.read_body_utf8().await.unwrap();
+
+        assert!(!body.contains("secret"));
+    }
+
+    #[test]
+    fn test_renderer_keeps_failures() {
+        let renderer = ErrorRenderer::from(ErrorFormat::Html);
+        let failure = Error::Failure(Box::new(with_status(
+            StatusCode::IM_A_TEAPOT,
+            Body::empty(),
+        )));
+
+        match renderer.render(failure) {
+            Error::Failure(res) => assert!(res.headers().is_empty()),
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! 1xx informational responses ahead of the final one, which hyper has no
+//! way for besides `100 Continue`
+use super::{response, Error, Request, StatusCode};
+use futures::task::AtomicWaker;
+use hyper::body::Bytes;
+use hyper::{HeaderMap, Version};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+
+#[derive(Debug, Default)]
+struct Queue {
+    pending: Mutex<VecDeque<Bytes>>,
+    waker: AtomicWaker,
+}
+
+/// Informational responses waiting to be written to an HTTP/1.1
+/// connection, ahead of whatever hyper writes to it next
+///
+/// hyper handles one request of a connection at a time, writing nothing
+/// while its handler runs, so they can't end up in another response.
+#[derive(Clone, Debug, Default)]
+pub struct Interim(Arc<Queue>);
+
+impl Interim {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, head: Bytes) {
+        self.0.pending.lock().unwrap().push_back(head);
+        self.0.waker.wake();
+    }
+
+    /// The next response head to write, `waker` being woken once another
+    /// is queued
+    pub fn poll_next(&self, waker: &Waker) -> Option<Bytes> {
+        self.0.waker.register(waker);
+        self.0.pending.lock().unwrap().pop_front()
+    }
+}
+
+/// The head of an informational response, such as
+/// `HTTP/1.1 103 Early Hints`
+fn head(status: StatusCode, headers: &HeaderMap) -> Bytes {
+    // Newer than the reason phrases `http` knows
+    let reason = match status.as_u16() {
+        103 => Some("Early Hints"),
+        _ => status.canonical_reason(),
+    };
+    let mut head =
+        format!("HTTP/1.1 {} {}\r\n", status.as_u16(), reason.unwrap_or(""))
+            .into_bytes();
+    for (name, value) in headers {
+        head.extend_from_slice(name.as_str().as_bytes());
+        head.extend_from_slice(b": ");
+        head.extend_from_slice(value.as_bytes());
+        head.extend_from_slice(b"\r\n");
+    }
+    head.extend_from_slice(b"\r\n");
+    head.into()
+}
+
+impl Request {
+    /// Sends a `status` informational response with `headers` before the
+    /// final one
+    ///
+    /// Fails with 505 outside HTTP/1.1, HTTP/1.0 knowing no informational
+    /// responses and hyper offering no way to send them over HTTP/2, and
+    /// with 400 for a status that is not 1xx or is 101 Switching Protocols.
+    pub fn send_informational(
+        &self,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> Result<(), Error> {
+        if !status.is_informational()
+            || status == StatusCode::SWITCHING_PROTOCOLS
+        {
+            return Err(Error::bad_request("not an informational status"));
+        }
+        match self.extensions().get::<Interim>() {
+            Some(interim) if self.version() == Version::HTTP_11 => {
+                interim.push(head(status, headers));
+                Ok(())
+            }
+            _ => Err(response()
+                .status(StatusCode::HTTP_VERSION_NOT_SUPPORTED)
+                .body("Only served over HTTP/1.1")
+                .map_or_else(|e| e, |res| Error::Failure(Box::new(res)))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_head() {
+        let mut headers = HeaderMap::new();
+        headers.append("link", "</a.css>; rel=preload".parse().unwrap());
+        headers.append("link", "</b.js>; rel=preload".parse().unwrap());
+
+        assert_eq!(
+            head(StatusCode::from_u16(103).unwrap(), &headers),
+            "HTTP/1.1 103 Early Hints\r\nlink: </a.css>; rel=preload\r\n\
+             link: </b.js>; rel=preload\r\n\r\n"
+        );
+    }
+}
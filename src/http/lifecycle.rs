@@ -0,0 +1,121 @@
+//! How long HTTP/1.x connections are kept open between requests
+use super::Request;
+use futures::task::AtomicWaker;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// Requests whose response has not been sent in full
+    in_flight: AtomicUsize,
+    /// How long the connection may be idle, if not for ever
+    timeout: Mutex<Option<Duration>>,
+    /// How many more requests the connection takes, if not any number
+    remaining: Mutex<Option<u64>>,
+    /// Upgraded to another protocol, or HTTP/2, which keep their
+    /// connections open for as long as they like
+    exempt: AtomicBool,
+    /// The connection, waiting for its requests to end
+    waker: AtomicWaker,
+}
+
+/// The keep-alive of a connection, shared by the connection and the
+/// requests on it
+#[derive(Clone, Debug, Default)]
+pub struct Lifecycle(Arc<Shared>);
+
+impl Lifecycle {
+    pub fn new(timeout: Option<Duration>) -> Self {
+        let lifecycle = Self::default();
+        *lifecycle.0.timeout.lock().unwrap() = timeout;
+        lifecycle
+    }
+
+    /// How long the connection may go without a request in flight or any
+    /// reads and writes, if it is to be closed at all, `waker` being woken
+    /// once the requests in flight end
+    pub fn poll_idle_timeout(&self, waker: &Waker) -> Option<Duration> {
+        self.0.waker.register(waker);
+        self.idle_timeout()
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        let idle = self.0.in_flight.load(Ordering::SeqCst) == 0
+            && !self.0.exempt.load(Ordering::SeqCst);
+        idle.then(|| *self.0.timeout.lock().unwrap()).flatten()
+    }
+
+    /// Counts a request in flight, returning whether the connection closes
+    /// after its response for having taken its last request
+    pub fn begin(&self) -> bool {
+        self.0.in_flight.fetch_add(1, Ordering::SeqCst);
+        let mut remaining = self.0.remaining.lock().unwrap();
+        match remaining.as_mut() {
+            Some(remaining) => {
+                *remaining = remaining.saturating_sub(1);
+                *remaining == 0
+            }
+            None => false,
+        }
+    }
+
+    /// Counts a response as sent in full, or given up on
+    pub fn end(&self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.waker.wake();
+        }
+    }
+
+    /// Stops ever closing the connection for being idle
+    pub fn exempt(&self) {
+        self.0.exempt.store(true, Ordering::SeqCst)
+    }
+}
+
+impl Request {
+    /// Closes the connection once it has been idle for `timeout`, or after
+    /// `max` more requests, for the rest of its life
+    ///
+    /// Returns whether it could, which only HTTP/1.x connections of the
+    /// server can.
+    pub fn keep_alive(&self, timeout: Duration, max: Option<u64>) -> bool {
+        match self.extensions().get::<Lifecycle>() {
+            Some(lifecycle) => {
+                *lifecycle.0.timeout.lock().unwrap() = Some(timeout);
+                *lifecycle.0.remaining.lock().unwrap() = max;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_idle_timeout() {
+        let lifecycle = Lifecycle::new(Some(Duration::from_secs(1)));
+        assert_eq!(lifecycle.idle_timeout(), Some(Duration::from_secs(1)));
+
+        assert!(!lifecycle.begin());
+        assert_eq!(lifecycle.idle_timeout(), None);
+        lifecycle.end();
+        assert!(lifecycle.idle_timeout().is_some());
+
+        lifecycle.exempt();
+        assert_eq!(lifecycle.idle_timeout(), None);
+    }
+
+    #[test]
+    fn test_max_requests() {
+        let lifecycle = Lifecycle::new(None);
+        *lifecycle.0.remaining.lock().unwrap() = Some(2);
+
+        assert!(!lifecycle.begin());
+        assert!(lifecycle.begin());
+    }
+}
@@ -0,0 +1,62 @@
+use super::Body;
+use futures::prelude::*;
+use std::error::Error as StdError;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct LengthLimitExceeded(pub usize);
+
+impl fmt::Display for LengthLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "body exceeded the limit of {} bytes", self.0)
+    }
+}
+
+impl StdError for LengthLimitExceeded {}
+
+/// Fails the body stream once more than `limit` bytes have been read
+pub(crate) fn limit_body(body: Body, limit: usize) -> Body {
+    let mut remaining = limit;
+    Body::wrap_stream(body.map(
+        move |chunk| -> Result<_, Box<dyn StdError + Send + Sync>> {
+            let chunk = chunk?;
+            remaining = remaining
+                .checked_sub(chunk.len())
+                .ok_or(LengthLimitExceeded(limit))?;
+            Ok(chunk)
+        },
+    ))
+}
+
+/// Whether `error`, or anything in its source chain, is a length limit
+pub(crate) fn is_length_limit_exceeded(
+    error: &(dyn StdError + 'static),
+) -> bool {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if error.is::<LengthLimitExceeded>() {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_within_limit() {
+        let body = limit_body(Body::from("hello"), 5);
+        let bytes = crate::http::to_bytes(body).await.unwrap();
+        assert_eq!(bytes, "hello");
+    }
+
+    #[tokio::test]
+    async fn test_exceeds_limit() {
+        let body = limit_body(Body::from("hello"), 4);
+        let error = crate::http::to_bytes(body).await.unwrap_err();
+        assert!(is_length_limit_exceeded(&*error));
+    }
+}
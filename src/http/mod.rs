@@ -1,14 +1,45 @@
+pub use hyper::body::Bytes;
 pub use hyper::http::{StatusCode, Uri};
-pub use hyper::{body::Bytes, Body};
 
+mod abort;
+mod body;
+pub mod compression;
+mod connection;
+mod disconnect;
 mod error;
+mod interim;
+mod lifecycle;
+mod limit;
+pub(crate) mod multipart;
+pub mod negotiation;
+mod proxy;
+pub mod range;
+mod rate_limit;
 mod request;
+mod request_id;
 mod response;
+mod shutdown;
+pub mod sse;
 mod stream;
+mod throttle;
+mod url;
 
-pub use self::error::Error;
+pub use self::abort::*;
+pub use self::body::*;
+pub use self::connection::*;
+pub use self::disconnect::*;
+pub use self::error::*;
+pub use self::interim::*;
+pub use self::lifecycle::*;
+pub(crate) use self::limit::*;
+pub use self::multipart::*;
+pub use self::proxy::*;
+pub use self::rate_limit::*;
 pub use self::request::*;
+pub use self::request_id::*;
 pub use self::response::*;
+pub use self::shutdown::*;
 pub(crate) use self::stream::*;
+pub use self::throttle::*;
 
 pub type Result = std::result::Result<Response, Error>;
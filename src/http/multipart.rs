@@ -0,0 +1,131 @@
+use super::{
+    bad_request, is_length_limit_exceeded, payload_too_large, Error, Request,
+};
+use crate::headers::ContentType;
+
+pub use multer::{Field, Multipart};
+
+impl Request {
+    /// Streams the parts of a `multipart/form-data` body
+    ///
+    /// Fields have to be consumed in order; nothing is buffered beyond the
+    /// part currently being read.
+    pub fn multipart(&mut self) -> Result<Multipart<'static>, Error> {
+        let content_type = self
+            .typed_header::<ContentType>()
+            .map(mime::Mime::from)
+            .ok_or_else(bad_request)?;
+        let boundary = multer::parse_boundary(content_type.as_ref())
+            .map_err(|_| bad_request())?;
+        Ok(Multipart::new(self.body(), boundary))
+    }
+}
+
+/// Maps a failure while reading parts to 413 or 400
+pub fn multipart_error(error: multer::Error) -> Error {
+    if exceeds_limit(&error) {
+        payload_too_large()
+    } else {
+        bad_request()
+    }
+}
+
+/// multer wraps stream errors, sometimes twice, without exposing a source
+fn exceeds_limit(error: &multer::Error) -> bool {
+    match error {
+        multer::Error::StreamReadFailed(e) => match e.downcast_ref() {
+            Some(inner) => exceeds_limit(inner),
+            None => is_length_limit_exceeded(e.as_ref()),
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::http::limit_body;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+
+    pub(crate) const BOUNDARY: &str = "X-BOUNDARY";
+
+    pub(crate) fn multipart_content_type() -> String {
+        format!("multipart/form-data; boundary={}", BOUNDARY)
+    }
+
+    /// Builds a body from `(name, file name, content)` parts
+    pub(crate) fn multipart_body(
+        parts: &[(&str, Option<&str>, &str)],
+    ) -> String {
+        let mut body = String::new();
+        for (name, file_name, content) in parts {
+            body.push_str(&format!("--{}\r\n", BOUNDARY));
+            match file_name {
+                Some(file_name) => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{}\"; \
+                     filename=\"{}\"\r\n",
+                    name, file_name
+                )),
+                None => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{}\"\r\n",
+                    name
+                )),
+            }
+            body.push_str(&format!("\r\n{}\r\n", content));
+        }
+        body.push_str(&format!("--{}--\r\n", BOUNDARY));
+        body
+    }
+
+    #[tokio::test]
+    async fn test_multipart() {
+        let mut req = request()
+            .header("content-type", &multipart_content_type())
+            .body(multipart_body(&[
+                ("key", None, "val"),
+                ("upload", Some("a.txt"), "contents"),
+            ]))
+            .build();
+
+        let mut multipart = req.multipart().unwrap();
+
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("key"));
+        assert_eq!(field.file_name(), None);
+        assert_eq!(field.text().await.unwrap(), "val");
+
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("upload"));
+        assert_eq!(field.file_name(), Some("a.txt"));
+        assert_eq!(field.text().await.unwrap(), "contents");
+
+        assert!(multipart.next_field().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_no_boundary() {
+        let mut req = request()
+            .header("content-type", "multipart/form-data")
+            .build();
+
+        let err = req.multipart().err().unwrap();
+        let res = err.into_result().await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_limit() {
+        let body = multipart_body(&[("upload", Some("a.txt"), "contents")]);
+        let mut multipart =
+            Multipart::new(limit_body(body.into(), 16), BOUNDARY);
+
+        let err = match multipart.next_field().await {
+            Ok(Some(field)) => field.bytes().await.unwrap_err(),
+            Ok(None) => panic!("expected a field"),
+            Err(e) => e,
+        };
+        let res = multipart_error(err).into_result().await.unwrap();
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,299 @@
+use super::Request;
+use crate::headers::{Accept, AcceptEncoding, AcceptLanguage};
+
+/// One comma separated entry of an `Accept*` header with its q-value
+#[derive(Clone, Debug, PartialEq)]
+pub struct Qualified<'a> {
+    pub value: &'a str,
+    pub q: f32,
+}
+
+fn parse_qualified(item: &str) -> Option<Qualified<'_>> {
+    let mut parts = item.split(';').map(str::trim);
+    let value = parts.next().filter(|value| !value.is_empty())?;
+    let q = match parts.find_map(|param| param.strip_prefix("q=")) {
+        Some(q) => q.parse().ok().filter(|q| (0.0..=1.0).contains(q))?,
+        None => 1.0,
+    };
+    Some(Qualified { value, q })
+}
+
+/// Parses a q-valued list, skipping malformed entries
+pub fn parse_list(header: &str) -> Vec<Qualified<'_>> {
+    header.split(',').filter_map(parse_qualified).collect()
+}
+
+/// Weighs each offer by its most specific matching range, ties going to the
+/// more specific match and then to the order of `offered`
+///
+/// `specificity` is `None` when a range doesn't match an offer at all, and
+/// `unmatched` gives the q-value of an offer no range mentions.
+fn best<'a, S, U>(
+    ranges: &[Qualified<'_>],
+    offered: &[&'a str],
+    specificity: S,
+    unmatched: U,
+) -> Option<&'a str>
+where
+    S: Fn(&str, &str) -> Option<u8>,
+    U: Fn(&str) -> f32,
+{
+    let mut best: Option<(&str, f32, u8)> = None;
+    for offer in offered {
+        let (specificity, q) = ranges
+            .iter()
+            .filter_map(|range| {
+                Some((specificity(range.value, offer)?, range.q))
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .unwrap_or((0, unmatched(offer)));
+
+        let better = best.is_none_or(|(_, best_q, best_specificity)| {
+            q > best_q || (q == best_q && specificity > best_specificity)
+        });
+        if q > 0.0 && better {
+            best = Some((offer, q, specificity));
+        }
+    }
+    best.map(|(offer, _, _)| offer)
+}
+
+/// How closely a media range matches a media type
+fn media_specificity(range: &str, offer: &str) -> Option<u8> {
+    let (range_type, range_subtype) = range.split_once('/')?;
+    let (offer_type, offer_subtype) = offer.split_once('/')?;
+
+    if range_type == "*" && range_subtype == "*" {
+        Some(0)
+    } else if !range_type.eq_ignore_ascii_case(offer_type) {
+        None
+    } else if range_subtype == "*" {
+        Some(1)
+    } else if range_subtype.eq_ignore_ascii_case(offer_subtype) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// How closely a content coding matches
+fn coding_specificity(range: &str, offer: &str) -> Option<u8> {
+    if range == "*" {
+        Some(0)
+    } else if range.eq_ignore_ascii_case(offer) {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+/// How closely a language range matches a tag, by RFC 4647 basic filtering
+fn language_specificity(range: &str, offer: &str) -> Option<u8> {
+    if range == "*" {
+        return Some(0);
+    }
+    let prefix = offer.get(..range.len())?;
+    let boundary = offer[range.len()..].is_empty()
+        || offer[range.len()..].starts_with('-');
+    if prefix.eq_ignore_ascii_case(range) && boundary {
+        Some(range.split('-').count() as u8)
+    } else {
+        None
+    }
+}
+
+/// Picks the offered media type the client prefers from `Accept`
+///
+/// Without the header the first offer wins.
+pub fn negotiate_media_type<'a>(
+    accept: Option<&str>,
+    offered: &[&'a str],
+) -> Option<&'a str> {
+    match accept {
+        Some(accept) => {
+            best(&parse_list(accept), offered, media_specificity, |_| 0.0)
+        }
+        None => offered.first().copied(),
+    }
+}
+
+/// Picks the offered content coding the client prefers from
+/// `Accept-Encoding`
+///
+/// `identity` is acceptable unless excluded outright. Without the header
+/// nothing else is assumed to be, since clients that can't decode a coding
+/// are the ones that tend not to send it.
+pub fn negotiate_encoding<'a>(
+    accept_encoding: Option<&str>,
+    offered: &[&'a str],
+) -> Option<&'a str> {
+    let identity = |offer: &str| {
+        if offer.eq_ignore_ascii_case("identity") {
+            1.0
+        } else {
+            0.0
+        }
+    };
+    let ranges = accept_encoding.map(parse_list).unwrap_or_default();
+    best(&ranges, offered, coding_specificity, identity)
+}
+
+/// Picks the offered language tag the client prefers from `Accept-Language`
+///
+/// Without the header the first offer wins.
+pub fn negotiate_language<'a>(
+    accept_language: Option<&str>,
+    offered: &[&'a str],
+) -> Option<&'a str> {
+    match accept_language {
+        Some(accept) => {
+            best(&parse_list(accept), offered, language_specificity, |_| 0.0)
+        }
+        None => offered.first().copied(),
+    }
+}
+
+impl Request {
+    /// The offered media type the `Accept` header prefers
+    pub fn negotiate<'a>(&self, offered: &[&'a str]) -> Option<&'a str> {
+        let accept = self.typed_header::<Accept>();
+        negotiate_media_type(accept.as_ref().map(Accept::as_str), offered)
+    }
+
+    /// The offered content coding the `Accept-Encoding` header prefers
+    pub fn negotiate_encoding<'a>(
+        &self,
+        offered: &[&'a str],
+    ) -> Option<&'a str> {
+        let accept = self.typed_header::<AcceptEncoding>();
+        negotiate_encoding(accept.as_ref().map(AcceptEncoding::as_str), offered)
+    }
+
+    /// The offered language tag the `Accept-Language` header prefers
+    pub fn negotiate_language<'a>(
+        &self,
+        offered: &[&'a str],
+    ) -> Option<&'a str> {
+        let accept = self.typed_header::<AcceptLanguage>();
+        negotiate_language(accept.as_ref().map(AcceptLanguage::as_str), offered)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const IMAGES: [&str; 3] = ["image/png", "image/jpeg", "image/webp"];
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list("text/html, application/json;q=0.5, bad;q=x, ,"),
+            [
+                Qualified {
+                    value: "text/html",
+                    q: 1.0
+                },
+                Qualified {
+                    value: "application/json",
+                    q: 0.5
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_accept() {
+        assert_eq!(negotiate_media_type(None, &IMAGES), Some("image/png"));
+    }
+
+    #[test]
+    fn test_exact() {
+        assert_eq!(
+            negotiate_media_type(Some("image/jpeg"), &IMAGES),
+            Some("image/jpeg")
+        );
+    }
+
+    #[test]
+    fn test_q_values() {
+        assert_eq!(
+            negotiate_media_type(
+                Some("image/png;q=0.5, image/webp;q=0.8"),
+                &IMAGES
+            ),
+            Some("image/webp")
+        );
+    }
+
+    #[test]
+    fn test_specific_beats_wildcard() {
+        assert_eq!(
+            negotiate_media_type(Some("image/webp, */*"), &IMAGES),
+            Some("image/webp")
+        );
+        assert_eq!(
+            negotiate_media_type(Some("image/*, image/png;q=0"), &IMAGES),
+            Some("image/jpeg")
+        );
+    }
+
+    #[test]
+    fn test_not_acceptable() {
+        assert_eq!(negotiate_media_type(Some("text/html"), &IMAGES), None);
+        assert_eq!(negotiate_media_type(Some("*/*;q=0"), &IMAGES), None);
+    }
+
+    #[test]
+    fn test_encoding() {
+        let offered = ["br", "gzip", "identity"];
+        assert_eq!(negotiate_encoding(Some("gzip"), &offered), Some("gzip"));
+        assert_eq!(negotiate_encoding(Some("gzip, br"), &offered), Some("br"));
+        assert_eq!(
+            negotiate_encoding(Some("deflate"), &offered),
+            Some("identity")
+        );
+        assert_eq!(negotiate_encoding(None, &offered), Some("identity"));
+        assert_eq!(negotiate_encoding(None, &["gzip"]), None);
+        assert_eq!(negotiate_encoding(Some("*;q=0"), &offered), None);
+        assert_eq!(negotiate_encoding(Some("identity;q=0"), &offered), None);
+    }
+
+    #[test]
+    fn test_language() {
+        let offered = ["en-US", "de", "fr-CA"];
+        assert_eq!(
+            negotiate_language(Some("de-AT, de;q=0.9, en;q=0.8"), &offered),
+            Some("de")
+        );
+        assert_eq!(negotiate_language(Some("fr"), &offered), Some("fr-CA"));
+        assert_eq!(negotiate_language(Some("*"), &offered), Some("en-US"));
+        assert_eq!(negotiate_language(Some("e"), &offered), None);
+        assert_eq!(negotiate_language(Some("es"), &offered), None);
+        assert_eq!(negotiate_language(None, &offered), Some("en-US"));
+    }
+
+    #[test]
+    fn test_request_negotiate() {
+        let req = crate::test::request()
+            .header("accept", "text/html;q=0.5, application/json")
+            .build();
+
+        assert_eq!(
+            req.negotiate(&["text/html", "application/json"]),
+            Some("application/json")
+        );
+    }
+
+    #[test]
+    fn test_request_negotiate_encoding_and_language() {
+        let req = crate::test::request()
+            .header("accept-encoding", "gzip;q=0.5, br")
+            .header("accept-language", "de-AT, fr;q=0.5")
+            .build();
+
+        assert_eq!(req.negotiate_encoding(&["gzip", "br"]), Some("br"));
+        assert_eq!(req.negotiate_language(&["fr", "de"]), Some("fr"));
+        assert_eq!(req.negotiate_language(&["fr", "de-AT"]), Some("de-AT"));
+    }
+}
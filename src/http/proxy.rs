@@ -0,0 +1,366 @@
+use super::Request;
+use crate::headers::{Forwarded, XForwardedFor};
+use clap::ValueEnum;
+use serde::de::{self, Deserialize, Deserializer};
+use serde_derive::Deserialize;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// An IP network like `10.0.0.0/8`, or a single address
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+#[derive(Debug)]
+pub struct InvalidCidr;
+
+impl fmt::Display for InvalidCidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid CIDR, expected an address or address/prefix")
+    }
+}
+
+impl std::error::Error for InvalidCidr {}
+
+fn bits(addr: IpAddr) -> (u128, u8) {
+    match addr {
+        IpAddr::V4(addr) => (u32::from(addr).into(), 32),
+        IpAddr::V6(addr) => (addr.into(), 128),
+    }
+}
+
+impl Cidr {
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = addr.to_canonical();
+        if self.addr.is_ipv4() != addr.is_ipv4() {
+            return false;
+        }
+
+        let ((network, width), (addr, _)) = (bits(self.addr), bits(addr));
+        let shift = u32::from(width - self.prefix);
+        network.checked_shr(shift).unwrap_or(0)
+            == addr.checked_shr(shift).unwrap_or(0)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = InvalidCidr;
+
+    fn from_str(s: &str) -> Result<Self, InvalidCidr> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr = addr.trim().parse::<IpAddr>().map_err(|_| InvalidCidr)?;
+        let (_, width) = bits(addr);
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse().map_err(|_| InvalidCidr)?,
+            None => width,
+        };
+
+        if prefix > width {
+            return Err(InvalidCidr);
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl<'de> Deserialize<'de> for Cidr {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// The header trusted proxies report the client address in
+///
+/// Only the one the proxies write is read, since the other comes from the
+/// client as is.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForwardingHeader {
+    /// `X-Forwarded-For`
+    #[default]
+    XForwardedFor,
+    /// `Forwarded` (RFC 7239)
+    Forwarded,
+}
+
+/// The proxies whose forwarding headers are believed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrustedProxies {
+    networks: Vec<Cidr>,
+    header: ForwardingHeader,
+}
+
+impl TrustedProxies {
+    pub fn new(networks: Vec<Cidr>) -> Self {
+        Self {
+            networks,
+            header: ForwardingHeader::default(),
+        }
+    }
+
+    /// Reads the client address from `header` instead
+    pub fn header(mut self, header: ForwardingHeader) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        self.networks.iter().any(|network| network.contains(addr))
+    }
+
+    /// Walks `chain` back from `peer` for as long as the hop we got it from
+    /// is trusted, so the client can't spoof its way past our own proxies
+    fn resolve(&self, peer: IpAddr, chain: Vec<Option<IpAddr>>) -> IpAddr {
+        let mut client = peer;
+        for hop in chain.into_iter().rev() {
+            match hop {
+                Some(hop) if self.contains(client) => client = hop,
+                _ => break,
+            }
+        }
+        client
+    }
+}
+
+/// The process on the other end of a Unix domain socket
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerCredentials {
+    /// Not every platform reports the process
+    pub pid: Option<i32>,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl fmt::Display for PeerCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("unix:")?;
+        if let Some(pid) = self.pid {
+            write!(f, "pid={},", pid)?;
+        }
+        write!(f, "uid={},gid={}", self.uid, self.gid)
+    }
+}
+
+/// Who a request came from, by IP or, over a Unix socket, by process
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientAddr {
+    Ip(IpAddr),
+    Unix(PeerCredentials),
+}
+
+impl fmt::Display for ClientAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Ip(ip) => ip.fmt(f),
+            Self::Unix(credentials) => credentials.fmt(f),
+        }
+    }
+}
+
+impl From<IpAddr> for ClientAddr {
+    fn from(ip: IpAddr) -> Self {
+        Self::Ip(ip)
+    }
+}
+
+impl Request {
+    /// The address of the other end of the connection
+    pub fn peer_addr(&self) -> Option<&SocketAddr> {
+        self.extensions().get::<SocketAddr>()
+    }
+
+    /// The credentials of the other end of a Unix socket connection
+    pub fn peer_credentials(&self) -> Option<&PeerCredentials> {
+        self.extensions().get::<PeerCredentials>()
+    }
+
+    /// The header the peer is trusted to report the client in, if it is a
+    /// trusted proxy
+    pub fn trusted_header(&self) -> Option<ForwardingHeader> {
+        let peer = self.peer_addr()?.ip();
+        let trusted = self.extensions().get::<Arc<TrustedProxies>>()?;
+        Some(trusted.header).filter(|_| trusted.contains(peer))
+    }
+
+    /// The address of the client, which is the peer unless it's a trusted
+    /// proxy reporting the client through the header it is trusted for
+    pub fn client_addr(&self) -> Option<ClientAddr> {
+        let peer = match self.peer_addr() {
+            Some(addr) => addr.ip(),
+            None => {
+                return self.peer_credentials().copied().map(ClientAddr::Unix)
+            }
+        };
+        let trusted = match self.extensions().get::<Arc<TrustedProxies>>() {
+            Some(trusted) if trusted.contains(peer) => trusted,
+            _ => return Some(peer.into()),
+        };
+
+        let chain = match trusted.header {
+            ForwardingHeader::XForwardedFor => self
+                .typed_header::<XForwardedFor>()
+                .map(|header| header.chain()),
+            ForwardingHeader::Forwarded => self
+                .typed_header::<Forwarded>()
+                .map(|header| header.chain()),
+        }
+        .unwrap_or_default();
+        Some(trusted.resolve(peer, chain).into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    fn trusted(networks: &[&str]) -> TrustedProxies {
+        TrustedProxies::new(
+            networks
+                .iter()
+                .map(|network| network.parse().unwrap())
+                .collect(),
+        )
+    }
+
+    fn ip(ip: &str) -> ClientAddr {
+        ClientAddr::Ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let network = "10.0.0.0/8".parse::<Cidr>().unwrap();
+        assert!(network.contains("10.1.2.3".parse().unwrap()));
+        assert!(network.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!network.contains("11.0.0.1".parse().unwrap()));
+        assert!(!network.contains("::1".parse().unwrap()));
+
+        let network = "fd00::/8".parse::<Cidr>().unwrap();
+        assert!(network.contains("fd12::1".parse().unwrap()));
+        assert!(!network.contains("fe80::1".parse().unwrap()));
+
+        let any = "0.0.0.0/0".parse::<Cidr>().unwrap();
+        assert!(any.contains("1.2.3.4".parse().unwrap()));
+
+        let single = "127.0.0.1".parse::<Cidr>().unwrap();
+        assert!(single.contains("127.0.0.1".parse().unwrap()));
+        assert!(!single.contains("127.0.0.2".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_cidr_invalid() {
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+        assert!("::/x".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let trusted = trusted(&["10.0.0.0/8"]);
+        let hop = |ip: &str| Some(ip.parse().unwrap());
+        let chain = vec![hop("6.6.6.6"), hop("1.2.3.4"), hop("10.0.0.2")];
+
+        assert_eq!(
+            trusted.resolve("10.0.0.1".parse().unwrap(), chain.clone()),
+            "1.2.3.4".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            trusted.resolve("1.1.1.1".parse().unwrap(), chain),
+            "1.1.1.1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            trusted.resolve("10.0.0.1".parse().unwrap(), vec![None]),
+            "10.0.0.1".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_client_addr_untrusted() {
+        let req = request()
+            .header("x-forwarded-for", "1.2.3.4")
+            .client_addr("127.0.0.1:1234".parse().unwrap())
+            .build();
+
+        assert_eq!(req.client_addr(), Some(ip("127.0.0.1")));
+    }
+
+    #[test]
+    fn test_client_addr_trusted() {
+        let req = request()
+            .header("x-forwarded-for", "1.2.3.4")
+            .client_addr("127.0.0.1:1234".parse().unwrap())
+            .trusted_proxies(trusted(&["127.0.0.1"]))
+            .build();
+
+        assert_eq!(req.client_addr(), Some(ip("1.2.3.4")));
+    }
+
+    #[test]
+    fn test_client_addr_ignores_untrusted_header() {
+        let req = request()
+            .header("forwarded", "for=5.6.7.8")
+            .header("x-forwarded-for", "1.2.3.4")
+            .client_addr("127.0.0.1:1234".parse().unwrap())
+            .trusted_proxies(trusted(&["127.0.0.0/8"]))
+            .build();
+
+        assert_eq!(req.client_addr(), Some(ip("1.2.3.4")));
+
+        let req = request()
+            .header("forwarded", "for=5.6.7.8")
+            .client_addr("127.0.0.1:1234".parse().unwrap())
+            .trusted_proxies(trusted(&["127.0.0.0/8"]))
+            .build();
+
+        assert_eq!(req.client_addr(), Some(ip("127.0.0.1")));
+    }
+
+    #[test]
+    fn test_client_addr_forwarded() {
+        let req = request()
+            .header("forwarded", "for=6.6.6.6, for=5.6.7.8;proto=https")
+            .header("x-forwarded-for", "1.2.3.4")
+            .client_addr("127.0.0.1:1234".parse().unwrap())
+            .trusted_proxies(
+                trusted(&["127.0.0.0/8"]).header(ForwardingHeader::Forwarded),
+            )
+            .build();
+
+        assert_eq!(req.client_addr(), Some(ip("5.6.7.8")));
+    }
+
+    #[test]
+    fn test_client_addr_unix() {
+        let credentials = PeerCredentials {
+            pid: Some(42),
+            uid: 1000,
+            gid: 100,
+        };
+        let req = request()
+            .header("x-forwarded-for", "1.2.3.4")
+            .extension(credentials)
+            .build();
+
+        let addr = req.client_addr().unwrap();
+        assert_eq!(addr, ClientAddr::Unix(credentials));
+        assert_eq!(addr.to_string(), "unix:pid=42,uid=1000,gid=100");
+    }
+}
@@ -0,0 +1,353 @@
+use super::{response, Body, Bytes, Error, Result, StatusCode};
+use crate::headers::{AcceptRanges, ContentRange, ContentType, Header, Range};
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use hyper::header::CONTENT_LENGTH;
+use rand::Rng;
+use std::io::SeekFrom;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// The most ranges a request gets served, beyond which the whole
+/// representation is sent instead (RFC 9110 §14.2)
+const MAX_RANGES: usize = 16;
+
+/// The bounds of each range in `range` as written, a suffix range having
+/// an unbounded start
+///
+/// `Range` only hands out bounds already resolved against a length, and
+/// drops suffixes longer than it rather than taking the whole.
+fn bounds(range: &Range) -> Vec<(Bound<u64>, Bound<u64>)> {
+    let bound = |bound: &str| match bound.trim() {
+        "" => Some(Bound::Unbounded),
+        bound => bound.parse().ok().map(Bound::Included),
+    };
+    let mut values = vec![];
+    range.encode(&mut values);
+    values
+        .iter()
+        .filter_map(|value| value.to_str().ok()?.strip_prefix("bytes="))
+        .flat_map(|specs| specs.split(','))
+        .filter_map(|spec| {
+            let (first, last) = spec.split_once('-')?;
+            Some((bound(first)?, bound(last)?))
+        })
+        .collect()
+}
+
+/// `range`, unless it asks for more ranges than are worth serving, in which
+/// case it is ignored
+pub fn requested(range: Option<Range>) -> Option<Range> {
+    range.filter(|range| bounds(range).len() <= MAX_RANGES)
+}
+
+/// Resolves the requested ranges against a representation of `len` bytes
+/// into inclusive `(first, last)` offsets, dropping any that can't be
+/// satisfied and merging those that overlap or touch
+pub fn satisfiable(range: &Range, len: u64) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = bounds(range)
+        .into_iter()
+        .filter_map(|bounds| match bounds {
+            (Bound::Included(first), Bound::Included(last))
+                if first <= last && first < len =>
+            {
+                Some((first, last.min(len - 1)))
+            }
+            (Bound::Included(first), Bound::Unbounded) if first < len => {
+                Some((first, len - 1))
+            }
+            // A suffix range, counting back from the end
+            (Bound::Unbounded, Bound::Included(suffix))
+                if suffix > 0 && len > 0 =>
+            {
+                Some((len - suffix.min(len), len - 1))
+            }
+            _ => None,
+        })
+        .collect();
+
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (first, last) in ranges {
+        match merged.last_mut() {
+            Some((_, end)) if first <= end.saturating_add(1) => {
+                *end = (*end).max(last)
+            }
+            _ => merged.push((first, last)),
+        }
+    }
+    merged
+}
+
+fn random_boundary() -> String {
+    format!("{:016x}", rand::thread_rng().gen::<u64>())
+}
+
+/// The headers opening the part of a `multipart/byteranges` body holding
+/// bytes `first` to `last` of `len`
+fn part_head(
+    boundary: &str,
+    content_type: &mime::Mime,
+    (first, last): (u64, u64),
+    len: u64,
+) -> String {
+    format!(
+        "--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+        boundary, content_type, first, last, len
+    )
+}
+
+fn multipart_byteranges(
+    data: &Bytes,
+    content_type: &mime::Mime,
+    ranges: &[(u64, u64)],
+    boundary: &str,
+) -> Vec<u8> {
+    let len = data.len() as u64;
+    let mut body = vec![];
+    for &(first, last) in ranges {
+        body.extend_from_slice(
+            part_head(boundary, content_type, (first, last), len).as_bytes(),
+        );
+        body.extend_from_slice(&data[first as usize..=last as usize]);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+/// Serves `data` whole, or just the parts asked for by a `Range` header
+///
+/// A single range is sent as is, several as `multipart/byteranges`, and a
+/// header with nothing satisfiable gets a 416. Too many ranges get the
+/// whole of `data`.
+pub fn ranged(
+    data: Bytes,
+    content_type: mime::Mime,
+    range: Option<Range>,
+) -> Result {
+    let len = data.len() as u64;
+    let res = response().typed_header(AcceptRanges::bytes());
+
+    let range = match requested(range) {
+        Some(range) => range,
+        None => {
+            return res.typed_header(ContentType::from(content_type)).body(data)
+        }
+    };
+
+    match satisfiable(&range, len).as_slice() {
+        [] => res
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .typed_header(ContentRange::unsatisfied_bytes(len))
+            .into(),
+        &[(first, last)] => res
+            .status(StatusCode::PARTIAL_CONTENT)
+            .typed_header(ContentType::from(content_type))
+            .typed_header(ContentRange::bytes(first..=last, len).unwrap())
+            .body(data.slice(first as usize..=last as usize)),
+        ranges => {
+            let boundary = random_boundary();
+            let body =
+                multipart_byteranges(&data, &content_type, ranges, &boundary);
+            res.status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    "content-type",
+                    format!("multipart/byteranges; boundary={}", boundary),
+                )
+                .body(body)
+        }
+    }
+}
+
+/// Streams bytes `first` to `last` of the file at `path`
+fn file_part(
+    path: PathBuf,
+    (first, last): (u64, u64),
+) -> impl Stream<Item = std::io::Result<Bytes>> {
+    stream::once(async move {
+        let mut file = File::open(path).await?;
+        file.seek(SeekFrom::Start(first)).await?;
+        Ok::<_, std::io::Error>(ReaderStream::new(file.take(last - first + 1)))
+    })
+    .try_flatten()
+}
+
+/// Like [`ranged`], but for the file at `path`, of which only the parts
+/// asked for are read
+pub async fn ranged_file(
+    path: &Path,
+    content_type: mime::Mime,
+    range: Option<Range>,
+) -> Result {
+    let res = response()
+        .typed_header(AcceptRanges::bytes())
+        .typed_header(ContentType::from(content_type.clone()));
+    let range = match requested(range) {
+        Some(range) => range,
+        None => return res.body_from_file(path).await,
+    };
+    let len = tokio::fs::metadata(path)
+        .await
+        .map_err(Error::internal)?
+        .len();
+
+    match satisfiable(&range, len).as_slice() {
+        [] => response()
+            .typed_header(AcceptRanges::bytes())
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .typed_header(ContentRange::unsatisfied_bytes(len))
+            .into(),
+        &[(first, last)] => res
+            .status(StatusCode::PARTIAL_CONTENT)
+            .typed_header(ContentRange::bytes(first..=last, len).unwrap())
+            .header(CONTENT_LENGTH, last - first + 1)
+            .body(Body::wrap_stream(file_part(path.into(), (first, last)))),
+        ranges => {
+            let boundary = random_boundary();
+            let closing = format!("--{}--\r\n", boundary);
+            let mut length = closing.len() as u64;
+            let mut parts = vec![];
+            for &range in ranges {
+                let head = part_head(&boundary, &content_type, range, len);
+                length += head.len() as u64 + range.1 - range.0 + 1 + 2;
+                parts.push(
+                    stream::once(future::ok(Bytes::from(head)))
+                        .chain(file_part(path.into(), range))
+                        .chain(stream::once(future::ok(Bytes::from("\r\n")))),
+                );
+            }
+            let body = stream::iter(parts)
+                .flatten()
+                .chain(stream::once(future::ok(Bytes::from(closing))));
+            response()
+                .typed_header(AcceptRanges::bytes())
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    "content-type",
+                    format!("multipart/byteranges; boundary={}", boundary),
+                )
+                .header(CONTENT_LENGTH, length)
+                .body(Body::wrap_stream(body))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headers::HeaderMapExt;
+    use crate::test::*;
+
+    fn header(value: &'static str) -> Range {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert("range", value.parse().unwrap());
+        headers.typed_get::<Range>().unwrap()
+    }
+
+    #[test]
+    fn test_satisfiable() {
+        assert_eq!(satisfiable(&header("bytes=0-4"), 10), [(0, 4)]);
+        assert_eq!(satisfiable(&header("bytes=5-"), 10), [(5, 9)]);
+        assert_eq!(satisfiable(&header("bytes=-3"), 10), [(7, 9)]);
+        assert_eq!(satisfiable(&header("bytes=-30"), 10), [(0, 9)]);
+        assert_eq!(satisfiable(&header("bytes=8-20"), 10), [(8, 9)]);
+        assert_eq!(
+            satisfiable(&header("bytes=0-1, 4-5"), 10),
+            [(0, 1), (4, 5)]
+        );
+    }
+
+    #[test]
+    fn test_satisfiable_merges() {
+        assert_eq!(satisfiable(&header("bytes=0-,0-,0-"), 10), [(0, 9)]);
+        assert_eq!(
+            satisfiable(&header("bytes=4-5, 0-1, 2-3, 8-"), 10),
+            [(0, 5), (8, 9)]
+        );
+        assert_eq!(satisfiable(&header("bytes=-2, 7-8"), 10), [(7, 9)]);
+    }
+
+    #[test]
+    fn test_unsatisfiable() {
+        assert!(satisfiable(&header("bytes=10-"), 10).is_empty());
+        assert!(satisfiable(&header("bytes=5-2"), 10).is_empty());
+        assert!(satisfiable(&header("bytes=-0"), 10).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ranged_full() {
+        let res = ranged("abcdef".into(), mime::TEXT_PLAIN, None).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("accept-ranges").unwrap(), "bytes");
+        assert_eq!(res.read_body_utf8().await.unwrap(), "abcdef");
+    }
+
+    #[tokio::test]
+    async fn test_ranged_single() {
+        let res = ranged(
+            "abcdef".into(),
+            mime::TEXT_PLAIN,
+            Some(header("bytes=1-2")),
+        )
+        .unwrap();
+
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.headers().get("content-range").unwrap(), "bytes 1-2/6");
+        assert_eq!(res.read_body_utf8().await.unwrap(), "bc");
+    }
+
+    #[tokio::test]
+    async fn test_ranged_multiple() {
+        let res = ranged(
+            "abcdef".into(),
+            mime::TEXT_PLAIN,
+            Some(header("bytes=0-0,-2")),
+        )
+        .unwrap();
+
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        let content_type =
+            res.headers().get("content-type").unwrap().to_str().unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap()
+            .to_owned();
+        assert_eq!(
+            res.read_body_utf8().await.unwrap(),
+            format!(
+                "--{b}\r\nContent-Type: text/plain\r\n\
+                 Content-Range: bytes 0-0/6\r\n\r\na\r\n\
+                 --{b}\r\nContent-Type: text/plain\r\n\
+                 Content-Range: bytes 4-5/6\r\n\r\nef\r\n--{b}--\r\n",
+                b = boundary
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ranged_too_many() {
+        let specs = vec!["0-0"; MAX_RANGES + 1].join(",");
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert("range", format!("bytes={}", specs).parse().unwrap());
+        let range = headers.typed_get::<Range>();
+
+        let res = ranged("abcdef".into(), mime::TEXT_PLAIN, range).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.read_body_utf8().await.unwrap(), "abcdef");
+    }
+
+    #[tokio::test]
+    async fn test_ranged_unsatisfiable() {
+        let res =
+            ranged("abcdef".into(), mime::TEXT_PLAIN, Some(header("bytes=6-")))
+                .unwrap();
+
+        assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(res.headers().get("content-range").unwrap(), "bytes */6");
+    }
+}
@@ -0,0 +1,227 @@
+//! Token buckets limiting how often each client may make requests
+use super::{response, Error, Request, StatusCode};
+use crate::headers::RetryAfter;
+use hyper::header::HeaderName;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The most buckets kept, beyond which the least recently used is dropped,
+/// being the likeliest to have refilled and be no different from a fresh one
+const MAX_BUCKETS: usize = 10_000;
+
+/// Requests refill at `rate` per second up to `burst` at once
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limit {
+    pub rate: f64,
+    pub burst: u32,
+}
+
+impl Limit {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self { rate, burst }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// What a client is told once it has no requests left
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Exhausted {
+    pub retry_after: Duration,
+}
+
+impl From<Exhausted> for Error {
+    fn from(exhausted: Exhausted) -> Self {
+        // Retry-After only has whole seconds, so round up to not be early
+        let seconds = exhausted.retry_after.as_secs_f64().ceil().max(1.0);
+        response()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .typed_header(RetryAfter::delay(Duration::from_secs(
+                seconds as u64,
+            )))
+            .into()
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    buckets: HashMap<String, Bucket>,
+    /// The keys of `buckets` by when they were last used, oldest first
+    used: BTreeSet<(Instant, String)>,
+}
+
+#[derive(Debug, Default)]
+pub struct Buckets(Mutex<State>);
+
+impl Buckets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a request from the bucket of `key`, returning how many are left
+    pub fn take(&self, key: &str, limit: Limit) -> Result<u32, Exhausted> {
+        self.take_at(key, limit, Instant::now())
+    }
+
+    fn take_at(
+        &self,
+        key: &str,
+        limit: Limit,
+        now: Instant,
+    ) -> Result<u32, Exhausted> {
+        let burst = f64::from(limit.burst);
+        let mut state = self.0.lock().unwrap();
+        let State { buckets, used } = &mut *state;
+        if !buckets.contains_key(key) && buckets.len() >= MAX_BUCKETS {
+            if let Some((_, oldest)) = used.pop_first() {
+                buckets.remove(&oldest);
+            }
+        }
+        let bucket = buckets.entry(key.to_owned()).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * limit.rate).min(burst);
+        used.remove(&(bucket.updated, key.to_owned()));
+        bucket.updated = now;
+        used.insert((bucket.updated, key.to_owned()));
+
+        if bucket.tokens < 1.0 {
+            let missing = 1.0 - bucket.tokens;
+            return Err(Exhausted {
+                retry_after: Duration::from_secs_f64(missing / limit.rate),
+            });
+        }
+        bucket.tokens -= 1.0;
+        Ok(bucket.tokens as u32)
+    }
+}
+
+/// What tells clients apart for rate limiting
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ClientKey {
+    /// The client address, see `Request::client_addr`
+    #[default]
+    Addr,
+    /// A request header, such as an API key, falling back to the address
+    Header(HeaderName),
+}
+
+impl Request {
+    /// The bucket the request counts against
+    pub fn client_key(&self, key: &ClientKey) -> String {
+        if let ClientKey::Header(name) = key {
+            if let Some(value) = self.headers().get(name) {
+                return String::from_utf8_lossy(value.as_bytes()).into_owned();
+            }
+        }
+        self.client_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    const LIMIT: Limit = Limit {
+        rate: 2.0,
+        burst: 3,
+    };
+
+    #[test]
+    fn test_burst() {
+        let buckets = Buckets::new();
+        let now = Instant::now();
+
+        assert_eq!(buckets.take_at("a", LIMIT, now), Ok(2));
+        assert_eq!(buckets.take_at("a", LIMIT, now), Ok(1));
+        assert_eq!(buckets.take_at("a", LIMIT, now), Ok(0));
+        assert_eq!(
+            buckets.take_at("a", LIMIT, now),
+            Err(Exhausted {
+                retry_after: Duration::from_millis(500)
+            })
+        );
+        assert_eq!(buckets.take_at("b", LIMIT, now), Ok(2));
+    }
+
+    #[test]
+    fn test_refill() {
+        let buckets = Buckets::new();
+        let now = Instant::now();
+        for _ in 0..3 {
+            buckets.take_at("a", LIMIT, now).unwrap();
+        }
+
+        let later = now + Duration::from_millis(500);
+        assert_eq!(buckets.take_at("a", LIMIT, later), Ok(0));
+        assert!(buckets.take_at("a", LIMIT, later).is_err());
+
+        let much_later = now + Duration::from_secs(60);
+        assert_eq!(buckets.take_at("a", LIMIT, much_later), Ok(2));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let buckets = Buckets::new();
+        let now = Instant::now();
+        for i in 0..MAX_BUCKETS {
+            let at = now + Duration::from_millis(i as u64);
+            buckets.take_at(&i.to_string(), LIMIT, at).unwrap();
+        }
+
+        let later = now + Duration::from_secs(60);
+        buckets.take_at("0", LIMIT, later).unwrap();
+        buckets.take_at("new", LIMIT, later).unwrap();
+        let state = buckets.0.lock().unwrap();
+        assert_eq!(state.buckets.len(), MAX_BUCKETS);
+        assert_eq!(state.used.len(), MAX_BUCKETS);
+        assert!(state.buckets.contains_key("0"));
+        assert!(!state.buckets.contains_key("1"));
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_response() {
+        let error: Error = Exhausted {
+            retry_after: Duration::from_millis(1500),
+        }
+        .into();
+        let res = error.into_result().await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()["retry-after"], "2");
+    }
+
+    #[test]
+    fn test_client_key() {
+        let req = request()
+            .client_addr("10.0.0.1:1234".parse().unwrap())
+            .header("x-api-key", "secret")
+            .build();
+
+        assert_eq!(req.client_key(&ClientKey::Addr), "10.0.0.1");
+        assert_eq!(
+            req.client_key(&ClientKey::Header(HeaderName::from_static(
+                "x-api-key"
+            ))),
+            "secret"
+        );
+        assert_eq!(
+            req.client_key(&ClientKey::Header(HeaderName::from_static(
+                "x-missing"
+            ))),
+            "10.0.0.1"
+        );
+    }
+}
@@ -1,9 +1,23 @@
-use crate::headers::{Header, HeaderMapExt};
-use hyper::http::Request as HTTPRequest;
-use hyper::Body;
-use std::net::SocketAddr;
+use super::{
+    bad_request, is_length_limit_exceeded, payload_too_large,
+    unsupported_media_type, Body, Error,
+};
+use crate::config::Config;
+use crate::headers::{ContentType, Cookie, Header, HeaderMapExt};
+use crate::router::{AllowedMethods, Route};
+use cookie::Cookie as HTTPCookie;
+use hyper::body::Bytes;
+use hyper::http::{Extensions, Request as HTTPRequest};
+use hyper::upgrade::OnUpgrade;
+use hyper::Method;
+use lazy_static::lazy_static;
+use std::sync::Arc;
 use uri_path::PathMatch;
 
+lazy_static! {
+    static ref DEFAULT_CONFIG: Config = Config::default();
+}
+
 mod de {
     use serde::de::{value::Error, Deserialize, IntoDeserializer};
 
@@ -32,18 +46,101 @@ impl Request {
         T::from_str(str).ok()
     }
 
+    /// The values of the route's parameters, by name
+    pub fn path_params(&self) -> &PathMatch {
+        &self.params
+    }
+
     pub fn params<'a, T: serde::de::Deserialize<'a>>(&self) -> Option<T> {
         de::deserialize(self.params.clone()).ok()
     }
 
     pub fn body(&mut self) -> Body {
-        std::mem::replace(self.req.body_mut(), Body::empty())
+        std::mem::take(self.req.body_mut())
+    }
+
+    /// For middleware to wrap the body before the handler reads it
+    pub fn body_mut(&mut self) -> &mut Body {
+        self.req.body_mut()
+    }
+
+    /// For middleware to hand values down to handlers
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        self.req.extensions_mut()
+    }
+
+    /// Reads the whole body, failing with 413 if it exceeds the route's limit
+    pub async fn bytes(&mut self) -> std::result::Result<Bytes, Error> {
+        crate::http::to_bytes(self.body()).await.map_err(|e| {
+            if is_length_limit_exceeded(&*e) {
+                payload_too_large()
+            } else {
+                bad_request()
+            }
+        })
+    }
+
+    /// Deserializes an `application/x-www-form-urlencoded` body
+    ///
+    /// Fails with 415 for any other content type and 400 if the body does not
+    /// deserialize into `T`.
+    pub async fn form<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> std::result::Result<T, Error> {
+        if !self.has_content_type(mime::APPLICATION, mime::WWW_FORM_URLENCODED)
+        {
+            return Err(unsupported_media_type());
+        }
+        let bytes = self.bytes().await?;
+        serde_urlencoded::from_bytes(&bytes).map_err(|_| bad_request())
+    }
+
+    /// Deserializes an `application/json` (or `+json`) body
+    ///
+    /// Fails with 415 for any other content type and 400 if the body does not
+    /// deserialize into `T`.
+    pub async fn json<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> std::result::Result<T, Error> {
+        if !self.has_content_type(mime::APPLICATION, mime::JSON) {
+            return Err(unsupported_media_type());
+        }
+        let bytes = self.bytes().await?;
+        serde_json::from_slice(&bytes).map_err(|_| bad_request())
+    }
+
+    /// Whether the body is `type_/subtype`, ignoring parameters and
+    /// accepting a `+subtype` suffix
+    pub fn has_content_type(
+        &self,
+        type_: mime::Name,
+        subtype: mime::Name,
+    ) -> bool {
+        self.typed_header::<ContentType>()
+            .map(mime::Mime::from)
+            .is_some_and(|content_type| {
+                content_type.type_() == type_
+                    && (content_type.subtype() == subtype
+                        || content_type.suffix() == Some(subtype))
+            })
+    }
+
+    /// Resolves to the raw connection once an upgrade response is sent
+    pub fn upgrade(&mut self) -> OnUpgrade {
+        hyper::upgrade::on(&mut self.req)
     }
 
     pub fn typed_header<H: Header>(&self) -> Option<H> {
         self.req.headers().typed_get::<H>()
     }
 
+    /// The cookies sent in the `Cookie` header, if it parses
+    pub fn cookies(&self) -> Vec<HTTPCookie<'static>> {
+        self.typed_header::<Cookie<'static>>()
+            .map(|cookie| cookie.0)
+            .unwrap_or_default()
+    }
+
     pub fn query<'a, T: serde::de::Deserialize<'a>>(
         &'a self,
     ) -> std::result::Result<T, serde_urlencoded::de::Error> {
@@ -51,8 +148,30 @@ impl Request {
         serde_urlencoded::from_str(query_string)
     }
 
-    pub fn client_addr(&self) -> Option<&SocketAddr> {
-        self.req.extensions().get::<SocketAddr>()
+    /// The route this request was dispatched to, if any
+    pub fn route(&self) -> Option<&Route> {
+        self.req.extensions().get::<Arc<Route>>().map(AsRef::as_ref)
+    }
+
+    /// The methods accepted at the request's path, if any route matches it,
+    /// which may not include its own method
+    pub fn allowed_methods(&self) -> Option<&[Method]> {
+        match self.route() {
+            Some(route) => Some(route.methods()),
+            None => self
+                .req
+                .extensions()
+                .get::<AllowedMethods>()
+                .map(|allowed| allowed.0.as_slice()),
+        }
+    }
+
+    /// The settings of the router, or the defaults outside of one
+    pub fn config(&self) -> &Config {
+        self.req
+            .extensions()
+            .get::<Arc<Config>>()
+            .map_or(&DEFAULT_CONFIG, AsRef::as_ref)
     }
 }
 
@@ -63,3 +182,93 @@ impl core::ops::Deref for Request {
         &self.req
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+    use serde_derive::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Login {
+        user: String,
+        remember: Option<bool>,
+    }
+
+    #[tokio::test]
+    async fn test_form() {
+        let mut req = request()
+            .typed_header(ContentType::form_url_encoded())
+            .body("user=akito&remember=true")
+            .build();
+
+        assert_eq!(
+            req.form::<Login>().await.unwrap(),
+            Login {
+                user: "akito".to_owned(),
+                remember: Some(true),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_form_invalid() {
+        let mut req = request()
+            .typed_header(ContentType::form_url_encoded())
+            .body("remember=true")
+            .build();
+
+        let res = req.form::<Login>().await.unwrap_err().into_result().await;
+        assert_eq!(res.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_form_content_type() {
+        let mut req = request()
+            .typed_header(ContentType::json())
+            .body(r#"{"user": "akito"}"#)
+            .build();
+
+        let res = req.form::<Login>().await.unwrap_err().into_result().await;
+        assert_eq!(res.unwrap().status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn test_json() {
+        let mut req = request()
+            .header("content-type", "application/merge-patch+json")
+            .body(r#"{"user": "akito"}"#)
+            .build();
+
+        assert_eq!(
+            req.json::<Login>().await.unwrap(),
+            Login {
+                user: "akito".to_owned(),
+                remember: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_invalid() {
+        let mut req = request()
+            .typed_header(ContentType::json())
+            .body(r#"{"user": 1}"#)
+            .build();
+
+        let res = req.json::<Login>().await.unwrap_err().into_result().await;
+        assert_eq!(res.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_json_content_type() {
+        let mut req = request()
+            .typed_header(ContentType::form_url_encoded())
+            .body("user=akito")
+            .build();
+
+        let res = req.json::<Login>().await.unwrap_err().into_result().await;
+        assert_eq!(res.unwrap().status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
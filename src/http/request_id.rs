@@ -0,0 +1,69 @@
+use super::Request;
+use std::fmt;
+
+/// Identifies a request across the client, the logs and the response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// The longest client supplied ID that is passed along as is
+    pub const MAX_LEN: usize = 128;
+
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// Accepts a client supplied ID of visible ASCII characters
+    pub fn parse(value: &[u8]) -> Option<Self> {
+        let valid = !value.is_empty()
+            && value.len() <= Self::MAX_LEN
+            && value.iter().all(|b| b.is_ascii_graphic());
+        valid.then(|| Self(String::from_utf8_lossy(value).into_owned()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Request {
+    /// The ID given to this request by the `RequestIds` middleware
+    pub fn id(&self) -> Option<&str> {
+        self.extensions().get::<RequestId>().map(RequestId::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(RequestId::parse(b"abc-123").unwrap().as_str(), "abc-123");
+        assert!(RequestId::parse(b"").is_none());
+        assert!(RequestId::parse(b"with space").is_none());
+        assert!(RequestId::parse(&[b'a'; RequestId::MAX_LEN + 1]).is_none());
+    }
+
+    #[test]
+    fn test_generate() {
+        assert_ne!(RequestId::generate(), RequestId::generate());
+    }
+
+    #[test]
+    fn test_id() {
+        assert_eq!(request().build().id(), None);
+
+        let req = request()
+            .extension(RequestId::parse(b"abc").unwrap())
+            .build();
+        assert_eq!(req.id(), Some("abc"));
+    }
+}
@@ -1,6 +1,7 @@
 use super::{Body, Error, Result, StatusCode, Uri};
-use crate::headers::{ContentType, Header, HeaderMapExt, Location};
+use crate::headers::{Allow, ContentType, Header, HeaderMapExt, Location};
 use hyper::http::Response as HTTPResponse;
+use hyper::Method;
 
 pub type Response = HTTPResponse<Body>;
 
@@ -19,10 +20,25 @@ impl ResponseTypedHeaderExt for hyper::http::response::Builder {
 
 mod wrapper {
     use super::{Body, Error, ResponseTypedHeaderExt, Result};
-    use crate::headers::Header;
+    use crate::headers::{ContentType, Header, SetCookie};
+    use crate::http::{body_with_trailers, body_with_trailers_from};
+    use cookie::Cookie;
+    use futures::Stream;
+    use hyper::body::Bytes;
+    use hyper::header::TRAILER;
     use hyper::header::{HeaderName, HeaderValue};
+    use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+    use hyper::HeaderMap;
     use hyper::StatusCode;
     use std::convert::TryFrom;
+    use std::io::ErrorKind;
+    use std::path::Path;
+    use tokio::fs::File;
+    use tokio::io::AsyncRead;
+    use tokio_util::io::ReaderStream;
+
+    /// Large enough for few reads of big files, the kernel reading ahead
+    const FILE_CHUNK_SIZE: usize = 64 * 1024;
 
     pub struct ResponseWrapper(pub hyper::http::response::Builder);
 
@@ -48,9 +64,110 @@ mod wrapper {
             self
         }
 
+        /// Appends a `Set-Cookie`, keeping any set before it
+        pub fn cookie(mut self, cookie: Cookie<'_>) -> Self {
+            if let Some(headers) = self.0.headers_mut() {
+                let mut values = vec![];
+                SetCookie(cookie).encode(&mut values);
+                for value in values {
+                    headers.append(SetCookie::name(), value);
+                }
+            }
+            self
+        }
+
         pub fn body<B: Into<Body>>(self, body: B) -> Result {
             self.0.body(body.into()).map_err(Into::into)
         }
+
+        /// Streams `chunks` followed by `trailers`, announcing their names
+        /// in the `Trailer` header
+        pub fn body_with_trailers<S>(
+            self,
+            chunks: S,
+            trailers: HeaderMap,
+        ) -> Result
+        where
+            S: Stream<Item = Bytes> + Send + 'static,
+        {
+            let names = trailers
+                .keys()
+                .map(HeaderName::as_str)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let builder = if names.is_empty() {
+                self
+            } else {
+                self.header(TRAILER, names)
+            };
+            builder.body(body_with_trailers(chunks, trailers))
+        }
+
+        /// Streams `chunks` followed by the trailers `trailers` makes after
+        /// the last of them, announced as `names`
+        pub fn body_with_trailers_from<S, F>(
+            self,
+            chunks: S,
+            names: &[&HeaderName],
+            trailers: F,
+        ) -> Result
+        where
+            S: Stream<Item = Bytes> + Send + 'static,
+            F: FnOnce() -> HeaderMap + Send + 'static,
+        {
+            let names = names
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.header(TRAILER, names)
+                .body(body_with_trailers_from(chunks, trailers))
+        }
+
+        /// Streams whatever `reader` reads until it is exhausted, failing
+        /// the body, and so the connection, if reading fails
+        pub fn body_from_reader<R>(self, reader: R) -> Result
+        where
+            R: AsyncRead + Send + 'static,
+        {
+            self.body(Body::wrap_stream(ReaderStream::new(reader)))
+        }
+
+        /// Streams the file at `path`, with its length as `Content-Length`
+        /// and, unless one is set, a `Content-Type` guessed from its
+        /// extension
+        ///
+        /// The file is never read into memory as a whole.
+        pub async fn body_from_file<P: AsRef<Path>>(self, path: P) -> Result {
+            let path = path.as_ref();
+            let file = File::open(path).await.map_err(|e| match e.kind() {
+                ErrorKind::NotFound => Error::NotFound,
+                _ => Error::internal(e),
+            })?;
+            let len = file.metadata().await.map_err(Error::internal)?.len();
+
+            let typed = self
+                .0
+                .headers_ref()
+                .is_some_and(|headers| headers.contains_key(CONTENT_TYPE));
+            let builder = if typed {
+                self
+            } else {
+                let mime = mime_guess::from_path(path).first_or_octet_stream();
+                self.typed_header(ContentType::from(mime))
+            };
+            let chunks = ReaderStream::with_capacity(file, FILE_CHUNK_SIZE);
+            builder
+                .header(CONTENT_LENGTH, len)
+                .body(Body::wrap_stream(chunks))
+        }
+
+        /// Serializes `value` as pretty-printed JSON
+        pub fn json<T: serde::Serialize + ?Sized>(self, value: &T) -> Result {
+            let body =
+                serde_json::to_vec_pretty(value).map_err(Error::internal)?;
+            self.typed_header(ContentType::json()).body(body)
+        }
     }
 
     impl From<ResponseWrapper> for Result {
@@ -74,6 +191,10 @@ pub fn html<B: Into<Body>>(body: B) -> Result {
     response().typed_header(ContentType::html()).body(body)
 }
 
+pub fn json<T: serde::Serialize + ?Sized>(value: &T) -> Result {
+    response().json(value)
+}
+
 pub fn ok<B>(body: B) -> Result
 where
     B: Into<Body>,
@@ -82,20 +203,49 @@ where
 }
 
 pub fn not_found() -> Error {
-    response().status(StatusCode::NOT_FOUND).into()
+    Error::NotFound
+}
+
+pub fn method_not_allowed<'a, I: IntoIterator<Item = &'a Method>>(
+    allowed: I,
+) -> Error {
+    response()
+        .status(StatusCode::METHOD_NOT_ALLOWED)
+        .typed_header(allowed.into_iter().cloned().collect::<Allow>())
+        .into()
 }
 
 pub fn bad_request() -> Error {
-    response().status(StatusCode::BAD_REQUEST).into()
+    Error::bad_request("")
+}
+
+pub fn not_acceptable() -> Error {
+    response().status(StatusCode::NOT_ACCEPTABLE).into()
+}
+
+pub fn payload_too_large() -> Error {
+    response().status(StatusCode::PAYLOAD_TOO_LARGE).into()
+}
+
+pub fn unsupported_media_type() -> Error {
+    response().status(StatusCode::UNSUPPORTED_MEDIA_TYPE).into()
 }
 
-pub fn internal_server_error() -> Error {
-    response().status(StatusCode::INTERNAL_SERVER_ERROR).into()
+pub fn gateway_timeout() -> Error {
+    Error::Timeout
+}
+
+pub fn client_disconnected() -> Error {
+    Error::Disconnected
 }
 
 pub fn redirect_to(uri: Uri) -> Result {
+    redirect_with_status(uri, StatusCode::FOUND)
+}
+
+pub fn redirect_with_status(uri: Uri, status: StatusCode) -> Result {
     response()
-        .status(StatusCode::FOUND)
+        .status(status)
         .typed_header(Location::from(uri))
         .into()
 }
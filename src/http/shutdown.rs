@@ -0,0 +1,69 @@
+//! Cutting long-running responses short once the server starts draining
+use super::Request;
+use futures::prelude::*;
+use tokio_util::sync::CancellationToken;
+
+/// Signalled when the server stops accepting connections, so streaming
+/// responses can end early instead of holding up the shutdown
+#[derive(Clone, Debug, Default)]
+pub struct Draining(CancellationToken);
+
+impl Draining {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start(&self) {
+        self.0.cancel()
+    }
+
+    pub async fn wait(&self) {
+        self.0.cancelled().await
+    }
+}
+
+impl Request {
+    /// Resolves once the server starts draining, or never outside a server
+    pub fn draining(&self) -> impl Future<Output = ()> + Send + 'static {
+        let draining = self.extensions().get::<Draining>().cloned();
+        async move {
+            match draining {
+                Some(draining) => draining.wait().await,
+                None => future::pending().await,
+            }
+        }
+    }
+
+    /// Ends `stream` as soon as the server starts draining
+    pub fn until_draining<S: Stream>(
+        &self,
+        stream: S,
+    ) -> impl Stream<Item = S::Item> {
+        stream.take_until(self.draining())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_until_draining() {
+        let draining = Draining::new();
+        let req = request().extension(draining.clone()).build();
+
+        let mut stream = Box::pin(req.until_draining(stream::repeat(1)));
+        assert_eq!(stream.next().await, Some(1));
+        draining.start();
+        assert_eq!(stream.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_until_draining_without_server() {
+        let req = request().build();
+
+        let stream = req.until_draining(stream::iter(vec![1, 2]));
+        assert_eq!(stream.collect::<Vec<_>>().await, vec![1, 2]);
+    }
+}
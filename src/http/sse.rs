@@ -0,0 +1,118 @@
+//! `text/event-stream` framing for Server-Sent Events
+use super::Bytes;
+use std::fmt;
+use std::time::Duration;
+
+fn single_line(value: String) -> String {
+    value.replace(['\r', '\n'], "")
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(single_line(id.into()));
+        self
+    }
+
+    pub fn event<S: Into<String>>(mut self, event: S) -> Self {
+        self.event = Some(single_line(event.into()));
+        self
+    }
+
+    pub fn data<S: Into<String>>(mut self, data: S) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Hints how long the client should wait before reconnecting
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(retry) = self.retry {
+            writeln!(f, "retry: {}", retry.as_millis())?;
+        }
+        if let Some(id) = &self.id {
+            writeln!(f, "id: {}", id)?;
+        }
+        if let Some(event) = &self.event {
+            writeln!(f, "event: {}", event)?;
+        }
+        if let Some(data) = &self.data {
+            for line in data.split('\n') {
+                writeln!(f, "data: {}", line.trim_end_matches('\r'))?;
+            }
+        }
+        writeln!(f)
+    }
+}
+
+impl From<Event> for Bytes {
+    fn from(event: Event) -> Self {
+        event.to_string().into()
+    }
+}
+
+/// Encodes a comment line, ignored by clients but useful as a keep-alive
+pub fn comment(text: &str) -> Bytes {
+    format!(": {}\n\n", single_line(text.to_owned())).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_data() {
+        assert_eq!(Event::new().data("hello").to_string(), "data: hello\n\n");
+    }
+
+    #[test]
+    fn test_encode_multiline_data() {
+        assert_eq!(
+            Event::new().data("first\r\nsecond").to_string(),
+            "data: first\ndata: second\n\n"
+        );
+    }
+
+    #[test]
+    fn test_encode_all_fields() {
+        assert_eq!(
+            Event::new()
+                .id("1")
+                .event("tick")
+                .data("1")
+                .retry(Duration::from_secs(3))
+                .to_string(),
+            "retry: 3000\nid: 1\nevent: tick\ndata: 1\n\n"
+        );
+    }
+
+    #[test]
+    fn test_encode_strips_newlines() {
+        assert_eq!(
+            Event::new().id("1\n2").event("a\r\nb").to_string(),
+            "id: 12\nevent: ab\n\n"
+        );
+    }
+
+    #[test]
+    fn test_comment() {
+        assert_eq!(comment("heartbeat"), Bytes::from(": heartbeat\n\n"));
+    }
+}
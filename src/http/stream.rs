@@ -1,6 +1,7 @@
+use super::Body;
 use futures::prelude::*;
 use hyper::body::Bytes;
-use hyper::Body;
+use hyper::HeaderMap;
 use std::convert::Infallible;
 
 pub(crate) fn ok_stream<T, S: Stream<Item = T>>(
@@ -17,3 +18,34 @@ where
 {
     Body::wrap_stream(ok_stream(stream).into_stream())
 }
+
+/// Sends each of `chunks` as its own chunk, then `trailers`
+///
+/// Trailers are only sent over HTTP/2, hyper dropping them from HTTP/1.1
+/// responses.
+pub(crate) fn body_with_trailers<S>(chunks: S, trailers: HeaderMap) -> Body
+where
+    S: Stream<Item = Bytes> + Send + 'static,
+{
+    body_with_trailers_from(chunks, move || trailers)
+}
+
+/// Like `body_with_trailers`, making the trailers only once every chunk is
+/// sent, so that they can depend on the chunks
+pub(crate) fn body_with_trailers_from<S, F>(chunks: S, trailers: F) -> Body
+where
+    S: Stream<Item = Bytes> + Send + 'static,
+    F: FnOnce() -> HeaderMap + Send + 'static,
+{
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        futures::pin_mut!(chunks);
+        while let Some(chunk) = chunks.next().await {
+            if sender.send_data(chunk).await.is_err() {
+                return;
+            }
+        }
+        let _ = sender.send_trailers(trailers()).await;
+    });
+    body
+}
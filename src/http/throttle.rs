@@ -0,0 +1,98 @@
+use futures::prelude::*;
+use hyper::body::Bytes;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+/// Paces a stream of chunks to `rate` bytes per second
+///
+/// Chunks are split so that no more than a tenth of a second's worth is
+/// sent at once, and each is held back until the bytes before it are due,
+/// so the stream keeps to the rate however fast it is polled.
+pub struct ThrottledStream<S> {
+    inner: S,
+    rate: f64,
+    max_chunk: usize,
+    /// What is left of the last chunk of `inner`
+    pending: Option<Bytes>,
+    start: Option<Instant>,
+    sent: u64,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> ThrottledStream<S> {
+    pub fn new(inner: S, rate: u64) -> Self {
+        let rate = rate.max(1);
+        Self {
+            inner,
+            rate: rate as f64,
+            max_chunk: (rate / 10).max(1) as usize,
+            pending: None,
+            start: None,
+            sent: 0,
+            sleep: None,
+        }
+    }
+}
+
+impl<S: Stream<Item = Bytes> + Unpin> Stream for ThrottledStream<S> {
+    type Item = Bytes;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Bytes>> {
+        if let Some(sleep) = &mut self.sleep {
+            futures::ready!(sleep.as_mut().poll(cx));
+            self.sleep = None;
+        }
+
+        let mut chunk = match self.pending.take() {
+            Some(chunk) => chunk,
+            None => match futures::ready!(self.inner.poll_next_unpin(cx)) {
+                Some(chunk) => chunk,
+                None => return Poll::Ready(None),
+            },
+        };
+        if chunk.len() > self.max_chunk {
+            self.pending = Some(chunk.split_off(self.max_chunk));
+        }
+
+        let start = *self.start.get_or_insert_with(Instant::now);
+        self.sent += chunk.len() as u64;
+        let due = start + Duration::from_secs_f64(self.sent as f64 / self.rate);
+        self.sleep = Some(Box::pin(tokio::time::sleep_until(due)));
+        Poll::Ready(Some(chunk))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_splits_chunks() {
+        let chunks = stream::iter(vec![Bytes::from(vec![0; 250])]);
+        let sizes = ThrottledStream::new(chunks, 1000)
+            .map(|chunk| chunk.len())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(sizes, [100, 100, 50]);
+    }
+
+    #[tokio::test]
+    async fn test_paces() {
+        let chunks = stream::iter(vec![Bytes::from(vec![0; 300])]);
+        let start = std::time::Instant::now();
+        let throttled = ThrottledStream::new(chunks, 1000);
+        let received = throttled
+            .fold(0, |received, chunk| async move { received + chunk.len() })
+            .await;
+
+        assert_eq!(received, 300);
+        // The first 100 bytes go out right away, the others 0.1s apart
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
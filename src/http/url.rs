@@ -0,0 +1,218 @@
+use super::{ForwardingHeader, Request, Uri};
+use crate::headers::Host;
+use hyper::header::FORWARDED;
+use lazy_static::lazy_static;
+use std::env;
+use url::Url;
+
+lazy_static! {
+    static ref BASE_URL: Option<Url> =
+        env::var_os("BASE_URL")?.into_string().ok()?.parse().ok();
+}
+
+fn host_to_url(scheme: &str, host: &str) -> anyhow::Result<Url> {
+    Ok(Uri::builder()
+        .scheme(scheme)
+        .authority(host)
+        .path_and_query("/")
+        .build()?
+        .to_string()
+        .parse::<Url>()?)
+}
+
+/// The last element of the comma separated `name` headers, which is the
+/// one the proxy nearest to us added
+fn last_element<'a>(req: &'a Request, name: &str) -> Option<&'a str> {
+    let value = req.headers().get_all(name).iter().next_back()?;
+    Some(value.to_str().ok()?.rsplit(',').next()?.trim())
+}
+
+/// The scheme the client used, as reported by a trusted proxy in front of
+/// us in the header it is trusted for
+fn forwarded_scheme(req: &Request) -> &'static str {
+    let proto = match req.trusted_header() {
+        Some(ForwardingHeader::XForwardedFor) => {
+            last_element(req, "x-forwarded-proto")
+        }
+        Some(ForwardingHeader::Forwarded) => {
+            last_element(req, FORWARDED.as_str()).and_then(|element| {
+                element.split(';').find_map(|pair| {
+                    let (name, proto) = pair.trim().split_once('=')?;
+                    name.eq_ignore_ascii_case("proto")
+                        .then(|| proto.trim_matches('"'))
+                })
+            })
+        }
+        None => None,
+    };
+    match proto {
+        Some(proto) if proto.eq_ignore_ascii_case("https") => "https",
+        _ => "http",
+    }
+}
+
+fn host_from_headers(req: &Request) -> anyhow::Result<Url> {
+    let host = req
+        .typed_header::<Host>()
+        .ok_or_else(|| anyhow::anyhow!("no host header found"))?
+        .to_string();
+
+    host_to_url(forwarded_scheme(req), &host)
+}
+
+impl Request {
+    /// The URL clients reach us at, either the `BASE_URL` environment
+    /// variable or derived from the `Host` header
+    pub fn base_url(&self) -> anyhow::Result<Url> {
+        match BASE_URL.clone() {
+            Some(url) => Ok(url),
+            None => host_from_headers(self),
+        }
+    }
+
+    /// Resolves `uri` against the base URL unless it is already absolute
+    pub fn absolute_url(&self, uri: &Uri) -> anyhow::Result<Url> {
+        if uri.scheme().is_some() {
+            Ok(uri.to_string().parse()?)
+        } else {
+            Ok(self.base_url()?.join(&uri.to_string())?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{host_from_headers, host_to_url};
+    use crate::headers::Host;
+    use crate::http::{ForwardingHeader, TrustedProxies};
+    use crate::test::*;
+    use hyper::http::{uri::Authority, Uri};
+
+    #[test]
+    fn test_host_to_url() {
+        assert_eq!(
+            host_to_url("http", "example.com").unwrap().to_string(),
+            "http://example.com/",
+        )
+    }
+
+    #[test]
+    fn test_host_to_url_parse_error() {
+        assert!(host_to_url("http", "a/b/c").is_err())
+    }
+
+    #[test]
+    fn test_host_from_headers() {
+        let req = request()
+            .typed_header(Host::from(Authority::from_static("example.com")))
+            .build();
+
+        assert_eq!(
+            host_from_headers(&req).unwrap().to_string(),
+            "http://example.com/"
+        )
+    }
+
+    #[test]
+    fn test_host_from_headers_with_port() {
+        let req = request()
+            .typed_header(Host::from(Authority::from_static(
+                "example.com:1234",
+            )))
+            .build();
+
+        assert_eq!(
+            host_from_headers(&req).unwrap().to_string(),
+            "http://example.com:1234/"
+        )
+    }
+
+    fn proxied(trusted: TrustedProxies) -> RequestBuilder {
+        request()
+            .typed_header(Host::from(Authority::from_static("example.com")))
+            .client_addr("10.0.0.1:1234".parse().unwrap())
+            .trusted_proxies(trusted)
+    }
+
+    #[test]
+    fn test_host_from_headers_forwarded_proto() {
+        let trusted = TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()]);
+        let req = proxied(trusted.clone())
+            .header("x-forwarded-proto", "https")
+            .build();
+        assert_eq!(
+            host_from_headers(&req).unwrap().to_string(),
+            "https://example.com/"
+        );
+
+        let req = proxied(trusted.header(ForwardingHeader::Forwarded))
+            .header("forwarded", "for=1.2.3.4;proto=http, proto=https")
+            .header("x-forwarded-proto", "http")
+            .build();
+        assert_eq!(
+            host_from_headers(&req).unwrap().to_string(),
+            "https://example.com/"
+        );
+    }
+
+    #[test]
+    fn test_host_from_headers_untrusted_proto() {
+        let req = proxied(TrustedProxies::default())
+            .header("x-forwarded-proto", "https")
+            .header("forwarded", "proto=https")
+            .build();
+
+        assert_eq!(
+            host_from_headers(&req).unwrap().to_string(),
+            "http://example.com/"
+        )
+    }
+
+    #[test]
+    fn test_host_from_headers_no_header() {
+        let req = request().build();
+
+        assert!(host_from_headers(&req).is_err())
+    }
+
+    #[test]
+    fn test_absolute_url() {
+        let req = request()
+            .typed_header(Host::from(Authority::from_static("example.com")))
+            .build();
+
+        let relative_uri = "/first/second?a=1&b=2".parse::<Uri>().unwrap();
+        let absolute_url = req.absolute_url(&relative_uri).unwrap();
+        assert_eq!(
+            absolute_url.to_string(),
+            "http://example.com/first/second?a=1&b=2"
+        )
+    }
+
+    #[test]
+    fn test_absolute_url_with_port() {
+        let req = request()
+            .typed_header(Host::from(Authority::from_static(
+                "example.com:1234",
+            )))
+            .build();
+
+        let relative_uri = "/first/second".parse::<Uri>().unwrap();
+        let absolute_url = req.absolute_url(&relative_uri).unwrap();
+        assert_eq!(
+            absolute_url.to_string(),
+            "http://example.com:1234/first/second"
+        )
+    }
+
+    #[test]
+    fn test_absolute_url_already_absolute() {
+        let req = request().build();
+
+        let uri = Uri::from_static("https://example.org/path");
+        assert_eq!(
+            req.absolute_url(&uri).unwrap().to_string(),
+            "https://example.org/path"
+        )
+    }
+}
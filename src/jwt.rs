@@ -0,0 +1,432 @@
+//! Decoding JSON Web Tokens and checking their signatures against a JSON Web
+//! Key Set, and minting them with a key of our own
+use anyhow::Context;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::hmac;
+use ring::rand::SystemRandom;
+use ring::signature::{
+    self, EcdsaKeyPair, KeyPair, RsaPublicKeyComponents, UnparsedPublicKey,
+    ECDSA_P256_SHA256_FIXED_SIGNING,
+};
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use std::path::Path;
+
+/// A token split into its parts, its signature still to be checked
+#[derive(Debug)]
+pub struct Token {
+    pub header: Value,
+    pub claims: Value,
+    /// What the signature is over, the encoded header and claims
+    signing_input: String,
+    signature: Vec<u8>,
+}
+
+fn part(part: &str) -> Result<Value, &'static str> {
+    let json = URL_SAFE_NO_PAD
+        .decode(part)
+        .map_err(|_| "invalid base64url")?;
+    serde_json::from_slice(&json).map_err(|_| "invalid JSON")
+}
+
+impl Token {
+    /// Splits and decodes a compact serialized token
+    pub fn decode(token: &str) -> Result<Self, &'static str> {
+        let mut parts = token.trim().split('.');
+        let (header, claims, signature) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(header), Some(claims), Some(signature), None) => {
+                    (header, claims, signature)
+                }
+                _ => return Err("not three dot separated parts"),
+            };
+        let token = Self {
+            header: part(header)?,
+            claims: part(claims)?,
+            signing_input: format!("{}.{}", header, claims),
+            signature: URL_SAFE_NO_PAD
+                .decode(signature)
+                .map_err(|_| "invalid base64url")?,
+        };
+        if token.alg().is_none() {
+            return Err("no alg in the header");
+        }
+        Ok(token)
+    }
+
+    pub fn alg(&self) -> Option<&str> {
+        self.header.get("alg")?.as_str()
+    }
+
+    pub fn kid(&self) -> Option<&str> {
+        self.header.get("kid")?.as_str()
+    }
+
+    /// A numeric date claim such as `exp`
+    pub fn date(&self, claim: &str) -> Option<f64> {
+        self.claims.get(claim)?.as_f64()
+    }
+}
+
+/// A key as it appears in a JSON Web Key Set, of which only the public
+/// parts are read
+#[derive(Debug, Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    alg: Option<String>,
+    /// The secret of an `oct` key
+    k: Option<String>,
+    /// The modulus and exponent of an `RSA` key
+    n: Option<String>,
+    e: Option<String>,
+    /// The curve and point of an `EC` key
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+#[derive(Debug)]
+enum Material {
+    Hmac(Vec<u8>),
+    Rsa {
+        n: Vec<u8>,
+        e: Vec<u8>,
+    },
+    /// The uncompressed point
+    Ec {
+        crv: String,
+        point: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+struct Key {
+    kid: Option<String>,
+    alg: Option<String>,
+    material: Material,
+}
+
+fn member(member: &Option<String>, name: &str) -> anyhow::Result<Vec<u8>> {
+    let member = member.as_deref().with_context(|| format!("no {}", name))?;
+    URL_SAFE_NO_PAD
+        .decode(member)
+        .with_context(|| format!("invalid base64url in {}", name))
+}
+
+impl TryFrom<Jwk> for Key {
+    type Error = anyhow::Error;
+
+    fn try_from(jwk: Jwk) -> anyhow::Result<Self> {
+        let material = match jwk.kty.as_str() {
+            "oct" => Material::Hmac(member(&jwk.k, "k")?),
+            "RSA" => Material::Rsa {
+                n: member(&jwk.n, "n")?,
+                e: member(&jwk.e, "e")?,
+            },
+            "EC" => {
+                let mut point = vec![0x04];
+                point.extend(member(&jwk.x, "x")?);
+                point.extend(member(&jwk.y, "y")?);
+                Material::Ec {
+                    crv: jwk.crv.context("no crv")?,
+                    point,
+                }
+            }
+            kty => anyhow::bail!("unsupported kty {}", kty),
+        };
+        Ok(Self {
+            kid: jwk.kid,
+            alg: jwk.alg,
+            material,
+        })
+    }
+}
+
+impl Key {
+    /// Whether the signature of `input` checks out with this key under
+    /// `alg`, `None` if the key is not one for `alg`
+    fn verify(&self, alg: &str, input: &[u8], sig: &[u8]) -> Option<bool> {
+        if self.alg.as_deref().is_some_and(|key_alg| key_alg != alg) {
+            return None;
+        }
+        let verified = match (&self.material, alg) {
+            (Material::Hmac(secret), "HS256" | "HS384" | "HS512") => {
+                let algorithm = match alg {
+                    "HS256" => hmac::HMAC_SHA256,
+                    "HS384" => hmac::HMAC_SHA384,
+                    _ => hmac::HMAC_SHA512,
+                };
+                let key = hmac::Key::new(algorithm, secret);
+                hmac::verify(&key, input, sig).is_ok()
+            }
+            (Material::Rsa { n, e }, _) => {
+                let params = match alg {
+                    "RS256" => &signature::RSA_PKCS1_2048_8192_SHA256,
+                    "RS384" => &signature::RSA_PKCS1_2048_8192_SHA384,
+                    "RS512" => &signature::RSA_PKCS1_2048_8192_SHA512,
+                    "PS256" => &signature::RSA_PSS_2048_8192_SHA256,
+                    "PS384" => &signature::RSA_PSS_2048_8192_SHA384,
+                    "PS512" => &signature::RSA_PSS_2048_8192_SHA512,
+                    _ => return None,
+                };
+                RsaPublicKeyComponents { n, e }
+                    .verify(params, input, sig)
+                    .is_ok()
+            }
+            (Material::Ec { crv, point }, _) => {
+                let algorithm = match (crv.as_str(), alg) {
+                    ("P-256", "ES256") => &signature::ECDSA_P256_SHA256_FIXED,
+                    ("P-384", "ES384") => &signature::ECDSA_P384_SHA384_FIXED,
+                    _ => return None,
+                };
+                UnparsedPublicKey::new(algorithm, point)
+                    .verify(input, sig)
+                    .is_ok()
+            }
+            _ => return None,
+        };
+        Some(verified)
+    }
+}
+
+/// How checking a signature went
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// By the key with this `kid`, if it has one
+    Verified(Option<String>),
+    /// With the reason it was not
+    Failed(&'static str),
+}
+
+/// The keys to check signatures against
+#[derive(Debug)]
+pub struct KeySet(Vec<Key>);
+
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+impl KeySet {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let jwks = serde_json::from_str::<Jwks>(json)?;
+        let keys = jwks
+            .keys
+            .into_iter()
+            .enumerate()
+            .map(|(i, jwk)| {
+                Key::try_from(jwk).with_context(|| format!("key {}", i))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self(keys))
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read {}", path.display()))?;
+        Self::from_json(&json)
+            .with_context(|| format!("Invalid JWK Set in {}", path.display()))
+    }
+
+    /// Checks the signature of `token` with the keys for its `alg`, only
+    /// the one with its `kid` if it names one
+    pub fn verify(&self, token: &Token) -> Verification {
+        let alg = token.alg().unwrap_or_default();
+        if alg == "none" {
+            return Verification::Failed("unsigned");
+        }
+        let keys = self.0.iter().filter(|key| match token.kid() {
+            Some(kid) => key.kid.as_deref() == Some(kid),
+            None => true,
+        });
+        let input = token.signing_input.as_bytes();
+        let mut matching = false;
+        for key in keys {
+            match key.verify(alg, input, &token.signature) {
+                Some(true) => return Verification::Verified(key.kid.clone()),
+                Some(false) => matching = true,
+                None => {}
+            }
+        }
+        Verification::Failed(if matching {
+            "signature mismatch"
+        } else {
+            "no matching key"
+        })
+    }
+}
+
+/// An ES256 key for signing test tokens, only ever kept in memory
+pub struct SigningKey {
+    pair: EcdsaKeyPair,
+    rng: SystemRandom,
+    kid: String,
+}
+
+impl SigningKey {
+    pub fn generate() -> Self {
+        let rng = SystemRandom::new();
+        let algorithm = &ECDSA_P256_SHA256_FIXED_SIGNING;
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(algorithm, &rng)
+            .expect("no randomness for a signing key");
+        let pair = EcdsaKeyPair::from_pkcs8(algorithm, pkcs8.as_ref(), &rng)
+            .expect("generated an invalid signing key");
+        let mut key = Self {
+            pair,
+            rng,
+            kid: String::new(),
+        };
+        // The RFC 7638 thumbprint, over the required members in order
+        let (x, y) = key.coordinates();
+        let thumbprint =
+            format!(r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#, x, y);
+        key.kid = URL_SAFE_NO_PAD.encode(Sha256::digest(thumbprint));
+        key
+    }
+
+    /// The x and y of the public point, in base64url
+    fn coordinates(&self) -> (String, String) {
+        // Uncompressed, after the leading 0x04
+        let point = &self.pair.public_key().as_ref()[1..];
+        let (x, y) = point.split_at(point.len() / 2);
+        (URL_SAFE_NO_PAD.encode(x), URL_SAFE_NO_PAD.encode(y))
+    }
+
+    /// The public key, for a JSON Web Key Set
+    pub fn jwk(&self) -> Value {
+        let (x, y) = self.coordinates();
+        json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": x,
+            "y": y,
+            "kid": self.kid,
+            "alg": "ES256",
+            "use": "sig",
+        })
+    }
+
+    /// A compact serialized token with `claims`
+    pub fn sign(&self, claims: &Value) -> String {
+        let header = json!({"alg": "ES256", "typ": "JWT", "kid": self.kid});
+        let input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = self
+            .pair
+            .sign(&self.rng, input.as_bytes())
+            .expect("no randomness for a signature");
+        format!("{}.{}", input, URL_SAFE_NO_PAD.encode(signature))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The HS256 example of RFC 7515, appendix A.1
+    const HS256: &str = "eyJ0eXAiOiJKV1QiLA0KICJhbGciOiJIUzI1NiJ9.\
+        eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFtcGxl\
+        LmNvbS9pc19yb290Ijp0cnVlfQ.\
+        dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const HS256_KEY: &str = r#"{"keys": [{"kty": "oct", "kid": "a",
+        "k": "AyM1SysPpbyDfgZld3umj1qzKObwVMkoqQ-EstJQLr_T-1qS0gZH75aKtMN3Yj0iPS4hcgUuTwjAzZr1Z9CAow"}]}"#;
+
+    #[test]
+    fn test_decode() {
+        let token = Token::decode(HS256).unwrap();
+        assert_eq!(token.alg(), Some("HS256"));
+        assert_eq!(token.claims["iss"], "joe");
+        assert_eq!(token.date("exp"), Some(1_300_819_380.0));
+
+        assert!(Token::decode("a.b").is_err());
+        assert!(Token::decode("e30.e30.").is_err());
+    }
+
+    #[test]
+    fn test_verify_hmac() {
+        let keys = KeySet::from_json(HS256_KEY).unwrap();
+        let token = Token::decode(HS256).unwrap();
+        assert_eq!(
+            keys.verify(&token),
+            Verification::Verified(Some("a".to_owned()))
+        );
+
+        let forged = Token::decode(&HS256.replace("dBjf", "dBjg")).unwrap();
+        assert_eq!(
+            keys.verify(&forged),
+            Verification::Failed("signature mismatch")
+        );
+    }
+
+    #[test]
+    fn test_verify_asymmetric() {
+        let keys =
+            KeySet::from_json(include_str!("testdata/jwks.json")).unwrap();
+        let es256 = Token::decode(
+            "eyJhbGciOiJFUzI1NiIsImtpZCI6ImVjIn0.eyJzdWIiOiJtZSJ9.\
+             NjTemzNXgSoG5f46lFmrQ7Q36_8MFLRWasJJyImXORlhKZ8qKki7dATqkW96iPAHy\
+             ePD5rghC4hzPZ4cDGtk0g",
+        )
+        .unwrap();
+        assert_eq!(
+            keys.verify(&es256),
+            Verification::Verified(Some("ec".to_owned()))
+        );
+
+        let rs256 = Token::decode(
+            "eyJhbGciOiJSUzI1NiIsImtpZCI6InJzYSJ9.eyJzdWIiOiJtZSJ9.\
+             WUHa8kvWjKrxYCC8zXE-J7FVIOXi63ajSYEYsYbv4uFfHydm6NyVrqYxn55U9Cnlp\
+             G939le5wFKHrU6raUUJtduWFIGrBoTmwsUpnTYIlCh-EM599IKPiie9nrYNAKKmSR\
+             AYrnFLfea_6d4yE_HwGSHfFg0ILkj9lhZJtzMZZpIqVsdggV989ZgtV53jxyLNkF9\
+             VvtTruIxvWai9W4sKb0sHYsdvuDQnrxPGxyUZIKO60vxC9-FtufCsJNsaDde4YRLN\
+             ncWvJ6UXYPpadyMASZQuxVfBp9Cng2KgdZvQe8ihvb7b2Z4Hfu1LkkUK6rrwJcapR\
+             wXnhIJ-h6rxymZoiw",
+        )
+        .unwrap();
+        assert_eq!(
+            keys.verify(&rs256),
+            Verification::Verified(Some("rsa".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_verify_no_matching_key() {
+        let keys = KeySet::from_json(
+            r#"{"keys": [{"kty": "oct", "alg": "HS512", "k": "c2VjcmV0"}]}"#,
+        )
+        .unwrap();
+        let token = Token::decode(HS256).unwrap();
+        assert_eq!(
+            keys.verify(&token),
+            Verification::Failed("no matching key")
+        );
+    }
+
+    #[test]
+    fn test_invalid_key_set() {
+        assert!(KeySet::from_json(r#"{"keys": [{"kty": "oct"}]}"#).is_err());
+        assert!(KeySet::from_json(r#"{"keys": [{"kty": "OKP"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_signing_key() {
+        let key = SigningKey::generate();
+        let keys = KeySet::from_json(&json!({"keys": [key.jwk()]}).to_string())
+            .unwrap();
+        let token = Token::decode(&key.sign(&json!({"sub": "me"}))).unwrap();
+
+        assert_eq!(token.kid(), Some(key.kid.as_str()));
+        assert_eq!(token.claims["sub"], "me");
+        assert_eq!(
+            keys.verify(&token),
+            Verification::Verified(Some(key.kid.clone()))
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! httpbox as a library, for serving its endpoints from another hyper or
+//! tower server, or calling them in tests without a socket
+//!
+//! `app` is the router the binary serves, a tower `Service` of hyper
+//! requests. The handlers of `service` may also be installed one by one on a
+//! router of one's own, see `router::Router::builder`.
+use crate::config::Config;
+use crate::router::Router;
+
+mod checksum;
+mod client;
+pub mod config;
+mod graphql;
+pub mod handler;
+mod headers;
+mod hex;
+pub mod http;
+mod jwt;
+pub mod middleware;
+mod num_cpus;
+pub mod openapi;
+mod random;
+pub mod router;
+pub mod server;
+pub mod service;
+mod x509;
+
+#[cfg(test)]
+mod test;
+
+/// The router of every endpoint, as configured by default
+pub fn app() -> Router {
+    app_with(&Config::default())
+}
+
+/// The router of the endpoints `config` enables, leaving out the admin
+/// endpoints if it asks for an admin listener, see `service::routers`
+pub fn app_with(config: &Config) -> Router {
+    service::routers(config).0
+}
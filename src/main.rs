@@ -1,127 +1,109 @@
-use clap::{Command, CommandFactory, Parser};
-use clap_complete::{generate, Generator, Shell};
-use futures::prelude::*;
-use hyper::server::conn::AddrStream;
-use hyper::Server;
-use hyper::{Body, Request as HTTPRequest};
+use clap::{Command, CommandFactory};
+use clap_complete::{generate, Generator};
+use futures::future;
+use httpbox::{config, server, service};
 use std::io;
-use std::net::ToSocketAddrs;
-use std::num::NonZeroUsize;
 use tokio::runtime;
-use tower::ServiceBuilder;
-use tower::ServiceExt;
-use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-mod handler;
-mod headers;
-mod http;
-mod num_cpus;
-mod random;
-mod router;
-mod service;
-
-#[cfg(test)]
-mod test;
-
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None, disable_help_flag = true)]
-struct Cli {
-    #[arg(
-        short,
-        long,
-        env,
-        default_value = "0.0.0.0",
-        help = "Host address to listen on"
-    )]
-    host: String,
-
-    #[arg(
-        short,
-        long,
-        env,
-        default_value_t = 3000,
-        help = "Port to listen on"
-    )]
-    port: u16,
-
-    #[arg(long, env, help = "Number of threads to process requests")]
-    threads: Option<NonZeroUsize>,
-
-    #[arg(long)]
-    completions: Option<Shell>,
-
-    #[arg(long, action = clap::ArgAction::Help, help = "Print help information")]
-    help: (),
-}
-
 fn print_completions<G: Generator>(gen: G, app: &mut Command) {
     generate(gen, app, app.get_name().to_string(), &mut io::stdout());
 }
 
 async fn shutdown_signal() {
-    // Wait for the CTRL+C signal
-    tokio::signal::ctrl_c().await.unwrap()
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        signal(SignalKind::terminate()).unwrap().recv().await;
+    };
+    #[cfg(not(unix))]
+    let terminate = futures::future::pending::<()>();
+
+    // Wait for SIGINT (CTRL+C) or SIGTERM
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result.unwrap(),
+        _ = terminate => {}
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
+    let config = config::Config::load()?;
+
+    let registry = tracing_subscriber::registry().with(
+        tracing_subscriber::EnvFilter::new(
             std::env::var("RUST_LOG")
                 .unwrap_or_else(|_| "httpbox=debug,tower_http=debug".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
-    let args = Cli::parse();
+        ),
+    );
+    let format = tracing_subscriber::fmt::layer();
+    match config.log_format() {
+        config::LogFormat::Text => registry.with(format).init(),
+        config::LogFormat::Pretty => registry.with(format.pretty()).init(),
+        config::LogFormat::Json => registry.with(format.json()).init(),
+    }
 
-    if let Some(generator) = args.completions {
-        let mut app = Cli::command();
+    if let Some(generator) = config.completions {
+        let mut app = config::Config::command();
         print_completions(generator, &mut app);
         return Ok(());
     }
 
-    let threads = args.threads.unwrap_or_else(num_cpus::num_cpus);
-    let addr = (args.host.clone(), args.port)
-        .to_socket_addrs()
-        .ok()
-        .and_then(|iter| iter.last())
-        .unwrap_or_else(|| {
-            panic!(
-                "Invalid listening address: {}:{}",
-                args.host.clone(),
-                args.port
-            )
-        });
+    let threads = config.threads();
+    let addr = config.addr()?;
 
     let runtime = runtime::Builder::new_multi_thread()
         .worker_threads(threads.get())
-        .enable_io()
+        .enable_all()
         .build()?;
 
-    tracing::info!("Listening on {} with {} threads", addr, threads);
+    let options = config.server_options();
+    let admin_addr = config.admin_addr()?;
+    let (router, admin) = service::routers(&config);
+
+    if !options.unix_only {
+        tracing::info!(
+            "Listening on {} with {} threads{}",
+            addr,
+            threads,
+            if options.tls.is_some() {
+                " over TLS"
+            } else {
+                ""
+            }
+        );
+    }
+    if let Some(path) = &options.unix_socket {
+        tracing::info!("Listening on {}", path.display());
+    }
     runtime.block_on(async {
-        let service = ServiceBuilder::new()
-            .layer(TraceLayer::new_for_http())
-            .service(service::router());
-
-        let factory = tower::service_fn(|conn: &AddrStream| {
-            let addr = conn.remote_addr();
-            future::ok::<_, std::convert::Infallible>(
-                service.clone().map_request(
-                    move |mut req: HTTPRequest<Body>| {
-                        req.extensions_mut().insert(addr);
-                        req
-                    },
-                ),
-            )
+        let (admin_addr, admin) = match (admin_addr, admin) {
+            (Some(admin_addr), Some(admin)) => (admin_addr, admin),
+            _ => {
+                return server::serve(addr, router, options, shutdown_signal())
+                    .await
+            }
+        };
+        tracing::info!("Admin listening on {}", admin_addr);
+        let shutdown = admin.shutdown.clone();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            shutdown.request();
         });
-
-        let server = Server::bind(&addr)
-            .serve(factory)
-            .with_graceful_shutdown(shutdown_signal());
-
-        server.await?;
-        Ok(())
-    })
+        let admin_options = server::Options {
+            drain_timeout: options.drain_timeout,
+            ..server::Options::default()
+        };
+        future::try_join(
+            server::serve(addr, router, options, admin.shutdown.requested()),
+            server::serve(
+                admin_addr,
+                admin.router,
+                admin_options,
+                admin.shutdown.requested(),
+            ),
+        )
+        .await
+        .map(|_| ())
+    })?;
+    Ok(())
 }
@@ -0,0 +1,139 @@
+use crate::http::{Error, Request, Result};
+use crate::router::{Middleware, Next, Route};
+use async_trait::async_trait;
+use hyper::body::Body as HttpBody;
+use hyper::StatusCode;
+use std::time::Instant;
+
+/// The response status and body size, when the size is known up front
+fn outcome(result: &Result) -> (StatusCode, Option<u64>) {
+    match result {
+        Ok(res) => (res.status(), res.body().size_hint().exact()),
+        Err(Error::Failure(res)) => {
+            (res.status(), res.body().size_hint().exact())
+        }
+        Err(e) => (e.status(), None),
+    }
+}
+
+/// Emits one `httpbox::access` event per request
+///
+/// Latency covers the time until the response headers are ready, so
+/// streamed bodies report their size only when it is known up front.
+#[derive(Clone, Debug, Default)]
+pub struct AccessLog;
+
+#[async_trait]
+impl Middleware for AccessLog {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result {
+        let method = req.method().clone();
+        let path = req.uri().path().to_owned();
+        let route = req.route().map(Route::name).map(str::to_owned);
+        let client = req.client_addr().map(|addr| addr.to_string());
+        let id = req.id().map(str::to_owned);
+
+        let start = Instant::now();
+        let result = next.run(req).await;
+        let latency = start.elapsed();
+        let (status, bytes) = outcome(&result);
+
+        tracing::info!(
+            target: "httpbox::access",
+            request_id = id.as_deref(),
+            method = %method,
+            path = %path,
+            route = route.as_deref(),
+            status = status.as_u16(),
+            latency_ms = latency.as_secs_f64() * 1000.0,
+            bytes,
+            client = client.as_deref(),
+        );
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{not_found, ok};
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use serde_json::Value;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use uri_path::path;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn handler(_: Request) -> Result {
+        ok("hello")
+    }
+
+    async fn missing(_: Request) -> Result {
+        Err(not_found())
+    }
+
+    fn router() -> Router {
+        Router::builder()
+            .install(handler, route(path!("hello" / name)))
+            .install(missing, route(path!("missing")))
+            .layer(crate::middleware::RequestIds)
+            .layer(AccessLog)
+            .build()
+    }
+
+    async fn access_log(path: &str) -> Value {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        request()
+            .path(path)
+            .header("x-request-id", "abc-123")
+            .client_addr("127.0.0.1:1234".parse().unwrap())
+            .call(&mut router())
+            .await;
+
+        let output = buffer.0.lock().unwrap().clone();
+        let event: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(event["target"], "httpbox::access");
+        event["fields"].clone()
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_access_log() {
+        let fields = access_log("/hello/world").await;
+
+        assert_eq!(fields["method"], "GET");
+        assert_eq!(fields["path"], "/hello/world");
+        assert_eq!(fields["route"], "/hello/:name");
+        assert_eq!(fields["status"], 200);
+        assert_eq!(fields["bytes"], 5);
+        assert_eq!(fields["client"], "127.0.0.1");
+        assert_eq!(fields["request_id"], "abc-123");
+        assert!(fields["latency_ms"].is_number());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_access_log_unmatched() {
+        let fields = access_log("/nowhere").await;
+
+        assert_eq!(fields["status"], 404);
+        assert!(fields.get("route").is_none());
+    }
+}
@@ -0,0 +1,63 @@
+use crate::http::{Request, Result};
+use crate::router::{Middleware, Next};
+use async_trait::async_trait;
+use hyper::header::{HeaderValue, ALT_SVC};
+use hyper::http::Version;
+
+/// How long clients may remember the advertisement, in seconds
+const MAX_AGE: u32 = 24 * 60 * 60;
+
+/// Advertises an HTTP/3 listener on `port` to HTTP/1.1 and HTTP/2 clients
+pub struct AltSvc(HeaderValue);
+
+impl AltSvc {
+    pub fn http3(port: u16) -> Self {
+        let value = format!("h3=\":{}\"; ma={}", port, MAX_AGE);
+        Self(HeaderValue::from_str(&value).unwrap())
+    }
+}
+
+#[async_trait]
+impl Middleware for AltSvc {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result {
+        let advertise = req.version() != Version::HTTP_3;
+
+        let mut res = next.run(req).await?;
+        if advertise {
+            res.headers_mut().insert(ALT_SVC, self.0.clone());
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::ok;
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use uri_path::path;
+
+    async fn handler(_: Request) -> Result {
+        ok("")
+    }
+
+    async fn alt_svc(version: Version) -> Option<HeaderValue> {
+        let mut router = Router::builder()
+            .install(handler, route(path!()))
+            .layer(AltSvc::http3(443))
+            .build();
+
+        let res = request().version(version).call(&mut router).await;
+        res.headers().get(ALT_SVC).cloned()
+    }
+
+    #[tokio::test]
+    async fn test_alt_svc() {
+        assert_eq!(
+            alt_svc(Version::HTTP_11).await.unwrap(),
+            "h3=\":443\"; ma=86400"
+        );
+        assert_eq!(alt_svc(Version::HTTP_3).await, None);
+    }
+}
@@ -0,0 +1,422 @@
+use crate::headers::{ContentLength, HeaderMapExt};
+use crate::http::{response, Body, Error, Request, Response, Result};
+use crate::http::{Bytes, StatusCode};
+use crate::router::{Middleware, Next};
+use async_trait::async_trait;
+use futures::prelude::*;
+use hyper::body::Body as HttpBody;
+use hyper::header::HeaderValue;
+use rand::Rng;
+use serde::de::{self, Deserialize, Deserializer};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+pub const CHAOS_HEADER: &str = "x-chaos";
+const DEFAULT_DELAY: Duration = Duration::from_secs(1);
+const FLUSH: Duration = Duration::from_millis(10);
+const ERRORS: [StatusCode; 4] = [
+    StatusCode::INTERNAL_SERVER_ERROR,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+/// How likely each fault is, written like
+/// `latency=0.5,delay=2,error=0.1,reset=0.05,truncate=0.05`
+///
+/// Latency of `delay` seconds is added independently of the others, while a
+/// request gets at most one of a 5xx error, a reset connection or a
+/// truncated body, so their probabilities can't add up to more than 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Faults {
+    pub latency: f64,
+    pub delay: Duration,
+    pub error: f64,
+    pub reset: f64,
+    pub truncate: f64,
+}
+
+#[derive(Debug)]
+pub struct InvalidFaults(String);
+
+impl fmt::Display for InvalidFaults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid chaos faults: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidFaults {}
+
+fn probability(value: &str) -> Option<f64> {
+    value.parse().ok().filter(|p| (0.0..=1.0).contains(p))
+}
+
+impl FromStr for Faults {
+    type Err = InvalidFaults;
+
+    fn from_str(s: &str) -> std::result::Result<Self, InvalidFaults> {
+        let mut faults = Faults {
+            delay: DEFAULT_DELAY,
+            ..Faults::default()
+        };
+        for pair in s.split(',').map(str::trim).filter(|pair| !pair.is_empty())
+        {
+            let invalid = || InvalidFaults(pair.to_owned());
+            let (key, value) = pair.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim();
+            match key.trim() {
+                "latency" => {
+                    faults.latency = probability(value).ok_or_else(invalid)?
+                }
+                "error" => {
+                    faults.error = probability(value).ok_or_else(invalid)?
+                }
+                "reset" => {
+                    faults.reset = probability(value).ok_or_else(invalid)?
+                }
+                "truncate" => {
+                    faults.truncate = probability(value).ok_or_else(invalid)?
+                }
+                "delay" => {
+                    faults.delay = value
+                        .parse()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                        .ok_or_else(invalid)?
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        if faults.error + faults.reset + faults.truncate > 1.0 {
+            return Err(InvalidFaults(
+                "error, reset and truncate add up to more than 1".to_owned(),
+            ));
+        }
+        Ok(faults)
+    }
+}
+
+/// Written the way it is parsed, with every fault
+impl fmt::Display for Faults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "latency={},delay={},error={},reset={},truncate={}",
+            self.latency,
+            self.delay.as_secs_f64(),
+            self.error,
+            self.reset,
+            self.truncate
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for Faults {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Fault {
+    Error(StatusCode),
+    Reset,
+    Truncate,
+}
+
+impl Faults {
+    fn pick(&self, rng: &mut impl Rng) -> Option<Fault> {
+        let roll = rng.gen::<f64>();
+        if roll < self.error {
+            Some(Fault::Error(ERRORS[rng.gen_range(0..ERRORS.len())]))
+        } else if roll < self.error + self.reset {
+            Some(Fault::Reset)
+        } else if roll < self.error + self.reset + self.truncate {
+            Some(Fault::Truncate)
+        } else {
+            None
+        }
+    }
+}
+
+fn broken() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionReset, "injected by chaos")
+}
+
+/// Sends half of a body of known length, or else its first chunk, then
+/// breaks off with an error, which makes hyper drop the connection
+///
+/// The full length is announced, so that clients can tell.
+fn truncate(mut res: Response) -> Response {
+    let len = res
+        .headers()
+        .typed_get::<ContentLength>()
+        .map(|len| len.0)
+        .or_else(|| HttpBody::size_hint(res.body()).exact());
+    if let Some(len) = len {
+        res.headers_mut().typed_insert(ContentLength(len));
+    }
+    let keep = len.map(|len| len / 2);
+    res.map(|body| {
+        let chunks =
+            stream::unfold((Some(body), keep), |(body, keep)| async move {
+                let mut body = body?;
+                match (body.next().await, keep) {
+                    (Some(Ok(chunk)), Some(keep)) if keep > 0 => {
+                        let len = (chunk.len() as u64).min(keep);
+                        let chunk: Bytes = chunk.slice(..len as usize);
+                        Some((Ok(chunk), (Some(body), Some(keep - len))))
+                    }
+                    (Some(Ok(chunk)), None) => {
+                        Some((Ok(chunk), (Some(body), Some(0))))
+                    }
+                    _ => {
+                        // Gives hyper the chance to send what came before
+                        tokio::time::sleep(FLUSH).await;
+                        Some((Err(broken()), (None, None)))
+                    }
+                }
+            });
+        Body::wrap_stream(chunks)
+    })
+}
+
+fn marked(mut res: Response, fault: &'static str) -> Response {
+    res.headers_mut()
+        .insert(CHAOS_HEADER, HeaderValue::from_static(fault));
+    res
+}
+
+/// Injects latency and failures into responses, at the configured rates or
+/// those a request asks for in an `X-Chaos` header
+///
+/// Injected errors and truncated bodies are marked with an `X-Chaos`
+/// response header. Clones share the configured faults, so they can be
+/// changed while serving.
+#[derive(Clone, Debug)]
+pub struct Chaos {
+    faults: Arc<RwLock<Option<Faults>>>,
+    from_header: bool,
+}
+
+impl Chaos {
+    pub fn new(faults: Option<Faults>) -> Self {
+        Self {
+            faults: Arc::new(RwLock::new(faults)),
+            from_header: false,
+        }
+    }
+
+    pub fn configured(&self) -> Option<Faults> {
+        *self.faults.read().unwrap()
+    }
+
+    /// Replaces the configured faults, `None` turning them off
+    pub fn set(&self, faults: Option<Faults>) {
+        *self.faults.write().unwrap() = faults;
+    }
+
+    /// Lets the `X-Chaos` request header replace the configured faults
+    pub fn header(mut self, from_header: bool) -> Self {
+        self.from_header = from_header;
+        self
+    }
+
+    fn faults(
+        &self,
+        req: &Request,
+    ) -> std::result::Result<Option<Faults>, Error> {
+        let header = match req.headers().get(CHAOS_HEADER) {
+            Some(header) if self.from_header => header,
+            _ => return Ok(self.configured()),
+        };
+        header
+            .to_str()
+            .ok()
+            .and_then(|faults| faults.parse().ok())
+            .map(Some)
+            .ok_or_else(|| Error::bad_request("invalid X-Chaos header"))
+    }
+}
+
+#[async_trait]
+impl Middleware for Chaos {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result {
+        let faults = match self.faults(&req)? {
+            Some(faults) => faults,
+            None => return next.run(req).await,
+        };
+        let (latency, fault) = {
+            let mut rng = rand::thread_rng();
+            (rng.gen_bool(faults.latency), faults.pick(&mut rng))
+        };
+
+        if latency {
+            tokio::time::sleep(faults.delay).await;
+        }
+        match fault {
+            Some(Fault::Error(status)) => {
+                let res = Result::from(response().status(status))?;
+                Err(Error::Failure(Box::new(marked(res, "error"))))
+            }
+            Some(Fault::Reset) => {
+                let body = Body::wrap_stream(stream::once(async {
+                    Err::<Bytes, _>(broken())
+                }));
+                response().body(body)
+            }
+            Some(Fault::Truncate) => {
+                let res = next.run(req).await?;
+                Ok(marked(truncate(res), "truncate"))
+            }
+            None => next.run(req).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::ok;
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use rand::rngs::mock::StepRng;
+    use uri_path::path;
+
+    async fn handler(_: Request) -> Result {
+        ok("0123456789")
+    }
+
+    fn router(chaos: Chaos) -> Router {
+        Router::builder()
+            .install(handler, route(path!()))
+            .layer(chaos)
+            .build()
+    }
+
+    async fn get(router: &mut Router, chaos: Option<&str>) -> Response {
+        let mut req = request();
+        if let Some(chaos) = chaos {
+            req = req.header(CHAOS_HEADER, chaos);
+        }
+        req.call(router).await
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "latency=0.5, delay=0.25,error=0.1"
+                .parse::<Faults>()
+                .unwrap(),
+            Faults {
+                latency: 0.5,
+                delay: Duration::from_millis(250),
+                error: 0.1,
+                ..Faults::default()
+            }
+        );
+        assert!("error=2".parse::<Faults>().is_err());
+        assert!("error=0.6,reset=0.6".parse::<Faults>().is_err());
+        assert!("explode=0.1".parse::<Faults>().is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let faults: Faults =
+            "latency=0.5,delay=0.25,reset=0.1".parse().unwrap();
+        assert_eq!(
+            faults.to_string(),
+            "latency=0.5,delay=0.25,error=0,reset=0.1,truncate=0"
+        );
+        assert_eq!(faults.to_string().parse::<Faults>().unwrap(), faults);
+    }
+
+    #[test]
+    fn test_pick() {
+        let faults: Faults =
+            "error=0.25,reset=0.25,truncate=0.25".parse().unwrap();
+        // Rolls of 0, 0.3, 0.6 and 0.9
+        let rolls = [0, 3, 6, 9].map(|tenth| {
+            let mut rng = StepRng::new(u64::MAX / 10 * tenth, 0);
+            faults.pick(&mut rng)
+        });
+
+        assert!(matches!(rolls[0], Some(Fault::Error(_))));
+        assert_eq!(
+            rolls[1..],
+            [Some(Fault::Reset), Some(Fault::Truncate), None]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error() {
+        let mut router = router(Chaos::new("error=1".parse().ok()));
+        let res = get(&mut router, None).await;
+
+        assert!(res.status().is_server_error());
+        assert_eq!(res.headers()[CHAOS_HEADER], "error");
+    }
+
+    #[tokio::test]
+    async fn test_reset() {
+        let mut router = router(Chaos::new("reset=1".parse().ok()));
+        let res = get(&mut router, None).await;
+
+        assert!(crate::http::to_bytes(res.into_body()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_truncate() {
+        let mut router = router(Chaos::new("truncate=1".parse().ok()));
+        let res = get(&mut router, None).await;
+
+        assert_eq!(res.headers()[CHAOS_HEADER], "truncate");
+        assert_eq!(res.headers()["content-length"], "10");
+        let mut body = res.into_body();
+        assert_eq!(body.next().await.unwrap().unwrap(), "01234");
+        assert!(body.next().await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_header() {
+        let mut router = router(Chaos::new(None).header(true));
+
+        let res = get(&mut router, None).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = get(&mut router, Some("error=1")).await;
+        assert!(res.status().is_server_error());
+
+        let res = get(&mut router, Some("error=x")).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_set() {
+        let chaos = Chaos::new(None);
+        let mut router = router(chaos.clone());
+
+        chaos.set("error=1".parse().ok());
+        let res = get(&mut router, None).await;
+        assert!(res.status().is_server_error());
+
+        chaos.set(None);
+        let res = get(&mut router, None).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_header_ignored() {
+        let mut router = router(Chaos::new(None));
+        let res = get(&mut router, Some("error=1")).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
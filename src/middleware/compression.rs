@@ -0,0 +1,149 @@
+use crate::headers::{CacheControl, HeaderMapExt};
+use crate::http::compression::{compress, Encoding};
+use crate::http::{Request, Response, Result};
+use crate::router::{Middleware, Next, Route};
+use async_trait::async_trait;
+use hyper::header::{
+    HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, VARY,
+};
+use hyper::Method;
+
+/// Encodings in order of preference when the client weighs them equally
+const SUPPORTED: [Encoding; 3] =
+    [Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+
+fn from_name(name: &str) -> Option<Encoding> {
+    SUPPORTED
+        .iter()
+        .copied()
+        .find(|encoding| encoding.as_str() == name)
+}
+
+fn compressible(res: &Response) -> bool {
+    let status = res.status();
+    let headers = res.headers();
+
+    !(status.is_informational()
+        || status == hyper::StatusCode::NO_CONTENT
+        || status == hyper::StatusCode::NOT_MODIFIED
+        || headers.contains_key(CONTENT_ENCODING)
+        || headers.contains_key(CONTENT_RANGE)
+        || headers.get(CONTENT_LENGTH).is_some_and(|len| len == "0")
+        || headers
+            .typed_get::<CacheControl>()
+            .is_some_and(|cache| cache.no_transform()))
+}
+
+/// Compresses response bodies according to the request's `Accept-Encoding`
+///
+/// Routes opt out with `route(...).compress(false)`, and responses opt out by
+/// setting their own `Content-Encoding` or `Cache-Control: no-transform`.
+#[derive(Clone, Debug, Default)]
+pub struct Compression;
+
+#[async_trait]
+impl Middleware for Compression {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result {
+        let enabled = req.route().is_none_or(Route::compress)
+            && req.method() != Method::HEAD;
+        let encoding = req
+            .negotiate_encoding(&SUPPORTED.map(|encoding| encoding.as_str()))
+            .and_then(from_name);
+
+        let mut res = next.run(req).await?;
+        if !enabled || !compressible(&res) {
+            return Ok(res);
+        }
+
+        res.headers_mut()
+            .append(VARY, HeaderValue::from_static("accept-encoding"));
+        Ok(match encoding {
+            Some(encoding) => compress(res, encoding),
+            None => res,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::compression::test::decompress;
+    use crate::http::ok;
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use hyper::header::ACCEPT_ENCODING;
+    use hyper::StatusCode;
+    use uri_path::path;
+
+    fn preferred_encoding(accept_encoding: &str) -> Option<Encoding> {
+        request()
+            .header(ACCEPT_ENCODING, accept_encoding)
+            .build()
+            .negotiate_encoding(&SUPPORTED.map(|encoding| encoding.as_str()))
+            .and_then(from_name)
+    }
+
+    #[test]
+    fn test_preferred_encoding() {
+        assert_eq!(preferred_encoding("gzip"), Some(Encoding::Gzip));
+        assert_eq!(preferred_encoding("gzip, br"), Some(Encoding::Brotli));
+        assert_eq!(
+            preferred_encoding("br;q=0.5, gzip;q=0.8"),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(preferred_encoding("*"), Some(Encoding::Brotli));
+        assert_eq!(preferred_encoding("*, br;q=0"), Some(Encoding::Gzip));
+        assert_eq!(preferred_encoding("identity"), None);
+        assert_eq!(preferred_encoding("gzip;q=0"), None);
+        assert_eq!(preferred_encoding(""), None);
+    }
+
+    async fn handler(_: Request) -> Result {
+        ok("hello")
+    }
+
+    fn router() -> Router {
+        Router::builder()
+            .install(handler, route(path!("compressed")))
+            .install(handler, route(path!("plain")).compress(false))
+            .layer(Compression)
+            .build()
+    }
+
+    async fn get(path: &str, accept_encoding: &str) -> Response {
+        request()
+            .path(path)
+            .header(ACCEPT_ENCODING, accept_encoding)
+            .call(&mut router())
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_compressed() {
+        let res = get("/compressed", "gzip").await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+        assert_eq!(res.headers().get(VARY).unwrap(), "accept-encoding");
+        let body = res.read_body().await.unwrap();
+        assert_eq!(decompress(&body, Encoding::Gzip).await, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_not_accepted() {
+        let res = get("/compressed", "identity").await;
+
+        assert!(res.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(res.headers().get(VARY).unwrap(), "accept-encoding");
+        assert_eq!(res.read_body_utf8().await.unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_route_opt_out() {
+        let res = get("/plain", "gzip").await;
+
+        assert!(res.headers().get(CONTENT_ENCODING).is_none());
+        assert!(res.headers().get(VARY).is_none());
+        assert_eq!(res.read_body_utf8().await.unwrap(), "hello");
+    }
+}
@@ -0,0 +1,110 @@
+use crate::headers::RetryAfter;
+use crate::http::{response, Error, Request, Result, StatusCode};
+use crate::router::{Middleware, Next};
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// What shed clients are told to wait before trying again
+const RETRY_AFTER: Duration = Duration::from_secs(1);
+
+fn service_unavailable() -> Error {
+    response()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .typed_header(RetryAfter::delay(RETRY_AFTER))
+        .into()
+}
+
+#[derive(Debug)]
+struct State {
+    max: usize,
+    permits: Semaphore,
+    shed: AtomicU64,
+}
+
+/// Answers with 503 Service Unavailable while `max` requests are already
+/// being handled, rather than queueing more of them
+///
+/// Like the in-flight gauge of `Metrics`, a request counts until its
+/// response is ready, not until its body has been sent.
+#[derive(Clone, Debug)]
+pub struct ConcurrencyLimit(Arc<State>);
+
+impl ConcurrencyLimit {
+    pub fn new(max: usize) -> Self {
+        Self(Arc::new(State {
+            max,
+            permits: Semaphore::new(max),
+            shed: AtomicU64::new(0),
+        }))
+    }
+
+    pub fn max(&self) -> usize {
+        self.0.max
+    }
+
+    /// Requests turned away so far
+    pub fn shed(&self) -> u64 {
+        self.0.shed.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl Middleware for ConcurrencyLimit {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result {
+        let _permit = self.0.permits.try_acquire().map_err(|_| {
+            self.0.shed.fetch_add(1, Ordering::Relaxed);
+            service_unavailable()
+        })?;
+        next.run(req).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{ok, Body};
+    use crate::router::{route, Router};
+    use hyper::http::Request as HTTPRequest;
+    use hyper::StatusCode;
+    use tokio::sync::Notify;
+    use tower::Service;
+    use uri_path::path;
+
+    #[tokio::test]
+    async fn test_sheds_load() {
+        let release = Arc::new(Notify::new());
+        let blocked = release.clone();
+        let limit = ConcurrencyLimit::new(1);
+        let mut router = Router::builder()
+            .install(
+                move |_| {
+                    let blocked = blocked.clone();
+                    async move {
+                        blocked.notified().await;
+                        ok("")
+                    }
+                },
+                route(path!("block")),
+            )
+            .install(|_| async { ok("") }, route(path!("fast")))
+            .layer(limit.clone())
+            .build();
+        let get = |path| HTTPRequest::get(path).body(Body::empty()).unwrap();
+
+        let pending = tokio::spawn(router.call(get("/block")));
+        tokio::task::yield_now().await;
+
+        let res = router.call(get("/fast")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers()["retry-after"], "1");
+        assert_eq!(limit.shed(), 1);
+
+        release.notify_one();
+        assert_eq!(pending.await.unwrap().unwrap().status(), StatusCode::OK);
+        let res = router.call(get("/fast")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::http::{Error, Request, Response, Result, StatusCode};
+use crate::router::{Middleware, Next};
+use async_trait::async_trait;
+use hyper::header::{
+    HeaderName, HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS,
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+    VARY,
+};
+use hyper::Method;
+use std::time::Duration;
+
+fn join<T: AsRef<str>>(values: &[T]) -> Option<HeaderValue> {
+    let joined = values
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(", ");
+    HeaderValue::from_str(&joined).ok()
+}
+
+/// Lets browsers call the endpoints from other origins, answering the
+/// preflight `OPTIONS` request for any path a route is installed at
+#[derive(Clone, Debug, Default)]
+pub struct Cors {
+    /// Any origin when empty
+    origins: Vec<String>,
+    /// The methods of the matching routes when empty
+    methods: Vec<Method>,
+    /// Whatever the preflight asks for when empty
+    headers: Vec<HeaderName>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl Cors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn origins(mut self, origins: Vec<String>) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    pub fn methods(mut self, methods: Vec<Method>) -> Self {
+        self.methods = methods;
+        self
+    }
+
+    pub fn headers(mut self, headers: Vec<HeaderName>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn credentials(mut self, credentials: bool) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    pub fn max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// The `Access-Control-Allow-Origin` for `origin` if it may call us,
+    /// which can only be `*` when no credentials are involved
+    fn allow_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        if self.origins.is_empty() {
+            return Some(if self.credentials {
+                origin.clone()
+            } else {
+                HeaderValue::from_static("*")
+            });
+        }
+        self.origins
+            .iter()
+            .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+            .then(|| origin.clone())
+    }
+
+    fn decorate(&self, res: &mut Response, allow_origin: &HeaderValue) {
+        let headers = res.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin.clone());
+        if self.credentials {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+        if allow_origin != "*" {
+            headers.append(VARY, HeaderValue::from_static("origin"));
+        }
+    }
+
+    fn preflight(
+        &self,
+        req: &Request,
+        allowed: &[Method],
+        allow_origin: &HeaderValue,
+    ) -> Response {
+        let mut res = Response::default();
+        *res.status_mut() = StatusCode::NO_CONTENT;
+        self.decorate(&mut res, allow_origin);
+
+        let headers = res.headers_mut();
+        let methods = if self.methods.is_empty() {
+            allowed
+        } else {
+            &self.methods
+        };
+        if let Some(methods) = join(methods) {
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+        let allow_headers = if self.headers.is_empty() {
+            req.headers().get(ACCESS_CONTROL_REQUEST_HEADERS).cloned()
+        } else {
+            join(&self.headers)
+        };
+        if let Some(allow_headers) = allow_headers {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allow_headers);
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+        }
+        headers.append(
+            VARY,
+            HeaderValue::from_static(
+                "access-control-request-method, access-control-request-headers",
+            ),
+        );
+        res
+    }
+}
+
+#[async_trait]
+impl Middleware for Cors {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result {
+        let allow_origin = match req.headers().get(ORIGIN) {
+            Some(origin) => match self.allow_origin(origin) {
+                Some(allow_origin) => allow_origin,
+                None => return next.run(req).await,
+            },
+            None => return next.run(req).await,
+        };
+
+        if req.method() == Method::OPTIONS
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            if let Some(allowed) = req.allowed_methods() {
+                return Ok(self.preflight(&req, allowed, &allow_origin));
+            }
+        }
+
+        match next.run(req).await {
+            Ok(mut res) => {
+                self.decorate(&mut res, &allow_origin);
+                Ok(res)
+            }
+            Err(Error::Failure(mut res)) => {
+                self.decorate(&mut res, &allow_origin);
+                Err(Error::Failure(res))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{not_found, ok};
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use uri_path::path;
+
+    const ORIGIN_VALUE: &str = "https://example.com";
+
+    async fn handler(_: Request) -> Result {
+        ok("")
+    }
+
+    async fn missing(_: Request) -> Result {
+        Err(not_found())
+    }
+
+    fn router(cors: Cors) -> Router {
+        Router::builder()
+            .install(
+                handler,
+                route(path!("get")).methods(vec![Method::GET, Method::POST]),
+            )
+            .install(missing, route(path!("missing")))
+            .layer(cors)
+            .build()
+    }
+
+    fn preflight(path: &str) -> RequestBuilder {
+        request()
+            .method(Method::OPTIONS)
+            .path(path)
+            .header(ORIGIN, ORIGIN_VALUE)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, "x-custom")
+    }
+
+    fn get(path: &str, origin: &str) -> RequestBuilder {
+        request().path(path).header(ORIGIN, origin)
+    }
+
+    #[tokio::test]
+    async fn test_preflight() {
+        let cors = Cors::new().max_age(Some(Duration::from_secs(600)));
+        let res = preflight("/get").call(&mut router(cors)).await;
+
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        let headers = res.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert_eq!(
+            headers[ACCESS_CONTROL_ALLOW_METHODS],
+            "GET, POST, HEAD, OPTIONS"
+        );
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "x-custom");
+        assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+    }
+
+    #[tokio::test]
+    async fn test_preflight_configured() {
+        let cors = Cors::new()
+            .origins(vec![ORIGIN_VALUE.to_owned()])
+            .methods(vec![Method::GET])
+            .headers(vec![HeaderName::from_static("x-allowed")])
+            .credentials(true);
+        let res = preflight("/get").call(&mut router(cors)).await;
+
+        let headers = res.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], ORIGIN_VALUE);
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "x-allowed");
+        assert!(headers.get(ACCESS_CONTROL_MAX_AGE).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_preflight_unknown_path() {
+        let res = preflight("/nowhere").call(&mut router(Cors::new())).await;
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_simple_request() {
+        let mut router = router(Cors::new().credentials(true));
+
+        let res = get("/get", ORIGIN_VALUE).call(&mut router).await;
+        assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], ORIGIN_VALUE);
+        assert_eq!(res.headers()[VARY], "origin");
+
+        let res = get("/missing", ORIGIN_VALUE).call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], ORIGIN_VALUE);
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_origin() {
+        let cors = Cors::new().origins(vec![ORIGIN_VALUE.to_owned()]);
+        let mut router = router(cors);
+
+        let res = get("/get", "https://evil.example").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::headers::Expect;
+use crate::http::{response, Request, Result, StatusCode};
+use crate::router::{Middleware, Next};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use serde_derive::Deserialize;
+use std::time::Duration;
+
+/// How a request sent with `Expect: 100-continue` is answered
+///
+/// hyper sends `100 Continue` the moment a handler first reads the body, so
+/// that is when the interim response goes out, if at all.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContinueMode {
+    /// `100 Continue` once the body is read
+    #[default]
+    Send,
+    /// 417 Expectation Failed, without reading the body
+    Reject,
+    /// The final response without ever reading the body, as servers not
+    /// wanting it do
+    Never,
+}
+
+impl ContinueMode {
+    /// What happened to the interim response
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Send => "sent",
+            Self::Reject => "rejected",
+            Self::Never => "withheld",
+        }
+    }
+}
+
+/// Holds back `100 Continue` for `delay`, then applies `mode` to `req`
+///
+/// Returns the response to answer with instead of handling `req`, if any,
+/// `req` being left without a body when the interim response is withheld.
+pub async fn expect_continue(
+    req: &mut Request,
+    mode: ContinueMode,
+    delay: Duration,
+) -> Option<Result> {
+    req.typed_header::<Expect>()?;
+    if let Err(e) = req.unless_disconnected(tokio::time::sleep(delay)).await {
+        return Some(Err(e));
+    }
+    match mode {
+        ContinueMode::Send => None,
+        ContinueMode::Reject => {
+            Some(response().status(StatusCode::EXPECTATION_FAILED).into())
+        }
+        ContinueMode::Never => {
+            drop(req.body());
+            None
+        }
+    }
+}
+
+/// Answers every request expecting `100 Continue` the configured way
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExpectContinue {
+    mode: ContinueMode,
+    delay: Duration,
+}
+
+impl ExpectContinue {
+    pub fn new(mode: ContinueMode, delay: Duration) -> Self {
+        Self { mode, delay }
+    }
+}
+
+#[async_trait]
+impl Middleware for ExpectContinue {
+    async fn handle(&self, mut req: Request, next: Next<'_>) -> Result {
+        match expect_continue(&mut req, self.mode, self.delay).await {
+            Some(res) => res,
+            None => next.run(req).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use uri_path::path;
+
+    async fn length(mut req: Request) -> Result {
+        let body = req.bytes().await?;
+        response().body(body.len().to_string())
+    }
+
+    async fn call(mode: ContinueMode, expect: bool) -> (StatusCode, String) {
+        let mut router = Router::builder()
+            .install(length, route(path!()).method(hyper::Method::POST))
+            .layer(ExpectContinue::new(mode, Duration::ZERO))
+            .build();
+
+        let mut req = request().method(hyper::Method::POST).body("abc");
+        if expect {
+            req = req.header("expect", "100-continue");
+        }
+        let res = req.call(&mut router).await;
+        (res.status(), res.read_body_utf8().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_expect_continue() {
+        assert_eq!(
+            call(ContinueMode::Send, true).await,
+            (StatusCode::OK, "3".to_owned())
+        );
+        assert_eq!(
+            call(ContinueMode::Reject, true).await.0,
+            StatusCode::EXPECTATION_FAILED
+        );
+        assert_eq!(
+            call(ContinueMode::Never, true).await,
+            (StatusCode::OK, "0".to_owned())
+        );
+        assert_eq!(
+            call(ContinueMode::Reject, false).await,
+            (StatusCode::OK, "3".to_owned())
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use crate::http::{Request, Response, Result, StatusCode};
+use crate::router::{Middleware, Next, Route};
+use async_trait::async_trait;
+use hyper::header::{self, HeaderName};
+
+/// Headers about the connection rather than the message, which a response
+/// only gets from hyper itself
+///
+/// `Upgrade` and `Connection` are left to 101 Switching Protocols, which
+/// needs them, and `Trailer` announces trailers end to end.
+pub(crate) const HOP_BY_HOP: [HeaderName; 6] = [
+    header::CONNECTION,
+    header::TE,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+    HeaderName::from_static("keep-alive"),
+    HeaderName::from_static("proxy-connection"),
+];
+
+/// Strips the hop-by-hop headers of `res`, and those named in its
+/// `Connection` header
+fn strip(res: &mut Response) {
+    if res.status() == StatusCode::SWITCHING_PROTOCOLS {
+        return;
+    }
+
+    let headers = res.headers_mut();
+    let named = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| name.trim().parse::<HeaderName>().ok())
+        .collect::<Vec<_>>();
+    for name in HOP_BY_HOP.iter().chain(&named) {
+        headers.remove(name);
+    }
+}
+
+/// Keeps handlers from setting hop-by-hop headers, which would confuse
+/// hyper framing the response, or be forwarded by an intermediary that
+/// ought to drop them
+///
+/// Routes testing intermediaries opt out with `route(...).hop_by_hop(true)`.
+#[derive(Clone, Debug, Default)]
+pub struct HopByHop;
+
+#[async_trait]
+impl Middleware for HopByHop {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result {
+        let allowed = req.route().is_some_and(Route::hop_by_hop);
+
+        let mut res = next.run(req).await?;
+        if !allowed {
+            strip(&mut res);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::response;
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use uri_path::path;
+
+    async fn handler(_: Request) -> Result {
+        response()
+            .header(header::CONNECTION, "close, x-hop")
+            .header("keep-alive", "timeout=5")
+            .header("x-hop", "1")
+            .header("x-end-to-end", "1")
+            .body("")
+    }
+
+    async fn headers(path: &str) -> hyper::HeaderMap {
+        let mut router = Router::builder()
+            .install(handler, route(path!("stripped")))
+            .install(handler, route(path!("kept")).hop_by_hop(true))
+            .layer(HopByHop)
+            .build();
+
+        request()
+            .path(path)
+            .call(&mut router)
+            .await
+            .headers()
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn test_strips_hop_by_hop() {
+        let headers = headers("/stripped").await;
+
+        assert!(headers.get(header::CONNECTION).is_none());
+        assert!(headers.get("keep-alive").is_none());
+        assert!(headers.get("x-hop").is_none());
+        assert_eq!(headers["x-end-to-end"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_route_keeps_hop_by_hop() {
+        let headers = headers("/kept").await;
+
+        assert_eq!(headers[header::CONNECTION], "close, x-hop");
+        assert_eq!(headers["x-hop"], "1");
+    }
+
+    #[test]
+    fn test_switching_protocols() {
+        let mut res = response()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, "websocket")
+            .body("")
+            .unwrap();
+        strip(&mut res);
+
+        assert_eq!(res.headers()[header::UPGRADE], "websocket");
+    }
+}
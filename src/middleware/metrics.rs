@@ -0,0 +1,404 @@
+use super::ConcurrencyLimit;
+use crate::handler::Handler;
+use crate::http::{response, Error, Request, Result};
+use crate::router::{Middleware, Next, Panic, Route};
+use async_trait::async_trait;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Method, StatusCode};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The route label of requests that didn't match any route
+const UNMATCHED: &str = "unmatched";
+
+const CONTENT_TYPE_TEXT: &str = "text/plain; version=0.0.4";
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// Keyed by route, method and status class
+    requests: BTreeMap<(String, &'static str, &'static str), u64>,
+    durations: BTreeMap<String, Histogram>,
+    in_flight: BTreeMap<String, i64>,
+    panics: BTreeMap<String, u64>,
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+/// Keeps arbitrary methods from blowing up the number of series
+fn method_label(method: &Method) -> &'static str {
+    const KNOWN: [&str; 9] = [
+        "GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS", "TRACE",
+        "CONNECT",
+    ];
+    KNOWN
+        .iter()
+        .copied()
+        .find(|known| *known == method.as_str())
+        .unwrap_or("OTHER")
+}
+
+/// Escapes a label value for the text exposition format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Registry {
+    fn render(&self, concurrency: Option<&ConcurrencyLimit>) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP httpbox_requests_total Requests handled.\n");
+        out.push_str("# TYPE httpbox_requests_total counter\n");
+        for ((route, method, class), count) in &self.requests {
+            let _ = writeln!(
+                out,
+                "httpbox_requests_total{{route=\"{}\",method=\"{}\",\
+                 status=\"{}\"}} {}",
+                escape(route),
+                method,
+                class,
+                count
+            );
+        }
+
+        out.push_str(
+            "# HELP httpbox_request_duration_seconds Time until the response \
+             headers were ready.\n",
+        );
+        out.push_str("# TYPE httpbox_request_duration_seconds histogram\n");
+        for (route, histogram) in &self.durations {
+            let route = escape(route);
+            for (count, bound) in histogram.buckets.iter().zip(BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "httpbox_request_duration_seconds_bucket{{route=\"{}\",\
+                     le=\"{}\"}} {}",
+                    route, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "httpbox_request_duration_seconds_bucket{{route=\"{}\",\
+                 le=\"+Inf\"}} {}",
+                route, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "httpbox_request_duration_seconds_sum{{route=\"{}\"}} {}",
+                route, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "httpbox_request_duration_seconds_count{{route=\"{}\"}} {}",
+                route, histogram.count
+            );
+        }
+
+        out.push_str(
+            "# HELP httpbox_requests_in_flight Requests being handled.\n",
+        );
+        out.push_str("# TYPE httpbox_requests_in_flight gauge\n");
+        for (route, count) in &self.in_flight {
+            let _ = writeln!(
+                out,
+                "httpbox_requests_in_flight{{route=\"{}\"}} {}",
+                escape(route),
+                count
+            );
+        }
+
+        out.push_str("# HELP httpbox_panics_total Handlers that panicked.\n");
+        out.push_str("# TYPE httpbox_panics_total counter\n");
+        for (route, count) in &self.panics {
+            let _ = writeln!(
+                out,
+                "httpbox_panics_total{{route=\"{}\"}} {}",
+                escape(route),
+                count
+            );
+        }
+
+        if let Some(concurrency) = concurrency {
+            out.push_str(
+                "# HELP httpbox_concurrency_limit Requests handled at once \
+                 before shedding.\n",
+            );
+            out.push_str("# TYPE httpbox_concurrency_limit gauge\n");
+            let _ = writeln!(
+                out,
+                "httpbox_concurrency_limit {}",
+                concurrency.max()
+            );
+            out.push_str(
+                "# HELP httpbox_requests_shed_total Requests turned away at \
+                 the concurrency limit.\n",
+            );
+            out.push_str("# TYPE httpbox_requests_shed_total counter\n");
+            let _ = writeln!(
+                out,
+                "httpbox_requests_shed_total {}",
+                concurrency.shed()
+            );
+        }
+        out
+    }
+}
+
+/// Takes a request off the in-flight gauge when it completes or is dropped
+struct InFlight<'a> {
+    registry: &'a Mutex<Registry>,
+    route: &'a str,
+}
+
+impl<'a> InFlight<'a> {
+    fn start(registry: &'a Mutex<Registry>, route: &'a str) -> Self {
+        let mut locked = registry.lock().unwrap();
+        *locked.in_flight.entry(route.to_owned()).or_default() += 1;
+        Self { registry, route }
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut registry = self.registry.lock().unwrap();
+        if let Some(count) = registry.in_flight.get_mut(self.route) {
+            *count -= 1;
+        }
+    }
+}
+
+/// Records request counts, latencies and in-flight requests per route, and
+/// serves them in the Prometheus text format when installed as a handler
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+    concurrency: Option<ConcurrencyLimit>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also reports the limit and the requests it shed
+    pub fn concurrency_limit(mut self, limit: ConcurrencyLimit) -> Self {
+        self.concurrency = Some(limit);
+        self
+    }
+}
+
+#[async_trait]
+impl Middleware for Metrics {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result {
+        let route = req.route().map_or(UNMATCHED, Route::name).to_owned();
+        let method = method_label(req.method());
+
+        let start = Instant::now();
+        let in_flight = InFlight::start(&self.registry, &route);
+        let result = next.run(req).await;
+        drop(in_flight);
+
+        let status = match &result {
+            Ok(res) => res.status(),
+            Err(e) => e.status(),
+        };
+        let panicked = matches!(
+            &result,
+            Err(Error::Failure(res)) if res.extensions().get::<Panic>().is_some()
+        );
+        let mut registry = self.registry.lock().unwrap();
+        if panicked {
+            *registry.panics.entry(route.clone()).or_default() += 1;
+        }
+        *registry
+            .requests
+            .entry((route.clone(), method, status_class(status)))
+            .or_default() += 1;
+        registry
+            .durations
+            .entry(route)
+            .or_default()
+            .observe(start.elapsed());
+        drop(registry);
+
+        result
+    }
+}
+
+#[async_trait]
+impl Handler for Metrics {
+    async fn handle(&self, _req: Request) -> Result {
+        let body = self
+            .registry
+            .lock()
+            .unwrap()
+            .render(self.concurrency.as_ref());
+        response()
+            .header(CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE_TEXT))
+            .body(body)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{not_found, ok};
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use uri_path::path;
+
+    async fn handler(_: Request) -> Result {
+        ok("")
+    }
+
+    async fn missing(_: Request) -> Result {
+        Err(not_found())
+    }
+
+    async fn panicking(_: Request) -> Result {
+        panic!("oops")
+    }
+
+    fn router(metrics: &Metrics) -> Router {
+        Router::builder()
+            .install(handler, route(path!("status" / code)))
+            .install(missing, route(path!("missing")).name("missing"))
+            .install(panicking, route(path!("panic")).name("panic"))
+            .install(metrics.clone(), route(path!("metrics")))
+            .layer(metrics.clone())
+            .build()
+    }
+
+    async fn get(router: &mut Router, path: &str) -> String {
+        let res = request().path(path).call(router).await;
+        res.read_body_utf8().await.unwrap()
+    }
+
+    #[test]
+    fn test_status_class() {
+        assert_eq!(status_class(StatusCode::OK), "2xx");
+        assert_eq!(status_class(StatusCode::NOT_FOUND), "4xx");
+        assert_eq!(status_class(StatusCode::BAD_GATEWAY), "5xx");
+    }
+
+    #[test]
+    fn test_method_label() {
+        assert_eq!(method_label(&Method::PATCH), "PATCH");
+        assert_eq!(
+            method_label(&Method::from_bytes(b"PURGE").unwrap()),
+            "OTHER"
+        );
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::default();
+        histogram.observe(Duration::from_millis(20));
+        histogram.observe(Duration::from_secs(20));
+
+        assert_eq!(histogram.buckets[..3], [0, 0, 0]);
+        assert_eq!(histogram.buckets[3..], [1; 9]);
+        assert_eq!(histogram.count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let metrics = Metrics::new();
+        let mut router = router(&metrics);
+
+        get(&mut router, "/status/200").await;
+        get(&mut router, "/status/201").await;
+        get(&mut router, "/missing").await;
+        get(&mut router, "/nowhere").await;
+        get(&mut router, "/panic").await;
+        let body = get(&mut router, "/metrics").await;
+
+        assert!(body.contains(
+            "httpbox_requests_total{route=\"/status/:code\",method=\"GET\",\
+             status=\"2xx\"} 2\n"
+        ));
+        assert!(body.contains(
+            "httpbox_requests_total{route=\"missing\",method=\"GET\",\
+             status=\"4xx\"} 1\n"
+        ));
+        assert!(body.contains(
+            "httpbox_requests_total{route=\"unmatched\",method=\"GET\",\
+             status=\"4xx\"} 1\n"
+        ));
+        assert!(body.contains(
+            "httpbox_request_duration_seconds_count{route=\"/status/:code\"} \
+             2\n"
+        ));
+        assert!(body.contains(
+            "httpbox_request_duration_seconds_bucket{route=\"missing\",\
+             le=\"+Inf\"} 1\n"
+        ));
+        assert!(body.contains(
+            "httpbox_requests_total{route=\"panic\",method=\"GET\",\
+             status=\"5xx\"} 1\n"
+        ));
+        assert!(body.contains("httpbox_panics_total{route=\"panic\"} 1\n"));
+        // The scrape itself is still being handled
+        assert!(
+            body.contains("httpbox_requests_in_flight{route=\"/metrics\"} 1")
+        );
+        assert!(
+            body.contains("httpbox_requests_in_flight{route=\"missing\"} 0")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit() {
+        let metrics =
+            Metrics::new().concurrency_limit(ConcurrencyLimit::new(8));
+        let body = get(&mut router(&metrics), "/metrics").await;
+
+        assert!(body.contains("httpbox_concurrency_limit 8\n"));
+        assert!(body.contains("httpbox_requests_shed_total 0\n"));
+        assert!(!get(&mut router(&Metrics::new()), "/metrics")
+            .await
+            .contains("httpbox_concurrency_limit"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,26 @@
+mod access_log;
+mod alt_svc;
+mod chaos;
+mod compression;
+mod concurrency;
+mod cors;
+mod expect;
+mod hop_by_hop;
+mod metrics;
+mod rate_limit;
+mod request_id;
+mod timeout;
+
+pub use self::access_log::AccessLog;
+pub use self::alt_svc::AltSvc;
+pub use self::chaos::{Chaos, Faults};
+pub use self::compression::Compression;
+pub use self::concurrency::ConcurrencyLimit;
+pub use self::cors::Cors;
+pub use self::expect::{expect_continue, ContinueMode, ExpectContinue};
+pub use self::hop_by_hop::HopByHop;
+pub(crate) use self::hop_by_hop::HOP_BY_HOP;
+pub use self::metrics::Metrics;
+pub use self::rate_limit::RateLimit;
+pub use self::request_id::RequestIds;
+pub use self::timeout::Timeouts;
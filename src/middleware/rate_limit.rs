@@ -0,0 +1,109 @@
+use crate::http::{Buckets, ClientKey, Limit, Request, Result};
+use crate::router::{Middleware, Next};
+use async_trait::async_trait;
+use std::sync::{Arc, RwLock};
+
+/// Answers with 429 Too Many Requests once a client exceeds the limit
+///
+/// Clones share the limit, so it can be changed or lifted while serving.
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    limit: Arc<RwLock<Option<Limit>>>,
+    key: ClientKey,
+    buckets: Arc<Buckets>,
+}
+
+impl RateLimit {
+    pub fn new(limit: Limit, key: ClientKey) -> Self {
+        let rate_limit = Self::unlimited(key);
+        rate_limit.set(Some(limit));
+        rate_limit
+    }
+
+    /// Lets every request through until a limit is set
+    pub fn unlimited(key: ClientKey) -> Self {
+        Self {
+            limit: Arc::new(RwLock::new(None)),
+            key,
+            buckets: Arc::new(Buckets::new()),
+        }
+    }
+
+    pub fn limit(&self) -> Option<Limit> {
+        *self.limit.read().unwrap()
+    }
+
+    /// Replaces the limit, `None` lifting it
+    pub fn set(&self, limit: Option<Limit>) {
+        *self.limit.write().unwrap() = limit;
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimit {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result {
+        if let Some(limit) = self.limit() {
+            self.buckets.take(&req.client_key(&self.key), limit)?;
+        }
+        next.run(req).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::ok;
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use hyper::StatusCode;
+    use uri_path::path;
+
+    async fn handler(_: Request) -> Result {
+        ok("")
+    }
+
+    async fn get(router: &mut Router, key: &str) -> crate::http::Response {
+        request().header("x-key", key).call(router).await
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let mut router = Router::builder()
+            .install(handler, route(path!()))
+            .layer(RateLimit::new(
+                Limit::new(0.001, 2),
+                ClientKey::Header("x-key".parse().unwrap()),
+            ))
+            .build();
+
+        for _ in 0..2 {
+            let res = get(&mut router, "a").await;
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+        let res = get(&mut router, "a").await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(res.headers().contains_key("retry-after"));
+
+        let res = get(&mut router, "b").await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_set_limit() {
+        let limit =
+            RateLimit::unlimited(ClientKey::Header("x-key".parse().unwrap()));
+        let mut router = Router::builder()
+            .install(handler, route(path!()))
+            .layer(limit.clone())
+            .build();
+
+        limit.set(Some(Limit::new(0.001, 1)));
+        get(&mut router, "a").await;
+        let res = get(&mut router, "a").await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        limit.set(None);
+        let res = get(&mut router, "a").await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::http::{Error, Request, RequestId, Result};
+use crate::router::{Middleware, Next};
+use async_trait::async_trait;
+use hyper::header::{HeaderName, HeaderValue};
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Takes the `X-Request-Id` of the request, or generates one, and echoes it
+/// in the response
+#[derive(Clone, Debug, Default)]
+pub struct RequestIds;
+
+#[async_trait]
+impl Middleware for RequestIds {
+    async fn handle(&self, mut req: Request, next: Next<'_>) -> Result {
+        let id = req
+            .headers()
+            .get(&X_REQUEST_ID)
+            .and_then(|value| RequestId::parse(value.as_bytes()))
+            .unwrap_or_else(RequestId::generate);
+        let value = HeaderValue::from_str(id.as_str()).unwrap();
+        req.extensions_mut().insert(id);
+
+        let tag = |res: &mut crate::http::Response| {
+            res.headers_mut().insert(X_REQUEST_ID, value.clone());
+        };
+        match next.run(req).await {
+            Ok(mut res) => {
+                tag(&mut res);
+                Ok(res)
+            }
+            Err(Error::Failure(mut res)) => {
+                tag(&mut res);
+                Err(Error::Failure(res))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{not_found, ok};
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use uri_path::path;
+
+    async fn handler(req: Request) -> Result {
+        ok(req.id().unwrap().to_owned())
+    }
+
+    async fn missing(_: Request) -> Result {
+        Err(not_found())
+    }
+
+    fn router() -> Router {
+        Router::builder()
+            .install(handler, route(path!("id")))
+            .install(missing, route(path!("missing")))
+            .layer(RequestIds)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_propagates_request_id() {
+        let res = request()
+            .path("/id")
+            .header("x-request-id", "abc-123")
+            .call(&mut router())
+            .await;
+
+        assert_eq!(res.headers()["x-request-id"], "abc-123");
+        assert_eq!(res.read_body_utf8().await.unwrap(), "abc-123");
+    }
+
+    #[tokio::test]
+    async fn test_generates_request_id() {
+        let res = request()
+            .path("/id")
+            .header("x-request-id", "not valid")
+            .call(&mut router())
+            .await;
+
+        let id = res.headers()["x-request-id"].to_str().unwrap().to_owned();
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+        assert_eq!(res.read_body_utf8().await.unwrap(), id);
+    }
+
+    #[tokio::test]
+    async fn test_request_id_on_failure() {
+        let res = request().path("/missing").call(&mut router()).await;
+
+        assert_eq!(res.status(), hyper::StatusCode::NOT_FOUND);
+        assert!(res.headers().contains_key("x-request-id"));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::http::{gateway_timeout, Body, Request, Result};
+use crate::router::{Middleware, Next, Route, Timeout};
+use async_trait::async_trait;
+use futures::prelude::*;
+use http_body_util::BodyExt;
+use std::io;
+use std::time::Duration;
+
+/// Cuts `body` off with an error once `idle` passes without a frame,
+/// passing its trailers on
+fn idle_body(body: Body, idle: Duration) -> Body {
+    let frames = stream::unfold(Some(body), move |body| async move {
+        let mut body = body?;
+        match tokio::time::timeout(idle, body.frame()).await {
+            Ok(Some(frame)) => Some((frame, Some(body))),
+            Ok(None) => None,
+            Err(_) => Some((
+                Err(io::Error::from(io::ErrorKind::TimedOut).into()),
+                None,
+            )),
+        }
+    });
+    Body::wrap_frames(frames)
+}
+
+/// Answers with 504 Gateway Timeout when a handler takes too long to
+/// respond, and cuts off request and response bodies of `Timeout::Idle`
+/// routes that stop sending data
+#[derive(Clone, Debug, Default)]
+pub struct Timeouts {
+    request: Option<Duration>,
+    idle: Option<Duration>,
+}
+
+impl Timeouts {
+    /// `None` switches the respective timeout off
+    pub fn new(request: Option<Duration>, idle: Option<Duration>) -> Self {
+        Self { request, idle }
+    }
+}
+
+#[async_trait]
+impl Middleware for Timeouts {
+    async fn handle(&self, mut req: Request, next: Next<'_>) -> Result {
+        let timeout = req.route().map_or(Timeout::Default, Route::timeout);
+        if let (Timeout::Idle, Some(idle)) = (timeout, self.idle) {
+            let body = req.body();
+            *req.body_mut() = idle_body(body, idle);
+        }
+        let limit = match timeout {
+            Timeout::Default => self.request,
+            Timeout::After(limit) => Some(limit),
+            Timeout::Idle => None,
+        };
+
+        let res = match limit {
+            Some(limit) => tokio::time::timeout(limit, next.run(req))
+                .await
+                .map_err(|_| gateway_timeout())??,
+            None => next.run(req).await?,
+        };
+
+        Ok(match (timeout, self.idle) {
+            (Timeout::Idle, Some(idle)) => {
+                res.map(|body: Body| idle_body(body, idle))
+            }
+            _ => res,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{ok, Response};
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use hyper::StatusCode;
+    use uri_path::path;
+
+    const SHORT: Duration = Duration::from_millis(20);
+
+    async fn slow(_: Request) -> Result {
+        tokio::time::sleep(SHORT * 5).await;
+        ok("slow")
+    }
+
+    async fn stalled(_: Request) -> Result {
+        let chunks = stream::once(future::ready(Ok::<_, io::Error>("first")))
+            .chain(stream::pending());
+        ok(Body::wrap_stream(chunks))
+    }
+
+    async fn read(mut req: Request) -> Result {
+        req.bytes().await?;
+        ok("read")
+    }
+
+    fn router() -> Router {
+        Router::builder()
+            .install(slow, route(path!("default")))
+            .install(
+                slow,
+                route(path!("after")).timeout(Timeout::After(SHORT * 20)),
+            )
+            .install(slow, route(path!("idle")).timeout(Timeout::Idle))
+            .install(stalled, route(path!("stalled")).timeout(Timeout::Idle))
+            .install(
+                read,
+                route(path!("upload"))
+                    .method(hyper::Method::POST)
+                    .timeout(Timeout::Idle),
+            )
+            .layer(Timeouts::new(Some(SHORT), Some(SHORT)))
+            .build()
+    }
+
+    async fn get(path: &str) -> Response {
+        request().path(path).call(&mut router()).await
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        assert_eq!(get("/default").await.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_route_timeout() {
+        assert_eq!(get("/after").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_idle_route_has_no_request_timeout() {
+        let res = get("/idle").await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body = crate::http::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "slow");
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let mut body = get("/stalled").await.into_body();
+
+        assert_eq!(body.next().await.unwrap().unwrap(), "first");
+        assert!(body.next().await.unwrap().is_err());
+        assert!(body.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_idle_request_body() {
+        let chunks = stream::once(future::ready(Ok::<_, io::Error>("first")))
+            .chain(stream::pending());
+        let mut router = router();
+        let res = request()
+            .method(hyper::Method::POST)
+            .path("/upload")
+            .streamed_body(Body::wrap_stream(chunks))
+            .call(&mut router);
+        // Without the timeout the handler waits for the rest forever
+        let res = tokio::time::timeout(SHORT * 50, res).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_disabled() {
+        let mut router = Router::builder()
+            .install(slow, route(path!("default")))
+            .layer(Timeouts::new(None, None))
+            .build();
+        let res = request().path("/default").call(&mut router).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
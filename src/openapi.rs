@@ -0,0 +1,474 @@
+//! An OpenAPI 3.1 document of the installed routes
+//!
+//! Path parameters come from the route patterns, with the types the `path!`
+//! macro checks them against, and query parameters from the structs the
+//! handlers extract with `Query`, whose names and types are found by
+//! deserializing them from a value that only answers what it is asked.
+use crate::router::Route;
+use hyper::Method;
+use serde::de::{self, DeserializeOwned, Deserializer, MapAccess, Visitor};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use uri_path::{PathSegment, PathToken};
+
+/// The JSON Schema type of a parameter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Boolean => "boolean",
+            Kind::Integer => "integer",
+            Kind::Number => "number",
+            Kind::String => "string",
+            Kind::Array => "array",
+            Kind::Object => "object",
+        }
+    }
+
+    /// The type a path segment is checked against by `path!`, like `u64`
+    fn of_token(token: &PathToken) -> (Self, Option<&'static str>) {
+        match token {
+            PathToken::Typed("uuid", _) => (Kind::String, Some("uuid")),
+            PathToken::Typed(name, _) => match *name {
+                "u8" | "u16" | "u32" | "u64" | "usize" | "i8" | "i16"
+                | "i32" | "i64" | "isize" => (Kind::Integer, None),
+                "f32" | "f64" => (Kind::Number, None),
+                "bool" => (Kind::Boolean, None),
+                _ => (Kind::String, None),
+            },
+            _ => (Kind::String, None),
+        }
+    }
+}
+
+/// A query parameter, listed as optional since whether it has a default
+/// can't be told from outside
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Parameter {
+    pub name: &'static str,
+    pub kind: Kind,
+}
+
+/// Stops the deserialization once it has told what it wanted to
+#[derive(Debug)]
+struct Probed;
+
+impl fmt::Display for Probed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "probed")
+    }
+}
+
+impl std::error::Error for Probed {}
+
+impl de::Error for Probed {
+    fn custom<T: fmt::Display>(_: T) -> Self {
+        Probed
+    }
+}
+
+/// Answers a struct with the one field `field`, whose value records the
+/// kind it is deserialized as, or else records the fields of the struct
+struct Probe<'a> {
+    field: Option<&'static str>,
+    fields: &'a mut &'static [&'static str],
+    kind: &'a mut Option<Kind>,
+}
+
+impl<'de, 'a> Deserializer<'de> for Probe<'a> {
+    type Error = Probed;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        _: V,
+    ) -> Result<V::Value, Probed> {
+        Err(Probed)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Probed> {
+        *self.fields = fields;
+        match self.field {
+            Some(field) => visitor.visit_map(OneField {
+                field: Some(field),
+                kind: self.kind,
+            }),
+            None => Err(Probed),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+struct OneField<'a> {
+    field: Option<&'static str>,
+    kind: &'a mut Option<Kind>,
+}
+
+impl<'de, 'a> MapAccess<'de> for OneField<'a> {
+    type Error = Probed;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Probed> {
+        match self.field.take() {
+            Some(field) => seed
+                .deserialize(de::value::BorrowedStrDeserializer::new(field))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Probed> {
+        seed.deserialize(FieldValue { kind: self.kind })
+    }
+}
+
+/// The value of the field, recording what it is asked to be
+struct FieldValue<'a> {
+    kind: &'a mut Option<Kind>,
+}
+
+impl<'a> FieldValue<'a> {
+    fn record<T>(self, kind: Kind) -> Result<T, Probed> {
+        *self.kind = Some(kind);
+        Err(Probed)
+    }
+}
+
+macro_rules! record {
+    ($kind:expr => $($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                _: V,
+            ) -> Result<V::Value, Probed> {
+                self.record($kind)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for FieldValue<'a> {
+    type Error = Probed;
+
+    record!(Kind::String => deserialize_any deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_identifier deserialize_unit deserialize_ignored_any);
+    record!(Kind::Boolean => deserialize_bool);
+    record!(Kind::Integer => deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16
+        deserialize_u32 deserialize_u64 deserialize_u128);
+    record!(Kind::Number => deserialize_f32 deserialize_f64);
+    record!(Kind::Array => deserialize_seq);
+    record!(Kind::Object => deserialize_map);
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Probed> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Probed> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: V,
+    ) -> Result<V::Value, Probed> {
+        self.record(Kind::String)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Probed> {
+        self.record(Kind::String)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _: usize,
+        _: V,
+    ) -> Result<V::Value, Probed> {
+        self.record(Kind::Array)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: usize,
+        _: V,
+    ) -> Result<V::Value, Probed> {
+        self.record(Kind::Array)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Probed> {
+        self.record(Kind::Object)
+    }
+}
+
+/// The fields of the struct `T` deserializes from, or none if it is not a
+/// struct, like the pairs of `Query<Vec<(String, String)>>`
+pub fn parameters<T: DeserializeOwned>() -> Vec<Parameter> {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(Probe {
+        field: None,
+        fields: &mut fields,
+        kind: &mut None,
+    });
+    let names = fields;
+    names
+        .iter()
+        .map(|&name| {
+            let mut kind = None;
+            let _ = T::deserialize(Probe {
+                field: Some(name),
+                fields: &mut fields,
+                kind: &mut kind,
+            });
+            Parameter {
+                name,
+                kind: kind.unwrap_or(Kind::String),
+            }
+        })
+        .collect()
+}
+
+/// The methods OpenAPI has operations for
+const METHODS: [Method; 8] = [
+    Method::GET,
+    Method::PUT,
+    Method::POST,
+    Method::DELETE,
+    Method::OPTIONS,
+    Method::HEAD,
+    Method::PATCH,
+    Method::TRACE,
+];
+
+/// Like `/status/{code}`, with the path parameters of the route
+fn path_item(route: &Route) -> (String, Vec<Value>) {
+    let mut parameters = vec![];
+    let mut template = String::new();
+    for segment in route.path().iter() {
+        template.push('/');
+        let (name, (kind, format), description) = match segment {
+            PathSegment::Literal(literal) => {
+                template.push_str(literal);
+                continue;
+            }
+            PathSegment::Dynamic(param) => {
+                (param.name(), Kind::of_token(param.token()), None)
+            }
+            PathSegment::Rest(name) => (
+                *name,
+                (Kind::String, None),
+                Some("The rest of the path, slashes included"),
+            ),
+        };
+        template.push_str(&format!("{{{}}}", name));
+        let mut schema = json!({ "type": kind.as_str() });
+        if let Some(format) = format {
+            schema["format"] = format.into();
+        }
+        let mut parameter = json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "schema": schema,
+        });
+        if let Some(description) = description {
+            parameter["description"] = description.into();
+        }
+        parameters.push(parameter);
+    }
+    if template.is_empty() {
+        template.push('/');
+    }
+    (template, parameters)
+}
+
+/// Unique among the operations, like `get_status_code`
+fn operation_id(method: &Method, template: &str) -> String {
+    let words = template
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty());
+    std::iter::once(method.as_str().to_ascii_lowercase())
+        .chain(words.map(str::to_owned))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// The OpenAPI document of `routes`, each route being an operation per
+/// method it handles
+pub fn document<'a>(routes: impl IntoIterator<Item = &'a Route>) -> Value {
+    let mut paths = Map::new();
+    let mut tags: Vec<&str> = vec![];
+    for route in routes {
+        for tag in route.tags() {
+            if !tags.contains(tag) {
+                tags.push(tag);
+            }
+        }
+        let (template, mut parameters) = path_item(route);
+        parameters.extend(route.parameters().iter().map(|parameter| {
+            json!({
+                "name": parameter.name,
+                "in": "query",
+                "schema": { "type": parameter.kind.as_str() },
+            })
+        }));
+        let item = paths.entry(template.clone()).or_insert_with(|| json!({}));
+        for method in METHODS.iter().filter(|method| route.allows(method)) {
+            // HEAD is implied by GET, unless routed on its own
+            if method == Method::HEAD && !route.methods().contains(method) {
+                continue;
+            }
+            let mut operation = json!({
+                "operationId": operation_id(
+                    method,
+                    route.explicit_name().unwrap_or(&template),
+                ),
+                "responses": {
+                    "default": { "description": "The response of the endpoint" }
+                },
+            });
+            if let Some(description) = route.description() {
+                operation["summary"] = description.into();
+            }
+            if !route.tags().is_empty() {
+                operation["tags"] = route.tags().into();
+            }
+            if !parameters.is_empty() {
+                operation["parameters"] = parameters.clone().into();
+            }
+            item[method.as_str().to_ascii_lowercase()] = operation;
+        }
+    }
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "httpbox",
+            "description": "An HTTP test tool, answering with what it was sent and whatever was asked for",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "tags": tags
+            .iter()
+            .map(|tag| json!({ "name": tag }))
+            .collect::<Vec<_>>(),
+        "paths": paths,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::router::route;
+    use serde_derive::Deserialize;
+    use uri_path::path;
+
+    #[derive(Deserialize)]
+    struct Params {
+        seed: Option<u32>,
+        rate: f64,
+        #[serde(default)]
+        verbose: bool,
+        name: Option<String>,
+    }
+
+    #[test]
+    fn test_parameters() {
+        let kinds = parameters::<Params>()
+            .into_iter()
+            .map(|parameter| (parameter.name, parameter.kind))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds,
+            [
+                ("seed", Kind::Integer),
+                ("rate", Kind::Number),
+                ("verbose", Kind::Boolean),
+                ("name", Kind::String),
+            ]
+        );
+        assert!(parameters::<Vec<(String, String)>>().is_empty());
+
+        // Describing them takes nothing away from deserializing them
+        let params: Params =
+            serde_urlencoded::from_str("seed=1&rate=0.5&name=a").unwrap();
+        assert_eq!(
+            (params.seed, params.rate, params.verbose, params.name),
+            (Some(1), 0.5, false, Some("a".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_document() {
+        let routes: Vec<Route> = vec![
+            route(path!("status" / [code: u16]))
+                .methods(vec![Method::GET, Method::POST])
+                .description("Returns the status code")
+                .into(),
+            route(path!("bin" / [id: uuid] / [*rest]))
+                .name("bin")
+                .tag("bins")
+                .into(),
+            route(path!()).into(),
+        ];
+        let document = document(&routes);
+
+        assert_eq!(document["openapi"], "3.1.0");
+        let status = &document["paths"]["/status/{code}"];
+        assert_eq!(status["get"]["operationId"], "get_status_code");
+        assert_eq!(status["post"]["summary"], "Returns the status code");
+        assert!(status.get("head").is_none());
+        let code = &status["get"]["parameters"][0];
+        assert_eq!(code["in"], "path");
+        assert_eq!(code["schema"]["type"], "integer");
+
+        let bin = &document["paths"]["/bin/{id}/{rest}"]["get"];
+        assert_eq!(bin["parameters"][0]["schema"]["format"], "uuid");
+        assert_eq!(bin["operationId"], "get_bin");
+        assert_eq!(bin["tags"][0], "bins");
+        assert_eq!(document["tags"][0]["name"], "bins");
+        assert!(status["get"].get("tags").is_none());
+        assert!(document["paths"]["/"]["get"].is_object());
+    }
+}
@@ -1,20 +1,97 @@
-use rand::prelude::*;
-use rand::rngs::SmallRng as Rng;
+//! The random numbers behind every endpoint taking a `seed`
+//!
+//! A seeded generator produces the same output in every release, so we
+//! implement the algorithm ourselves rather than rely on `rand`'s
+//! `SmallRng`, which may change between versions and platforms:
+//!
+//! - The generator is xoshiro256++.
+//! - Its 256 bit state is the little-endian `u32` seed repeated eight times,
+//!   read as four little-endian `u64`s. An all-zero state is replaced by
+//!   four outputs of SplitMix64 seeded with 0.
+//! - A `u32` is the upper half of the next `u64`.
+//! - A byte is the lowest byte of the next `u32`.
+//! - A UUID takes 16 bytes, the little-endian bytes of two `u64`s, with the
+//!   version and variant bits set for version 4.
+use rand::{Error, RngCore};
+use uuid::Uuid;
 
-fn to_bytes(val: u32) -> <Rng as SeedableRng>::Seed {
-    let slice = val.to_le_bytes();
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Rng {
+    pub fn from_seed(seed: u32) -> Self {
+        let half = u64::from(seed);
+        let word = half | (half << 32);
+        if word == 0 {
+            let mut splitmix = 0;
+            return Self {
+                state: [(); 4].map(|_| splitmix64(&mut splitmix)),
+            };
+        }
+        Self { state: [word; 4] }
+    }
+
+    pub fn byte(&mut self) -> u8 {
+        self.next_u32() as u8
+    }
 
-    let mut seed = <Rng as SeedableRng>::Seed::default();
-    for chunk in seed.as_mut().chunks_mut(4) {
-        chunk.copy_from_slice(&slice);
+    pub fn uuid(&mut self) -> Uuid {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.next_u64().to_le_bytes());
+        bytes[8..].copy_from_slice(&self.next_u64().to_le_bytes());
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
     }
-    seed
 }
 
+impl RngCore for Rng {
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Without a seed, the whole state comes from the operating system
 pub fn rng(seed: Option<u32>) -> Rng {
     match seed {
-        Some(seed) => Rng::from_seed(to_bytes(seed)),
-        None => Rng::from_rng(thread_rng()).unwrap(),
+        Some(seed) => Rng::from_seed(seed),
+        None => match rand::random::<[u64; 4]>() {
+            [0, 0, 0, 0] => Rng::from_seed(0),
+            state => Rng { state },
+        },
     }
 }
 
@@ -35,4 +112,29 @@ mod tests {
         assert_eq!(rng.next_u32(), 19744u32);
         assert_eq!(rng.next_u32(), 2636570400u32);
     }
+
+    #[test]
+    fn rng_zero_seed_consistent() {
+        let mut rng = rng(Some(0));
+        assert_eq!(rng.next_u64(), 0x53175d61490b23df);
+        assert_eq!(rng.next_u64(), 0x61da6f3dc380d507);
+    }
+
+    #[test]
+    fn rng_bytes_consistent() {
+        let mut rng = rng(Some(1234));
+        let bytes = (0..4).map(|_| rng.byte()).collect::<Vec<_>>();
+        assert_eq!(bytes, [214, 212, 32, 32]);
+
+        let mut buf = [0; 4];
+        rng.fill_bytes(&mut buf);
+        assert_eq!(buf, [116, 242, 62, 221]);
+    }
+
+    #[test]
+    fn rng_uuid_consistent() {
+        let uuid = rng(Some(1234)).uuid();
+        assert_eq!(uuid.get_version_num(), 4);
+        assert_eq!(uuid.to_string(), "d60400d2-d604-40d2-9404-0069d4040069");
+    }
 }
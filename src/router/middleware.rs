@@ -0,0 +1,159 @@
+use super::panic::catch_panics;
+use crate::handler::Handler;
+use crate::http::{Error, ErrorRenderer, Request, Response, Result};
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result;
+}
+
+enum Target<'a> {
+    Handler(&'a (dyn Handler + Sync)),
+    Failure(Error),
+    /// Answered by the router itself
+    Response(Response),
+}
+
+/// The remainder of the middleware chain, ending in the routed handler.
+pub struct Next<'a> {
+    middleware: &'a [Box<dyn Middleware>],
+    target: Target<'a>,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(
+        middleware: &'a [Box<dyn Middleware>],
+        handler: &'a (dyn Handler + Sync),
+    ) -> Self {
+        Self {
+            middleware,
+            target: Target::Handler(handler),
+        }
+    }
+
+    pub(crate) fn failure(
+        middleware: &'a [Box<dyn Middleware>],
+        error: Error,
+    ) -> Self {
+        Self {
+            middleware,
+            target: Target::Failure(error),
+        }
+    }
+
+    pub(crate) fn respond(
+        middleware: &'a [Box<dyn Middleware>],
+        response: Response,
+    ) -> Self {
+        Self {
+            middleware,
+            target: Target::Response(response),
+        }
+    }
+
+    /// Errors without a response of their own come back rendered, see
+    /// `RouterBuilder::errors`
+    pub async fn run(self, req: Request) -> Result {
+        let errors = req.extensions().get::<ErrorRenderer>().cloned();
+        let result = match self.middleware.split_first() {
+            Some((current, middleware)) => {
+                let next = Next {
+                    middleware,
+                    target: self.target,
+                };
+                current.handle(req, next).await
+            }
+            None => match self.target {
+                Target::Handler(handler) => {
+                    catch_panics(req, |req| handler.handle(req)).await
+                }
+                Target::Failure(error) => Err(error),
+                Target::Response(response) => Ok(response),
+            },
+        };
+        match errors {
+            Some(errors) => result.map_err(|e| errors.render(e)),
+            None => result,
+        }
+    }
+}
+
+pub struct Layered<H, M> {
+    handler: H,
+    middleware: M,
+}
+
+#[async_trait]
+impl<H, M> Handler for Layered<H, M>
+where
+    H: Handler + Sync,
+    M: Middleware,
+{
+    async fn handle(&self, req: Request) -> Result {
+        self.middleware
+            .handle(req, Next::new(&[], &self.handler))
+            .await
+    }
+}
+
+pub trait HandlerExt: Handler + Sync + Sized {
+    /// Wraps this handler alone with the given middleware
+    fn layer<M: Middleware>(self, middleware: M) -> Layered<Self, M> {
+        Layered {
+            handler: self,
+            middleware,
+        }
+    }
+}
+
+impl<H: Handler + Sync> HandlerExt for H {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::ok;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+
+    struct Reject;
+
+    #[async_trait]
+    impl Middleware for Reject {
+        async fn handle(&self, _: Request, _: Next<'_>) -> Result {
+            Err(crate::http::bad_request())
+        }
+    }
+
+    async fn handler(_: Request) -> Result {
+        ok("handled")
+    }
+
+    #[tokio::test]
+    async fn test_layered_handler() {
+        let res = request().handle(handler.layer(Tag("one"))).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("x-tag").unwrap(), "one");
+        let body = res.read_body_utf8().await.unwrap();
+        assert_eq!(body, "handled");
+    }
+
+    #[tokio::test]
+    async fn test_nested_layers() {
+        let res = request()
+            .handle(handler.layer(Tag("inner")).layer(Tag("outer")))
+            .await
+            .unwrap();
+
+        let tags = res.headers().get_all("x-tag").iter().collect::<Vec<_>>();
+        assert_eq!(tags, ["inner", "outer"]);
+    }
+
+    #[tokio::test]
+    async fn test_short_circuit() {
+        let res = request().handle(handler.layer(Reject)).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
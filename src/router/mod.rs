@@ -1,45 +1,158 @@
+use crate::config::Config;
 use crate::handler::Handler;
-use crate::http::{internal_server_error, not_found, Error, Request, Response};
+use crate::headers::{Allow, ContentLength, HeaderMapExt};
+use crate::http::{
+    limit_body, method_not_allowed, not_found, payload_too_large, Body,
+    BoxError, Bytes, Error, ErrorRenderer, Request, Response, TrustedProxies,
+};
 use futures::prelude::*;
-use hyper::{service::Service, Body, Request as HTTPRequest};
+use hyper::body::Body as HttpBody;
+use hyper::http::StatusCode;
+use hyper::{Method, Request as HTTPRequest};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use uri_path::PathMatch;
+use tower::Service;
+use uri_path::{Path, PathMatch, PathTrie};
 
+mod middleware;
+mod panic;
 mod routes;
 
-pub use self::routes::{route, Route};
-pub use uri_path::{Path, PathSegment};
+pub use self::middleware::{HandlerExt, Layered, Middleware, Next};
+pub use self::panic::{Panic, PanicHook};
+pub use self::routes::{route, Route, Timeout};
 
-async fn handle_panics(
-    fut: impl Future<Output = crate::http::Result>,
-) -> crate::http::Result {
-    let wrapped = std::panic::AssertUnwindSafe(fut).catch_unwind();
-    wrapped.await.map_err(|_| internal_server_error())?
+/// The methods the routes matching a request's path accept, set by the
+/// router when none of them accepts the request's own method
+#[derive(Clone, Debug)]
+pub struct AllowedMethods(pub Vec<Method>);
+
+fn limit_request_body(
+    req: &mut HTTPRequest<Body>,
+    limit: usize,
+) -> Result<(), Error> {
+    if let Some(ContentLength(length)) = req.headers().typed_get() {
+        if length > limit as u64 {
+            return Err(payload_too_large());
+        }
+    }
+
+    let body = std::mem::take(req.body_mut());
+    *req.body_mut() = limit_body(body, limit);
+    Ok(())
+}
+
+/// The `Allow` list for routes accepting `methods`, with what the router
+/// answers on their behalf
+fn allow(mut methods: Vec<Method>) -> Vec<Method> {
+    if methods.contains(&Method::GET) && !methods.contains(&Method::HEAD) {
+        methods.push(Method::HEAD);
+    }
+    if !methods.contains(&Method::OPTIONS) {
+        methods.push(Method::OPTIONS);
+    }
+    methods
+}
+
+fn options(allowed: &[Method]) -> Response {
+    let mut res = Response::default();
+    *res.status_mut() = StatusCode::NO_CONTENT;
+    res.headers_mut()
+        .typed_insert(allowed.iter().cloned().collect::<Allow>());
+    res
+}
+
+/// Drops the body of a response to `HEAD`, but keeps its length
+fn head(mut res: Response) -> Response {
+    let status = res.status();
+    let has_body = !(status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED);
+    if has_body && !res.headers().contains_key(hyper::header::CONTENT_LENGTH) {
+        if let Some(length) = HttpBody::size_hint(res.body()).exact() {
+            res.headers_mut().typed_insert(ContentLength(length));
+        }
+    }
+    *res.body_mut() = Body::empty();
+    res
+}
+
+/// Hands the request to `handler` once its body is within `limit`
+fn dispatch<'a>(
+    middleware: &'a [Box<dyn Middleware>],
+    handler: &'a (dyn Handler + Sync),
+    req: &mut HTTPRequest<Body>,
+    limit: Option<usize>,
+) -> Next<'a> {
+    match limit.map_or(Ok(()), |limit| limit_request_body(req, limit)) {
+        Ok(()) => Next::new(middleware, handler),
+        Err(e) => Next::failure(middleware, e),
+    }
 }
 
 pub struct Endpoint {
-    route: Route,
+    route: Arc<Route>,
     handler: Box<dyn Handler + Sync>,
 }
 
 impl Endpoint {
     fn new<H: Handler + Sync + 'static>(route: Route, handler: H) -> Self {
         Self {
-            route,
+            route: Arc::new(route),
             handler: Box::new(handler),
         }
     }
 }
 
+/// A handler of a mounted router, wrapped in that router's own middleware
+struct Mounted {
+    middleware: Arc<[Box<dyn Middleware>]>,
+    handler: Box<dyn Handler + Sync>,
+}
+
+#[async_trait::async_trait]
+impl Handler for Mounted {
+    async fn handle(&self, req: Request) -> crate::http::Result {
+        Next::new(&self.middleware, &*self.handler).run(req).await
+    }
+
+    fn name(&self) -> &'static str {
+        self.handler.name()
+    }
+
+    fn describe(&self) -> Option<&'static str> {
+        self.handler.describe()
+    }
+
+    fn parameters(&self) -> Vec<crate::openapi::Parameter> {
+        self.handler.parameters()
+    }
+}
+
 pub struct RouterBuilder {
     endpoints: Vec<Endpoint>,
+    middleware: Vec<Box<dyn Middleware>>,
+    max_body_size: Option<usize>,
+    trusted_proxies: Arc<TrustedProxies>,
+    config: Arc<Config>,
+    fallback: Option<Box<dyn Handler + Sync>>,
+    errors: ErrorRenderer,
+    on_panic: Option<PanicHook>,
 }
 
 impl RouterBuilder {
     fn new() -> Self {
-        Self { endpoints: vec![] }
+        Self {
+            endpoints: vec![],
+            middleware: vec![],
+            max_body_size: None,
+            trusted_proxies: Arc::default(),
+            config: Arc::default(),
+            fallback: None,
+            errors: ErrorRenderer::default(),
+            on_panic: None,
+        }
     }
 
     pub fn install<H: Handler + Sync + 'static, R: Into<Route>>(
@@ -47,33 +160,167 @@ impl RouterBuilder {
         handler: H,
         route: R,
     ) -> Self {
-        self.endpoints.push(Endpoint::new(route.into(), handler));
+        let route = route.into().with_handler(&handler);
+        self.endpoints.push(Endpoint::new(route, handler));
+        self
+    }
+
+    /// Tags the routes installed by `install` with `tag`, so the route table
+    /// and the OpenAPI document list them together
+    pub fn group<F: FnOnce(Self) -> Self>(
+        self,
+        tag: &'static str,
+        install: F,
+    ) -> Self {
+        let before = self.endpoints.len();
+        let mut builder = install(self);
+        for endpoint in &mut builder.endpoints[before..] {
+            Arc::get_mut(&mut endpoint.route)
+                .expect("routes are only shared once built")
+                .tag(tag);
+        }
+        builder
+    }
+
+    /// Installs the routes of `router` below `prefix`, so `/status/:code`
+    /// mounted at `/api` is served at `/api/status/:code` with the same
+    /// parameters.
+    ///
+    /// The middleware of `router` only wraps its own routes, inside the
+    /// middleware of this router, and its maximum body size only applies to
+    /// them. Everything else, like its fallback, is left to this router.
+    pub fn mount<P: Into<Path>>(
+        mut self,
+        prefix: P,
+        router: RouterBuilder,
+    ) -> Self {
+        let prefix = prefix.into();
+        let middleware: Arc<[Box<dyn Middleware>]> = router.middleware.into();
+        for endpoint in router.endpoints {
+            let mut route = endpoint.route.prefixed(&prefix);
+            if route.max_body_size().is_none() {
+                route = route.with_max_body_size(router.max_body_size);
+            }
+            let handler = Mounted {
+                middleware: middleware.clone(),
+                handler: endpoint.handler,
+            };
+            self.endpoints.push(Endpoint::new(route, handler));
+        }
+        self
+    }
+
+    /// Wraps every request, routed or not, with the given middleware.
+    ///
+    /// Middleware runs in the order it was added, the first being outermost.
+    pub fn layer<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// Rejects request bodies larger than this with 413 Payload Too Large,
+    /// unless the route sets its own limit
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = Some(max_body_size);
+        self
+    }
+
+    /// Peers whose `Forwarded` and `X-Forwarded-For` headers are believed
+    /// when resolving the client address
+    pub fn trusted_proxies(mut self, trusted_proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = Arc::new(trusted_proxies);
+        self
+    }
+
+    /// The settings handlers see through `Request::config`
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Arc::new(config);
+        self
+    }
+
+    /// Handles requests for paths no route is installed at, instead of
+    /// answering 404 Not Found
+    pub fn fallback<H: Handler + Sync + 'static>(mut self, handler: H) -> Self {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+
+    /// Renders the errors handlers and middleware fail with, unless they
+    /// come with a response, before the middleware wrapping them sees them
+    pub fn errors<E: Into<ErrorRenderer>>(mut self, errors: E) -> Self {
+        self.errors = errors.into();
+        self
+    }
+
+    /// Answers requests whose handler panicked with the response of `hook`
+    /// instead of 500 Internal Server Error, the panic being logged either way
+    pub fn on_panic<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Panic) -> Response + Send + Sync + 'static,
+    {
+        self.on_panic = Some(PanicHook::new(hook));
         self
     }
 
     pub fn routes(&self) -> impl Iterator<Item = &Route> {
-        self.endpoints.iter().map(|endpoint| &endpoint.route)
+        self.endpoints.iter().map(|endpoint| &*endpoint.route)
     }
 
+    /// Endpoints are tried from the most specific path to the least, so a
+    /// literal segment wins over a parameter regardless of which route was
+    /// installed first, routes as specific as each other keeping their order
     pub fn build(self) -> Router {
+        let endpoints = self
+            .endpoints
+            .into_iter()
+            .map(|endpoint| (endpoint.route.path().clone(), endpoint))
+            .collect();
         Router::new(RouterInternal {
-            endpoints: self.endpoints,
+            endpoints,
+            middleware: self.middleware,
+            max_body_size: self.max_body_size,
+            trusted_proxies: self.trusted_proxies,
+            config: self.config,
+            fallback: self.fallback,
+            errors: self.errors,
+            on_panic: self.on_panic,
         })
     }
 }
 
 pub struct RouterInternal {
-    endpoints: Vec<Endpoint>,
+    endpoints: PathTrie<Endpoint>,
+    middleware: Vec<Box<dyn Middleware>>,
+    max_body_size: Option<usize>,
+    trusted_proxies: Arc<TrustedProxies>,
+    config: Arc<Config>,
+    fallback: Option<Box<dyn Handler + Sync>>,
+    errors: ErrorRenderer,
+    on_panic: Option<PanicHook>,
 }
 
 impl RouterInternal {
+    /// The endpoint for the request, or else the methods other endpoints
+    /// for its path accept, which are none if there is no such path
+    ///
+    /// `HEAD` is answered by the `GET` endpoint unless one is installed for
+    /// it, the body being dropped afterwards.
     pub fn route(
         &self,
         req: &HTTPRequest<Body>,
-    ) -> Option<(&Endpoint, PathMatch)> {
-        self.endpoints.iter().find_map(|endpoint| {
-            endpoint.route.matches(req).map(|params| (endpoint, params))
-        })
+    ) -> Result<(&Endpoint, PathMatch), Vec<Method>> {
+        let mut allowed = vec![];
+        for (endpoint, params) in self.endpoints.matches(req.uri().path()) {
+            if endpoint.route.allows(req.method()) {
+                return Ok((endpoint, params));
+            }
+            for method in endpoint.route.methods() {
+                if !allowed.contains(method) {
+                    allowed.push(method.clone());
+                }
+            }
+        }
+        Err(allowed)
     }
 }
 
@@ -90,7 +337,13 @@ impl Router {
     }
 }
 
-impl Service<HTTPRequest<Body>> for Router {
+/// Takes requests with any body, like the ones hyper reads from a
+/// connection, which handlers get as a `Body`
+impl<B> Service<HTTPRequest<B>> for Router
+where
+    B: HttpBody<Data = Bytes> + Send + Sync + 'static,
+    B::Error: Into<BoxError>,
+{
     type Response = Response;
     type Error = hyper::http::Error;
     #[allow(clippy::type_complexity)]
@@ -105,17 +358,64 @@ impl Service<HTTPRequest<Body>> for Router {
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, req: HTTPRequest<Body>) -> Self::Future {
+    fn call(&mut self, req: HTTPRequest<B>) -> Self::Future {
+        let mut req = req.map(Body::new);
         let router = self.0.clone();
+        let is_head = req.method() == Method::HEAD;
 
         async move {
-            let (endpoint, matched_path) =
-                router.route(&req).ok_or_else(not_found)?;
+            req.extensions_mut().insert(router.trusted_proxies.clone());
+            req.extensions_mut().insert(router.config.clone());
+            req.extensions_mut().insert(router.errors.clone());
+            if let Some(hook) = &router.on_panic {
+                req.extensions_mut().insert(hook.clone());
+            }
+            let middleware = &router.middleware;
+            let (next, matched_path) = match router.route(&req) {
+                Ok((endpoint, matched_path)) => {
+                    req.extensions_mut().insert(endpoint.route.clone());
+
+                    let limit =
+                        endpoint.route.max_body_size().or(router.max_body_size);
+                    let next = dispatch(
+                        middleware,
+                        &*endpoint.handler,
+                        &mut req,
+                        limit,
+                    );
+                    (next, matched_path)
+                }
+                Err(allowed) if allowed.is_empty() => {
+                    let next = match &router.fallback {
+                        Some(fallback) => dispatch(
+                            middleware,
+                            &**fallback,
+                            &mut req,
+                            router.max_body_size,
+                        ),
+                        None => Next::failure(middleware, not_found()),
+                    };
+                    (next, PathMatch::default())
+                }
+                Err(allowed) => {
+                    let allowed = allow(allowed);
+                    let next = if req.method() == Method::OPTIONS {
+                        Next::respond(middleware, options(&allowed))
+                    } else {
+                        Next::failure(middleware, method_not_allowed(&allowed))
+                    };
+                    req.extensions_mut().insert(AllowedMethods(allowed));
+                    (next, PathMatch::default())
+                }
+            };
 
             let client_req = Request::new(req, matched_path);
-            handle_panics(endpoint.handler.handle(client_req)).await
+            panic::catch_panics(client_req, |req| next.run(req))
+                .await
+                .map_err(|e| router.errors.render(e))
         }
         .or_else(|e: Error| e.into_result())
+        .map_ok(move |res| if is_head { head(res) } else { res })
         .boxed()
     }
 }
@@ -124,9 +424,12 @@ impl Service<HTTPRequest<Body>> for Router {
 mod test {
 
     use super::*;
-    use crate::http::Request;
+    use crate::headers::Allow;
+    use crate::http::{ok, Request};
+    use crate::test::{request, Tag, TestResponseExt};
     use hyper::http::Request as HTTPRequest;
     use hyper::http::StatusCode;
+    use hyper::Method;
 
     use uri_path::path;
 
@@ -139,7 +442,361 @@ mod test {
         let router = Router::builder().install(handler, route(path!())).build();
         let mut service = router;
 
-        let res = service.call(HTTPRequest::default()).await.unwrap();
+        let res = service.call(HTTPRequest::<Body>::default()).await.unwrap();
         assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
     }
+
+    #[tokio::test]
+    async fn test_panic_hook() {
+        let handler = |_: Request| async {
+            panic!("broken {}", 42);
+        };
+        let mut router = Router::builder()
+            .install(handler, route(path!("broken")).name("broken"))
+            .on_panic(|panic| {
+                assert_eq!(panic.message, "broken 42");
+                assert_eq!(panic.route.as_deref(), Some("broken"));
+                let mut res = Response::new(Body::from(panic.path.clone()));
+                *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                res
+            })
+            .layer(Tag("tagged"))
+            .build();
+
+        let res = request().path("/broken").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers().get("x-tag").unwrap(), "tagged");
+        assert_eq!(
+            res.extensions().get::<Panic>().unwrap().method,
+            Method::GET
+        );
+        let body = crate::http::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "/broken");
+    }
+
+    async fn handler(_: Request) -> crate::http::Result {
+        ok("")
+    }
+
+    #[tokio::test]
+    async fn test_layer() {
+        let mut router = Router::builder()
+            .install(handler, route(path!("get")))
+            .layer(Tag("tagged"))
+            .build();
+
+        let res = request().path("/get").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("x-tag").unwrap(), "tagged");
+
+        let res = request().path("/post").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.headers().get("x-tag").unwrap(), "tagged");
+    }
+
+    async fn read_body(mut req: Request) -> crate::http::Result {
+        let body = req.bytes().await?;
+        ok(body)
+    }
+
+    fn post(path: &str, body: &'static str) -> HTTPRequest<Body> {
+        HTTPRequest::builder()
+            .method(Method::POST)
+            .uri(path)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_max_body_size() {
+        let mut router = Router::builder()
+            .install(read_body, route(path!("post")).method(Method::POST))
+            .max_body_size(4)
+            .build();
+
+        let res = router.call(post("/post", "1234")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = router.call(post("/post", "12345")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_max_body_size_content_length() {
+        let mut router = Router::builder()
+            .install(handler, route(path!("post")).method(Method::POST))
+            .max_body_size(4)
+            .build();
+
+        let mut req = post("/post", "12345");
+        req.headers_mut().typed_insert(ContentLength(5));
+        let res = router.call(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_route_max_body_size() {
+        let mut router = Router::builder()
+            .install(
+                read_body,
+                route(path!("small")).method(Method::POST).max_body_size(2),
+            )
+            .install(read_body, route(path!("large")).method(Method::POST))
+            .max_body_size(4)
+            .build();
+
+        let res = router.call(post("/small", "123")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let res = router.call(post("/large", "123")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_not_found() {
+        let mut router = Router::builder()
+            .install(handler, route(path!("get")))
+            .build();
+
+        let res = request().path("/post").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_fallback() {
+        let mut router = Router::builder()
+            .install(handler, route(path!("get")))
+            .fallback(|req: Request| async move {
+                ok(format!("no {}", req.uri().path()))
+            })
+            .layer(Tag("tagged"))
+            .build();
+
+        let res = request().path("/post").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("x-tag").unwrap(), "tagged");
+        let body = crate::http::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "no /post");
+
+        let res = request()
+            .method(Method::POST)
+            .path("/get")
+            .call(&mut router)
+            .await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    async fn code(req: Request) -> crate::http::Result {
+        ok(req.param::<String>("code").unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_mount() {
+        let api = Router::builder()
+            .install(code, route(path!("status" / code)))
+            .install(handler, route(path!()))
+            .layer(Tag("tagged"));
+        let mut router = Router::builder()
+            .install(handler, route(path!("get")))
+            .mount("/api/v1", api)
+            .build();
+
+        let res = request().path("/api/v1/status/418").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("x-tag").unwrap(), "tagged");
+        let body = crate::http::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "418");
+
+        let res = request().path("/api/v1").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = request().path("/get").call(&mut router).await;
+        assert!(res.headers().get("x-tag").is_none());
+
+        let res = request().path("/status/418").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_mounted_route() {
+        let api = Router::builder().install(
+            code,
+            route(path!("status" / code)).add_example_param("code", "200"),
+        );
+        let router = Router::builder().mount("/api", api);
+
+        let route = router.routes().next().unwrap();
+        assert_eq!(route.name(), "/api/status/:code");
+        assert_eq!(route.example_path(), Some("/api/status/200"));
+    }
+
+    #[test]
+    fn test_group() {
+        let router = Router::builder()
+            .install(handler, route(path!("get")))
+            .group("status", |builder| {
+                builder.install(
+                    code,
+                    route(path!("status" / code)).name("status").tag("codes"),
+                )
+            });
+
+        let routes = router.routes().collect::<Vec<_>>();
+        assert!(routes[0].tags().is_empty());
+        assert_eq!(routes[0].explicit_name(), None);
+        assert_eq!(routes[1].tags(), ["codes", "status"]);
+        assert_eq!(routes[1].explicit_name(), Some("status"));
+    }
+
+    #[tokio::test]
+    async fn test_method_not_allowed() {
+        let mut router = Router::builder()
+            .install(handler, route(path!("get")))
+            .install(handler, route(path!("get")).method(Method::PUT))
+            .build();
+
+        let res = request()
+            .method(Method::POST)
+            .path("/get")
+            .call(&mut router)
+            .await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            res.headers().typed_get::<Allow>().unwrap(),
+            vec![Method::GET, Method::PUT, Method::HEAD, Method::OPTIONS]
+                .into_iter()
+                .collect::<Allow>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_options() {
+        let mut router = Router::builder()
+            .install(handler, route(path!("get")))
+            .install(handler, route(path!("get")).method(Method::PUT))
+            .build();
+
+        let res = request()
+            .method(Method::OPTIONS)
+            .path("/get")
+            .call(&mut router)
+            .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            res.headers().typed_get::<Allow>().unwrap(),
+            vec![Method::GET, Method::PUT, Method::HEAD, Method::OPTIONS]
+                .into_iter()
+                .collect::<Allow>()
+        );
+
+        let res = request()
+            .method(Method::OPTIONS)
+            .path("/nowhere")
+            .call(&mut router)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_head() {
+        let mut router = Router::builder()
+            .install(|_| async { ok("hello") }, route(path!("get")))
+            .install(handler, route(path!("post")).method(Method::POST))
+            .build();
+
+        let res = request()
+            .method(Method::HEAD)
+            .path("/get")
+            .call(&mut router)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().typed_get::<ContentLength>(),
+            Some(ContentLength(5))
+        );
+        let body = crate::http::to_bytes(res.into_body()).await.unwrap();
+        assert!(body.is_empty());
+
+        let res = request()
+            .method(Method::HEAD)
+            .path("/post")
+            .call(&mut router)
+            .await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_multiple_methods() {
+        let mut router = Router::builder()
+            .install(
+                handler,
+                route(path!("anything"))
+                    .methods(vec![Method::GET, Method::POST]),
+            )
+            .build();
+
+        let res = request().path("/anything").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = request()
+            .method(Method::POST)
+            .path("/anything")
+            .call(&mut router)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = request()
+            .method(Method::DELETE)
+            .path("/anything")
+            .call(&mut router)
+            .await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_most_specific_route() {
+        let mut router = Router::builder()
+            .install(code, route(path!("status" / [*code])))
+            .install(code, route(path!("status" / code)))
+            .install(handler, route(path!("status" / "teapot")))
+            .build();
+
+        for (path, expected) in [
+            ("/status/teapot", ""),
+            ("/status/418", "418"),
+            ("/status/4/18", "4/18"),
+        ] {
+            let res = request().path(path).call(&mut router).await;
+            let body = crate::http::to_bytes(res.into_body()).await.unwrap();
+            assert_eq!(body, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let failing = |_: Request| async {
+            Err::<Response, _>(Error::bad_request("no good"))
+        };
+        let mut router = Router::builder()
+            .install(failing, route(path!("bad")))
+            .layer(Tag("tagged"))
+            .errors(crate::config::ErrorFormat::Json)
+            .build();
+
+        let res = request().path("/bad").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.headers().get("x-tag").unwrap(), "tagged");
+        assert_eq!(
+            res.read_body_json().await.unwrap(),
+            serde_json::json!({
+                "status": 400,
+                "error": "Bad Request",
+                "message": "no good",
+            })
+        );
+
+        let res = request().path("/none").call(&mut router).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.headers().get("x-tag").unwrap(), "tagged");
+    }
 }
@@ -0,0 +1,84 @@
+//! Answering requests whose handler panicked
+use crate::http::{Error, ErrorRenderer, Request, Response, Result};
+use futures::prelude::*;
+use hyper::Method;
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+/// What is known about a handler that panicked, also found in the
+/// extensions of the response sent for it
+#[derive(Clone, Debug)]
+pub struct Panic {
+    /// The panic payload, if it was a string
+    pub message: String,
+    pub method: Method,
+    pub path: String,
+    /// The name of the matched route, if any
+    pub route: Option<String>,
+}
+
+fn message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "non-string payload".to_owned()
+    }
+}
+
+/// Turns a panic into the response sent for it, see
+/// `RouterBuilder::on_panic`
+#[derive(Clone)]
+pub struct PanicHook(Arc<dyn Fn(&Panic) -> Response + Send + Sync>);
+
+impl PanicHook {
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&Panic) -> Response + Send + Sync + 'static,
+    {
+        Self(Arc::new(hook))
+    }
+}
+
+/// Runs `f`, logging a panic and failing with the panic hook's response, or
+/// else 500 Internal Server Error
+pub(super) async fn catch_panics<F, Fut>(req: Request, f: F) -> Result
+where
+    F: FnOnce(Request) -> Fut,
+    Fut: Future<Output = Result>,
+{
+    let method = req.method().clone();
+    let path = req.uri().path().to_owned();
+    let route = req.route().map(|route| route.name().to_owned());
+    let hook = req.extensions().get::<PanicHook>().cloned();
+    let errors = req.extensions().get::<ErrorRenderer>().cloned();
+
+    let payload = match AssertUnwindSafe(f(req)).catch_unwind().await {
+        Ok(result) => return result,
+        Err(payload) => payload,
+    };
+    let panic = Panic {
+        message: message(&*payload),
+        method,
+        path,
+        route,
+    };
+    tracing::error!(
+        method = %panic.method,
+        path = %panic.path,
+        route = panic.route.as_deref(),
+        message = %panic.message,
+        "handler panicked"
+    );
+
+    let mut res = match hook {
+        Some(hook) => (hook.0)(&panic),
+        None => errors.unwrap_or_default().response(&Error::internal(
+            anyhow::anyhow!("handler panicked: {}", panic.message),
+        )),
+    };
+    res.extensions_mut().insert(panic);
+    Err(Error::Failure(Box::new(res)))
+}
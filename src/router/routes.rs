@@ -1,32 +1,124 @@
-use hyper::{Body, Method, Request as HTTPRequest};
+use crate::handler::Handler;
+use crate::openapi::Parameter;
+use hyper::Method;
 use std::collections::BTreeMap;
-use uri_path::{Path, PathMatch};
+use std::time::Duration;
+use uri_path::{Path, PathSegment};
+
+/// How long a route's handler may take, see the `Timeouts` middleware
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Timeout {
+    /// The router-wide timeout for producing the response
+    #[default]
+    Default,
+    /// A timeout of its own for producing the response
+    After(Duration),
+    /// No timeout for producing the response, but a streamed body is cut
+    /// off once the router-wide idle timeout passes without data
+    Idle,
+}
 
 #[derive(Debug)]
 pub struct RouteBuilder {
     path: Path,
-    method: Method,
+    name: Option<&'static str>,
+    methods: Vec<Method>,
     description: Option<&'static str>,
     example_params: BTreeMap<&'static str, &'static str>,
+    compress: bool,
+    hop_by_hop: bool,
+    max_body_size: Option<usize>,
+    timeout: Timeout,
+    tags: Vec<&'static str>,
 }
 
 impl RouteBuilder {
     pub fn new<P: Into<Path>>(path: P) -> Self {
         RouteBuilder {
             path: path.into(),
-            method: Method::GET,
+            name: None,
+            methods: vec![Method::GET],
             description: None,
             example_params: BTreeMap::new(),
+            compress: true,
+            hop_by_hop: false,
+            max_body_size: None,
+            timeout: Timeout::Default,
+            tags: vec![],
         }
     }
 
+    /// Labels the route in metrics, instead of its path pattern
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
     pub fn description(mut self, description: &'static str) -> Self {
         self.description = Some(description);
         self
     }
 
-    pub fn method(mut self, method: Method) -> Self {
-        self.method = method;
+    /// Files the route under `tag` in the route table and the OpenAPI
+    /// document, on top of the tag of its group
+    pub fn tag(mut self, tag: &'static str) -> Self {
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+
+    pub fn method(self, method: Method) -> Self {
+        self.methods(std::iter::once(method))
+    }
+
+    /// Accepts every method that carries meaning for an echo endpoint
+    pub fn any_method(self) -> Self {
+        self.methods(vec![
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+            Method::TRACE,
+        ])
+    }
+
+    pub fn methods<I: IntoIterator<Item = Method>>(
+        mut self,
+        methods: I,
+    ) -> Self {
+        self.methods = vec![];
+        for method in methods {
+            if !self.methods.contains(&method) {
+                self.methods.push(method);
+            }
+        }
+        self
+    }
+
+    /// Whether responses may be compressed on the fly, on by default
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Whether responses may carry hop-by-hop headers, which are stripped by
+    /// default, see the `HopByHop` middleware
+    pub fn hop_by_hop(mut self, hop_by_hop: bool) -> Self {
+        self.hop_by_hop = hop_by_hop;
+        self
+    }
+
+    /// Overrides the router's maximum request body size for this route
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = Some(max_body_size);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Timeout) -> Self {
+        self.timeout = timeout;
         self
     }
 
@@ -40,7 +132,7 @@ impl RouteBuilder {
     }
 
     fn example_path(&self) -> Option<String> {
-        if self.method != Method::GET {
+        if !self.methods.contains(&Method::GET) {
             return None;
         }
 
@@ -51,9 +143,17 @@ impl RouteBuilder {
 #[derive(Debug)]
 pub struct Route {
     path: Path,
-    method: Method,
+    name: String,
+    methods: Vec<Method>,
     description: Option<&'static str>,
     example_path: Option<String>,
+    compress: bool,
+    hop_by_hop: bool,
+    max_body_size: Option<usize>,
+    timeout: Timeout,
+    handler: Option<&'static str>,
+    parameters: Vec<Parameter>,
+    tags: Vec<&'static str>,
 }
 
 impl Route {
@@ -61,8 +161,19 @@ impl Route {
         &self.path
     }
 
-    pub fn method(&self) -> &Method {
-        &self.method
+    /// The explicit name of the route, or its path pattern like
+    /// `/status/:code`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name given to the route, unless it goes by its path pattern
+    pub fn explicit_name(&self) -> Option<&str> {
+        Some(self.name.as_str()).filter(|name| *name != self.path.to_string())
+    }
+
+    pub fn methods(&self) -> &[Method] {
+        &self.methods
     }
 
     pub fn description(&self) -> Option<&'static str> {
@@ -73,23 +184,126 @@ impl Route {
         self.example_path.as_ref().map(String::as_ref)
     }
 
-    pub fn matches(&self, req: &HTTPRequest<Body>) -> Option<PathMatch> {
-        if self.method() != req.method() {
-            return None;
+    pub fn compress(&self) -> bool {
+        self.compress
+    }
+
+    pub fn hop_by_hop(&self) -> bool {
+        self.hop_by_hop
+    }
+
+    pub fn max_body_size(&self) -> Option<usize> {
+        self.max_body_size
+    }
+
+    pub fn timeout(&self) -> Timeout {
+        self.timeout
+    }
+
+    /// The name of the handler installed at the route, see `Handler::name`
+    pub fn handler(&self) -> Option<&'static str> {
+        self.handler
+    }
+
+    /// The query parameters of the handler, see `Handler::parameters`
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    pub fn tags(&self) -> &[&'static str] {
+        &self.tags
+    }
+
+    /// Whether the route handles `method`, where `GET` routes also answer
+    /// `HEAD`, see `Router`
+    pub fn allows(&self, method: &Method) -> bool {
+        self.methods.contains(method)
+            || (method == Method::HEAD && self.methods.contains(&Method::GET))
+    }
+
+    pub(super) fn with_max_body_size(
+        mut self,
+        max_body_size: Option<usize>,
+    ) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// The route as installed with `handler`, which describes it unless it
+    /// has a description of its own
+    ///
+    /// Routes listing themselves need it before their handler exists.
+    pub fn with_handler<H: Handler + ?Sized>(mut self, handler: &H) -> Self {
+        self.handler = Some(handler.name());
+        self.parameters = handler.parameters();
+        self.description = self.description.or_else(|| handler.describe());
+        self
+    }
+
+    /// Adds the tag of the group installing the route, see
+    /// `RouterBuilder::group`
+    pub(super) fn tag(&mut self, tag: &'static str) {
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
         }
+    }
 
-        self.path.matches(req.uri().path())
+    /// The same route below `prefix`, keeping an explicit name
+    pub(super) fn prefixed(&self, prefix: &Path) -> Self {
+        let path =
+            Path(prefix.iter().chain(self.path.iter()).cloned().collect());
+        let name = self
+            .explicit_name()
+            .map_or_else(|| path.to_string(), str::to_owned);
+        // The prefix has no example values for parameters of its own
+        let literal = prefix
+            .iter()
+            .all(|segment| matches!(segment, PathSegment::Literal(_)));
+        let example_path =
+            self.example_path
+                .as_ref()
+                .filter(|_| literal)
+                .map(|example| match example.as_str() {
+                    _ if prefix.is_empty() => example.clone(),
+                    "/" => prefix.to_string(),
+                    example => format!("{}{}", prefix, example),
+                });
+        Self {
+            path,
+            name,
+            methods: self.methods.clone(),
+            description: self.description,
+            example_path,
+            compress: self.compress,
+            hop_by_hop: self.hop_by_hop,
+            max_body_size: self.max_body_size,
+            timeout: self.timeout,
+            handler: self.handler,
+            parameters: self.parameters.clone(),
+            tags: self.tags.clone(),
+        }
     }
 }
 
 impl From<RouteBuilder> for Route {
     fn from(route: RouteBuilder) -> Self {
         let example_path = route.example_path();
+        let name = route
+            .name
+            .map_or_else(|| route.path.to_string(), str::to_owned);
         Route {
             path: route.path,
-            method: route.method,
+            name,
+            methods: route.methods,
             description: route.description,
             example_path,
+            compress: route.compress,
+            hop_by_hop: route.hop_by_hop,
+            max_body_size: route.max_body_size,
+            timeout: route.timeout,
+            handler: None,
+            parameters: vec![],
+            tags: route.tags,
         }
     }
 }
@@ -0,0 +1,272 @@
+//! Connections that handlers can cut off, see `Request::abort`, that tell
+//! them once their client goes away, see `Request::on_disconnect`, that
+//! take their informational responses, see `Request::send_informational`,
+//! and that close once idle, see `Request::keep_alive`
+use super::Connection;
+use crate::http::{
+    Abort, Aborting, Bytes, Disconnected, Interim, Lifecycle, PeerCredentials,
+    Tls,
+};
+use futures::prelude::*;
+use futures::stream::BoxStream;
+use hyper::body::Buf;
+use std::io;
+use std::mem;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+fn aborted() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "aborted by the handler")
+}
+
+/// Where a connection is in writing out its replacement
+enum Replacing {
+    NotYet,
+    /// The rest of the replacement, and what is left of its last chunk
+    Writing(BoxStream<'static, Bytes>, Bytes),
+    Done,
+}
+
+/// A connection that fails every read and write once aborted, which makes
+/// hyper drop it
+///
+/// A replacement for the response is written on the next write or flush,
+/// in place of what hyper asks for, and reads go on until it is.
+///
+/// The client counts as gone once a read reaches the end of the connection
+/// or fails, or the connection is dropped.
+///
+/// Informational responses are written as soon as they are queued, which
+/// wakes hyper to flush the connection.
+///
+/// Once no request is in flight and nothing has been read or written for
+/// the idle timeout of its lifecycle, reads reach the end of the connection,
+/// which hyper then closes.
+pub struct Abortable<C: Connection> {
+    inner: C,
+    aborting: Aborting,
+    replacing: Replacing,
+    disconnected: Disconnected,
+    interim: Interim,
+    /// What is left of the informational response being written
+    writing_interim: Bytes,
+    lifecycle: Lifecycle,
+    /// Runs out once the connection has been idle for its timeout
+    idle: Option<Pin<Box<Sleep>>>,
+    /// Read or written since the idle timer was last reset
+    active: bool,
+}
+
+impl<C: Connection> Abortable<C> {
+    pub fn new(inner: C, keep_alive: Option<Duration>) -> Self {
+        Self {
+            inner,
+            aborting: Aborting::new(),
+            replacing: Replacing::NotYet,
+            disconnected: Disconnected::new(),
+            interim: Interim::new(),
+            writing_interim: Bytes::new(),
+            lifecycle: Lifecycle::new(keep_alive),
+            idle: None,
+            active: false,
+        }
+    }
+
+    pub fn aborting(&self) -> &Aborting {
+        &self.aborting
+    }
+
+    pub fn disconnected(&self) -> &Disconnected {
+        &self.disconnected
+    }
+
+    pub fn interim(&self) -> &Interim {
+        &self.interim
+    }
+
+    pub fn lifecycle(&self) -> &Lifecycle {
+        &self.lifecycle
+    }
+
+    /// Ends the connection, as if the client had, once it has been idle for
+    /// its timeout
+    fn poll_idle(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        let timeout = match self.lifecycle.poll_idle_timeout(cx.waker()) {
+            Some(timeout) => timeout,
+            None => {
+                self.idle = None;
+                return Poll::Pending;
+            }
+        };
+        let idle = self
+            .idle
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        if mem::take(&mut self.active) {
+            idle.as_mut().reset(Instant::now() + timeout);
+        }
+        ready!(idle.as_mut().poll(cx));
+        Poll::Ready(Ok(()))
+    }
+
+    /// Writes out the queued informational responses
+    fn poll_write_interim(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        loop {
+            while !self.writing_interim.is_empty() {
+                let written = ready!(Pin::new(&mut self.inner)
+                    .poll_write(cx, &self.writing_interim))?;
+                if written == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                }
+                self.writing_interim.advance(written);
+            }
+            match self.interim.poll_next(cx.waker()) {
+                Some(head) => self.writing_interim = head,
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+
+    fn check_read(&self) -> io::Result<()> {
+        match (self.aborting.get(), &self.replacing) {
+            (None, _) => Ok(()),
+            (
+                Some(Abort::Replace(_)),
+                Replacing::NotYet | Replacing::Writing(..),
+            ) => Ok(()),
+            (Some(_), _) => Err(aborted()),
+        }
+    }
+
+    /// Writes out any replacement, each chunk as soon as it comes, then
+    /// fails if aborted
+    fn poll_check_write(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        match self.aborting.get() {
+            None => return Poll::Ready(Ok(())),
+            Some(Abort::Replace(replacement)) => {
+                if let Replacing::NotYet = self.replacing {
+                    self.replacing = match replacement.take() {
+                        Some(bytes) => Replacing::Writing(bytes, Bytes::new()),
+                        None => Replacing::Done,
+                    };
+                }
+            }
+            Some(_) => return Poll::Ready(Err(aborted())),
+        }
+
+        while let Replacing::Writing(bytes, chunk) = &mut self.replacing {
+            while !chunk.is_empty() {
+                let written =
+                    ready!(Pin::new(&mut self.inner).poll_write(cx, chunk))?;
+                if written == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                }
+                chunk.advance(written);
+            }
+            ready!(Pin::new(&mut self.inner).poll_flush(cx))?;
+            match ready!(bytes.poll_next_unpin(cx)) {
+                Some(next) => *chunk = next,
+                None => self.replacing = Replacing::Done,
+            }
+        }
+        Poll::Ready(Err(aborted()))
+    }
+}
+
+impl<C: Connection> Connection for Abortable<C> {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.inner.remote_addr()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn peer_credentials(&self) -> Option<PeerCredentials> {
+        self.inner.peer_credentials()
+    }
+
+    fn tls(&self) -> Option<Tls> {
+        self.inner.tls()
+    }
+
+    fn reset_on_close(&self) {
+        self.inner.reset_on_close()
+    }
+}
+
+impl<C: Connection> Drop for Abortable<C> {
+    fn drop(&mut self) {
+        // Runs before `inner` is dropped, closing the socket
+        if let Some(Abort::Reset) = self.aborting.get() {
+            self.inner.reset_on_close()
+        }
+        self.disconnected.disconnect()
+    }
+}
+
+impl<C: Connection> AsyncRead for Abortable<C> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        self.check_read()?;
+        let (filled, room) = (buf.filled().len(), buf.remaining());
+        let read = match Pin::new(&mut self.inner).poll_read(cx, buf) {
+            Poll::Ready(read) => read,
+            Poll::Pending => return self.poll_idle(cx),
+        };
+        if read.is_err() || (room > 0 && buf.filled().len() == filled) {
+            self.disconnected.disconnect()
+        }
+        self.active = true;
+        Poll::Ready(read)
+    }
+}
+
+impl<C: Connection> AsyncWrite for Abortable<C> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_check_write(cx))?;
+        ready!(self.poll_write_interim(cx))?;
+        self.active = true;
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<io::Result<()>> {
+        ready!(self.poll_check_write(cx))?;
+        ready!(self.poll_write_interim(cx))?;
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<io::Result<()>> {
+        ready!(self.poll_check_write(cx))?;
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Makes every connection accepted from `incoming` abortable, closing them
+/// once idle for `keep_alive`
+pub fn incoming<I>(
+    incoming: I,
+    keep_alive: Option<Duration>,
+) -> impl Stream<Item = Abortable<I::Item>>
+where
+    I: Stream,
+    I::Item: Connection,
+{
+    incoming.map(move |conn| Abortable::new(conn, keep_alive))
+}
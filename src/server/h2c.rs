@@ -0,0 +1,500 @@
+//! Upgrading HTTP/1.1 connections to cleartext HTTP/2 (RFC 7540 §3.2)
+//!
+//! hyper can serve HTTP/2 on an upgraded connection but has no notion of the
+//! request that asked for the upgrade, which has to be answered on stream 1.
+//! So that request is replayed to the HTTP/2 server as a `HEADERS` frame,
+//! slotted in right after the client's connection preface.
+use super::stack;
+use crate::http::{Aborting, Body, ConnectionInfo, Disconnected, Draining};
+use crate::middleware::HOP_BY_HOP;
+use crate::router::Router;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::{alphabet, Engine};
+use futures::prelude::*;
+use hyper::header::{
+    HeaderMap, HeaderValue, CONNECTION, CONTENT_LENGTH, HOST,
+    TRANSFER_ENCODING, UPGRADE,
+};
+use hyper::http::{Response, StatusCode, Version};
+use hyper::server::conn::http2;
+use hyper::Request as HTTPRequest;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::service::TowerToHyperService;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tower::Service;
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const FRAME_HEADER_LEN: usize = 9;
+/// The largest frame a peer has to accept before settings are exchanged
+const MAX_FRAME_SIZE: usize = 16_384;
+
+/// Each setting being a 16-bit identifier and a 32-bit value
+const SETTING_LEN: usize = 6;
+
+const HEADERS: u8 = 0x1;
+const SETTINGS: u8 = 0x4;
+const CONTINUATION: u8 = 0x9;
+const END_STREAM: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+
+fn has_token(
+    headers: &HeaderMap,
+    name: impl hyper::header::AsHeaderName,
+    token: &str,
+) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+/// `HTTP2-Settings` is base64url, with or without padding
+const SETTINGS_ENCODING: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Whether `len` bytes could be the payload of a `SETTINGS` frame, which
+/// is made of settings and sent before any frame size was agreed on
+fn settings_len(len: usize) -> bool {
+    len <= MAX_FRAME_SIZE && len.is_multiple_of(SETTING_LEN)
+}
+
+/// Whether the request has a single `HTTP2-Settings` that decodes to a
+/// `SETTINGS` payload (RFC 7540 §3.2.1)
+fn has_settings(headers: &HeaderMap) -> bool {
+    let mut values = headers.get_all("http2-settings").iter();
+    match (values.next(), values.next()) {
+        (Some(value), None) => SETTINGS_ENCODING
+            .decode(value.as_bytes())
+            .is_ok_and(|settings| settings_len(settings.len())),
+        _ => false,
+    }
+}
+
+/// Whether the request asks for `h2c` and can be replayed, which bodies
+/// can't be
+fn is_upgrade(req: &HTTPRequest<Body>) -> bool {
+    let headers = req.headers();
+    let bodyless = !headers.contains_key(TRANSFER_ENCODING)
+        && headers
+            .get(CONTENT_LENGTH)
+            .is_none_or(|length| length == "0");
+
+    req.version() == Version::HTTP_11
+        && has_token(headers, UPGRADE, "h2c")
+        && has_token(headers, CONNECTION, "upgrade")
+        && has_settings(headers)
+        && bodyless
+}
+
+/// An HPACK integer with an `n` bit prefix
+fn encode_integer(out: &mut Vec<u8>, n: u8, value: usize) {
+    let max = (1 << n) - 1;
+    if value < max {
+        out.push(value as u8);
+        return;
+    }
+    out.push(max as u8);
+    let mut value = value - max;
+    while value >= 0x80 {
+        out.push((value % 0x80) as u8 | 0x80);
+        value /= 0x80;
+    }
+    out.push(value as u8);
+}
+
+/// A literal field that isn't added to the dynamic table, without Huffman
+/// coding
+fn encode_field(out: &mut Vec<u8>, name: &[u8], value: &[u8]) {
+    out.push(0);
+    encode_integer(out, 7, name.len());
+    out.extend_from_slice(name);
+    encode_integer(out, 7, value.len());
+    out.extend_from_slice(value);
+}
+
+/// The header block of the upgrade request, minus the hop-by-hop headers
+fn header_block(req: &HTTPRequest<Body>) -> Vec<u8> {
+    let headers = req.headers();
+    let authority = headers
+        .get(HOST)
+        .map(HeaderValue::as_bytes)
+        .or_else(|| req.uri().authority().map(|a| a.as_str().as_bytes()))
+        .unwrap_or_default();
+    let path = req.uri().path_and_query().map_or("/", |path| path.as_str());
+
+    let mut block = vec![];
+    encode_field(&mut block, b":method", req.method().as_str().as_bytes());
+    encode_field(&mut block, b":scheme", b"http");
+    encode_field(&mut block, b":authority", authority);
+    encode_field(&mut block, b":path", path.as_bytes());
+
+    for (name, value) in headers {
+        // `Host` being replaced by `:authority`
+        let connection_specific = HOP_BY_HOP.contains(name)
+            || has_token(headers, CONNECTION, name.as_str())
+            || name == HOST
+            || name == "http2-settings";
+        if !connection_specific {
+            encode_field(
+                &mut block,
+                name.as_str().as_bytes(),
+                value.as_bytes(),
+            );
+        }
+    }
+    block
+}
+
+fn frame(out: &mut Vec<u8>, kind: u8, flags: u8, payload: &[u8]) {
+    let len = payload.len() as u32;
+    out.extend_from_slice(&len.to_be_bytes()[1..]);
+    out.push(kind);
+    out.push(flags);
+    out.extend_from_slice(&1u32.to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// Stream 1's `HEADERS`, continued as needed to stay within the frame size
+fn headers_frames(block: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut chunks = block.chunks(MAX_FRAME_SIZE).peekable();
+    let mut kind = HEADERS;
+    let mut flags = END_STREAM;
+    while let Some(chunk) = chunks.next() {
+        if chunks.peek().is_none() {
+            flags |= END_HEADERS;
+        }
+        frame(&mut out, kind, flags, chunk);
+        kind = CONTINUATION;
+        flags = 0;
+    }
+    if block.is_empty() {
+        frame(&mut out, HEADERS, END_STREAM | END_HEADERS, &[]);
+    }
+    out
+}
+
+/// Replays `prefix` ahead of whatever is read from the connection
+struct Rewind<T> {
+    prefix: Vec<u8>,
+    inner: T,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Rewind<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if !self.prefix.is_empty() {
+            let n = std::cmp::min(self.prefix.len(), buf.remaining());
+            buf.put_slice(&self.prefix[..n]);
+            self.prefix.drain(..n);
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Rewind<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Reads the client preface and its `SETTINGS` frame, then slips `frames` in
+/// behind them
+async fn inject<T: AsyncRead + Unpin>(
+    mut io: T,
+    frames: Vec<u8>,
+) -> io::Result<Rewind<T>> {
+    let mut prefix = vec![0; PREFACE.len() + FRAME_HEADER_LEN];
+    io.read_exact(&mut prefix).await?;
+    if !prefix.starts_with(PREFACE) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing HTTP/2 connection preface",
+        ));
+    }
+
+    let header = &prefix[PREFACE.len()..];
+    let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+    if header[3] != SETTINGS || !settings_len(len) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the HTTP/2 connection preface has to end with SETTINGS",
+        ));
+    }
+    let mut settings = vec![0; len];
+    io.read_exact(&mut settings).await?;
+
+    prefix.extend(settings);
+    prefix.extend(frames);
+    Ok(Rewind { prefix, inner: io })
+}
+
+/// Answers `Upgrade: h2c` requests with 101 Switching Protocols and serves
+/// the rest of the connection over HTTP/2, passing anything else through
+#[derive(Clone)]
+pub struct H2c {
+    router: Router,
+    info: ConnectionInfo,
+    draining: Draining,
+    aborting: Aborting,
+    disconnected: Disconnected,
+    enabled: bool,
+}
+
+impl H2c {
+    pub fn new(
+        router: Router,
+        info: ConnectionInfo,
+        draining: Draining,
+        aborting: Aborting,
+        disconnected: Disconnected,
+        enabled: bool,
+    ) -> Self {
+        Self {
+            router,
+            info,
+            draining,
+            aborting,
+            disconnected,
+            enabled,
+        }
+    }
+
+    fn upgrade(&self, mut req: HTTPRequest<Body>) -> Response<Body> {
+        let frames = headers_frames(&header_block(&req));
+        let on_upgrade = hyper::upgrade::on(&mut req);
+        let (router, info) = (self.router.clone(), self.info.clone());
+        let draining = self.draining.clone();
+        // The upgraded connection still goes through the abortable one
+        let aborting = Some(self.aborting.clone());
+        let disconnected = Some(self.disconnected.clone());
+
+        tokio::spawn(async move {
+            let result = async {
+                let io =
+                    inject(TokioIo::new(on_upgrade.await?), frames).await?;
+                let service = stack(
+                    router,
+                    info,
+                    draining,
+                    aborting,
+                    disconnected,
+                    None,
+                    None,
+                );
+                http2::Builder::new(TokioExecutor::new())
+                    .serve_connection(
+                        TokioIo::new(io),
+                        TowerToHyperService::new(service),
+                    )
+                    .await?;
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+            };
+            if let Err(e) = result.await {
+                tracing::debug!("h2c connection failed: {}", e);
+            }
+        });
+
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+        res.headers_mut()
+            .insert(CONNECTION, HeaderValue::from_static("upgrade"));
+        res.headers_mut()
+            .insert(UPGRADE, HeaderValue::from_static("h2c"));
+        res
+    }
+}
+
+impl Service<HTTPRequest<Body>> for H2c {
+    type Response = Response<Body>;
+    type Error = hyper::http::Error;
+    type Future = <Router as Service<HTTPRequest<Body>>>::Future;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        Service::<HTTPRequest<Body>>::poll_ready(&mut self.router, cx)
+    }
+
+    fn call(&mut self, req: HTTPRequest<Body>) -> Self::Future {
+        if self.enabled && is_upgrade(&req) {
+            future::ok(self.upgrade(req)).boxed()
+        } else {
+            self.router.call(req)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn upgrade_request() -> hyper::http::request::Builder {
+        HTTPRequest::builder()
+            .uri("/get?a=1")
+            .header("host", "example.com")
+            .header("connection", "Upgrade, HTTP2-Settings")
+            .header("upgrade", "h2c")
+            .header("http2-settings", "AAMAAABkAARAAAAAAAIAAAAA")
+    }
+
+    #[test]
+    fn test_is_upgrade() {
+        assert!(is_upgrade(&upgrade_request().body(Body::empty()).unwrap()));
+
+        let with_body = upgrade_request()
+            .header("content-length", "4")
+            .body(Body::from("body"))
+            .unwrap();
+        assert!(!is_upgrade(&with_body));
+
+        for settings in ["AAMAAABkAA", "not base64!"] {
+            let mut req = upgrade_request().body(Body::empty()).unwrap();
+            req.headers_mut()
+                .insert("http2-settings", HeaderValue::from_static(settings));
+            assert!(!is_upgrade(&req), "{}", settings);
+        }
+        let twice = upgrade_request()
+            .header("http2-settings", "AAMAAABk")
+            .body(Body::empty())
+            .unwrap();
+        assert!(!is_upgrade(&twice));
+
+        let websocket = HTTPRequest::builder()
+            .header("connection", "upgrade")
+            .header("upgrade", "websocket")
+            .body(Body::empty())
+            .unwrap();
+        assert!(!is_upgrade(&websocket));
+    }
+
+    #[test]
+    fn test_encode_integer() {
+        // RFC 7541 C.1
+        let mut out = vec![];
+        encode_integer(&mut out, 5, 10);
+        assert_eq!(out, [10]);
+
+        let mut out = vec![];
+        encode_integer(&mut out, 5, 1337);
+        assert_eq!(out, [31, 154, 10]);
+    }
+
+    #[test]
+    fn test_header_block() {
+        let req = upgrade_request()
+            .header("x-key", "val")
+            .body(Body::empty())
+            .unwrap();
+
+        let mut expected = vec![];
+        encode_field(&mut expected, b":method", b"GET");
+        encode_field(&mut expected, b":scheme", b"http");
+        encode_field(&mut expected, b":authority", b"example.com");
+        encode_field(&mut expected, b":path", b"/get?a=1");
+        encode_field(&mut expected, b"x-key", b"val");
+        assert_eq!(header_block(&req), expected);
+    }
+
+    #[test]
+    fn test_headers_frames() {
+        let frames = headers_frames(b"abc");
+        assert_eq!(
+            frames,
+            [
+                0,
+                0,
+                3,
+                HEADERS,
+                END_STREAM | END_HEADERS,
+                0,
+                0,
+                0,
+                1,
+                b'a',
+                b'b',
+                b'c'
+            ]
+        );
+
+        let block = vec![0; MAX_FRAME_SIZE + 1];
+        let frames = headers_frames(&block);
+        assert_eq!(frames.len(), 2 * FRAME_HEADER_LEN + block.len());
+        assert_eq!(&frames[3..5], [HEADERS, END_STREAM]);
+        let continuation = FRAME_HEADER_LEN + MAX_FRAME_SIZE;
+        assert_eq!(
+            &frames[continuation..continuation + 5],
+            [0, 0, 1, CONTINUATION, END_HEADERS]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inject() {
+        let mut client = PREFACE.to_vec();
+        client.extend([0, 0, 0, 0x4, 0, 0, 0, 0, 0]);
+        client.extend(b"rest");
+
+        let mut io = inject(&client[..], b"injected".to_vec()).await.unwrap();
+        let mut read = vec![];
+        io.read_to_end(&mut read).await.unwrap();
+
+        let mut expected = client[..PREFACE.len() + FRAME_HEADER_LEN].to_vec();
+        expected.extend(b"injected");
+        expected.extend(b"rest");
+        assert_eq!(read, expected);
+    }
+
+    #[tokio::test]
+    async fn test_inject_bounds_settings() {
+        for header in [
+            [0xff, 0xff, 0xff, 0x4, 0, 0, 0, 0, 0],
+            [0, 0, 5, 0x4, 0, 0, 0, 0, 0],
+            [0, 0, 0, HEADERS, 0, 0, 0, 0, 0],
+        ] {
+            let mut client = PREFACE.to_vec();
+            client.extend(header);
+            client.extend([0; 16]);
+
+            assert!(inject(&client[..], vec![]).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inject_without_preface() {
+        let client = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+        assert!(inject(&client[..], vec![]).await.is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! An experimental HTTP/3 listener, serving the same router over QUIC
+use super::{stack, TlsConfig};
+use crate::http::{Body, ConnectionInfo, Draining, Tls};
+use crate::router::Router;
+use http_body_util::BodyExt;
+use hyper::body::{Buf, Bytes};
+use hyper::{Request as HTTPRequest, Response};
+use quinn::crypto::rustls::HandshakeData;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio_rustls::rustls::Certificate;
+use tower::ServiceExt;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+async fn handle_request<S>(
+    req: HTTPRequest<()>,
+    stream: h3::server::RequestStream<S, Bytes>,
+    router: Router,
+    info: ConnectionInfo,
+    draining: Draining,
+) -> Result<(), Error>
+where
+    S: h3::quic::BidiStream<Bytes>,
+    S::RecvStream: Send + 'static,
+{
+    let (mut send, mut recv) = stream.split();
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        while let Ok(Some(mut chunk)) = recv.recv_data().await {
+            let chunk = chunk.copy_to_bytes(chunk.remaining());
+            if sender.send_data(chunk).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let (parts, ()) = req.into_parts();
+    let res = stack(router, info, draining, None, None, None, None)
+        .oneshot(HTTPRequest::from_parts(parts, body))
+        .await?;
+
+    let (parts, mut body) = res.into_parts();
+    send.send_response(Response::from_parts(parts, ())).await?;
+    while let Some(frame) = body.frame().await {
+        if let Ok(chunk) = frame?.into_data() {
+            send.send_data(chunk).await?;
+        }
+    }
+    send.finish().await?;
+    Ok(())
+}
+
+/// What the handshake of `connection` tells about it, QUIC always being
+/// TLS 1.3
+fn connection_info(
+    connection: &quinn::Connection,
+    local_addr: Option<SocketAddr>,
+) -> ConnectionInfo {
+    let handshake = connection
+        .handshake_data()
+        .and_then(|data| data.downcast::<HandshakeData>().ok());
+    let mut info = ConnectionInfo::new();
+    info.peer_addr = Some(connection.remote_address());
+    info.local_addr = local_addr;
+    info.tls = Some(Tls {
+        version: Some("TLSv1_3"),
+        cipher: None,
+        alpn: handshake.as_ref().and_then(|handshake| {
+            let alpn = handshake.protocol.as_deref()?;
+            Some(String::from_utf8_lossy(alpn).into_owned())
+        }),
+        server_name: handshake.and_then(|handshake| handshake.server_name),
+        client_certificate: connection
+            .peer_identity()
+            .and_then(|identity| identity.downcast::<Vec<Certificate>>().ok())
+            .and_then(|chain| chain.into_iter().next())
+            .map(|cert| Bytes::from(cert.0)),
+    });
+    info
+}
+
+async fn handle_connection(
+    connecting: quinn::Connecting,
+    router: Router,
+    local_addr: Option<SocketAddr>,
+    draining: Draining,
+) -> Result<(), Error> {
+    let connection = connecting.await?;
+    let info = connection_info(&connection, local_addr);
+    let mut connection =
+        h3::server::Connection::new(h3_quinn::Connection::new(connection))
+            .await?;
+
+    let mut going_away = false;
+    loop {
+        let (req, stream) = tokio::select! {
+            accepted = connection.accept() => match accepted? {
+                Some(accepted) => accepted,
+                None => break,
+            },
+            // Tell the client to finish up and open no more streams
+            _ = draining.wait(), if !going_away => {
+                connection.shutdown(0).await?;
+                going_away = true;
+                continue;
+            }
+        };
+
+        let (router, draining) = (router.clone(), draining.clone());
+        let info = info.clone();
+        tokio::spawn(async move {
+            if let Err(e) =
+                handle_request(req, stream, router, info, draining).await
+            {
+                tracing::debug!("HTTP/3 request failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Serves `router` over QUIC on the UDP port of `addr`
+pub async fn serve(
+    addr: SocketAddr,
+    router: Router,
+    tls: &TlsConfig,
+    draining: Draining,
+) -> anyhow::Result<()> {
+    let crypto = tls.server_config(&[b"h3"])?;
+    let config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+    let endpoint = quinn::Endpoint::server(config, addr)?;
+    let local_addr = endpoint.local_addr().ok();
+
+    loop {
+        let connecting = tokio::select! {
+            connecting = endpoint.accept() => connecting,
+            _ = draining.wait() => None,
+        };
+        let connecting = match connecting {
+            Some(connecting) => connecting,
+            None => break,
+        };
+
+        let (router, draining) = (router.clone(), draining.clone());
+        tokio::spawn(async move {
+            if let Err(e) =
+                handle_connection(connecting, router, local_addr, draining)
+                    .await
+            {
+                tracing::debug!("HTTP/3 connection failed: {}", e);
+            }
+        });
+    }
+
+    // Connections close once their clients are done, or get dropped with
+    // the endpoint when the drain timeout runs out
+    endpoint.wait_idle().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{ok, Request};
+    use crate::server::tls::test::{config, roots};
+    use tokio_rustls::rustls::ClientConfig;
+    use uri_path::path;
+
+    async fn version(req: Request) -> crate::http::Result {
+        ok(format!("{:?}", req.version()))
+    }
+
+    #[tokio::test]
+    async fn test_http3() {
+        let router = Router::builder()
+            .install(version, crate::router::route(path!("version")))
+            .build();
+        let addr = {
+            let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket.local_addr().unwrap()
+        };
+        tokio::spawn(async move {
+            serve(addr, router, &config(), Draining::new()).await
+        });
+
+        let mut crypto = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots())
+            .with_no_client_auth();
+        crypto.alpn_protocols = vec![b"h3".to_vec()];
+        let mut client =
+            quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+        client.set_default_client_config(quinn::ClientConfig::new(Arc::new(
+            crypto,
+        )));
+
+        let connection =
+            client.connect(addr, "localhost").unwrap().await.unwrap();
+        let (mut driver, mut requests) =
+            h3::client::new(h3_quinn::Connection::new(connection))
+                .await
+                .unwrap();
+        tokio::spawn(async move {
+            futures::future::poll_fn(|cx| driver.poll_close(cx)).await
+        });
+
+        let req = HTTPRequest::get("https://localhost/version")
+            .body(())
+            .unwrap();
+        let mut stream = requests.send_request(req).await.unwrap();
+        stream.finish().await.unwrap();
+
+        let res = stream.recv_response().await.unwrap();
+        assert_eq!(res.status(), hyper::StatusCode::OK);
+        let mut body = vec![];
+        while let Some(mut chunk) = stream.recv_data().await.unwrap() {
+            body.extend(chunk.copy_to_bytes(chunk.remaining()));
+        }
+        assert_eq!(body, b"HTTP/3.0");
+    }
+}
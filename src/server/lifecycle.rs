@@ -0,0 +1,112 @@
+//! Counting the requests in flight on a connection, so that it is only
+//! closed for being idle between them
+use crate::http::Lifecycle;
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use hyper::body::{Body as HttpBody, Frame, SizeHint};
+use hyper::header::{HeaderValue, CONNECTION};
+use hyper::http::{Request, Response, StatusCode, Version};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::Service;
+
+/// Ends the request of `lifecycle` when dropped, with the response body or
+/// the response future
+struct InFlight(Lifecycle);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.end()
+    }
+}
+
+/// A response body keeping its request in flight until it is dropped
+pub struct TrackedBody<B> {
+    inner: Pin<Box<B>>,
+    _in_flight: Option<InFlight>,
+}
+
+impl<B: HttpBody> HttpBody for TrackedBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.inner.as_mut().poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Tracks the requests of a connection in its `Lifecycle`, closing it after
+/// the last one it takes and exempting it from the idle timeout once it is
+/// upgraded or speaks HTTP/2
+#[derive(Clone)]
+pub struct Tracked<S> {
+    inner: S,
+    lifecycle: Option<Lifecycle>,
+}
+
+impl<S> Tracked<S> {
+    pub fn new(inner: S, lifecycle: Option<Lifecycle>) -> Self {
+        Self { inner, lifecycle }
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for Tracked<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<TrackedBody<ResBody>>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let lifecycle = self.lifecycle.clone();
+        let (in_flight, last) = match &lifecycle {
+            Some(lifecycle) => {
+                if req.version() == Version::HTTP_2 {
+                    lifecycle.exempt();
+                }
+                let last = lifecycle.begin();
+                req.extensions_mut().insert(lifecycle.clone());
+                (Some(InFlight(lifecycle.clone())), last)
+            }
+            None => (None, false),
+        };
+
+        let res = self.inner.call(req);
+        async move {
+            let mut res = res.await?;
+            if res.status() == StatusCode::SWITCHING_PROTOCOLS {
+                if let Some(lifecycle) = &lifecycle {
+                    lifecycle.exempt();
+                }
+            } else if last {
+                res.headers_mut()
+                    .insert(CONNECTION, HeaderValue::from_static("close"));
+            }
+            Ok(res.map(|body| TrackedBody {
+                inner: Box::pin(body),
+                _in_flight: in_flight,
+            }))
+        }
+        .boxed()
+    }
+}
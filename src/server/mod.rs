@@ -0,0 +1,700 @@
+//! Serving the router over plaintext or TLS connections, on TCP or Unix
+//! sockets
+use crate::http::{
+    Aborting, Body, BoxError, Bytes, ConnectionInfo, Disconnected, Draining,
+    Interim, Lifecycle, PeerCredentials, Tls,
+};
+use crate::router::Router;
+use futures::prelude::*;
+use hyper::body::Body as HttpBody;
+use hyper::server::conn::http1;
+use hyper::Request as HTTPRequest;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tower::util::MapRequest;
+use tower::ServiceBuilder;
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::trace::{Trace, TraceLayer};
+
+mod abort;
+mod h2c;
+#[cfg(feature = "http3")]
+mod http3;
+mod lifecycle;
+mod tls;
+#[cfg(unix)]
+mod unix;
+
+use self::h2c::H2c;
+use self::lifecycle::Tracked;
+pub use self::tls::TlsConfig;
+
+/// A connection that knows who its peer is
+pub trait Connection: AsyncRead + AsyncWrite + Send + Unpin + 'static {
+    fn remote_addr(&self) -> Option<SocketAddr>;
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    fn peer_credentials(&self) -> Option<PeerCredentials> {
+        None
+    }
+
+    /// How the TLS handshake went, over TLS
+    fn tls(&self) -> Option<Tls> {
+        None
+    }
+
+    /// Makes closing the connection reset it instead, where it can be
+    fn reset_on_close(&self) {}
+}
+
+/// What the acceptor knows about `conn`, under a new number
+fn connection_info<C: Connection>(conn: &C) -> ConnectionInfo {
+    let mut info = ConnectionInfo::new();
+    info.peer_addr = conn.remote_addr();
+    info.local_addr = conn.local_addr();
+    info.credentials = conn.peer_credentials();
+    info.tls = conn.tls();
+    info
+}
+
+impl Connection for TcpStream {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr().ok()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        TcpStream::local_addr(self).ok()
+    }
+
+    #[cfg(unix)]
+    fn reset_on_close(&self) {
+        let _ = socket2::SockRef::from(self).set_linger(Some(Duration::ZERO));
+    }
+}
+
+/// Whether accepting failed for the connection alone, which the listener
+/// doesn't have to back off from
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// Waits a moment after failing to accept for want of something like file
+/// descriptors, so as not to fail again straight away
+async fn accept_failed(e: io::Error) {
+    if !is_connection_error(&e) {
+        tracing::warn!("Failed to accept connection: {}", e);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Accepts connections on `listener` until it is dropped
+fn tcp_incoming(listener: TcpListener) -> impl Stream<Item = TcpStream> {
+    stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => return Some((stream, listener)),
+                Err(e) => accept_failed(e).await,
+            }
+        }
+    })
+}
+
+/// How long in-flight requests get to finish once shutdown starts
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Serve HTTPS instead of plaintext HTTP
+    pub tls: Option<TlsConfig>,
+    /// Accept cleartext HTTP/2, with prior knowledge or through
+    /// `Upgrade: h2c`
+    pub h2c: bool,
+    /// Also serve HTTP/3 on the same port over UDP, which requires TLS
+    #[cfg(feature = "http3")]
+    pub http3: bool,
+    /// Also listen on a Unix domain socket at this path
+    pub unix_socket: Option<PathBuf>,
+    /// Only listen on the Unix socket
+    pub unix_only: bool,
+    /// How long to wait for in-flight requests before dropping them
+    pub drain_timeout: Duration,
+    /// How long an idle HTTP/1.x connection is kept open, if not for as
+    /// long as the client likes, zero closing it after each response
+    pub keep_alive: Option<Duration>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tls: None,
+            h2c: false,
+            #[cfg(feature = "http3")]
+            http3: false,
+            unix_socket: None,
+            unix_only: false,
+            drain_timeout: DRAIN_TIMEOUT,
+            keep_alive: None,
+        }
+    }
+}
+
+/// Wraps `service` in what every connection gets: tracing, and what is
+/// known about the connection and its peer, the draining signal, and ways
+/// to abort the connection, learn of its client going away, send
+/// informational responses and keep it alive, if it has them, in the
+/// request extensions, the request body being boxed on the way in
+#[allow(clippy::type_complexity)]
+fn stack<S, B>(
+    service: S,
+    info: ConnectionInfo,
+    draining: Draining,
+    aborting: Option<Aborting>,
+    disconnected: Option<Disconnected>,
+    interim: Option<Interim>,
+    lifecycle: Option<Lifecycle>,
+) -> Tracked<
+    Trace<
+        MapRequest<
+            S,
+            impl Fn(HTTPRequest<B>) -> HTTPRequest<Body> + Clone + Send,
+        >,
+        SharedClassifier<ServerErrorsAsFailures>,
+    >,
+>
+where
+    B: HttpBody<Data = Bytes> + Send + Sync + 'static,
+    B::Error: Into<BoxError>,
+{
+    let with_peer = move |mut req: HTTPRequest<B>| {
+        if let Some(addr) = info.peer_addr {
+            req.extensions_mut().insert(addr);
+        }
+        if let Some(credentials) = info.credentials {
+            req.extensions_mut().insert(credentials);
+        }
+        req.extensions_mut().insert(info.next_request());
+        req.extensions_mut().insert(draining.clone());
+        if let Some(aborting) = &aborting {
+            req.extensions_mut().insert(aborting.clone());
+        }
+        if let Some(disconnected) = &disconnected {
+            req.extensions_mut().insert(disconnected.clone());
+        }
+        if let Some(interim) = &interim {
+            req.extensions_mut().insert(interim.clone());
+        }
+        req.map(Body::new)
+    };
+
+    let service = ServiceBuilder::new()
+        .layer(TraceLayer::new_for_http())
+        .map_request(with_peer)
+        .service(service);
+    Tracked::new(service, lifecycle)
+}
+
+/// Serves a connection on a task of its own until it closes, shutting it
+/// down gracefully once draining starts
+///
+/// The task holds on to `open` until then.
+fn spawn_connection<C, E>(
+    conn: C,
+    shutdown: impl FnOnce(Pin<&mut C>) + Send + 'static,
+    draining: Draining,
+    open: mpsc::Sender<()>,
+) where
+    C: Future<Output = Result<(), E>> + Send + 'static,
+    E: std::fmt::Display + Send,
+{
+    tokio::spawn(async move {
+        let _open = open;
+        futures::pin_mut!(conn);
+        let result = tokio::select! {
+            result = conn.as_mut() => result,
+            _ = draining.wait() => {
+                shutdown(conn.as_mut());
+                conn.await
+            }
+        };
+        if let Err(e) = result {
+            tracing::debug!("Connection failed: {}", e);
+        }
+    });
+}
+
+/// Serves `router` on the connections of `incoming`, until draining starts
+/// and the connections it left open have closed
+async fn run<I>(
+    incoming: I,
+    router: Router,
+    tls: bool,
+    h2c: bool,
+    draining: Draining,
+    keep_alive: Option<Duration>,
+) where
+    I: Stream,
+    I::Item: Connection,
+{
+    // Closing after each response is up to hyper, which says so in the
+    // response, so neither the timer of the connection nor hyper's own
+    let kept_alive = keep_alive != Some(Duration::ZERO);
+    let idle_timeout = keep_alive.filter(|_| kept_alive);
+    let mut http1 = http1::Builder::new();
+    http1.keep_alive(kept_alive);
+    let mut auto = auto::Builder::new(TokioExecutor::new());
+    auto.http1().keep_alive(kept_alive);
+    // Over TLS, ALPN decides between HTTP/1.1 and HTTP/2 instead
+    let http1_only = !h2c && !tls;
+
+    let (open, mut all_closed) = mpsc::channel(1);
+    let incoming = abort::incoming(incoming, idle_timeout);
+    futures::pin_mut!(incoming);
+    loop {
+        let conn = tokio::select! {
+            conn = incoming.next() => match conn {
+                Some(conn) => conn,
+                None => break,
+            },
+            _ = draining.wait() => break,
+        };
+
+        let info = connection_info(&conn);
+        let aborting = conn.aborting().clone();
+        let disconnected = conn.disconnected().clone();
+        let interim = conn.interim().clone();
+        let lifecycle = conn.lifecycle().clone();
+        let service = H2c::new(
+            router.clone(),
+            info.clone(),
+            draining.clone(),
+            aborting.clone(),
+            disconnected.clone(),
+            h2c && !tls,
+        );
+        let service = stack(
+            service,
+            info,
+            draining.clone(),
+            Some(aborting),
+            Some(disconnected),
+            Some(interim),
+            Some(lifecycle),
+        );
+
+        let (io, service) =
+            (TokioIo::new(conn), TowerToHyperService::new(service));
+        let (draining, open) = (draining.clone(), open.clone());
+        if http1_only {
+            let conn = http1.serve_connection(io, service).with_upgrades();
+            spawn_connection(
+                conn,
+                |conn| conn.graceful_shutdown(),
+                draining,
+                open,
+            );
+        } else {
+            let conn = auto.serve_connection_with_upgrades(io, service);
+            spawn_connection(
+                conn.into_owned(),
+                |conn| conn.graceful_shutdown(),
+                draining,
+                open,
+            );
+        }
+    }
+    drop(open);
+    all_closed.recv().await;
+}
+
+/// Serves `router` on `addr` until `shutdown` resolves, then stops
+/// accepting connections and gives in-flight requests the drain timeout to
+/// finish
+pub async fn serve(
+    addr: SocketAddr,
+    router: Router,
+    options: Options,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    let draining = Draining::new();
+
+    #[cfg(feature = "http3")]
+    let http3 = match (&options.tls, options.http3) {
+        (Some(tls), true) => {
+            http3::serve(addr, router.clone(), tls, draining.clone()).boxed()
+        }
+        (None, true) => {
+            return Err(anyhow::anyhow!("HTTP/3 requires a TLS certificate"))
+        }
+        (_, false) => future::ok(()).boxed(),
+    };
+    #[cfg(not(feature = "http3"))]
+    let http3 = future::ok::<_, anyhow::Error>(());
+
+    #[cfg(unix)]
+    let unix = async {
+        if let Some(path) = &options.unix_socket {
+            let incoming = unix::incoming(path)?;
+            run(
+                incoming,
+                router.clone(),
+                false,
+                options.h2c,
+                draining.clone(),
+                options.keep_alive,
+            )
+            .await;
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    #[cfg(not(unix))]
+    let unix = async {
+        match &options.unix_socket {
+            Some(_) => Err(anyhow::anyhow!("Unix sockets are unsupported")),
+            None => Ok(()),
+        }
+    };
+
+    let tcp = async {
+        if options.unix_only {
+            return Ok(());
+        }
+        match &options.tls {
+            Some(tls) => {
+                let incoming = tls::incoming(addr, tls.acceptor()?).await?;
+                run(
+                    incoming,
+                    router.clone(),
+                    true,
+                    false,
+                    draining.clone(),
+                    options.keep_alive,
+                )
+                .await
+            }
+            None => {
+                let incoming = tcp_incoming(TcpListener::bind(addr).await?);
+                run(
+                    incoming,
+                    router.clone(),
+                    false,
+                    options.h2c,
+                    draining.clone(),
+                    options.keep_alive,
+                )
+                .await
+            }
+        }
+        Ok(())
+    };
+
+    let drain = async {
+        shutdown.await;
+        tracing::info!(
+            "Shutting down, draining connections for up to {:?}",
+            options.drain_timeout
+        );
+        draining.start();
+        tokio::time::sleep(options.drain_timeout).await;
+    };
+
+    let result = tokio::select! {
+        result = future::try_join3(tcp, unix, http3) => result.map(|_| ()),
+        _ = drain => {
+            tracing::warn!("Drain timeout elapsed, dropping connections");
+            Ok(())
+        }
+    };
+
+    #[cfg(unix)]
+    if let Some(path) = &options.unix_socket {
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{
+        body_from_stream, ok, response, Bytes, Request, StatusCode,
+    };
+    use hyper::client::conn;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::net::TcpStream;
+    use uri_path::path;
+
+    static HUNG_UP: AtomicBool = AtomicBool::new(false);
+
+    async fn version(req: Request) -> crate::http::Result {
+        ok(format!("{:?}", req.version()))
+    }
+
+    async fn ticks(req: Request) -> crate::http::Result {
+        let ticks = stream::repeat(Bytes::from_static(b"tick")).then(|tick| {
+            tokio::time::sleep(Duration::from_millis(10)).map(|_| tick)
+        });
+        response().body(body_from_stream(Box::pin(req.until_draining(ticks))))
+    }
+
+    async fn reset(req: Request) -> crate::http::Result {
+        req.abort(crate::http::Abort::Reset);
+        ok("never sent")
+    }
+
+    async fn hang(req: Request) -> crate::http::Result {
+        tokio::spawn(
+            req.on_disconnect()
+                .map(|_| HUNG_UP.store(true, Ordering::SeqCst)),
+        );
+        future::pending().await
+    }
+
+    async fn replace(req: Request) -> crate::http::Result {
+        let raw = Bytes::from_static(b"HTTP/1.1 200 OK\r\nX-Raw: 1\r\n\r\n");
+        req.abort(crate::http::Abort::Replace(crate::http::Replacement::new(
+            raw,
+        )));
+        ok("never sent")
+    }
+
+    async fn early_hints(req: Request) -> crate::http::Result {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert("link", "</a.css>; rel=preload".parse().unwrap());
+        req.send_informational(StatusCode::from_u16(103).unwrap(), &headers)?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        ok("final")
+    }
+
+    async fn last(req: Request) -> crate::http::Result {
+        req.keep_alive(Duration::from_secs(5), Some(1));
+        ok("")
+    }
+
+    fn server_with(
+        h2c: bool,
+        draining: Draining,
+        keep_alive: Option<Duration>,
+    ) -> (SocketAddr, tokio::task::JoinHandle<()>) {
+        let router = Router::builder()
+            .install(version, crate::router::route(path!("version")))
+            .install(ticks, crate::router::route(path!("ticks")))
+            .install(reset, crate::router::route(path!("reset")))
+            .install(replace, crate::router::route(path!("replace")))
+            .install(hang, crate::router::route(path!("hang")))
+            .install(early_hints, crate::router::route(path!("early-hints")))
+            .install(last, crate::router::route(path!("last")))
+            .build();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = tcp_incoming(TcpListener::from_std(listener).unwrap());
+
+        (
+            addr,
+            tokio::spawn(run(
+                incoming, router, false, h2c, draining, keep_alive,
+            )),
+        )
+    }
+
+    async fn server(h2c: bool) -> SocketAddr {
+        server_with(h2c, Draining::new(), None).0
+    }
+
+    #[tokio::test]
+    async fn test_prior_knowledge() {
+        let addr = server(true).await;
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut client, connection) =
+            conn::http2::Builder::new(TokioExecutor::new())
+                .handshake::<_, Body>(TokioIo::new(stream))
+                .await
+                .unwrap();
+        tokio::spawn(connection);
+
+        let req = HTTPRequest::get("http://localhost/version")
+            .body(Body::empty())
+            .unwrap();
+        let res = client.send_request(req).await.unwrap();
+        let body = crate::http::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "HTTP/2.0");
+    }
+
+    #[tokio::test]
+    async fn test_prior_knowledge_disabled() {
+        let addr = server(false).await;
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut client, connection) =
+            conn::http2::Builder::new(TokioExecutor::new())
+                .handshake::<_, Body>(TokioIo::new(stream))
+                .await
+                .unwrap();
+        tokio::spawn(connection);
+
+        let req = HTTPRequest::get("http://localhost/version")
+            .body(Body::empty())
+            .unwrap();
+        assert!(client.send_request(req).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_draining_ends_streams() {
+        let draining = Draining::new();
+        let (addr, server) = server_with(false, draining.clone(), None);
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut client, connection) =
+            conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+
+        let req = HTTPRequest::get("/ticks").body(Body::empty()).unwrap();
+        let res = client.send_request(req).await.unwrap();
+        let mut body = Body::new(res.into_body());
+        assert_eq!(body.data().await.unwrap().unwrap(), "tick");
+
+        draining.start();
+        while body.data().await.transpose().unwrap().is_some() {}
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_abort_resets_connection() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let addr = server(false).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /reset HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = vec![];
+        let err = stream.read_to_end(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::ConnectionReset);
+        assert!(buf.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_abort_replaces_response() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let addr = server(false).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /replace HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"HTTP/1.1 200 OK\r\nX-Raw: 1\r\n\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_signalled() {
+        use tokio::io::AsyncWriteExt;
+        let addr = server(false).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /hang HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!HUNG_UP.load(Ordering::SeqCst));
+
+        drop(stream);
+        for _ in 0..100 {
+            if HUNG_UP.load(Ordering::SeqCst) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the disconnect went unnoticed");
+    }
+
+    #[tokio::test]
+    async fn test_informational_response() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let addr = server(false).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET /early-hints HTTP/1.1\r\nhost: localhost\r\n\
+                  connection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut raw = String::new();
+        stream.read_to_string(&mut raw).await.unwrap();
+
+        assert!(raw.starts_with(
+            "HTTP/1.1 103 Early Hints\r\nlink: </a.css>; rel=preload\r\n\r\n\
+             HTTP/1.1 200 OK\r\n"
+        ));
+        assert!(raw.ends_with("final"));
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_closes_connection() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let (addr, _) = server_with(
+            false,
+            Draining::new(),
+            Some(Duration::from_millis(50)),
+        );
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /version HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut raw = String::new();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            stream.read_to_string(&mut raw),
+        )
+        .await
+        .expect("the idle connection stayed open")
+        .unwrap();
+        assert!(raw.ends_with("HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_max_closes_connection() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let addr = server(false).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET /last HTTP/1.1\r\nhost: localhost\r\n\r\n\
+                  GET /version HTTP/1.1\r\nhost: localhost\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut raw = String::new();
+        stream.read_to_string(&mut raw).await.unwrap();
+
+        assert_eq!(raw.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(raw.contains("connection: close"));
+    }
+}
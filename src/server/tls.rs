@@ -0,0 +1,421 @@
+use super::{accept_failed, Connection};
+use crate::http::{Bytes, Tls};
+use anyhow::{anyhow, Context};
+use futures::channel::mpsc;
+use futures::prelude::*;
+use rustls_pemfile::Item;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls::server::AllowAnyAnonymousOrAuthenticatedClient;
+use tokio_rustls::rustls::{
+    Certificate, PrivateKey, RootCertStore, ServerConfig,
+};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::{CancellationToken, DropGuard};
+
+/// Handshakes that have completed but not yet been picked up by the server
+const BACKLOG: usize = 128;
+/// How long a client gets to complete its handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// PEM files for the certificate chain and its private key, and the CAs
+/// of the client certificates to ask for
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub client_ca: Option<PathBuf>,
+}
+
+impl Connection for TlsStream<TcpStream> {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.get_ref().0.peer_addr().ok()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.get_ref().0.local_addr().ok()
+    }
+
+    fn tls(&self) -> Option<Tls> {
+        let session = self.get_ref().1;
+        Some(Tls {
+            version: session
+                .protocol_version()
+                .and_then(|version| version.as_str()),
+            cipher: session
+                .negotiated_cipher_suite()
+                .and_then(|suite| suite.suite().as_str()),
+            alpn: session
+                .alpn_protocol()
+                .map(|alpn| String::from_utf8_lossy(alpn).into_owned()),
+            server_name: session.server_name().map(str::to_owned),
+            client_certificate: session
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .map(|cert| Bytes::copy_from_slice(&cert.0)),
+        })
+    }
+
+    fn reset_on_close(&self) {
+        self.get_ref().0.reset_on_close()
+    }
+}
+
+fn read_pem(path: &PathBuf) -> anyhow::Result<Vec<Item>> {
+    let file = File::open(path)
+        .with_context(|| format!("Unable to open {}", path.display()))?;
+    rustls_pemfile::read_all(&mut BufReader::new(file))
+        .with_context(|| format!("Invalid PEM in {}", path.display()))
+}
+
+/// The certificates in the PEM file at `path`, of which there has to be
+/// at least one
+fn read_certs(path: &PathBuf) -> anyhow::Result<Vec<Certificate>> {
+    let certs = read_pem(path)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(cert) => Some(Certificate(cert)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if certs.is_empty() {
+        return Err(anyhow!("No certificate in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+impl TlsConfig {
+    pub(super) fn certs(&self) -> anyhow::Result<Vec<Certificate>> {
+        read_certs(&self.cert)
+    }
+
+    /// The CAs client certificates are checked against, if any are asked
+    /// for
+    fn client_roots(&self) -> anyhow::Result<Option<RootCertStore>> {
+        let path = match &self.client_ca {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let mut roots = RootCertStore::empty();
+        for cert in read_certs(path)? {
+            roots.add(&cert).with_context(|| {
+                format!("Invalid CA certificate in {}", path.display())
+            })?;
+        }
+        Ok(Some(roots))
+    }
+
+    fn key(&self) -> anyhow::Result<PrivateKey> {
+        read_pem(&self.key)?
+            .into_iter()
+            .find_map(|item| match item {
+                Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => {
+                    Some(PrivateKey(key))
+                }
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("No private key in {}", self.key.display()))
+    }
+
+    /// The server configuration, offering `protocols` in order through ALPN
+    /// and asking for a client certificate, without requiring one, if there
+    /// are CAs for them
+    pub fn server_config(
+        &self,
+        protocols: &[&[u8]],
+    ) -> anyhow::Result<ServerConfig> {
+        let builder = ServerConfig::builder().with_safe_defaults();
+        let builder = match self.client_roots()? {
+            Some(roots) => builder.with_client_cert_verifier(
+                AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed(),
+            ),
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder
+            .with_single_cert(self.certs()?, self.key()?)
+            .context("Invalid certificate or private key")?;
+        config.alpn_protocols =
+            protocols.iter().map(|protocol| protocol.to_vec()).collect();
+        Ok(config)
+    }
+
+    /// The acceptor, offering HTTP/2 ahead of HTTP/1.1
+    pub fn acceptor(&self) -> anyhow::Result<TlsAcceptor> {
+        let config = self.server_config(&[b"h2", b"http/1.1"])?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+/// The TLS connection of `stream`, unless its handshake fails or takes
+/// longer than `timeout`
+async fn handshake(
+    acceptor: &TlsAcceptor,
+    stream: TcpStream,
+    timeout: Duration,
+) -> Option<TlsStream<TcpStream>> {
+    match tokio::time::timeout(timeout, acceptor.accept(stream)).await {
+        Ok(Ok(stream)) => Some(stream),
+        Ok(Err(e)) => {
+            tracing::debug!("TLS handshake failed: {}", e);
+            None
+        }
+        Err(_) => {
+            tracing::debug!("TLS handshake timed out");
+            None
+        }
+    }
+}
+
+/// The connections of `incoming`, which stops accepting them and closes its
+/// listener once this is dropped
+struct Incoming {
+    streams: mpsc::Receiver<TlsStream<TcpStream>>,
+    _stop: DropGuard,
+}
+
+impl Stream for Incoming {
+    type Item = TlsStream<TcpStream>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.streams.poll_next_unpin(cx)
+    }
+}
+
+/// Accepts TLS connections on `addr`, until the stream is dropped
+///
+/// Each handshake runs on its own task, so a slow client can't hold up the
+/// ones behind it, and for at most `HANDSHAKE_TIMEOUT`, so it can't keep
+/// its connection open without one either.
+pub async fn incoming(
+    addr: SocketAddr,
+    acceptor: TlsAcceptor,
+) -> io::Result<impl Stream<Item = TlsStream<TcpStream>>> {
+    let listener = TcpListener::bind(addr).await?;
+    let (tx, rx) = mpsc::channel(BACKLOG);
+    let stop = CancellationToken::new();
+    let stopped = stop.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = stopped.cancelled() => break,
+            };
+            let stream = match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    accept_failed(e).await;
+                    continue;
+                }
+            };
+
+            let acceptor = acceptor.clone();
+            let mut tx = tx.clone();
+            tokio::spawn(async move {
+                let handshake = handshake(&acceptor, stream, HANDSHAKE_TIMEOUT);
+                if let Some(stream) = handshake.await {
+                    let _ = tx.send(stream).await;
+                }
+            });
+        }
+    });
+
+    Ok(Incoming {
+        streams: rx,
+        _stop: stop.drop_guard(),
+    })
+}
+
+#[cfg(test)]
+pub(super) mod test {
+    use super::*;
+    use std::convert::TryFrom;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerName};
+    use tokio_rustls::TlsConnector;
+
+    fn testdata(name: &str) -> PathBuf {
+        [
+            env!("CARGO_MANIFEST_DIR"),
+            "src",
+            "server",
+            "testdata",
+            name,
+        ]
+        .iter()
+        .collect()
+    }
+
+    pub(crate) fn config() -> TlsConfig {
+        TlsConfig {
+            cert: testdata("cert.pem"),
+            key: testdata("key.pem"),
+            client_ca: None,
+        }
+    }
+
+    pub(crate) fn roots() -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        for cert in config().certs().unwrap() {
+            roots.add(&cert).unwrap();
+        }
+        roots
+    }
+
+    async fn connect(
+        addr: SocketAddr,
+        protocols: &[&[u8]],
+    ) -> tokio_rustls::client::TlsStream<TcpStream> {
+        let mut client = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots())
+            .with_no_client_auth();
+        client.alpn_protocols =
+            protocols.iter().map(|protocol| protocol.to_vec()).collect();
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        TlsConnector::from(Arc::new(client))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_missing_files() {
+        let config = TlsConfig {
+            cert: testdata("missing.pem"),
+            key: testdata("key.pem"),
+            client_ca: None,
+        };
+        assert!(config.acceptor().is_err());
+    }
+
+    #[test]
+    fn test_key_is_not_a_cert() {
+        let config = TlsConfig {
+            cert: testdata("key.pem"),
+            key: testdata("key.pem"),
+            client_ca: None,
+        };
+        assert!(config.acceptor().is_err());
+    }
+
+    async fn echo_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let incoming =
+            incoming(addr, config().acceptor().unwrap()).await.unwrap();
+        tokio::spawn(async move {
+            futures::pin_mut!(incoming);
+            while let Some(mut stream) = incoming.next().await {
+                let protocol =
+                    stream.get_ref().1.alpn_protocol().map(<[u8]>::to_vec);
+                stream
+                    .write_all(protocol.as_deref().unwrap_or(b"none"))
+                    .await
+                    .unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_alpn() {
+        let addr = echo_server().await;
+
+        for (offered, expected) in [
+            (&[&b"h2"[..], b"http/1.1"][..], "h2"),
+            (&[&b"http/1.1"[..]][..], "http/1.1"),
+        ] {
+            let mut stream = connect(addr, offered).await;
+            let mut protocol = String::new();
+            stream.read_to_string(&mut protocol).await.unwrap();
+            assert_eq!(protocol, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _silent = TcpStream::connect(addr).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        let acceptor = config().acceptor().unwrap();
+        let timeout = Duration::from_millis(50);
+        assert!(handshake(&acceptor, stream, timeout).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stops_accepting_once_dropped() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let incoming =
+            incoming(addr, config().acceptor().unwrap()).await.unwrap();
+        drop(incoming);
+
+        // The accept loop lets go of the listener on its next turn
+        let mut rebound = TcpListener::bind(addr).await;
+        for _ in 0..100 {
+            if rebound.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            rebound = TcpListener::bind(addr).await;
+        }
+        assert!(rebound.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let config = TlsConfig {
+            client_ca: Some(testdata("client-ca.pem")),
+            ..config()
+        };
+        let incoming =
+            incoming(addr, config.acceptor().unwrap()).await.unwrap();
+        let accepted = tokio::spawn(async move {
+            futures::pin_mut!(incoming);
+            incoming.next().await.unwrap()
+        });
+
+        let chain = read_certs(&testdata("client.pem")).unwrap();
+        let key = match read_pem(&testdata("client-key.pem")).unwrap().pop() {
+            Some(Item::PKCS8Key(key)) => PrivateKey(key),
+            _ => panic!("not a key"),
+        };
+        let client = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots())
+            .with_client_auth_cert(chain.clone(), key)
+            .unwrap();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let _client = TlsConnector::from(Arc::new(client))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap();
+
+        let tls = accepted.await.unwrap().tls().unwrap();
+        assert_eq!(tls.client_certificate.unwrap(), chain[0].0);
+    }
+}
@@ -0,0 +1,113 @@
+use super::{accept_failed, Connection};
+use crate::http::PeerCredentials;
+use futures::prelude::*;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use tokio::net::{UnixListener, UnixStream};
+
+impl Connection for UnixStream {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    fn peer_credentials(&self) -> Option<PeerCredentials> {
+        let credentials = self.peer_cred().ok()?;
+        Some(PeerCredentials {
+            pid: credentials.pid(),
+            uid: credentials.uid(),
+            gid: credentials.gid(),
+        })
+    }
+}
+
+/// Accepts connections on the socket at `path`, replacing a stale socket
+/// left behind by an earlier run
+pub fn incoming(path: &Path) -> io::Result<impl Stream<Item = UnixStream>> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            std::fs::remove_file(path)?
+        }
+        _ => {}
+    }
+    let listener = UnixListener::bind(path)?;
+
+    Ok(stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => return Some((stream, listener)),
+                Err(e) => accept_failed(e).await,
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{ok, Body, Draining, Request};
+    use crate::router::{route, Router};
+    use hyper::client::conn;
+    use hyper::Request as HTTPRequest;
+    use hyper_util::rt::TokioIo;
+    use uri_path::path;
+
+    async fn origin(req: Request) -> crate::http::Result {
+        ok(req.client_addr().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket() {
+        let dir = std::env::temp_dir().join(format!(
+            "httpbox-{}-{}",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("httpbox.sock");
+
+        let router = Router::builder()
+            .install(origin, route(path!("origin")))
+            .build();
+        let incoming = incoming(&path).unwrap();
+        tokio::spawn(super::super::run(
+            incoming,
+            router,
+            false,
+            false,
+            Draining::new(),
+            None,
+        ));
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let (mut client, connection) =
+            conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+
+        let req = HTTPRequest::get("/origin").body(Body::empty()).unwrap();
+        let res = client.send_request(req).await.unwrap();
+        let body = crate::http::to_bytes(res.into_body()).await.unwrap();
+        let expected = format!("pid={},", std::process::id());
+        assert!(std::str::from_utf8(&body).unwrap().contains(&expected));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_replaces_stale_socket() {
+        let dir = std::env::temp_dir().join(format!(
+            "httpbox-{}-{}",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("httpbox.sock");
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async { assert!(incoming(&path).is_ok()) });
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
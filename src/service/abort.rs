@@ -0,0 +1,127 @@
+//! Connections cut off partway through the response
+use crate::headers::{ContentLength, ContentType, HeaderMapExt};
+use crate::http::{bad_request, response, Abort, Body, Bytes, Request, Result};
+use futures::prelude::*;
+use serde_derive::Deserialize;
+use std::io;
+use std::time::Duration;
+
+/// What the body of a response cut off mid-body would have been, of which
+/// the first half is sent
+const BODY: &[u8] = b"This response is cut off halfway through, as requested\n";
+/// Gives hyper the chance to send what came before the cut
+const FLUSH: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+enum When {
+    #[default]
+    BeforeHeaders,
+    AfterHeaders,
+    MidBody,
+}
+
+#[derive(Deserialize)]
+pub struct AbortQueryParams {
+    #[serde(default)]
+    when: When,
+}
+
+fn cut_off() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "aborted on request")
+}
+
+/// Cuts off the connection at the point the `when` query asks for
+///
+/// The body ends in an error as well, which ends the exchange even where the
+/// connection can't be cut, such as over HTTP/3.
+async fn cut(req: Request, how: Abort) -> Result {
+    let query = req.query::<AbortQueryParams>().map_err(|_| bad_request())?;
+
+    let (sent, delay) = match query.when {
+        When::BeforeHeaders => {
+            req.abort(how.clone());
+            (None, Duration::ZERO)
+        }
+        When::AfterHeaders => (None, FLUSH),
+        When::MidBody => {
+            (Some(Bytes::from_static(&BODY[..BODY.len() / 2])), FLUSH)
+        }
+    };
+    let abort = async move {
+        tokio::time::sleep(delay).await;
+        req.abort(how);
+        Err::<Bytes, _>(cut_off())
+    };
+    let chunks = stream::iter(sent.map(Ok)).chain(stream::once(abort));
+
+    let mut res = response()
+        .typed_header(ContentType::text())
+        .body(Body::wrap_stream(chunks))?;
+    if query.when == When::MidBody {
+        res.headers_mut()
+            .typed_insert(ContentLength(BODY.len() as u64));
+    }
+    Ok(res)
+}
+
+/// Closes the connection without finishing the response
+pub async fn abort(req: Request) -> Result {
+    cut(req, Abort::Close).await
+}
+
+/// Resets the connection without finishing the response
+pub async fn reset(req: Request) -> Result {
+    cut(req, Abort::Reset).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{Aborting, StatusCode};
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_before_headers() {
+        let aborting = Aborting::new();
+        let res = request()
+            .extension(aborting.clone())
+            .handle(reset)
+            .await
+            .unwrap();
+
+        assert!(matches!(aborting.get(), Some(Abort::Reset)));
+        assert!(res.read_body().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mid_body() {
+        let aborting = Aborting::new();
+        let res = request()
+            .path("/?when=mid-body")
+            .extension(aborting.clone())
+            .handle(abort)
+            .await
+            .unwrap();
+
+        assert!(aborting.get().is_none());
+        assert_eq!(
+            res.headers()["content-length"],
+            BODY.len().to_string().as_str()
+        );
+        let mut body = res.into_body();
+        assert_eq!(
+            body.next().await.unwrap().unwrap(),
+            &BODY[..BODY.len() / 2]
+        );
+        assert!(body.next().await.unwrap().is_err());
+        assert!(matches!(aborting.get(), Some(Abort::Close)));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_when() {
+        let res = request().path("/?when=never").handle(abort).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
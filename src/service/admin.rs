@@ -0,0 +1,265 @@
+//! Runtime controls of the public endpoints, served by the admin listener
+//! only
+use super::health::Health;
+use crate::config::Config;
+use crate::handler::Handler;
+use crate::http::{
+    bad_request, json, response, Error, Limit, Request, Result, StatusCode,
+};
+use crate::middleware::{Chaos, Faults, RateLimit};
+use crate::router::Router;
+use async_trait::async_trait;
+use futures::prelude::*;
+use hyper::Method;
+use serde_derive::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+/// A request to shut the server down, shared by the admin endpoint making
+/// it and the listeners waiting on it
+#[derive(Clone, Debug, Default)]
+pub struct Shutdown(CancellationToken);
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&self) {
+        self.0.cancel()
+    }
+
+    pub fn requested(&self) -> impl Future<Output = ()> + Send + 'static {
+        let token = self.0.clone();
+        async move { token.cancelled().await }
+    }
+}
+
+/// What the admin listener controls of the public router
+///
+/// The chaos and rate limit middleware are there even when not configured,
+/// doing nothing until they are set.
+#[derive(Clone)]
+pub struct Controls {
+    pub chaos: Chaos,
+    pub rate_limit: RateLimit,
+    pub live: Health,
+    pub ready: Health,
+    pub shutdown: Shutdown,
+}
+
+impl Controls {
+    pub fn new(config: &Config) -> Self {
+        let key = config.rate_limit_key();
+        Self {
+            chaos: config.chaos().unwrap_or_else(|| Chaos::new(None)),
+            rate_limit: match config.rate_limit() {
+                Some(limit) => RateLimit::new(limit, key),
+                None => RateLimit::unlimited(key),
+            },
+            live: Health::new(),
+            ready: Health::new(),
+            shutdown: Shutdown::new(),
+        }
+    }
+}
+
+/// The router of the admin listener, and the shutdown it may request
+pub struct Admin {
+    pub router: Router,
+    pub shutdown: Shutdown,
+}
+
+#[derive(Serialize)]
+struct ChaosState {
+    faults: Option<String>,
+}
+
+/// Reads the faults with `GET /chaos`, replaces them with the `PUT` body,
+/// written like `--chaos`, and turns them off with `DELETE`
+#[derive(Clone, Debug)]
+pub struct ChaosControl(pub Chaos);
+
+#[async_trait]
+impl Handler for ChaosControl {
+    async fn handle(&self, mut req: Request) -> Result {
+        match *req.method() {
+            Method::PUT => {
+                let body = req.bytes().await?;
+                let faults = std::str::from_utf8(&body)
+                    .ok()
+                    .and_then(|faults| faults.parse::<Faults>().ok())
+                    .ok_or_else(|| {
+                        Error::bad_request("invalid chaos faults")
+                    })?;
+                self.0.set(Some(faults));
+            }
+            Method::DELETE => self.0.set(None),
+            _ => {}
+        }
+        json(&ChaosState {
+            faults: self.0.configured().map(|faults| faults.to_string()),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RateLimitQueryParams {
+    rate: Option<f64>,
+    burst: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct RateLimitState {
+    rate: Option<f64>,
+    burst: Option<u32>,
+}
+
+/// Reads the limit with `GET /rate-limit`, replaces it with
+/// `PUT ?rate=...&burst=...`, the burst being the rate rounded up unless
+/// given, and lifts it with `DELETE`
+#[derive(Clone, Debug)]
+pub struct RateLimitControl(pub RateLimit);
+
+#[async_trait]
+impl Handler for RateLimitControl {
+    async fn handle(&self, req: Request) -> Result {
+        match *req.method() {
+            Method::PUT => {
+                let query = req
+                    .query::<RateLimitQueryParams>()
+                    .map_err(|_| bad_request())?;
+                let rate = query
+                    .rate
+                    .filter(|rate| rate.is_finite() && *rate > 0.0)
+                    .ok_or_else(|| {
+                        Error::bad_request("rate has to be a positive number")
+                    })?;
+                let burst = query.burst.unwrap_or(rate.ceil() as u32);
+                if burst == 0 {
+                    return Err(Error::bad_request(
+                        "burst has to be at least 1",
+                    ));
+                }
+                self.0.set(Some(Limit::new(rate, burst)));
+            }
+            Method::DELETE => self.0.set(None),
+            _ => {}
+        }
+        let limit = self.0.limit();
+        json(&RateLimitState {
+            rate: limit.map(|limit| limit.rate),
+            burst: limit.map(|limit| limit.burst),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct ShuttingDown {
+    drain_timeout: u64,
+}
+
+/// Starts a graceful shutdown, as a SIGTERM would
+#[derive(Clone, Debug)]
+pub struct ShutdownControl(pub Shutdown);
+
+#[async_trait]
+impl Handler for ShutdownControl {
+    async fn handle(&self, req: Request) -> Result {
+        tracing::info!("Shutdown requested on the admin listener");
+        self.0.request();
+        response().status(StatusCode::ACCEPTED).json(&ShuttingDown {
+            drain_timeout: req.config().drain_timeout().as_secs(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::ClientKey;
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_chaos() {
+        let chaos = Chaos::new(None);
+        let control = ChaosControl(chaos.clone());
+
+        let res = request()
+            .method(Method::PUT)
+            .body("error=0.5")
+            .handle(control.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            res.read_body_json().await.unwrap()["faults"],
+            "latency=0,delay=1,error=0.5,reset=0,truncate=0"
+        );
+        assert_eq!(chaos.configured().unwrap().error, 0.5);
+
+        let res = request()
+            .method(Method::PUT)
+            .body("error=2")
+            .handle(control.clone())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = request()
+            .method(Method::DELETE)
+            .handle(control)
+            .await
+            .unwrap();
+        assert!(res.read_body_json().await.unwrap()["faults"].is_null());
+        assert!(chaos.configured().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let rate_limit = RateLimit::unlimited(ClientKey::Addr);
+        let control = RateLimitControl(rate_limit.clone());
+
+        let res = request()
+            .method(Method::PUT)
+            .path("/rate-limit?rate=2.5")
+            .handle(control.clone())
+            .await
+            .unwrap();
+        let state = res.read_body_json().await.unwrap();
+        assert_eq!(state["rate"], 2.5);
+        assert_eq!(state["burst"], 3);
+        assert_eq!(rate_limit.limit(), Some(Limit::new(2.5, 3)));
+
+        for query in ["rate=0", "rate=1&burst=0", ""] {
+            let res = request()
+                .method(Method::PUT)
+                .path(&format!("/rate-limit?{}", query))
+                .handle(control.clone())
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+
+        request()
+            .method(Method::DELETE)
+            .handle(control)
+            .await
+            .unwrap();
+        assert_eq!(rate_limit.limit(), None);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let shutdown = Shutdown::new();
+        let requested = shutdown.requested();
+
+        let res = request()
+            .method(Method::POST)
+            .handle(ShutdownControl(shutdown))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        tokio::time::timeout(std::time::Duration::from_secs(1), requested)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+use crate::http::{json, Request, Result};
+use crate::service::reflection::Reflection;
+
+pub async fn anything(mut req: Request) -> Result {
+    json(&Reflection::request(&mut req).await?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headers::ContentType;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+    use hyper::Method;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_anything() {
+        let res = request()
+            .path("/anything?key=val")
+            .header("x-request-id", "1234")
+            .client_addr("127.0.0.1:1234".parse().unwrap())
+            .handle(anything)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "args": {"key": "val"},
+                "data": "",
+                "files": {},
+                "form": {},
+                "headers": {"X-Request-Id": "1234"},
+                "json": null,
+                "method": "GET",
+                "origin": "127.0.0.1",
+                "url": "/anything?key=val",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anything_post() {
+        let res = request()
+            .method(Method::POST)
+            .typed_header(ContentType::json())
+            .body("[1, 2]")
+            .handle(anything)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(body["method"], "POST");
+        assert_eq!(body["json"], json!([1, 2]));
+        assert_eq!(body["data"], "[1, 2]");
+    }
+}
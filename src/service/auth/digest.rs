@@ -0,0 +1,377 @@
+use super::nonce::{Nonces, Validation};
+use super::{Authenticated, REALM};
+use crate::handler::Handler;
+use crate::headers::{
+    Algorithm, Authorization, Digest, DigestChallenge, Qop, WWWAuthenticate,
+};
+use crate::http::{
+    bad_request, json, response, Error, Request, Result, StatusCode,
+};
+use async_trait::async_trait;
+use md5::Md5;
+use sha2::{Digest as _, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+
+const NONCE_TTL: Duration = Duration::from_secs(300);
+
+fn hash(algorithm: Algorithm, data: &[u8]) -> String {
+    match algorithm {
+        Algorithm::Md5 => format!("{:x}", Md5::digest(data)),
+        Algorithm::Sha256 => format!("{:x}", Sha256::digest(data)),
+    }
+}
+
+/// The `response` the client should have sent, given the password
+///
+/// Only `auth-int` covers the body, so it is `None` for plain `auth`.
+fn expected_response(
+    digest: &Digest,
+    passwd: &str,
+    method: &str,
+    body: Option<&[u8]>,
+) -> Option<String> {
+    let h = |data: String| hash(digest.algorithm, data.as_bytes());
+
+    let ha1 = h(format!("{}:{}:{}", digest.username, digest.realm, passwd));
+    let ha2 = match body {
+        Some(body) => h(format!(
+            "{}:{}:{}",
+            method,
+            digest.uri,
+            hash(digest.algorithm, body)
+        )),
+        None => h(format!("{}:{}", method, digest.uri)),
+    };
+
+    Some(h(format!(
+        "{}:{}:{:08x}:{}:{}:{}",
+        ha1,
+        digest.nonce,
+        digest.nc?,
+        digest.cnonce.as_ref()?,
+        digest.qop?.as_str(),
+        ha2
+    )))
+}
+
+fn unauthorized_challenge(
+    nonces: &Nonces,
+    qop: Qop,
+    algorithm: Algorithm,
+    stale: bool,
+) -> Error {
+    let (nonce, opaque) = nonces.issue();
+    response()
+        .status(StatusCode::UNAUTHORIZED)
+        .typed_header(WWWAuthenticate::digest(DigestChallenge {
+            realm: REALM.to_owned(),
+            qop,
+            algorithm,
+            nonce,
+            opaque,
+            stale,
+        }))
+        .into()
+}
+
+/// The digest auth challenge, with the nonces it hands out shared by the
+/// routes it is installed at
+#[derive(Clone)]
+pub struct DigestAuth(Arc<Nonces>);
+
+impl DigestAuth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for DigestAuth {
+    fn default() -> Self {
+        Self(Arc::new(Nonces::new(NONCE_TTL)))
+    }
+}
+
+#[async_trait]
+impl Handler for DigestAuth {
+    async fn handle(&self, req: Request) -> Result {
+        authenticate(req, &self.0).await
+    }
+}
+
+async fn authenticate(mut req: Request, nonces: &Nonces) -> Result {
+    let qop = req.param::<Qop>("qop").ok_or_else(bad_request)?;
+    let user = req.param::<String>("user").ok_or_else(bad_request)?;
+    let passwd = req.param::<String>("passwd").ok_or_else(bad_request)?;
+    let algorithm = match req.param::<String>("algorithm") {
+        Some(algorithm) => algorithm.parse().map_err(|_| bad_request())?,
+        None => Algorithm::default(),
+    };
+    let challenge =
+        |stale| unauthorized_challenge(nonces, qop, algorithm, stale);
+
+    let digest = req
+        .typed_header::<Authorization<Digest>>()
+        .map(|header| header.0)
+        .ok_or_else(|| challenge(false))?;
+
+    let uri = req.uri().path_and_query().map_or("/", |uri| uri.as_str());
+    if digest.username != user
+        || digest.realm != REALM
+        || digest.uri != uri
+        || digest.algorithm != algorithm
+        || digest.qop != Some(qop)
+    {
+        return Err(challenge(false));
+    }
+
+    let body = match qop {
+        Qop::Auth => None,
+        Qop::AuthInt => Some(req.bytes().await?),
+    };
+    let expected = expected_response(
+        &digest,
+        &passwd,
+        req.method().as_str(),
+        body.as_deref(),
+    );
+    if expected.as_deref() != Some(digest.response.as_str()) {
+        return Err(challenge(false));
+    }
+
+    let opaque = digest.opaque.as_deref().unwrap_or_default();
+    match nonces.validate(&digest.nonce, opaque, digest.nc.unwrap_or_default())
+    {
+        Validation::Valid => json(&Authenticated {
+            authenticated: true,
+            user: &user,
+        }),
+        Validation::Stale => Err(challenge(true)),
+        Validation::Invalid => Err(challenge(false)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headers::HeaderMapExt;
+    use crate::http::Response;
+    use crate::test::*;
+    use hyper::Method;
+
+    fn rfc7616(algorithm: Algorithm) -> Digest {
+        Digest {
+            username: "Mufasa".to_owned(),
+            realm: "http-auth@example.org".to_owned(),
+            uri: "/dir/index.html".to_owned(),
+            algorithm,
+            nonce: "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v".to_owned(),
+            opaque: Some(
+                "FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS".to_owned(),
+            ),
+            qop: Some(Qop::Auth),
+            nc: Some(1),
+            cnonce: Some(
+                "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ".to_owned(),
+            ),
+            response: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_expected_response_md5() {
+        assert_eq!(
+            expected_response(
+                &rfc7616(Algorithm::Md5),
+                "Circle of Life",
+                "GET",
+                None
+            )
+            .unwrap(),
+            "8ca523f5e9506fed4657c9700eebdbec"
+        );
+    }
+
+    #[test]
+    fn test_expected_response_sha256() {
+        assert_eq!(
+            expected_response(
+                &rfc7616(Algorithm::Sha256),
+                "Circle of Life",
+                "GET",
+                None
+            )
+            .unwrap(),
+            "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1"
+        );
+    }
+
+    fn digest_request(qop: &str) -> RequestBuilder {
+        request()
+            .path("/digest-auth/auth/user/passwd")
+            .param("qop", qop)
+            .param("user", "user")
+            .param("passwd", "passwd")
+    }
+
+    async fn call(nonces: &Nonces, req: RequestBuilder) -> Response {
+        match authenticate(req.build(), nonces).await {
+            Ok(res) => res,
+            Err(e) => e.into_result().await.unwrap(),
+        }
+    }
+
+    async fn challenge(
+        nonces: &Nonces,
+        req: RequestBuilder,
+    ) -> DigestChallenge {
+        let res = call(nonces, req).await;
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        res.headers()
+            .typed_get::<WWWAuthenticate>()
+            .unwrap()
+            .digest_challenge()
+            .cloned()
+            .unwrap()
+    }
+
+    fn answer(
+        challenge: &DigestChallenge,
+        passwd: &str,
+        body: &[u8],
+    ) -> Digest {
+        let mut digest = Digest {
+            username: "user".to_owned(),
+            realm: challenge.realm.clone(),
+            uri: "/digest-auth/auth/user/passwd".to_owned(),
+            algorithm: challenge.algorithm,
+            nonce: challenge.nonce.clone(),
+            opaque: Some(challenge.opaque.clone()),
+            qop: Some(challenge.qop),
+            nc: Some(1),
+            cnonce: Some("0a4f113b".to_owned()),
+            response: String::new(),
+        };
+        let body = match challenge.qop {
+            Qop::Auth => None,
+            Qop::AuthInt => Some(body),
+        };
+        digest.response =
+            expected_response(&digest, passwd, "POST", body).unwrap();
+        digest
+    }
+
+    #[tokio::test]
+    async fn test_digest_challenge() {
+        let nonces = Nonces::new(NONCE_TTL);
+        let challenge = challenge(&nonces, digest_request("auth")).await;
+
+        assert_eq!(challenge.realm, REALM);
+        assert_eq!(challenge.qop, Qop::Auth);
+        assert_eq!(challenge.algorithm, Algorithm::Md5);
+        assert!(!challenge.stale);
+    }
+
+    #[tokio::test]
+    async fn test_digest_authorized() {
+        let nonces = Nonces::new(NONCE_TTL);
+        let challenge = challenge(&nonces, digest_request("auth")).await;
+
+        let req = digest_request("auth")
+            .method(Method::POST)
+            .typed_header(Authorization(answer(&challenge, "passwd", b"")));
+        let res = call(&nonces, req).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_digest_auth_int() {
+        let nonces = Nonces::new(NONCE_TTL);
+        let challenge = challenge(&nonces, digest_request("auth-int")).await;
+        let digest = answer(&challenge, "passwd", b"payload");
+
+        let req = digest_request("auth-int")
+            .method(Method::POST)
+            .typed_header(Authorization(digest.clone()))
+            .body("tampered");
+        let res = call(&nonces, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = digest_request("auth-int")
+            .method(Method::POST)
+            .typed_header(Authorization(digest))
+            .body("payload");
+        let res = call(&nonces, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_digest_sha256() {
+        let nonces = Nonces::new(NONCE_TTL);
+        let req = || digest_request("auth").param("algorithm", "SHA-256");
+        let challenge = challenge(&nonces, req()).await;
+        assert_eq!(challenge.algorithm, Algorithm::Sha256);
+
+        let req = req()
+            .method(Method::POST)
+            .typed_header(Authorization(answer(&challenge, "passwd", b"")));
+        let res = call(&nonces, req).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_digest_wrong_password() {
+        let nonces = Nonces::new(NONCE_TTL);
+        let challenge = challenge(&nonces, digest_request("auth")).await;
+
+        let req = digest_request("auth")
+            .method(Method::POST)
+            .typed_header(Authorization(answer(&challenge, "wrong", b"")));
+        let res = call(&nonces, req).await;
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_digest_replay() {
+        let nonces = Nonces::new(NONCE_TTL);
+        let challenge = challenge(&nonces, digest_request("auth")).await;
+        let digest = answer(&challenge, "passwd", b"");
+
+        for status in &[StatusCode::OK, StatusCode::UNAUTHORIZED] {
+            let req = digest_request("auth")
+                .method(Method::POST)
+                .typed_header(Authorization(digest.clone()));
+            let res = call(&nonces, req).await;
+            assert_eq!(res.status(), *status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_digest_stale() {
+        let nonces = Nonces::new(Duration::ZERO);
+        let challenge = challenge(&nonces, digest_request("auth")).await;
+        std::thread::sleep(Duration::from_millis(1));
+
+        let req = digest_request("auth")
+            .method(Method::POST)
+            .typed_header(Authorization(answer(&challenge, "passwd", b"")));
+        let stale = self::challenge(&nonces, req).await;
+
+        assert!(stale.stale);
+        assert_ne!(stale.nonce, challenge.nonce);
+    }
+
+    #[tokio::test]
+    async fn test_digest_invalid_qop() {
+        let res = digest_request("auth-conf")
+            .handle(DigestAuth::new())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
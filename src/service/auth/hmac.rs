@@ -0,0 +1,584 @@
+//! Checking HMAC signatures over requests, for debugging the code that
+//! signs them
+//!
+//! Requests with an `AWS4-HMAC-SHA256` authorization are checked as AWS
+//! Signature Version 4 does. Anything else is taken to carry a signature
+//! such as `sha256=<hex>` over its signed headers and body, the kind
+//! webhooks and /callback send.
+use crate::hex::hex;
+use crate::http::{json, Bytes, Request, Result};
+use hyper::header::{HeaderName, AUTHORIZATION, HOST};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet};
+use ring::hmac;
+use serde_derive::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+const AWS4: &str = "AWS4-HMAC-SHA256";
+const DEFAULT_SIGNATURE_HEADER: &str = "x-httpbox-signature";
+
+/// What SigV4 leaves as is when encoding query parameters
+const UNRESERVED: &AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// The values computed along the way, and the first step where the request
+/// disagrees with its signature
+struct Report {
+    scheme: &'static str,
+    steps: Map<String, Value>,
+    mismatch: Option<(&'static str, String)>,
+}
+
+impl Report {
+    fn new(scheme: &'static str) -> Self {
+        Self {
+            scheme,
+            steps: Map::new(),
+            mismatch: None,
+        }
+    }
+
+    fn step(&mut self, step: &str, value: impl Into<Value>) {
+        self.steps.insert(step.to_owned(), value.into());
+    }
+
+    fn mismatch(mut self, step: &'static str, message: String) -> Self {
+        self.mismatch = Some((step, message));
+        self
+    }
+
+    fn into_json(self) -> Value {
+        let mut report = json!({
+            "scheme": self.scheme,
+            "valid": self.mismatch.is_none(),
+            "steps": self.steps,
+        });
+        if let Some((step, message)) = self.mismatch {
+            report["mismatch"] = json!({ "step": step, "message": message });
+        }
+        report
+    }
+}
+
+fn sign(key: &[u8], message: &[u8]) -> hmac::Tag {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), message)
+}
+
+/// The values of a header joined as SigV4 canonicalizes them, the host of
+/// the URI standing in for `Host` over HTTP/2
+fn header_value(req: &Request, name: &HeaderName) -> Option<String> {
+    let values = req
+        .headers()
+        .get_all(name)
+        .iter()
+        .map(|value| {
+            String::from_utf8_lossy(value.as_bytes())
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>();
+    if values.is_empty() && name == HOST {
+        return req.uri().authority().map(|a| a.as_str().to_owned());
+    }
+    Some(values.join(",")).filter(|_| !values.is_empty())
+}
+
+/// The query with every name and value encoded alike and sorted
+fn canonical_query(query: &str) -> String {
+    let encode = |s: &str| {
+        let decoded = percent_decode_str(s).decode_utf8_lossy();
+        utf8_percent_encode(&decoded, UNRESERVED).to_string()
+    };
+    let mut pairs = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (encode(name), encode(value))
+        })
+        .collect::<Vec<_>>();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// The `Credential`, `SignedHeaders` and `Signature` of the authorization
+fn aws4_params(authorization: &str) -> Option<[&str; 3]> {
+    let mut params = [None; 3];
+    for param in authorization.split(',') {
+        let (name, value) = param.trim().split_once('=')?;
+        let index = ["Credential", "SignedHeaders", "Signature"]
+            .iter()
+            .position(|known| *known == name)?;
+        params[index] = Some(value);
+    }
+    Some([params[0]?, params[1]?, params[2]?])
+}
+
+/// Whether `date` is shaped like `YYYYMMDD'T'HHMMSS'Z'`
+fn is_amz_date(date: &str) -> bool {
+    let bytes = date.as_bytes();
+    bytes.len() == 16
+        && bytes.iter().enumerate().all(|(i, b)| match i {
+            8 => *b == b'T',
+            15 => *b == b'Z',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+fn aws4(req: &Request, body: &Bytes, secret: &str, params: &str) -> Report {
+    let mut report = Report::new(AWS4);
+    let [credential, signed_headers, signature] =
+        match aws4_params(params) {
+            Some(params) => params,
+            None => return report.mismatch(
+                "authorization",
+                "The authorization has to have a Credential, SignedHeaders \
+                 and Signature, separated by commas"
+                    .to_owned(),
+            ),
+        };
+
+    let scope = match credential.split('/').collect::<Vec<_>>()[..] {
+        [key, date, region, service, "aws4_request"] => {
+            report.step(
+                "credential",
+                json!({
+                    "access_key": key,
+                    "date": date,
+                    "region": region,
+                    "service": service,
+                }),
+            );
+            &credential[key.len() + 1..]
+        }
+        _ => {
+            return report.mismatch(
+                "credential",
+                "The Credential has to be \
+                 <key>/<date>/<region>/<service>/aws4_request"
+                    .to_owned(),
+            )
+        }
+    };
+    let date = match header_value(req, &HeaderName::from_static("x-amz-date"))
+        .filter(|date| is_amz_date(date))
+    {
+        Some(date) => date,
+        None => {
+            return report.mismatch(
+                "date",
+                "X-Amz-Date has to be given, as in 20261014T120000Z".to_owned(),
+            )
+        }
+    };
+    if !scope.starts_with(&date[..8]) {
+        return report.mismatch(
+            "credential",
+            format!(
+                "The date of the Credential is not the {} of X-Amz-Date",
+                &date[..8]
+            ),
+        );
+    }
+
+    let names = signed_headers.split(';').collect::<Vec<_>>();
+    report.step("signed_headers", names.clone());
+    let mut sorted = names.clone();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if sorted != names || signed_headers.bytes().any(|b| b.is_ascii_uppercase())
+    {
+        return report.mismatch(
+            "signed_headers",
+            "SignedHeaders has to be lowercase, sorted and without \
+             duplicates"
+                .to_owned(),
+        );
+    }
+    if !names.contains(&"host") {
+        return report
+            .mismatch("signed_headers", "host has to be signed".to_owned());
+    }
+    let mut canonical_headers = String::new();
+    for name in &names {
+        let value = HeaderName::from_bytes(name.as_bytes())
+            .ok()
+            .and_then(|name| header_value(req, &name));
+        match value {
+            Some(value) => {
+                canonical_headers.push_str(&format!("{}:{}\n", name, value))
+            }
+            None => {
+                return report.mismatch(
+                    "signed_headers",
+                    format!("{} is signed but not sent", name),
+                )
+            }
+        }
+    }
+
+    let body_hash = hex(&Sha256::digest(body));
+    let payload_hash =
+        header_value(req, &HeaderName::from_static("x-amz-content-sha256"))
+            .unwrap_or_else(|| body_hash.clone());
+    report.step("payload_hash", payload_hash.clone());
+    if payload_hash != body_hash && payload_hash != "UNSIGNED-PAYLOAD" {
+        return report.mismatch(
+            "payload_hash",
+            format!(
+                "X-Amz-Content-Sha256 is not the {} the body hashes to",
+                body_hash
+            ),
+        );
+    }
+
+    let path = match req.uri().path() {
+        "" => "/",
+        path => path,
+    };
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        req.method(),
+        path,
+        canonical_query(req.uri().query().unwrap_or("")),
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+    let string_to_sign = format!(
+        "{}\n{}\n{}\n{}",
+        AWS4,
+        date,
+        scope,
+        hex(&Sha256::digest(&canonical_request))
+    );
+    report.step("canonical_request", canonical_request);
+    report.step("string_to_sign", string_to_sign.clone());
+
+    let mut key = format!("AWS4{}", secret).into_bytes();
+    for part in scope.split('/') {
+        key = sign(&key, part.as_bytes()).as_ref().to_vec();
+    }
+    let expected = hex(sign(&key, string_to_sign.as_bytes()).as_ref());
+    report.step("signature", expected.clone());
+    if !signature.eq_ignore_ascii_case(&expected) {
+        return report.mismatch(
+            "signature",
+            "The Signature is not that of the string to sign, so compare \
+             the canonical request and string to sign with the client's"
+                .to_owned(),
+        );
+    }
+    report
+}
+
+#[derive(Deserialize)]
+struct GenericParams {
+    /// The header with the signature
+    header: Option<String>,
+    /// The headers signed ahead of the body, separated by `;`
+    headers: Option<String>,
+}
+
+/// Decodes a signature in hex or base64
+fn decode_signature(signature: &str) -> Option<Vec<u8>> {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    let hex = (0..signature.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(signature.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>();
+    hex.filter(|_| signature.len().is_multiple_of(2))
+        .or_else(|| STANDARD.decode(signature).ok())
+}
+
+fn generic(req: &Request, body: &Bytes, secret: &str) -> Report {
+    let mut report = Report::new("hmac");
+    let params = match req.query::<GenericParams>() {
+        Ok(params) => params,
+        Err(_) => {
+            return report.mismatch("request", "Invalid query".to_owned())
+        }
+    };
+    let header = params.header.as_deref().unwrap_or(DEFAULT_SIGNATURE_HEADER);
+    let presented = match HeaderName::from_bytes(header.as_bytes())
+        .ok()
+        .and_then(|name| header_value(req, &name))
+    {
+        Some(presented) => presented,
+        None => {
+            return report.mismatch(
+                "signature",
+                format!("No {} header with the signature", header),
+            )
+        }
+    };
+
+    let (name, signature) = match presented.split_once('=') {
+        Some((name, signature))
+            if !name.is_empty() && !signature.is_empty() =>
+        {
+            (name, signature)
+        }
+        _ => ("sha256", presented.as_str()),
+    };
+    let algorithm = match &*name.to_ascii_lowercase() {
+        "sha1" => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+        "sha256" => hmac::HMAC_SHA256,
+        "sha384" => hmac::HMAC_SHA384,
+        "sha512" => hmac::HMAC_SHA512,
+        _ => {
+            return report.mismatch(
+                "algorithm",
+                format!(
+                    "{} is not one of sha1, sha256, sha384 and sha512",
+                    name
+                ),
+            )
+        }
+    };
+    report.step("algorithm", name.to_ascii_lowercase());
+
+    let names = params
+        .headers
+        .as_deref()
+        .unwrap_or("")
+        .split(';')
+        .filter(|name| !name.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>();
+    report.step("signed_headers", names.clone());
+    let mut string_to_sign = Vec::new();
+    for name in &names {
+        let value = HeaderName::from_bytes(name.as_bytes())
+            .ok()
+            .and_then(|name| header_value(req, &name));
+        match value {
+            Some(value) => string_to_sign
+                .extend_from_slice(format!("{}:{}\n", name, value).as_bytes()),
+            None => {
+                return report.mismatch(
+                    "signed_headers",
+                    format!("{} is signed but not sent", name),
+                )
+            }
+        }
+    }
+    string_to_sign.extend_from_slice(body);
+    report.step(
+        "string_to_sign",
+        String::from_utf8_lossy(&string_to_sign).into_owned(),
+    );
+
+    let expected = hmac::sign(
+        &hmac::Key::new(algorithm, secret.as_bytes()),
+        &string_to_sign,
+    );
+    report.step("signature", hex(expected.as_ref()));
+    match decode_signature(signature) {
+        Some(signature) if signature == expected.as_ref() => report,
+        Some(_) => report.mismatch(
+            "signature",
+            "The signature is not that of the string to sign".to_owned(),
+        ),
+        None => report.mismatch(
+            "encoding",
+            "The signature has to be in hex or base64".to_owned(),
+        ),
+    }
+}
+
+/// Checks the signature of the request with the `hmac-secret`, answering
+/// with what went into it whether it checks out or not
+pub async fn hmac(mut req: Request) -> Result {
+    let body = req.bytes().await?;
+    let secret = req.config().hmac_secret();
+    let authorization = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix(AWS4))
+        .filter(|params| params.starts_with(' '));
+
+    let report = match authorization {
+        Some(params) => aws4(&req, &body, secret, params),
+        None => generic(&req, &body, secret),
+    };
+    json(&report.into_json())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+    use crate::test::*;
+    use hyper::header::HeaderValue;
+    use std::sync::Arc;
+
+    /// The `get-vanilla` case of the AWS Signature Version 4 test suite
+    const AWS_SECRET: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+    const AWS_AUTHORIZATION: &str = "AWS4-HMAC-SHA256 \
+        Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+        SignedHeaders=host;x-amz-date, \
+        Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31";
+
+    fn config(secret: &str) -> Arc<Config> {
+        Arc::new(Config {
+            hmac_secret: Some(secret.to_owned()),
+            ..Config::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_aws4() {
+        let res = request()
+            .header("host", "example.amazonaws.com")
+            .header("x-amz-date", "20150830T123600Z")
+            .header("authorization", AWS_AUTHORIZATION)
+            .extension(config(AWS_SECRET))
+            .handle(hmac)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["valid"], true, "{}", body);
+        assert_eq!(body["steps"]["credential"]["region"], "us-east-1");
+        assert!(body.get("mismatch").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_aws4_signature_mismatch() {
+        let res = request()
+            .path("/?b=2&a=1")
+            .header("host", "example.amazonaws.com")
+            .header("x-amz-date", "20150830T123600Z")
+            .header("authorization", AWS_AUTHORIZATION)
+            .extension(config(AWS_SECRET))
+            .handle(hmac)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["valid"], false);
+        assert_eq!(body["mismatch"]["step"], "signature");
+        let canonical = body["steps"]["canonical_request"].as_str().unwrap();
+        assert!(canonical.starts_with("GET\n/\na=1&b=2\n"), "{}", canonical);
+    }
+
+    #[tokio::test]
+    async fn test_aws4_payload_hash_mismatch() {
+        let res = request()
+            .method(hyper::Method::POST)
+            .header("host", "example.amazonaws.com")
+            .header("x-amz-date", "20150830T123600Z")
+            .header("x-amz-content-sha256", hex(&Sha256::digest("other")))
+            .header("authorization", AWS_AUTHORIZATION)
+            .body("body")
+            .handle(hmac)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["mismatch"]["step"], "payload_hash");
+    }
+
+    #[tokio::test]
+    async fn test_aws4_malformed_date() {
+        for date in ["2015083OT123600Z", "0123456é890123Z"] {
+            let res = request()
+                .header("host", "example.amazonaws.com")
+                .header(
+                    "x-amz-date",
+                    HeaderValue::from_bytes(date.as_bytes()).unwrap(),
+                )
+                .header("authorization", AWS_AUTHORIZATION)
+                .extension(config(AWS_SECRET))
+                .handle(hmac)
+                .await
+                .unwrap();
+            let body = res.read_body_json().await.unwrap();
+
+            assert_eq!(body["mismatch"]["step"], "date", "{}", date);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_aws4_unsent_header() {
+        let res = request()
+            .header("host", "example.amazonaws.com")
+            .header("x-amz-date", "20150830T123600Z")
+            .header(
+                "authorization",
+                AWS_AUTHORIZATION.replace("x-amz-date", "x-amz-date;x-extra"),
+            )
+            .handle(hmac)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["mismatch"]["step"], "signed_headers");
+        assert_eq!(
+            body["mismatch"]["message"],
+            "x-extra is signed but not sent"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generic() {
+        let res = request()
+            .method(hyper::Method::POST)
+            .path("/hmac?headers=date")
+            .header("date", "Wed")
+            .header(
+                "x-httpbox-signature",
+                "sha256=17e11c928686ea3356913dd7d16cbfe4342384025fca98f09a912820ed527986",
+            )
+            .body(r#"{"a":1}"#)
+            .handle(hmac)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["valid"], true, "{}", body);
+        assert_eq!(body["steps"]["string_to_sign"], "date:Wed\n{\"a\":1}");
+    }
+
+    #[tokio::test]
+    async fn test_generic_mismatch() {
+        let res = request()
+            .method(hyper::Method::POST)
+            .path("/hmac?header=x-signature")
+            .header("x-signature", "c2lnbmF0dXJl")
+            .body("body")
+            .extension(config("other"))
+            .handle(hmac)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["valid"], false);
+        assert_eq!(body["mismatch"]["step"], "signature");
+
+        let res = request().handle(hmac).await.unwrap();
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(
+            body["mismatch"]["message"],
+            "No x-httpbox-signature header with the signature"
+        );
+    }
+
+    #[test]
+    fn test_canonical_query() {
+        assert_eq!(canonical_query("b=2&a=x%2Fy&c"), "a=x%2Fy&b=2&c=");
+        assert_eq!(canonical_query("a=x y~"), "a=x%20y~");
+    }
+}
@@ -0,0 +1,139 @@
+//! Decoding JSON Web Tokens, and checking them against the configured keys,
+//! for debugging tokens without handing them to anyone else
+use crate::headers::authorization::Bearer;
+use crate::headers::Authorization;
+use crate::http::{json, Error, Request, Result};
+use crate::jwt::{Token, Verification};
+use serde_derive::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize)]
+struct Decoded<'a> {
+    header: &'a Value,
+    claims: &'a Value,
+    /// Whether each check that applies passed: `signature` with keys
+    /// configured, `exp` and `nbf` with those claims
+    checks: BTreeMap<&'static str, bool>,
+    /// The `kid` of the key the signature checked out with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    /// Why the signature did not
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+}
+
+/// Decodes the bearer token, or else the body, and reports which checks it
+/// passes, answering even if it fails them
+pub async fn jwt(mut req: Request) -> Result {
+    let token = match req.typed_header::<Authorization<Bearer>>() {
+        Some(header) => header.0.token().to_owned(),
+        None => String::from_utf8_lossy(&req.bytes().await?).into_owned(),
+    };
+    if token.trim().is_empty() {
+        return Err(Error::bad_request(
+            "No token in the Authorization header or body",
+        ));
+    }
+    let token = Token::decode(&token).map_err(Error::bad_request)?;
+    let keys = req.config().jwt_keys().map_err(Error::internal)?;
+
+    let mut checks = BTreeMap::new();
+    let (mut key, mut error) = (None, None);
+    if let Some(keys) = keys {
+        let verified = match keys.verify(&token) {
+            Verification::Verified(kid) => {
+                key = kid;
+                true
+            }
+            Verification::Failed(reason) => {
+                error = Some(reason);
+                false
+            }
+        };
+        checks.insert("signature", verified);
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    if let Some(exp) = token.date("exp") {
+        checks.insert("exp", now < exp);
+    }
+    if let Some(nbf) = token.date("nbf") {
+        checks.insert("nbf", nbf <= now);
+    }
+
+    json(&Decoded {
+        header: &token.header,
+        claims: &token.claims,
+        checks,
+        key,
+        error,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+    use crate::http::StatusCode;
+    use crate::test::*;
+    use std::sync::Arc;
+
+    /// `{"alg":"HS256","kid":"a"}` and `{"sub":"me","exp":4102444800}`
+    /// signed with `secret`
+    const TOKEN: &str = "eyJhbGciOiJIUzI1NiIsImtpZCI6ImEifQ.\
+        eyJzdWIiOiJtZSIsImV4cCI6NDEwMjQ0NDgwMH0.\
+        e7HYsJUEEmleuMDB6dYqBCPUZ6gZzVhZ_gj-3bEQvLM";
+
+    #[tokio::test]
+    async fn test_jwt_decode() {
+        let res = request()
+            .typed_header(Authorization::bearer(TOKEN).unwrap())
+            .handle(jwt)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["header"]["kid"], "a");
+        assert_eq!(body["claims"]["sub"], "me");
+        assert_eq!(body["checks"], serde_json::json!({"exp": true}));
+    }
+
+    #[tokio::test]
+    async fn test_jwt_verify() {
+        let path = std::env::temp_dir().join("httpbox-test-jwks.json");
+        std::fs::write(
+            &path,
+            r#"{"keys": [{"kty": "oct", "kid": "a", "k": "c2VjcmV0"}]}"#,
+        )
+        .unwrap();
+        let config = Config {
+            jwt_keys: Some(path),
+            ..Config::default()
+        };
+        let res = request()
+            .method(hyper::Method::POST)
+            .body(TOKEN)
+            .extension(Arc::new(config))
+            .handle(jwt)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(
+            body["checks"],
+            serde_json::json!({"exp": true, "signature": true})
+        );
+        assert_eq!(body["key"], "a");
+    }
+
+    #[tokio::test]
+    async fn test_jwt_malformed() {
+        let res = request().body("not-a-token").handle(jwt).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
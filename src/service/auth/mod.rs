@@ -1,9 +1,21 @@
 use crate::headers::authorization::{Basic, Bearer};
 use crate::headers::Authorization;
 use crate::headers::WWWAuthenticate;
-use crate::http::{ok, response, Error, Request, Result, StatusCode};
+use crate::http::{json, ok, response, Error, Request, Result, StatusCode};
 
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
+
+mod digest;
+mod hmac;
+mod jwt;
+mod nonce;
+mod oauth;
+mod oidc;
+
+pub use self::digest::DigestAuth;
+pub use self::hmac::hmac;
+pub use self::jwt::jwt;
+pub use self::oidc::{configuration, Endpoint, Provider, ProviderEndpoint};
 
 pub(crate) const REALM: &str = "User Visible Realm";
 
@@ -14,8 +26,20 @@ fn unauthorized_authenticate() -> Error {
         .into()
 }
 
-fn unauthorized() -> Error {
-    response().status(StatusCode::UNAUTHORIZED).into()
+fn unauthorized_bearer() -> Error {
+    response()
+        .status(StatusCode::UNAUTHORIZED)
+        .typed_header(WWWAuthenticate::bearer())
+        .into()
+}
+
+/// The token of an `Authorization: Bearer` header, or a 401 challenge
+pub(crate) fn bearer_token(
+    req: &Request,
+) -> std::result::Result<String, Error> {
+    req.typed_header::<Authorization<Bearer>>()
+        .map(|header| header.0.token().to_owned())
+        .ok_or_else(unauthorized_bearer)
 }
 
 #[derive(Deserialize)]
@@ -24,6 +48,18 @@ pub struct BasicAuthParams {
     passwd: String,
 }
 
+#[derive(Serialize)]
+struct Authenticated<'a> {
+    authenticated: bool,
+    user: &'a str,
+}
+
+#[derive(Serialize)]
+struct AuthenticatedToken<'a> {
+    authenticated: bool,
+    token: &'a str,
+}
+
 pub async fn basic(req: Request) -> Result {
     let BasicAuthParams { user, passwd } = req
         .params::<BasicAuthParams>()
@@ -35,21 +71,33 @@ pub async fn basic(req: Request) -> Result {
         .filter(|basic| basic.username() == user && basic.password() == passwd);
 
     let _ = headers.ok_or_else(unauthorized_authenticate)?;
-    ok("Authenticated")
+    json(&Authenticated {
+        authenticated: true,
+        user: &user,
+    })
 }
 
 pub async fn bearer(req: Request) -> Result {
-    let token = req.param::<String>("token").ok_or_else(unauthorized)?;
-
-    let headers = req
-        .typed_header::<Authorization<Bearer>>()
-        .map(|header| header.0)
-        .filter(|bearer| bearer.token() == token);
+    let token = req
+        .param::<String>("token")
+        .ok_or_else(unauthorized_bearer)?;
 
-    let _ = headers.ok_or_else(unauthorized)?;
+    if bearer_token(&req)? != token {
+        return Err(unauthorized_bearer());
+    }
     ok("Authenticated")
 }
 
+/// Accepts any bearer token
+pub async fn token(req: Request) -> Result {
+    let token = bearer_token(&req)?;
+
+    json(&AuthenticatedToken {
+        authenticated: true,
+        token: &token,
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -86,6 +134,10 @@ mod test {
             .unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.read_body_json().await.unwrap(),
+            serde_json::json!({"authenticated": true, "user": "my-username"})
+        );
     }
 
     #[tokio::test]
@@ -144,4 +196,37 @@ mod test {
 
         assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
     }
+
+    #[tokio::test]
+    async fn test_token_missing() {
+        let res = request().handle(token).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            res.headers().typed_get::<WWWAuthenticate>().unwrap(),
+            WWWAuthenticate::bearer(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_token_basic() {
+        let auth = Authorization::basic("my-username", "my-password");
+
+        let res = request().typed_header(auth).handle(token).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_token() {
+        let auth = Authorization::bearer("my-token").unwrap();
+
+        let res = request().typed_header(auth).handle(token).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.read_body_json().await.unwrap(),
+            serde_json::json!({"authenticated": true, "token": "my-token"})
+        );
+    }
 }
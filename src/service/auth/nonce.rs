@@ -0,0 +1,155 @@
+use crate::hex::hex;
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The most nonces kept, the oldest making way for a new one
+const MAX_NONCES: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Validation {
+    Valid,
+    /// Issued by us but past its lifetime, so the client should retry
+    Stale,
+    /// Unknown, mismatched opaque or a replayed nonce count
+    Invalid,
+}
+
+struct Entry {
+    opaque: String,
+    issued: Instant,
+    nc: u32,
+}
+
+/// The server side of the digest challenge/response roundtrip
+///
+/// Expired nonces are kept around for another lifetime so they can be
+/// reported as stale rather than unknown.
+pub struct Nonces {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_nonce: HashMap<String, Entry>,
+    /// The nonces in the order they were issued, some perhaps already gone
+    /// from `by_nonce`
+    issued: VecDeque<(Instant, String)>,
+}
+
+pub(super) fn random_hex() -> String {
+    hex(&rand::thread_rng().gen::<[u8; 16]>())
+}
+
+impl Nonces {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Issues a fresh `(nonce, opaque)` pair
+    pub fn issue(&self) -> (String, String) {
+        let (nonce, opaque) = (random_hex(), random_hex());
+        let now = Instant::now();
+
+        let mut entries = self.entries.lock().unwrap();
+        let Entries { by_nonce, issued } = &mut *entries;
+        while let Some((at, oldest)) = issued.front() {
+            if now - *at <= self.ttl * 2 && issued.len() < MAX_NONCES {
+                break;
+            }
+            by_nonce.remove(oldest);
+            issued.pop_front();
+        }
+        issued.push_back((now, nonce.clone()));
+        by_nonce.insert(
+            nonce.clone(),
+            Entry {
+                opaque: opaque.clone(),
+                issued: now,
+                nc: 0,
+            },
+        );
+        (nonce, opaque)
+    }
+
+    /// Checks a nonce, recording `nc` so the same count can't be replayed
+    pub fn validate(&self, nonce: &str, opaque: &str, nc: u32) -> Validation {
+        let entries = &mut self.entries.lock().unwrap().by_nonce;
+        let entry = match entries.get_mut(nonce) {
+            Some(entry) if entry.opaque == opaque => entry,
+            _ => return Validation::Invalid,
+        };
+
+        if entry.issued.elapsed() > self.ttl {
+            entries.remove(nonce);
+            Validation::Stale
+        } else if nc <= entry.nc {
+            Validation::Invalid
+        } else {
+            entry.nc = nc;
+            Validation::Valid
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let nonces = Nonces::new(Duration::from_secs(60));
+        let (nonce, opaque) = nonces.issue();
+
+        assert_eq!(nonces.validate(&nonce, &opaque, 1), Validation::Valid);
+        assert_eq!(nonces.validate(&nonce, &opaque, 2), Validation::Valid);
+    }
+
+    #[test]
+    fn test_validate_replay() {
+        let nonces = Nonces::new(Duration::from_secs(60));
+        let (nonce, opaque) = nonces.issue();
+
+        assert_eq!(nonces.validate(&nonce, &opaque, 1), Validation::Valid);
+        assert_eq!(nonces.validate(&nonce, &opaque, 1), Validation::Invalid);
+    }
+
+    #[test]
+    fn test_validate_unknown() {
+        let nonces = Nonces::new(Duration::from_secs(60));
+        let (nonce, _) = nonces.issue();
+
+        assert_eq!(nonces.validate("other", "", 1), Validation::Invalid);
+        assert_eq!(nonces.validate(&nonce, "other", 1), Validation::Invalid);
+    }
+
+    #[test]
+    fn test_issue_capacity() {
+        let nonces = Nonces::new(Duration::from_secs(60));
+        let (first, opaque) = nonces.issue();
+        for _ in 0..MAX_NONCES {
+            nonces.issue();
+        }
+
+        let entries = nonces.entries.lock().unwrap();
+        assert_eq!(entries.by_nonce.len(), MAX_NONCES);
+        assert_eq!(entries.issued.len(), MAX_NONCES);
+        drop(entries);
+        assert_eq!(nonces.validate(&first, &opaque, 1), Validation::Invalid);
+    }
+
+    #[test]
+    fn test_validate_stale() {
+        let nonces = Nonces::new(Duration::ZERO);
+        let (nonce, opaque) = nonces.issue();
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert_eq!(nonces.validate(&nonce, &opaque, 1), Validation::Stale);
+        assert_eq!(nonces.validate(&nonce, &opaque, 1), Validation::Invalid);
+    }
+}
@@ -0,0 +1,531 @@
+//! A stand-in OAuth 2.0 authorization server, approving every authorization
+//! request straight away and exchanging its codes for tokens signed like
+//! those of /token
+use super::nonce::random_hex;
+use super::oidc::{claims, issuer, Provider};
+use crate::headers::authorization::Basic;
+use crate::headers::Authorization;
+use crate::http::{
+    bad_request, redirect_to, response, Error, Request, Result, StatusCode,
+};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hyper::header::CACHE_CONTROL;
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// How long codes can be exchanged for, as RFC 6749 recommends at most
+pub(super) const CODE_TTL: Duration = Duration::from_secs(600);
+
+/// How long the tokens are valid for, in seconds
+const EXPIRES_IN: u64 = 3600;
+
+/// The most codes waiting to be exchanged, the oldest making way for a new
+/// one
+const MAX_CODES: usize = 10_000;
+
+/// How a PKCE code verifier is turned into the challenge
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ChallengeMethod {
+    Plain,
+    S256,
+}
+
+impl ChallengeMethod {
+    fn from_param(param: Option<&str>) -> Option<Self> {
+        match param {
+            // RFC 7636 has clients leaving it out mean plain
+            None | Some("plain") => Some(Self::Plain),
+            Some("S256") => Some(Self::S256),
+            Some(_) => None,
+        }
+    }
+
+    fn challenge(self, verifier: &str) -> String {
+        match self {
+            Self::Plain => verifier.to_owned(),
+            Self::S256 => URL_SAFE_NO_PAD.encode(Sha256::digest(verifier)),
+        }
+    }
+}
+
+/// What the client was granted, for exchanging the code it got for tokens
+#[derive(Clone, Debug)]
+struct Grant {
+    client_id: String,
+    redirect_uri: String,
+    sub: String,
+    scope: Option<String>,
+    nonce: Option<String>,
+    challenge: Option<(String, ChallengeMethod)>,
+    issued: Instant,
+}
+
+/// The codes handed out and not exchanged yet
+pub(super) struct Codes {
+    ttl: Duration,
+    grants: Mutex<Grants>,
+}
+
+#[derive(Default)]
+struct Grants {
+    by_code: HashMap<String, Grant>,
+    /// The codes in the order they were issued, some perhaps already
+    /// exchanged
+    issued: VecDeque<(Instant, String)>,
+}
+
+impl Codes {
+    pub(super) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            grants: Mutex::default(),
+        }
+    }
+
+    fn issue(&self, grant: Grant) -> String {
+        let code = random_hex();
+        let mut grants = self.grants.lock().unwrap();
+        let Grants { by_code, issued } = &mut *grants;
+        while let Some((at, oldest)) = issued.front() {
+            if at.elapsed() <= self.ttl && issued.len() < MAX_CODES {
+                break;
+            }
+            by_code.remove(oldest);
+            issued.pop_front();
+        }
+        issued.push_back((grant.issued, code.clone()));
+        by_code.insert(code.clone(), grant);
+        code
+    }
+
+    /// The grant of a code, which can be exchanged once only
+    fn redeem(&self, code: &str) -> Option<Grant> {
+        let grant = self.grants.lock().unwrap().by_code.remove(code)?;
+        Some(grant).filter(|grant| grant.issued.elapsed() <= self.ttl)
+    }
+}
+
+#[derive(Deserialize)]
+struct AuthorizeParams {
+    response_type: Option<String>,
+    client_id: Option<String>,
+    redirect_uri: Option<String>,
+    scope: Option<String>,
+    state: Option<String>,
+    nonce: Option<String>,
+    /// Who to sign in as, `httpbox` if left out
+    login_hint: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+}
+
+/// Sends the user agent back to the client with `params` and the `state`
+/// it came with
+fn back_to_client(
+    mut redirect_uri: Url,
+    params: &[(&str, &str)],
+    state: Option<&str>,
+) -> Result {
+    {
+        let mut query = redirect_uri.query_pairs_mut();
+        query.extend_pairs(params);
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+    }
+    redirect_to(redirect_uri.as_str().parse().map_err(|_| bad_request())?)
+}
+
+/// Approves the authorization request, redirecting to `redirect_uri` with
+/// a code for /oauth/token or an `error`
+///
+/// Without a client or a valid `redirect_uri` there is nowhere to send
+/// errors, so those are a 400 here.
+pub(super) fn authorize(req: Request, provider: &Provider) -> Result {
+    let params = req.query::<AuthorizeParams>().map_err(|_| bad_request())?;
+    let client_id = params
+        .client_id
+        .ok_or_else(|| Error::bad_request("Missing client_id"))?;
+    let redirect_uri = params
+        .redirect_uri
+        .ok_or_else(|| Error::bad_request("Missing redirect_uri"))?;
+    let url = Url::parse(&redirect_uri)
+        .ok()
+        .filter(|url| url.fragment().is_none())
+        .ok_or_else(|| Error::bad_request("Invalid redirect_uri"))?;
+    let state = params.state.as_deref();
+
+    if params.response_type.as_deref() != Some("code") {
+        return back_to_client(
+            url,
+            &[("error", "unsupported_response_type")],
+            state,
+        );
+    }
+    let method =
+        ChallengeMethod::from_param(params.code_challenge_method.as_deref());
+    let challenge = match (params.code_challenge, method) {
+        (Some(challenge), Some(method)) => Some((challenge, method)),
+        (None, _) if params.code_challenge_method.is_none() => None,
+        _ => {
+            return back_to_client(
+                url,
+                &[
+                    ("error", "invalid_request"),
+                    ("error_description", "Unsupported code_challenge_method"),
+                ],
+                state,
+            )
+        }
+    };
+
+    let code = provider.codes.issue(Grant {
+        client_id,
+        redirect_uri,
+        sub: params.login_hint.unwrap_or_else(|| "httpbox".to_owned()),
+        scope: params.scope,
+        nonce: params.nonce,
+        challenge,
+        issued: Instant::now(),
+    });
+    back_to_client(url, &[("code", &code)], state)
+}
+
+#[derive(Deserialize)]
+struct TokenParams {
+    grant_type: Option<String>,
+    code: Option<String>,
+    redirect_uri: Option<String>,
+    client_id: Option<String>,
+    code_verifier: Option<String>,
+    scope: Option<String>,
+}
+
+/// An error response of the token endpoint, as RFC 6749 section 5.2 has
+/// them
+fn token_error(error: &str, description: &str) -> Error {
+    response()
+        .status(StatusCode::BAD_REQUEST)
+        .header(CACHE_CONTROL, "no-store")
+        .json(&json!({
+            "error": error,
+            "error_description": description,
+        }))
+        .map_or_else(|e| e, |res| Error::Failure(Box::new(res)))
+}
+
+/// The `grant` of the code in `params`, if the client is the one it was
+/// issued to and has the verifier of its challenge
+fn redeem(
+    codes: &Codes,
+    params: &TokenParams,
+    client_id: Option<&str>,
+) -> std::result::Result<Grant, Error> {
+    let code = params
+        .code
+        .as_deref()
+        .ok_or_else(|| token_error("invalid_request", "Missing code"))?;
+    let grant = codes.redeem(code).ok_or_else(|| {
+        token_error("invalid_grant", "Unknown, expired or used code")
+    })?;
+
+    if params.redirect_uri.as_deref() != Some(&grant.redirect_uri) {
+        return Err(token_error("invalid_grant", "Mismatched redirect_uri"));
+    }
+    if client_id != Some(&grant.client_id) {
+        return Err(token_error("invalid_grant", "Mismatched client_id"));
+    }
+    if let Some((challenge, method)) = &grant.challenge {
+        let verifier = params.code_verifier.as_deref().ok_or_else(|| {
+            token_error("invalid_grant", "Missing code_verifier")
+        })?;
+        if method.challenge(verifier) != *challenge {
+            return Err(token_error(
+                "invalid_grant",
+                "Mismatched code_verifier",
+            ));
+        }
+    }
+    Ok(grant)
+}
+
+/// Exchanges a code from /oauth/authorize for tokens, or hands a client
+/// its own with `grant_type=client_credentials`
+///
+/// The client is identified by `client_id` in the form or as the user of
+/// Basic auth, its secret not being checked.
+pub(super) async fn token(mut req: Request, provider: &Provider) -> Result {
+    let basic = req
+        .typed_header::<Authorization<Basic>>()
+        .map(|header| header.0.username().to_owned());
+    let params = req.form::<TokenParams>().await?;
+    let client_id = params.client_id.clone().or(basic);
+    let issuer = issuer(&req)?;
+
+    let (grant, sub) = match params.grant_type.as_deref() {
+        Some("authorization_code") => {
+            let grant = redeem(&provider.codes, &params, client_id.as_deref())?;
+            let sub = grant.sub.clone();
+            (Some(grant), sub)
+        }
+        Some("client_credentials") => {
+            let client_id = client_id.clone().ok_or_else(|| {
+                token_error("invalid_request", "Missing client_id")
+            })?;
+            (None, client_id)
+        }
+        Some(_) => {
+            return Err(token_error(
+                "unsupported_grant_type",
+                "Only authorization_code and client_credentials are \
+                 supported",
+            ))
+        }
+        None => {
+            return Err(token_error("invalid_request", "Missing grant_type"))
+        }
+    };
+    let client_id = client_id.unwrap_or_default();
+    let scope = match &grant {
+        Some(grant) => grant.scope.clone(),
+        None => params.scope,
+    };
+
+    let mut access = claims(
+        issuer.clone(),
+        sub.clone().into(),
+        client_id.clone().into(),
+        EXPIRES_IN,
+    );
+    access.insert("client_id".to_owned(), client_id.clone().into());
+    if let Some(scope) = &scope {
+        access.insert("scope".to_owned(), scope.clone().into());
+    }
+    let mut body = json!({
+        "access_token": provider.sign(access),
+        "token_type": "Bearer",
+        "expires_in": EXPIRES_IN,
+    });
+    if let Some(scope) = &scope {
+        body["scope"] = scope.clone().into();
+    }
+
+    let openid = scope
+        .as_deref()
+        .is_some_and(|scope| scope.split(' ').any(|s| s == "openid"));
+    if let Some(grant) = grant.filter(|_| openid) {
+        let mut id = claims(issuer, sub.into(), client_id.into(), EXPIRES_IN);
+        if let Some(nonce) = grant.nonce {
+            id.insert("nonce".to_owned(), Value::from(nonce));
+        }
+        body["id_token"] = provider.sign(id).into();
+    }
+
+    response().header(CACHE_CONTROL, "no-store").json(&body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jwt::Token;
+    use crate::service::auth::Endpoint;
+    use crate::test::*;
+    use hyper::header::LOCATION;
+
+    const REDIRECT_URI: &str = "http://client.example/callback";
+
+    /// The query of the redirect back to the client
+    fn redirected(res: &crate::http::Response) -> HashMap<String, String> {
+        assert_eq!(res.status(), StatusCode::FOUND);
+        let location = res.headers()[LOCATION].to_str().unwrap();
+        assert!(location.starts_with(REDIRECT_URI), "{}", location);
+        Url::parse(location)
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect()
+    }
+
+    async fn authorized(
+        provider: &Provider,
+        query: &str,
+    ) -> HashMap<String, String> {
+        let res = request()
+            .path(&format!(
+                "/oauth/authorize?client_id=app&redirect_uri={}&{}",
+                REDIRECT_URI, query
+            ))
+            .handle(provider.endpoint(Endpoint::Authorize))
+            .await
+            .unwrap();
+        redirected(&res)
+    }
+
+    async fn exchange(provider: &Provider, form: &str) -> (StatusCode, Value) {
+        let res = request()
+            .method(hyper::Method::POST)
+            .header("host", "example.com")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(form)
+            .handle(provider.endpoint(Endpoint::ExchangeToken))
+            .await
+            .unwrap();
+        let status = res.status();
+        (status, res.read_body_json().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_code_flow_with_pkce() {
+        let provider = Provider::new();
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let query = authorized(
+            &provider,
+            "response_type=code&state=xyz&scope=openid%20profile&nonce=n-1\
+             &login_hint=alice&code_challenge_method=S256\
+             &code_challenge=E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM",
+        )
+        .await;
+        assert_eq!(query["state"], "xyz");
+
+        let form = format!(
+            "grant_type=authorization_code&code={}&redirect_uri={}\
+             &client_id=app&code_verifier={}",
+            query["code"], REDIRECT_URI, verifier
+        );
+        let (status, body) = exchange(&provider, &form).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["token_type"], "Bearer");
+        assert_eq!(body["scope"], "openid profile");
+
+        let id = Token::decode(body["id_token"].as_str().unwrap()).unwrap();
+        assert_eq!(id.claims["iss"], "http://example.com");
+        assert_eq!(id.claims["sub"], "alice");
+        assert_eq!(id.claims["aud"], "app");
+        assert_eq!(id.claims["nonce"], "n-1");
+        let access =
+            Token::decode(body["access_token"].as_str().unwrap()).unwrap();
+        assert_eq!(access.claims["scope"], "openid profile");
+
+        let (status, body) = exchange(&provider, &form).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_grant");
+    }
+
+    #[tokio::test]
+    async fn test_code_flow_wrong_verifier() {
+        let provider = Provider::new();
+        let query = authorized(
+            &provider,
+            "response_type=code&code_challenge=secret&code_challenge_method=plain",
+        )
+        .await;
+        assert!(!query.contains_key("state"));
+
+        let (status, body) = exchange(
+            &provider,
+            &format!(
+                "grant_type=authorization_code&code={}&redirect_uri={}\
+             &client_id=app&code_verifier=guess",
+                query["code"], REDIRECT_URI
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_grant");
+        assert_eq!(body["error_description"], "Mismatched code_verifier");
+    }
+
+    #[tokio::test]
+    async fn test_code_flow_without_openid() {
+        let provider = Provider::new();
+        let query = authorized(&provider, "response_type=code").await;
+        let (status, body) = exchange(
+            &provider,
+            &format!(
+                "grant_type=authorization_code&code={}&redirect_uri={}\
+             &client_id=app",
+                query["code"], REDIRECT_URI
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.get("id_token").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_authorize_errors() {
+        let provider = Provider::new();
+        let query = authorized(&provider, "response_type=token&state=s").await;
+        assert_eq!(query["error"], "unsupported_response_type");
+        assert_eq!(query["state"], "s");
+
+        let query = authorized(
+            &provider,
+            "response_type=code&code_challenge_method=S512",
+        )
+        .await;
+        assert_eq!(query["error"], "invalid_request");
+
+        let res = request()
+            .path("/oauth/authorize?client_id=app&response_type=code")
+            .handle(provider.endpoint(Endpoint::Authorize))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_client_credentials() {
+        let provider = Provider::new();
+        let res = request()
+            .method(hyper::Method::POST)
+            .header("host", "example.com")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .typed_header(Authorization::basic("app", "secret"))
+            .body("grant_type=client_credentials&scope=read")
+            .handle(provider.endpoint(Endpoint::ExchangeToken))
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+        let access =
+            Token::decode(body["access_token"].as_str().unwrap()).unwrap();
+
+        assert_eq!(access.claims["sub"], "app");
+        assert_eq!(access.claims["scope"], "read");
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_grant_type() {
+        let provider = Provider::new();
+        let (status, body) = exchange(&provider, "grant_type=password").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "unsupported_grant_type");
+    }
+
+    #[test]
+    fn test_codes_capacity() {
+        let codes = Codes::new(CODE_TTL);
+        let grant = Grant {
+            client_id: "app".to_owned(),
+            redirect_uri: REDIRECT_URI.to_owned(),
+            sub: "httpbox".to_owned(),
+            scope: None,
+            nonce: None,
+            challenge: None,
+            issued: Instant::now(),
+        };
+        let first = codes.issue(grant.clone());
+        for _ in 0..MAX_CODES {
+            codes.issue(grant.clone());
+        }
+
+        assert_eq!(codes.grants.lock().unwrap().by_code.len(), MAX_CODES);
+        assert!(codes.redeem(&first).is_none());
+    }
+}
@@ -0,0 +1,272 @@
+//! A stand-in OpenID Connect provider, minting tokens for whatever claims
+//! are asked for with a key generated at startup
+use super::oauth::{self, Codes};
+use crate::handler::Handler;
+use crate::http::{bad_request, json, Error, Request, Result};
+use crate::jwt::SigningKey;
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use std::any::type_name_of_val;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How long tokens are valid for unless asked otherwise, in seconds
+const DEFAULT_EXPIRES_IN: u64 = 3600;
+
+/// Parameters of the token request itself rather than claims
+const RESERVED: &[&str] = &[
+    "grant_type",
+    "client_id",
+    "client_secret",
+    "audience",
+    "expires_in",
+];
+
+/// The issuer, which is where clients reach us
+pub(super) fn issuer(req: &Request) -> std::result::Result<String, Error> {
+    let url = req.base_url().map_err(|_| bad_request())?;
+    Ok(url.as_str().trim_end_matches('/').to_owned())
+}
+
+/// The claims every token has, valid for `expires_in` seconds from now
+pub(super) fn claims(
+    issuer: String,
+    sub: Value,
+    aud: Value,
+    expires_in: u64,
+) -> Map<String, Value> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut claims = Map::new();
+    claims.insert("iss".to_owned(), issuer.into());
+    claims.insert("sub".to_owned(), sub);
+    claims.insert("aud".to_owned(), aud);
+    claims.insert("iat".to_owned(), now.into());
+    claims.insert("exp".to_owned(), now.saturating_add(expires_in).into());
+    claims
+}
+
+/// The state of the provider, shared by its endpoints: the key it signs
+/// tokens with, generated along with it, and the codes handed out by
+/// /oauth/authorize
+#[derive(Clone)]
+pub struct Provider {
+    key: Arc<SigningKey>,
+    pub(super) codes: Arc<Codes>,
+}
+
+/// The endpoints of a `Provider` that need its state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    Jwks,
+    Token,
+    Authorize,
+    ExchangeToken,
+}
+
+impl Provider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A handler for `endpoint`, sharing the state of this provider
+    pub fn endpoint(&self, endpoint: Endpoint) -> ProviderEndpoint {
+        ProviderEndpoint {
+            provider: self.clone(),
+            endpoint,
+        }
+    }
+
+    pub(super) fn sign(&self, claims: Map<String, Value>) -> String {
+        self.key.sign(&Value::Object(claims))
+    }
+}
+
+impl Default for Provider {
+    fn default() -> Self {
+        Self {
+            key: Arc::new(SigningKey::generate()),
+            codes: Arc::new(Codes::new(oauth::CODE_TTL)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ProviderEndpoint {
+    provider: Provider,
+    endpoint: Endpoint,
+}
+
+#[async_trait]
+impl Handler for ProviderEndpoint {
+    async fn handle(&self, req: Request) -> Result {
+        let provider = &self.provider;
+        match self.endpoint {
+            Endpoint::Jwks => jwks(provider),
+            Endpoint::Token => token(req, provider).await,
+            Endpoint::Authorize => oauth::authorize(req, provider),
+            Endpoint::ExchangeToken => oauth::token(req, provider).await,
+        }
+    }
+
+    /// The function answering at the endpoint, rather than the provider
+    fn name(&self) -> &'static str {
+        match self.endpoint {
+            Endpoint::Jwks => type_name_of_val(&jwks),
+            Endpoint::Token => type_name_of_val(&token),
+            Endpoint::Authorize => type_name_of_val(&oauth::authorize),
+            Endpoint::ExchangeToken => type_name_of_val(&oauth::token),
+        }
+    }
+}
+
+pub async fn configuration(req: Request) -> Result {
+    let issuer = issuer(&req)?;
+    json(&json!({
+        "issuer": issuer,
+        "jwks_uri": format!("{}/jwks.json", issuer),
+        "authorization_endpoint": format!("{}/oauth/authorize", issuer),
+        "token_endpoint": format!("{}/oauth/token", issuer),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["ES256"],
+        "grant_types_supported": ["authorization_code", "client_credentials"],
+        "code_challenge_methods_supported": ["S256", "plain"],
+        "token_endpoint_auth_methods_supported": [
+            "client_secret_basic",
+            "client_secret_post",
+            "none"
+        ],
+    }))
+}
+
+fn jwks(provider: &Provider) -> Result {
+    json(&json!({ "keys": [provider.key.jwk()] }))
+}
+
+/// Mints a token with the claims of the query, the form or a JSON object
+/// body, `sub` and `aud` defaulting to the `client_id` and `audience`
+async fn token(mut req: Request, provider: &Provider) -> Result {
+    let mut params = req
+        .query::<Vec<(String, String)>>()
+        .map_err(|_| bad_request())?
+        .into_iter()
+        .map(|(key, value)| (key, Value::from(value)))
+        .collect::<Map<_, _>>();
+    if req.has_content_type(mime::APPLICATION, mime::WWW_FORM_URLENCODED) {
+        let form = req.form::<Vec<(String, String)>>().await?;
+        params.extend(form.into_iter().map(|(k, v)| (k, Value::from(v))));
+    } else if req.has_content_type(mime::APPLICATION, mime::JSON) {
+        params.extend(req.json::<Map<String, Value>>().await?);
+    }
+
+    let expires_in = match params.get("expires_in") {
+        Some(Value::Number(n)) => n.as_u64(),
+        Some(Value::String(s)) => s.parse().ok(),
+        Some(_) => None,
+        None => Some(DEFAULT_EXPIRES_IN),
+    }
+    .ok_or_else(bad_request)?;
+    let client_id = params.get("client_id").cloned();
+    let aud = params
+        .get("audience")
+        .cloned()
+        .or_else(|| client_id.clone());
+    let mut claims = claims(
+        issuer(&req)?,
+        client_id.unwrap_or_else(|| "httpbox".into()),
+        aud.unwrap_or_else(|| "httpbox".into()),
+        expires_in,
+    );
+    claims.extend(
+        params
+            .into_iter()
+            .filter(|(key, _)| !RESERVED.contains(&key.as_str())),
+    );
+
+    let token = provider.sign(claims);
+    json(&json!({
+        "access_token": token,
+        "id_token": token,
+        "token_type": "Bearer",
+        "expires_in": expires_in,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jwt::{KeySet, Token, Verification};
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_configuration() {
+        let res = request()
+            .header("host", "example.com")
+            .handle(configuration)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["issuer"], "http://example.com");
+        assert_eq!(body["jwks_uri"], "http://example.com/jwks.json");
+        assert_eq!(
+            body["authorization_endpoint"],
+            "http://example.com/oauth/authorize"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token() {
+        let provider = Provider::new();
+        let res = request()
+            .path("/token?scope=read&role=admin")
+            .header("host", "example.com")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("grant_type=client_credentials&client_id=app&expires_in=60")
+            .handle(provider.endpoint(Endpoint::Token))
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(body["expires_in"], 60);
+
+        let token =
+            Token::decode(body["access_token"].as_str().unwrap()).unwrap();
+        assert_eq!(token.claims["iss"], "http://example.com");
+        assert_eq!(token.claims["sub"], "app");
+        assert_eq!(token.claims["aud"], "app");
+        assert_eq!(token.claims["role"], "admin");
+        assert_eq!(token.claims["scope"], "read");
+        assert!(token.claims.get("grant_type").is_none());
+        assert_eq!(
+            token.date("exp").unwrap() - token.date("iat").unwrap(),
+            60.0
+        );
+
+        let jwks = request()
+            .handle(provider.endpoint(Endpoint::Jwks))
+            .await
+            .unwrap();
+        let keys =
+            KeySet::from_json(&jwks.read_body_utf8().await.unwrap()).unwrap();
+        assert!(matches!(keys.verify(&token), Verification::Verified(_)));
+    }
+
+    #[tokio::test]
+    async fn test_token_json_claims() {
+        let res = request()
+            .header("host", "example.com")
+            .header("content-type", "application/json")
+            .body(r#"{"groups": ["a", "b"], "sub": "someone"}"#)
+            .handle(Provider::new().endpoint(Endpoint::Token))
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+        let token = Token::decode(body["id_token"].as_str().unwrap()).unwrap();
+
+        assert_eq!(token.claims["groups"], json!(["a", "b"]));
+        assert_eq!(token.claims["sub"], "someone");
+    }
+}
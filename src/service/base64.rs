@@ -0,0 +1,128 @@
+use crate::headers::ContentType;
+use crate::http::StatusCode;
+use crate::http::{bad_request, ok, response, Request, Result};
+use base64::alphabet::URL_SAFE;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+
+/// The URL-safe alphabet, with or without padding
+const LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+fn invalid(value: &str) -> Result {
+    response()
+        .status(StatusCode::BAD_REQUEST)
+        .typed_header(ContentType::text())
+        .body(format!("Incorrect Base64 data: {}", value))
+}
+
+/// Decodes both the standard and the URL-safe alphabet
+pub fn decode(value: &str) -> Option<Vec<u8>> {
+    let normalized = value.replace('+', "-").replace('/', "_");
+    LENIENT.decode(normalized).ok()
+}
+
+pub async fn decode_base64(req: Request) -> Result {
+    let value = req.param::<String>("value").ok_or_else(bad_request)?;
+    let data = match decode(&value) {
+        Some(data) => data,
+        None => return invalid(&value),
+    };
+
+    match String::from_utf8(data) {
+        Ok(text) => ok(text),
+        Err(e) => response()
+            .typed_header(ContentType::octet_stream())
+            .body(e.into_bytes()),
+    }
+}
+
+pub async fn encode_base64(req: Request) -> Result {
+    let value = req.param::<String>("value").ok_or_else(bad_request)?;
+
+    ok(LENIENT.encode(value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_decode() {
+        let cases = [
+            ("SFRUUEJJTiBpcyBhd2Vzb21l", "HTTPBIN is awesome"),
+            ("aHR0cGJveD8-", "httpbox?>"),
+            ("aHR0cGJveD8+", "httpbox?>"),
+        ];
+        for (value, expected) in &cases {
+            let res = request()
+                .param("value", value)
+                .handle(decode_base64)
+                .await
+                .unwrap();
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.read_body_utf8().await.unwrap(), *expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_unpadded() {
+        let res = request()
+            .param("value", "aHR0cGJveA")
+            .handle(decode_base64)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.read_body_utf8().await.unwrap(), "httpbox");
+    }
+
+    #[tokio::test]
+    async fn test_decode_binary() {
+        let res = request()
+            .param("value", "__4")
+            .handle(decode_base64)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("content-type").unwrap(),
+            "application/octet-stream"
+        );
+        assert_eq!(res.read_body().await.unwrap(), [0xff, 0xfe]);
+    }
+
+    #[tokio::test]
+    async fn test_decode_invalid() {
+        let res = request()
+            .param("value", "not base64!")
+            .handle(decode_base64)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            res.read_body_utf8().await.unwrap(),
+            "Incorrect Base64 data: not base64!"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_encode() {
+        let res = request()
+            .param("value", "httpbox?>")
+            .handle(encode_base64)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.read_body_utf8().await.unwrap(), "aHR0cGJveD8-");
+    }
+}
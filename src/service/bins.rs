@@ -0,0 +1,370 @@
+//! Request bins, which capture whatever is sent to them for later
+//! inspection
+use super::reflection::Reflection;
+use crate::handler::Handler;
+use crate::headers::Location;
+use crate::http::{
+    json, not_found, response, Error, Request, Result, StatusCode, Uri,
+};
+use async_trait::async_trait;
+use serde_derive::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// How long a bin lives after it is created
+pub const BIN_TTL: Duration = Duration::from_secs(60 * 60);
+/// The most bins at once, the one expiring first making way for a new one
+pub const MAX_BINS: usize = 1000;
+/// The most requests a bin keeps, the oldest making way for a new one
+pub const MAX_REQUESTS: usize = 100;
+/// The largest body captured, larger ones being refused with 413
+pub const MAX_BODY_SIZE: usize = 64 * 1024;
+/// The most bytes of requests kept across all bins, the oldest requests of
+/// the bins expiring first making way for new ones
+pub const MAX_RETAINED: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, Debug, Serialize)]
+struct Captured {
+    /// In seconds since the Unix epoch
+    received: u64,
+    #[serde(flatten)]
+    request: Reflection,
+    /// How much of `MAX_RETAINED` the request takes, as listed
+    #[serde(skip)]
+    size: usize,
+}
+
+struct Bin {
+    expires: Instant,
+    requests: VecDeque<Captured>,
+    /// The size of `requests`
+    retained: usize,
+}
+
+#[derive(Default)]
+struct Store {
+    bins: HashMap<Uuid, Bin>,
+    /// The size of the requests in all of `bins`
+    retained: usize,
+}
+
+impl Store {
+    fn remove(&mut self, id: &Uuid) {
+        if let Some(bin) = self.bins.remove(id) {
+            self.retained -= bin.retained;
+        }
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        let retained = &mut self.retained;
+        self.bins.retain(|_, bin| {
+            let live = bin.expires > now;
+            if !live {
+                *retained -= bin.retained;
+            }
+            live
+        });
+    }
+
+    /// Keeps `captured` in the bin, dropping the oldest requests of the
+    /// bin or, past `MAX_RETAINED`, of any bin to make room
+    fn push(&mut self, id: &Uuid, captured: Captured) -> Option<()> {
+        let bin = self.bins.get_mut(id)?;
+        if bin.requests.len() >= MAX_REQUESTS {
+            if let Some(oldest) = bin.requests.pop_front() {
+                bin.retained -= oldest.size;
+                self.retained -= oldest.size;
+            }
+        }
+        bin.retained += captured.size;
+        self.retained += captured.size;
+        bin.requests.push_back(captured);
+
+        while self.retained > MAX_RETAINED {
+            let bin = self
+                .bins
+                .values_mut()
+                .filter(|bin| !bin.requests.is_empty())
+                .min_by_key(|bin| bin.expires)?;
+            let oldest = bin.requests.pop_front()?;
+            bin.retained -= oldest.size;
+            self.retained -= oldest.size;
+        }
+        Some(())
+    }
+}
+
+#[derive(Serialize)]
+struct Created {
+    id: String,
+    /// Where to send requests to capture
+    url: String,
+    /// Where to list the captured requests
+    requests: String,
+    /// In seconds
+    ttl: u64,
+}
+
+#[derive(Serialize)]
+struct Requests<'a> {
+    id: String,
+    requests: &'a VecDeque<Captured>,
+}
+
+/// The bins, shared by the endpoints creating, filling and listing them
+///
+/// Created by `POST /bin`, `/bin/:id/...` captures requests in any method
+/// and `GET /bin/:id/requests` lists them, oldest first.
+#[derive(Clone, Default)]
+pub struct Bins(Arc<Mutex<Store>>);
+
+impl Bins {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn create(&self, now: Instant) -> Uuid {
+        let mut store = self.0.lock().unwrap();
+        store.remove_expired(now);
+        if store.bins.len() >= MAX_BINS {
+            let first = store
+                .bins
+                .iter()
+                .min_by_key(|(_, bin)| bin.expires)
+                .map(|(id, _)| *id);
+            if let Some(id) = first {
+                store.remove(&id);
+            }
+        }
+
+        let id = Uuid::new_v4();
+        store.bins.insert(
+            id,
+            Bin {
+                expires: now + BIN_TTL,
+                requests: VecDeque::new(),
+                retained: 0,
+            },
+        );
+        id
+    }
+
+    /// Runs `f` on the bin, if it exists and has not expired
+    fn with_bin<T>(
+        &self,
+        id: &Uuid,
+        now: Instant,
+        f: impl FnOnce(&mut Bin) -> T,
+    ) -> Option<T> {
+        let mut store = self.0.lock().unwrap();
+        match store.bins.get_mut(id) {
+            Some(bin) if bin.expires > now => Some(f(bin)),
+            Some(_) => {
+                store.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn url(req: &Request, path: &str) -> String {
+        path.parse::<Uri>()
+            .ok()
+            .and_then(|uri| req.absolute_url(&uri).ok())
+            .map_or_else(|| path.to_owned(), |url| url.to_string())
+    }
+
+    fn created(&self, req: &Request) -> Result {
+        let id = self.create(Instant::now());
+        let path = format!("/bin/{}", id);
+        let requests = format!("{}/requests", path);
+        let location = path.parse::<Uri>().map_err(Error::internal)?;
+        response()
+            .status(StatusCode::CREATED)
+            .typed_header(Location::from(location))
+            .json(&Created {
+                id: id.to_string(),
+                url: Self::url(req, &path),
+                requests: Self::url(req, &requests),
+                ttl: BIN_TTL.as_secs(),
+            })
+    }
+
+    async fn capture(&self, id: &Uuid, mut req: Request) -> Result {
+        // Not to fill the bin with requests it doesn't take
+        let now = Instant::now();
+        self.with_bin(id, now, |_| ()).ok_or_else(not_found)?;
+
+        let mut captured = Captured {
+            received: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            request: Reflection::request(&mut req).await?,
+            size: 0,
+        };
+        captured.size = serde_json::to_vec(&captured)
+            .map_err(Error::internal)?
+            .len();
+        self.0
+            .lock()
+            .unwrap()
+            .push(id, captured)
+            .ok_or_else(not_found)?;
+        response().status(StatusCode::NO_CONTENT).into()
+    }
+
+    fn requests(&self, id: &Uuid) -> Result {
+        self.with_bin(id, Instant::now(), |bin| {
+            json(&Requests {
+                id: id.to_string(),
+                requests: &bin.requests,
+            })
+        })
+        .ok_or_else(not_found)?
+    }
+}
+
+#[async_trait]
+impl Handler for Bins {
+    /// Creates a bin at `/bin`, lists `/bin/:id/requests` and captures at
+    /// anything else below the bin, which is told apart by its `rest`
+    async fn handle(&self, req: Request) -> Result {
+        let id = match req.param::<String>("id") {
+            Some(id) => id.parse::<Uuid>().map_err(|_| not_found())?,
+            None => return self.created(&req),
+        };
+        match req.param::<String>("rest") {
+            Some(_) => self.capture(&id, req).await,
+            None => self.requests(&id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::Method;
+
+    #[tokio::test]
+    async fn test_capture_and_list() {
+        let bins = Bins::new();
+        let res = request()
+            .method(Method::POST)
+            .handle(bins.clone())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let created = res.read_body_json().await.unwrap();
+        let id = created["id"].as_str().unwrap().to_owned();
+        assert_eq!(created["ttl"], 3600);
+
+        let res = request()
+            .method(Method::PUT)
+            .path(&format!("/bin/{}/hook?a=1", id))
+            .param("id", &id)
+            .param("rest", "hook")
+            .header("content-type", "application/json")
+            .body(r#"{"event":"push"}"#)
+            .handle(bins.clone())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+        let res = request()
+            .param("id", &id)
+            .handle(bins.clone())
+            .await
+            .unwrap();
+        let listed = res.read_body_json().await.unwrap();
+        let captured = &listed["requests"][0];
+        assert_eq!(captured["method"], "PUT");
+        assert_eq!(captured["args"]["a"], "1");
+        assert_eq!(captured["json"]["event"], "push");
+        assert!(captured["url"].as_str().unwrap().ends_with("/hook?a=1"));
+        assert!(captured["received"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_bin() {
+        let bins = Bins::new();
+        let id = Uuid::new_v4().to_string();
+        for rest in [None, Some("")] {
+            let mut req = request().param("id", &id);
+            if let Some(rest) = rest {
+                req = req.param("rest", rest);
+            }
+            let res = req.handle(bins.clone()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[test]
+    fn test_expiry_and_capacity() {
+        let bins = Bins::new();
+        let now = Instant::now();
+        let first = bins.create(now);
+        assert!(bins.with_bin(&first, now + BIN_TTL, |_| ()).is_none());
+
+        let ids = (0..=MAX_BINS)
+            .map(|i| bins.create(now + Duration::from_millis(i as u64)))
+            .collect::<Vec<_>>();
+        assert_eq!(bins.0.lock().unwrap().bins.len(), MAX_BINS);
+        assert!(bins.with_bin(&ids[0], now, |_| ()).is_none());
+        assert!(bins.with_bin(&ids[MAX_BINS], now, |_| ()).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_keeps_latest_requests() {
+        let bins = Bins::new();
+        let id = bins.create(Instant::now());
+        for i in 0..=MAX_REQUESTS {
+            request()
+                .path(&format!("/bin/{}?i={}", id, i))
+                .param("id", &id.to_string())
+                .param("rest", "")
+                .handle(bins.clone())
+                .await
+                .unwrap();
+        }
+
+        let requests =
+            bins.with_bin(&id, Instant::now(), |bin| bin.requests.clone());
+        let requests = requests.unwrap();
+        assert_eq!(requests.len(), MAX_REQUESTS);
+        let first = serde_json::to_value(&requests[0]).unwrap();
+        assert_eq!(first["args"]["i"], "1");
+    }
+
+    #[test]
+    fn test_retained_budget() {
+        let bins = Bins::new();
+        let now = Instant::now();
+        let older = bins.create(now);
+        let newer = bins.create(now + Duration::from_secs(1));
+        let captured = |i: usize| Captured {
+            received: i as u64,
+            request: Reflection::default(),
+            size: MAX_RETAINED / 3,
+        };
+
+        let mut store = bins.0.lock().unwrap();
+        store.push(&older, captured(0)).unwrap();
+        store.push(&older, captured(1)).unwrap();
+        store.push(&newer, captured(2)).unwrap();
+        store.push(&newer, captured(3)).unwrap();
+
+        assert_eq!(store.retained, MAX_RETAINED / 3 * 3);
+        let received = |id: &Uuid| {
+            let bin = &store.bins[id];
+            bin.requests.iter().map(|c| c.received).collect::<Vec<_>>()
+        };
+        assert_eq!(received(&older), [1]);
+        assert_eq!(received(&newer), [2, 3]);
+
+        store.remove(&newer);
+        assert_eq!(store.retained, MAX_RETAINED / 3);
+    }
+}
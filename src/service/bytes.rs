@@ -1,66 +1,273 @@
+use crate::checksum::Algorithm;
+use crate::handler::{Path, Query};
 use crate::headers::ContentLength;
-use crate::headers::ContentType;
-use crate::http::{bad_request, body_from_stream, response, Request, Result};
-use crate::random::rng;
+use crate::headers::{ContentType, Header, HeaderMapExt, ReprDigest};
+use crate::http::{
+    bad_request, body_from_stream, response, Bytes, Error, Result,
+    ThrottledStream,
+};
+use crate::random::{rng, Rng};
 use futures::prelude::*;
-use rand::Rng;
+use hyper::HeaderMap;
 use serde_derive::Deserialize;
-use std::iter::ExactSizeIterator;
+use std::sync::{Arc, Mutex};
+
+/// Large enough to not spend the time on chunk overhead, small enough to
+/// keep the memory of many concurrent downloads bounded
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+/// A digest header takes generating the bytes twice, once before the
+/// response, so it is kept to bodies quick to generate
+const MAX_DIGESTED_IN_HEADER: u64 = 64 * 1024 * 1024;
+
+/// What the bytes of a body are made of, as named by the `pattern` query
+///
+/// Anything other than `random`, `zeros` and `incrementing` is text that is
+/// repeated, so that a client can check what it got without a seed.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(from = "String")]
+pub enum Pattern {
+    #[default]
+    Random,
+    Zeros,
+    /// 0 to 255, over and over
+    Incrementing,
+    Repeat(String),
+}
+
+impl From<String> for Pattern {
+    fn from(pattern: String) -> Self {
+        match pattern.as_str() {
+            "random" => Self::Random,
+            "zeros" => Self::Zeros,
+            "incrementing" => Self::Incrementing,
+            _ => Self::Repeat(pattern),
+        }
+    }
+}
+
+/// Where the `Repr-Digest` of the bytes goes
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestIn {
+    #[default]
+    Header,
+    /// Which hyper sends over HTTP/2, or over HTTP/1.1 when the client
+    /// sends `TE: trailers`
+    Trailer,
+}
 
 #[derive(Deserialize)]
 pub struct BytesQueryParams {
     seed: Option<u32>,
     chunk_size: Option<usize>,
+    /// Bytes per second
+    rate: Option<u64>,
+    #[serde(default)]
+    pattern: Pattern,
+    digest: Option<Algorithm>,
+    #[serde(default)]
+    digest_in: DigestIn,
 }
 
-pub fn iter_bytes(
-    count: u32,
-    seed: Option<u32>,
-) -> impl ExactSizeIterator<Item = u8> {
-    let mut rng = rng(seed);
-    (0..count).map(move |_| rng.gen::<u8>())
+/// An endless supply of bytes following a `Pattern`
+pub enum ByteSource {
+    Random(Rng),
+    Zeros,
+    Incrementing(u8),
+    Repeat { text: Bytes, offset: usize },
 }
 
-pub async fn bytes(req: Request) -> Result {
-    let n = req.param::<u32>("n").ok_or_else(bad_request)?;
-    let query = req.query::<BytesQueryParams>().map_err(|_| bad_request())?;
+impl ByteSource {
+    /// The source of `pattern`, with `seed` for random bytes, or `None` for
+    /// an empty text to repeat
+    pub fn new(pattern: &Pattern, seed: Option<u32>) -> Option<Self> {
+        Some(match pattern {
+            Pattern::Random => Self::Random(rng(seed)),
+            Pattern::Zeros => Self::Zeros,
+            Pattern::Incrementing => Self::Incrementing(0),
+            Pattern::Repeat(text) if text.is_empty() => return None,
+            Pattern::Repeat(text) => Self::Repeat {
+                text: Bytes::copy_from_slice(text.as_bytes()),
+                offset: 0,
+            },
+        })
+    }
 
-    let data = iter_bytes(n, query.seed).collect::<Vec<u8>>();
+    /// The next `len` bytes, following on from the last taken
+    pub fn take(&mut self, len: usize) -> Bytes {
+        match self {
+            Self::Random(rng) => (0..len).map(|_| rng.byte()).collect(),
+            Self::Zeros => Bytes::from(vec![0; len]),
+            Self::Incrementing(next) => (0..len)
+                .map(|_| {
+                    let byte = *next;
+                    *next = next.wrapping_add(1);
+                    byte
+                })
+                .collect(),
+            Self::Repeat { text, offset } => {
+                let bytes = text.iter().cycle().skip(*offset).take(len);
+                let bytes = bytes.copied().collect();
+                *offset = (*offset + len) % text.len();
+                bytes
+            }
+        }
+    }
+}
 
-    response()
-        .typed_header(ContentType::octet_stream())
-        .body(data)
+/// The bytes of a source, taken one chunk at a time as the body is polled,
+/// so slow clients hold back generation and `n` can be arbitrarily large
+///
+/// The bytes only depend on the source, not on the chunk size.
+pub struct Chunks {
+    source: ByteSource,
+    remaining: u64,
+    chunk_size: usize,
+}
+
+impl Chunks {
+    pub fn new(source: ByteSource, count: u64, chunk_size: usize) -> Self {
+        Self {
+            source,
+            remaining: count,
+            chunk_size: chunk_size.max(1),
+        }
+    }
 }
 
-pub async fn stream_bytes(req: Request) -> Result {
-    let n = req.param::<u32>("n").ok_or_else(bad_request)?;
-    let query = req.query::<BytesQueryParams>().map_err(|_| bad_request())?;
+impl Iterator for Chunks {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Bytes> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let len = self.remaining.min(self.chunk_size as u64) as usize;
+        self.remaining -= len as u64;
+        Some(self.source.take(len))
+    }
+}
 
-    let data = iter_bytes(n, query.seed);
-    let chunk_size = query.chunk_size;
-    let content_length = data.len() as u64;
+/// The `Repr-Digest` of the `n` bytes `source` makes
+fn digest_of(source: ByteSource, n: u64, algorithm: Algorithm) -> ReprDigest {
+    let mut hasher = algorithm.hasher();
+    for chunk in Chunks::new(source, n, DEFAULT_CHUNK_SIZE) {
+        hasher.update(&chunk);
+    }
+    ReprDigest {
+        algorithm,
+        digest: hasher.finish(),
+    }
+}
 
+/// Streams `chunks` followed by their `Repr-Digest` trailer
+fn digest_trailer<S>(chunks: S, algorithm: Algorithm) -> Result
+where
+    S: Stream<Item = Bytes> + Send + 'static,
+{
+    let hasher = Arc::new(Mutex::new(algorithm.hasher()));
+    let hashed = hasher.clone();
+    let chunks = chunks.inspect(move |chunk| {
+        hashed.lock().unwrap().update(chunk);
+    });
+    let trailers = move || {
+        let hasher = hasher.lock().unwrap().clone();
+        let mut trailers = HeaderMap::new();
+        trailers.typed_insert(ReprDigest {
+            algorithm,
+            digest: hasher.finish(),
+        });
+        trailers
+    };
     response()
         .typed_header(ContentType::octet_stream())
-        .typed_header(ContentLength(content_length))
-        .body(body_from_stream(
-            stream::iter(data).chunks(chunk_size.unwrap_or(1)),
-        ))
+        .body_with_trailers_from(chunks, &[ReprDigest::name()], trailers)
+}
+
+fn body(n: u64, query: &BytesQueryParams, chunk_size: usize) -> Result {
+    // Generating the bytes again for their digest takes a seed
+    let seed = match query.digest {
+        Some(_) => query.seed.or_else(|| Some(rand::random())),
+        None => query.seed,
+    };
+    let source =
+        || ByteSource::new(&query.pattern, seed).ok_or_else(bad_request);
+    let chunks = stream::iter(Chunks::new(source()?, n, chunk_size));
+    let rate = match query.rate {
+        Some(0) => return Err(bad_request()),
+        rate => rate,
+    };
+
+    match (query.digest, query.digest_in) {
+        (Some(algorithm), DigestIn::Trailer) => match rate {
+            None => digest_trailer(chunks, algorithm),
+            Some(rate) => {
+                digest_trailer(ThrottledStream::new(chunks, rate), algorithm)
+            }
+        },
+        (digest, _) => {
+            let digest = match digest {
+                Some(_) if n > MAX_DIGESTED_IN_HEADER => {
+                    return Err(Error::bad_request(
+                        "use digest_in=trailer for bodies over 64 MiB",
+                    ))
+                }
+                Some(algorithm) => Some(digest_of(source()?, n, algorithm)),
+                None => None,
+            };
+            let body = match rate {
+                None => body_from_stream(chunks),
+                Some(rate) => {
+                    body_from_stream(ThrottledStream::new(chunks, rate))
+                }
+            };
+            let mut res = response()
+                .typed_header(ContentType::octet_stream())
+                .typed_header(ContentLength(n));
+            if let Some(digest) = digest {
+                res = res.typed_header(digest);
+            }
+            res.body(body)
+        }
+    }
+}
+
+pub async fn bytes(
+    Path(n): Path<u64>,
+    Query(query): Query<BytesQueryParams>,
+) -> Result {
+    body(n, &query, DEFAULT_CHUNK_SIZE)
+}
+
+/// Like `bytes`, but in chunks of the requested size
+pub async fn stream_bytes(
+    Path(n): Path<u64>,
+    Query(query): Query<BytesQueryParams>,
+) -> Result {
+    let chunk_size = match query.chunk_size {
+        None => DEFAULT_CHUNK_SIZE,
+        Some(size) if (1..=MAX_CHUNK_SIZE).contains(&size) => size,
+        Some(_) => return Err(bad_request()),
+    };
+    body(n, &query, chunk_size)
 }
 
 #[cfg(test)]
 mod test {
 
     use super::*;
+    use crate::handler::extract;
     use crate::test::*;
     use hyper::http::StatusCode;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_bytes() {
         let res = request()
             .param("n", "4")
             .path("/?seed=1234")
-            .handle(bytes)
+            .handle(extract(bytes))
             .await
             .unwrap();
 
@@ -73,7 +280,7 @@ mod test {
         let res = request()
             .param("n", "4")
             .path("/?seed=abc")
-            .handle(bytes)
+            .handle(extract(bytes))
             .await
             .unwrap();
 
@@ -85,7 +292,7 @@ mod test {
         let res = request()
             .param("n", "4")
             .path("/?seed=1234")
-            .handle(stream_bytes)
+            .handle(extract(stream_bytes))
             .await
             .unwrap();
 
@@ -98,7 +305,7 @@ mod test {
         let res = request()
             .param("n", "4")
             .path("/?seed=abc")
-            .handle(stream_bytes)
+            .handle(extract(stream_bytes))
             .await
             .unwrap();
 
@@ -110,7 +317,7 @@ mod test {
         let res = request()
             .param("n", "4")
             .path("/?seed=1234&chunk_size=2")
-            .handle(stream_bytes)
+            .handle(extract(stream_bytes))
             .await
             .unwrap();
 
@@ -123,7 +330,172 @@ mod test {
         let res = request()
             .param("n", "4")
             .path("/?seed=1234&chunk_size=abc")
-            .handle(stream_bytes)
+            .handle(extract(stream_bytes))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_stream_bytes_with_zero_chunk_size() {
+        let res = request()
+            .param("n", "4")
+            .path("/?chunk_size=0")
+            .handle(extract(stream_bytes))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_chunks_independent_of_size() {
+        let patterns = [
+            Pattern::Random,
+            Pattern::Incrementing,
+            Pattern::Repeat("abc".to_owned()),
+        ];
+        for pattern in &patterns {
+            let joined = |chunk_size| {
+                let source = ByteSource::new(pattern, Some(1234)).unwrap();
+                Chunks::new(source, 300, chunk_size)
+                    .flatten()
+                    .collect::<Vec<_>>()
+            };
+
+            assert_eq!(joined(1), joined(7));
+            assert_eq!(joined(7), joined(DEFAULT_CHUNK_SIZE));
+        }
+        let source = ByteSource::new(&Pattern::Random, Some(1234)).unwrap();
+        assert_eq!(
+            Chunks::new(source, 100, 7)
+                .map(|chunk| chunk.len())
+                .collect::<Vec<_>>(),
+            [[7; 14].as_slice(), &[2]].concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bytes_with_pattern() {
+        let body = |path| async move {
+            let res = request()
+                .param("n", "5")
+                .path(path)
+                .handle(extract(stream_bytes))
+                .await
+                .unwrap();
+            res.read_body().await.unwrap()
+        };
+
+        assert_eq!(body("/?pattern=zeros").await, [0; 5].as_slice());
+        assert_eq!(
+            body("/?pattern=incrementing").await,
+            [0, 1, 2, 3, 4].as_slice()
+        );
+        assert_eq!(body("/?pattern=ab&chunk_size=2").await, b"ababa");
+        assert_eq!(
+            body("/?seed=1234&pattern=random").await[..4],
+            [214, 212, 32, 32]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bytes_with_empty_pattern() {
+        let res = request()
+            .param("n", "4")
+            .path("/?pattern=")
+            .handle(extract(bytes))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_bytes_with_digest() {
+        let res = request()
+            .param("n", "9")
+            .path("/?pattern=123456789&digest=crc32c")
+            .handle(extract(bytes))
+            .await
+            .unwrap();
+
+        assert_eq!(res.headers()["repr-digest"], "crc32c=:4waSgw==:");
+        assert_eq!(res.read_body().await.unwrap(), b"123456789");
+    }
+
+    #[tokio::test]
+    async fn test_random_bytes_with_digest() {
+        let res = request()
+            .param("n", "100")
+            .path("/?digest=sha256")
+            .handle(extract(bytes))
+            .await
+            .unwrap();
+        let digest = res.headers().typed_get::<ReprDigest>().unwrap();
+
+        let mut hasher = Algorithm::Sha256.hasher();
+        hasher.update(&res.read_body().await.unwrap());
+        assert_eq!(digest.digest, hasher.finish());
+    }
+
+    #[tokio::test]
+    async fn test_stream_bytes_with_digest_trailer() {
+        let res = request()
+            .param("n", "9")
+            .path("/?pattern=123456789&digest=crc32c&digest_in=trailer&chunk_size=2")
+            .handle(extract(stream_bytes))
+            .await
+            .unwrap();
+
+        assert_eq!(res.headers()["trailer"], "repr-digest");
+        let mut body = res.into_body();
+        let mut data = vec![];
+        while let Some(chunk) = body.data().await {
+            data.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(data, b"123456789");
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["repr-digest"], "crc32c=:4waSgw==:");
+    }
+
+    #[tokio::test]
+    async fn test_bytes_lazy() {
+        let n = 10 * 1024 * 1024 * 1024_u64;
+        let res = request()
+            .param("n", &n.to_string())
+            .path("/?chunk_size=16")
+            .handle(extract(stream_bytes))
+            .await
+            .unwrap();
+
+        assert_eq!(res.headers()["content-length"], n.to_string().as_str());
+        let mut body = res.into_body();
+        assert_eq!(body.next().await.unwrap().unwrap().len(), 16);
+    }
+
+    #[tokio::test]
+    async fn test_bytes_with_rate() {
+        let start = std::time::Instant::now();
+        let res = request()
+            .param("n", "30")
+            .path("/?seed=1234&rate=100")
+            .handle(extract(bytes))
+            .await
+            .unwrap();
+        let body = res.read_body().await.unwrap();
+
+        assert_eq!(body[..4], [214, 212, 32, 32]);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_bytes_with_zero_rate() {
+        let res = request()
+            .param("n", "4")
+            .path("/?rate=0")
+            .handle(extract(bytes))
             .await
             .unwrap();
 
@@ -1,20 +1,195 @@
-use crate::headers::{CacheControl, IfModifiedSince, IfNoneMatch};
-use crate::http::{bad_request, response, Request, Result, StatusCode};
-use std::time::Duration;
+use crate::handler::Path;
+use crate::headers::{
+    evaluate_preconditions, vary_on, CacheControl, ETag, IfModifiedSince,
+    IfNoneMatch, LastModified, Precondition, StaleCacheControl,
+    SurrogateControl,
+};
+use crate::hex::hex;
+use crate::http::{bad_request, response, Error, Request, Result, StatusCode};
+use crate::service::reflection::Reflection;
+use hyper::header::HeaderName;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+/// Counts the responses generated by the origin, so a cache in front of it
+/// can be told to have answered from storage when the count stays put
+static GENERATED: AtomicU64 = AtomicU64::new(0);
+
+fn generated() -> u64 {
+    GENERATED.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Any validator is taken to match, otherwise a fresh one is handed out
 pub async fn cache(req: Request) -> Result {
-    let mut res = response();
     if req.typed_header::<IfModifiedSince>().is_some()
         || req.typed_header::<IfNoneMatch>().is_some()
     {
-        res = res.status(StatusCode::NOT_MODIFIED);
+        return response().status(StatusCode::NOT_MODIFIED).into();
+    }
+
+    let etag = format!("\"{:032x}\"", rand::thread_rng().gen::<u128>())
+        .parse::<ETag>()
+        .unwrap();
+    response()
+        .typed_header(LastModified::from(SystemTime::now()))
+        .typed_header(etag)
+        .into()
+}
+
+/// Treats the path as the current ETag of the resource
+pub async fn etag(req: Request) -> Result {
+    let etag = req
+        .param::<String>("etag")
+        .and_then(|etag| format!("\"{}\"", etag).parse::<ETag>().ok())
+        .ok_or_else(bad_request)?;
+
+    match evaluate_preconditions(req.headers(), req.method(), Some(&etag), None)
+    {
+        Precondition::Passed => {
+            let reflection = Reflection::new()
+                .args(&req)?
+                .headers(&req)
+                .origin(&req)
+                .url(&req);
+            response().typed_header(etag).json(&reflection)
+        }
+        Precondition::NotModified => response()
+            .status(StatusCode::NOT_MODIFIED)
+            .typed_header(etag)
+            .into(),
+        Precondition::Failed => {
+            response().status(StatusCode::PRECONDITION_FAILED).into()
+        }
     }
-    res.into()
 }
 
-pub async fn set_cache(req: Request) -> Result {
-    let n = req.param::<u64>("n").ok_or_else(bad_request)?;
+#[derive(Deserialize)]
+pub struct VaryQueryParams {
+    /// Comma-separated request header names, or `*`
+    vary: Option<String>,
+    max_age: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Variant {
+    /// The value of each header varied on, as the origin saw it
+    vary: BTreeMap<String, Option<String>>,
+    generated: u64,
+}
+
+/// Varies on the given request headers, `Accept-Encoding` by default, with
+/// an ETag telling the variants apart
+///
+/// `Vary: *` makes every response unusable for any later request.
+pub async fn vary(req: Request) -> Result {
+    let query = req.query::<VaryQueryParams>().map_err(|_| bad_request())?;
+    let vary = query.vary.as_deref().unwrap_or("accept-encoding");
+    let names = match vary.trim() {
+        "*" => vec![],
+        vary => vary
+            .split(',')
+            .map(|name| name.trim().parse::<HeaderName>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| Error::bad_request("invalid header name in vary"))?,
+    };
+    let values = names
+        .iter()
+        .map(|name| {
+            let value = req
+                .headers()
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok());
+            let value = value.collect::<Vec<_>>();
+            (
+                name.to_string(),
+                (!value.is_empty()).then(|| value.join(", ")),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let mut hash = Sha256::new();
+    for (name, value) in &values {
+        hash.update(format!("{}:{:?}\n", name, value));
+    }
+    let hash = hash.finalize();
+    let etag = format!("\"{}\"", hex(&hash[..16]))
+        .parse::<ETag>()
+        .map_err(Error::internal)?;
+    let cache_control = CacheControl::new()
+        .with_max_age(Duration::from_secs(query.max_age.unwrap_or(60)));
+
+    let res = response()
+        .typed_header(vary_on(&names))
+        .typed_header(cache_control)
+        .typed_header(etag.clone());
+    match evaluate_preconditions(req.headers(), req.method(), Some(&etag), None)
+    {
+        Precondition::Passed => res.json(&Variant {
+            vary: values,
+            generated: generated(),
+        }),
+        Precondition::NotModified => {
+            res.status(StatusCode::NOT_MODIFIED).into()
+        }
+        Precondition::Failed => {
+            response().status(StatusCode::PRECONDITION_FAILED).into()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StaleQueryParams {
+    max_age: Option<u64>,
+    stale_while_revalidate: Option<u64>,
+    stale_if_error: Option<u64>,
+    /// For shared caches honoring Surrogate-Control, which CDNs strip
+    surrogate_max_age: Option<u64>,
+    /// To have the origin fail, and a cache fall back on what it stored
+    status: Option<u16>,
+}
+
+#[derive(Serialize)]
+struct Stale {
+    /// In milliseconds since the Unix epoch
+    time: u128,
+    generated: u64,
+}
+
+/// Fresh for `max_age` seconds, then usable while revalidating for
+/// `stale_while_revalidate` and in place of errors for `stale_if_error`
+/// more, per RFC 5861
+pub async fn stale(req: Request) -> Result {
+    let query = req.query::<StaleQueryParams>().map_err(|_| bad_request())?;
+    let status = StatusCode::from_u16(query.status.unwrap_or(200))
+        .map_err(|_| bad_request())?;
+    let cache_control = StaleCacheControl {
+        max_age: Duration::from_secs(query.max_age.unwrap_or(10)),
+        stale_while_revalidate: Duration::from_secs(
+            query.stale_while_revalidate.unwrap_or(30),
+        ),
+        stale_if_error: Duration::from_secs(query.stale_if_error.unwrap_or(60)),
+    };
+
+    let mut res = response().status(status).typed_header(cache_control);
+    if let Some(max_age) = query.surrogate_max_age {
+        res = res.typed_header(SurrogateControl {
+            max_age: Duration::from_secs(max_age),
+        });
+    }
+    res.json(&Stale {
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis()),
+        generated: generated(),
+    })
+}
 
+pub async fn set_cache(Path(n): Path<u64>) -> Result {
     response()
         .typed_header(CacheControl::new().with_max_age(Duration::from_secs(n)))
         .into()
@@ -23,6 +198,7 @@ pub async fn set_cache(req: Request) -> Result {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::handler::extract;
     use crate::headers::HeaderMapExt;
     use crate::test::*;
     use hyper::http::StatusCode;
@@ -35,6 +211,14 @@ mod test {
         assert_eq!(res.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_cache_validators() {
+        let res = request().handle(cache).await.unwrap();
+
+        assert!(res.headers().typed_get::<LastModified>().is_some());
+        assert!(res.headers().typed_get::<ETag>().is_some());
+    }
+
     #[tokio::test]
     async fn test_cache_if_modified_since() {
         let header: IfModifiedSince = SystemTime::now().into();
@@ -54,9 +238,121 @@ mod test {
         assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
     }
 
+    #[tokio::test]
+    async fn test_etag() {
+        let res = request().param("etag", "abc").handle(etag).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("etag").unwrap(), "\"abc\"");
+    }
+
+    #[tokio::test]
+    async fn test_etag_if_none_match() {
+        let res = request()
+            .param("etag", "abc")
+            .header("if-none-match", "\"abc\"")
+            .handle(etag)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers().get("etag").unwrap(), "\"abc\"");
+    }
+
+    #[tokio::test]
+    async fn test_etag_if_match() {
+        let res = request()
+            .param("etag", "abc")
+            .header("if-match", "\"xyz\"")
+            .handle(etag)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+    }
+
+    #[tokio::test]
+    async fn test_etag_invalid() {
+        let res = request().param("etag", "a\"b").handle(etag).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_vary() {
+        let res = request()
+            .path("/cache/vary?vary=Accept-Language,X-Variant")
+            .header("accept-language", "de")
+            .handle(vary)
+            .await
+            .unwrap();
+
+        assert_eq!(res.headers()["vary"], "accept-language, x-variant");
+        assert_eq!(res.headers()["cache-control"], "max-age=60");
+        let etag = res.headers()["etag"].clone();
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(body["vary"]["accept-language"], "de");
+        assert!(body["vary"]["x-variant"].is_null());
+
+        let other = request()
+            .path("/cache/vary?vary=Accept-Language,X-Variant")
+            .header("accept-language", "fr")
+            .handle(vary)
+            .await
+            .unwrap();
+        assert_ne!(other.headers()["etag"], etag);
+
+        let revalidated = request()
+            .path("/cache/vary?vary=Accept-Language,X-Variant")
+            .header("accept-language", "de")
+            .header("if-none-match", etag.clone())
+            .handle(vary)
+            .await
+            .unwrap();
+        assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(revalidated.headers()["vary"], "accept-language, x-variant");
+    }
+
+    #[tokio::test]
+    async fn test_vary_any() {
+        let res = request()
+            .path("/cache/vary?vary=*")
+            .handle(vary)
+            .await
+            .unwrap();
+        assert_eq!(res.headers()["vary"], "*");
+
+        let res = request()
+            .path("/cache/vary?vary=a%20b")
+            .handle(vary)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_stale() {
+        let res = request()
+            .path("/cache/stale?max_age=1&status=503&surrogate_max_age=300")
+            .handle(stale)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            res.headers()["cache-control"],
+            "max-age=1, stale-while-revalidate=30, stale-if-error=60"
+        );
+        assert_eq!(res.headers()["surrogate-control"], "max-age=300");
+    }
+
     #[tokio::test]
     async fn test_set_cache() {
-        let res = request().param("n", "30").handle(set_cache).await.unwrap();
+        let res = request()
+            .param("n", "30")
+            .handle(extract(set_cache))
+            .await
+            .unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
         assert_eq!(
@@ -0,0 +1,414 @@
+//! Webhook callbacks, fired at a registered URL after a delay so that
+//! receivers can be tested against them
+use crate::client;
+use crate::handler::Handler;
+use crate::headers::Location;
+use crate::hex::hex;
+use crate::http::{
+    json, not_found, response, Body, Bytes, Error, Request, Result, StatusCode,
+    Uri,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use hyper::header::{self, HeaderName, HeaderValue};
+use hyper::{Method, Request as HTTPRequest};
+use ring::hmac;
+use serde_derive::{Deserialize, Serialize};
+use std::cmp::min;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+use uuid::Uuid;
+
+/// The most outcomes kept, the oldest making way for a new one
+const MAX_CALLBACKS: usize = 1000;
+/// How long a receiver gets to answer a callback
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(10);
+pub const MAX_BODY_SIZE: usize = 64 * 1024;
+const DEFAULT_SIGNATURE_HEADER: &str = "x-httpbox-signature";
+
+#[derive(Deserialize)]
+struct CallbackRequest {
+    url: String,
+    method: Option<String>,
+    body: Option<String>,
+    content_type: Option<String>,
+    /// In seconds, up to the maximum delay
+    delay: Option<f64>,
+    /// Signs the body with HMAC-SHA256, if given
+    secret: Option<String>,
+    signature_header: Option<String>,
+}
+
+#[derive(Debug)]
+struct Callback {
+    url: Url,
+    method: Method,
+    body: Bytes,
+    content_type: HeaderValue,
+    delay: Duration,
+    signature: Option<(HeaderName, HeaderValue)>,
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> hmac::Tag {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), message)
+}
+
+impl Callback {
+    fn parse(
+        req: CallbackRequest,
+        max_delay: Duration,
+    ) -> std::result::Result<Self, Error> {
+        let url = Url::parse(&req.url)
+            .ok()
+            .filter(|url| {
+                matches!(url.scheme(), "http" | "https") && url.has_host()
+            })
+            .ok_or_else(|| Error::bad_request("url has to be http or https"))?;
+        let method = match req.method {
+            Some(method) => method
+                .parse()
+                .map_err(|_| Error::bad_request("invalid method"))?,
+            None => Method::POST,
+        };
+        let content_type = req
+            .content_type
+            .as_deref()
+            .unwrap_or("application/json")
+            .parse()
+            .map_err(|_| Error::bad_request("invalid content_type"))?;
+        let delay = Duration::try_from_secs_f64(req.delay.unwrap_or(0.0))
+            .map_err(|_| Error::bad_request("invalid delay"))?;
+        let body = Bytes::from(req.body.unwrap_or_default());
+
+        let signature = match req.secret {
+            Some(secret) => {
+                let name = req
+                    .signature_header
+                    .as_deref()
+                    .unwrap_or(DEFAULT_SIGNATURE_HEADER)
+                    .parse()
+                    .map_err(|_| {
+                        Error::bad_request("invalid signature_header")
+                    })?;
+                let mac = hmac_sha256(secret.as_bytes(), &body);
+                let value = format!("sha256={}", hex(mac.as_ref()))
+                    .parse()
+                    .map_err(Error::internal)?;
+                Some((name, value))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            url,
+            method,
+            body,
+            content_type,
+            delay: min(delay, max_delay),
+            signature,
+        })
+    }
+
+    fn request(&self) -> anyhow::Result<HTTPRequest<Body>> {
+        let mut req = client::request(&self.url)?
+            .method(self.method.clone())
+            .header(header::CONTENT_TYPE, self.content_type.clone())
+            .header(header::CONTENT_LENGTH, self.body.len());
+        if let Some((name, value)) = &self.signature {
+            req = req.header(name, value);
+        }
+        Ok(req.body(Body::from(self.body.clone()))?)
+    }
+
+    async fn fire(&self, private: bool) -> anyhow::Result<StatusCode> {
+        let res = client::send(&self.url, self.request()?, private).await?;
+        Ok(res.status())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+enum Outcome {
+    Pending,
+    Delivered { status: u16 },
+    Failed { error: String },
+}
+
+#[derive(Default)]
+struct Outcomes {
+    outcomes: HashMap<Uuid, Outcome>,
+    order: VecDeque<Uuid>,
+}
+
+#[derive(Serialize)]
+struct Registered {
+    id: String,
+    /// Where to follow how the callback went
+    status: String,
+    /// In seconds
+    delay: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Status<'a> {
+    id: String,
+    #[serde(flatten)]
+    outcome: &'a Outcome,
+}
+
+/// Registers callbacks at `POST /callback` and tells how each went at
+/// `GET /callback/:id`
+#[derive(Clone, Default)]
+pub struct Callbacks(Arc<Mutex<Outcomes>>);
+
+impl Callbacks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&self, id: Uuid, outcome: Outcome) {
+        let mut outcomes = self.0.lock().unwrap();
+        if outcomes.outcomes.insert(id, outcome).is_none() {
+            outcomes.order.push_back(id);
+            if outcomes.order.len() > MAX_CALLBACKS {
+                if let Some(oldest) = outcomes.order.pop_front() {
+                    outcomes.outcomes.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    async fn register(&self, mut req: Request) -> Result {
+        let callback =
+            Callback::parse(req.json().await?, req.config().max_delay())?;
+        let private = req.config().outbound_private();
+        let id = Uuid::new_v4();
+        self.record(id, Outcome::Pending);
+
+        let path = format!("/callback/{}", id);
+        let status = path
+            .parse::<Uri>()
+            .ok()
+            .and_then(|uri| req.absolute_url(&uri).ok())
+            .map_or_else(|| path.clone(), |url| url.to_string());
+        let registered = Registered {
+            id: id.to_string(),
+            status,
+            delay: callback.delay.as_secs_f64(),
+            signature: callback
+                .signature
+                .as_ref()
+                .and_then(|(_, value)| value.to_str().ok())
+                .map(str::to_owned),
+        };
+
+        let callbacks = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(callback.delay).await;
+            let outcome =
+                tokio::time::timeout(CALLBACK_TIMEOUT, callback.fire(private))
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow!("timed out")));
+            let outcome = match outcome {
+                Ok(status) => Outcome::Delivered {
+                    status: status.as_u16(),
+                },
+                Err(e) => {
+                    tracing::debug!(
+                        "Callback to {} failed: {}",
+                        callback.url,
+                        e
+                    );
+                    Outcome::Failed {
+                        error: e.to_string(),
+                    }
+                }
+            };
+            callbacks.record(id, outcome);
+        });
+
+        let location = path.parse::<Uri>().map_err(Error::internal)?;
+        response()
+            .status(StatusCode::ACCEPTED)
+            .typed_header(Location::from(location))
+            .json(&registered)
+    }
+
+    fn status(&self, id: &Uuid) -> Result {
+        let outcomes = self.0.lock().unwrap();
+        let outcome = outcomes.outcomes.get(id).ok_or_else(not_found)?;
+        json(&Status {
+            id: id.to_string(),
+            outcome,
+        })
+    }
+}
+
+#[async_trait]
+impl Handler for Callbacks {
+    async fn handle(&self, req: Request) -> Result {
+        match req.param::<String>("id") {
+            Some(id) => {
+                self.status(&id.parse::<Uuid>().map_err(|_| not_found())?)
+            }
+            None => self.register(req).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+    use crate::test::*;
+    use hyper::body::Incoming;
+    use hyper::service::service_fn;
+    use serde_json::Value;
+    use std::convert::Infallible;
+    use tokio::sync::mpsc;
+
+    fn callback(json: &str) -> std::result::Result<Callback, Error> {
+        Callback::parse(
+            serde_json::from_str(json).unwrap(),
+            Duration::from_secs(10),
+        )
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            hex(mac.as_ref()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let parsed = callback(
+            r#"{"url": "https://example.com/hook?a=1", "method": "PUT",
+                "body": "{}", "delay": 60, "secret": "s",
+                "signature_header": "x-sig"}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.method, Method::PUT);
+        assert_eq!(parsed.delay, Duration::from_secs(10));
+        let (name, value) = parsed.signature.unwrap();
+        assert_eq!(name, "x-sig");
+        assert!(value.to_str().unwrap().starts_with("sha256="));
+
+        assert!(callback(r#"{"url": "ftp://example.com/"}"#).is_err());
+        assert!(callback(r#"{"url": "/relative"}"#).is_err());
+        assert!(callback(r#"{"url": "http://a/", "method": "A B"}"#).is_err());
+    }
+
+    async fn status(callbacks: &Callbacks, id: &str) -> Value {
+        let res = request()
+            .param("id", id)
+            .handle(callbacks.clone())
+            .await
+            .unwrap();
+        res.read_body_json().await.unwrap()
+    }
+
+    async fn register(
+        callbacks: &Callbacks,
+        config: Config,
+        body: String,
+    ) -> Value {
+        let res = request()
+            .method(Method::POST)
+            .header("content-type", "application/json")
+            .extension(Arc::new(config))
+            .body(body)
+            .handle(callbacks.clone())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        res.read_body_json().await.unwrap()
+    }
+
+    async fn settled(callbacks: &Callbacks, id: &str) -> Value {
+        loop {
+            let status = status(callbacks, id).await;
+            if status["state"] != "pending" {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fires_signed_callback() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let addr = serve(service_fn(move |req: HTTPRequest<Incoming>| {
+            let tx = tx.clone();
+            async move {
+                let (parts, body) = req.into_parts();
+                let body = crate::http::to_bytes(body).await.unwrap();
+                tx.send((parts, body)).unwrap();
+                let mut res = hyper::Response::new(Body::empty());
+                *res.status_mut() = StatusCode::CREATED;
+                Ok::<_, Infallible>(res)
+            }
+        }));
+
+        let callbacks = Callbacks::new();
+        let config = Config {
+            outbound_private: Some(true),
+            ..Config::default()
+        };
+        let body = format!(
+            r#"{{"url": "http://{}/hook?a=1", "body": "{{}}", "secret": "s"}}"#,
+            addr
+        );
+        let registered = register(&callbacks, config, body).await;
+        let id = registered["id"].as_str().unwrap();
+
+        let (parts, body) = rx.recv().await.unwrap();
+        assert_eq!(parts.method, Method::POST);
+        assert_eq!(parts.uri, "/hook?a=1");
+        assert_eq!(parts.headers["content-type"], "application/json");
+        assert_eq!(
+            parts.headers[DEFAULT_SIGNATURE_HEADER],
+            format!("sha256={}", hex(hmac_sha256(b"s", b"{}").as_ref()))
+        );
+        assert_eq!(
+            parts.headers[DEFAULT_SIGNATURE_HEADER],
+            registered["signature"].as_str().unwrap()
+        );
+        assert_eq!(body, "{}");
+
+        let status = settled(&callbacks, id).await;
+        assert_eq!(status["state"], "delivered");
+        assert_eq!(status["status"], 201);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_private_address() {
+        let callbacks = Callbacks::new();
+        let body = r#"{"url": "http://127.0.0.1:1/"}"#.to_owned();
+        let registered = register(&callbacks, Config::default(), body).await;
+
+        let status =
+            settled(&callbacks, registered["id"].as_str().unwrap()).await;
+        assert_eq!(status["state"], "failed");
+        assert!(status["error"].as_str().unwrap().contains("not a public"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_callback() {
+        let res = request()
+            .param("id", &Uuid::new_v4().to_string())
+            .handle(Callbacks::new())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
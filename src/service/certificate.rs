@@ -0,0 +1,83 @@
+//! The client certificate of a TLS connection, for testing how clients
+//! present theirs
+use crate::hex::hex;
+use crate::http::{json, Error, Request, Result};
+use crate::x509;
+use serde_derive::Serialize;
+use sha2::{Digest, Sha256};
+
+#[derive(Serialize)]
+struct Certificate {
+    subject: String,
+    issuer: String,
+    serial: String,
+    not_before: String,
+    not_after: String,
+    /// Of the DER, in hex
+    sha256: String,
+}
+
+/// Describes the certificate the client presented, which it is only asked
+/// for with CAs for client certificates configured
+pub async fn cert(req: Request) -> Result {
+    let der = req
+        .connection_info()
+        .and_then(|info| info.tls.as_ref())
+        .and_then(|tls| tls.client_certificate.as_ref())
+        .ok_or_else(|| Error::bad_request("No client certificate presented"))?;
+    let cert = x509::parse(der)
+        .ok_or_else(|| Error::bad_request("Unreadable client certificate"))?;
+    let sha256 = hex(&Sha256::digest(der));
+    json(&Certificate {
+        subject: cert.subject,
+        issuer: cert.issuer,
+        serial: cert.serial,
+        not_before: cert.not_before,
+        not_after: cert.not_after,
+        sha256,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{Bytes, ConnectionInfo, StatusCode, Tls};
+    use crate::test::*;
+    use rustls_pemfile::Item;
+    use std::io::BufReader;
+
+    fn client_certificate() -> Bytes {
+        let pem = include_bytes!("../server/testdata/client.pem");
+        match rustls_pemfile::read_one(&mut BufReader::new(&pem[..])) {
+            Ok(Some(Item::X509Certificate(der))) => der.into(),
+            _ => panic!("not a certificate"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cert() {
+        let mut info = ConnectionInfo::new();
+        info.tls = Some(Tls {
+            client_certificate: Some(client_certificate()),
+            ..Tls::default()
+        });
+        let res = request().extension(info).handle(cert).await.unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["subject"], "C=DE, O=httpbox, CN=client");
+        assert_eq!(body["issuer"], "O=httpbox, CN=httpbox test CA");
+        assert_eq!(body["serial"], "1234");
+        assert_eq!(body["not_after"], "2126-09-20T16:06:38Z");
+        assert_eq!(
+            body["sha256"],
+            "0eb69d6325e13135e6008be2ea15565031874a8fd95d6520a246f8688d70d834"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cert_missing() {
+        let res = request().handle(cert).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
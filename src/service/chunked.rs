@@ -0,0 +1,107 @@
+use crate::handler::Query;
+use crate::headers::ContentType;
+use crate::http::{bad_request, response, Bytes, Result};
+use futures::prelude::*;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::HeaderMap;
+
+const DEFAULT_CHUNKS: [usize; 3] = [16, 16, 16];
+const MAX_CHUNKS: usize = 100;
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunk sizes such as `16,1,1024`
+fn parse_chunks(chunks: &str) -> Option<Vec<usize>> {
+    let sizes = chunks
+        .split(',')
+        .map(|size| size.trim().parse().ok())
+        .collect::<Option<Vec<usize>>>()?;
+    let valid = sizes.len() <= MAX_CHUNKS
+        && sizes.iter().all(|size| (1..=MAX_CHUNK_SIZE).contains(size));
+    valid.then_some(sizes)
+}
+
+/// A trailer such as `x-checksum:abc`
+fn parse_trailer(trailer: &str) -> Option<(HeaderName, HeaderValue)> {
+    let (name, value) = trailer.split_once(':')?;
+    Some((name.trim().parse().ok()?, value.trim().parse().ok()?))
+}
+
+/// The i-th chunk repeats the i-th letter of the alphabet, so clients can
+/// tell where each chunk starts
+fn chunk(i: usize, size: usize) -> Bytes {
+    vec![b'a' + (i % 26) as u8; size].into()
+}
+
+/// Sends chunks of exactly the given sizes, followed by any trailers
+pub async fn chunked(Query(query): Query<Vec<(String, String)>>) -> Result {
+    let mut sizes = DEFAULT_CHUNKS.to_vec();
+    let mut trailers = HeaderMap::new();
+    for (key, value) in &query {
+        match key.as_str() {
+            "chunks" => sizes = parse_chunks(value).ok_or_else(bad_request)?,
+            "trailer" => {
+                let (name, value) =
+                    parse_trailer(value).ok_or_else(bad_request)?;
+                trailers.append(name, value);
+            }
+            _ => {}
+        }
+    }
+
+    let chunks = stream::iter(sizes.into_iter().enumerate())
+        .map(|(i, size)| chunk(i, size));
+    response()
+        .typed_header(ContentType::octet_stream())
+        .body_with_trailers(chunks, trailers)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::handler::extract;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+
+    #[test]
+    fn test_parse_chunks() {
+        assert_eq!(parse_chunks("1, 2,3"), Some(vec![1, 2, 3]));
+        assert_eq!(parse_chunks("1,0"), None);
+        assert_eq!(parse_chunks("1,,2"), None);
+        assert_eq!(parse_chunks(&"1,".repeat(MAX_CHUNKS + 1)), None);
+    }
+
+    #[tokio::test]
+    async fn test_chunked() {
+        let res = request()
+            .path("/?chunks=2,1,3&trailer=x-checksum:abc&trailer=x-n:3")
+            .handle(extract(chunked))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get("content-length").is_none());
+        assert_eq!(res.headers()["trailer"], "x-checksum, x-n");
+
+        let mut body = res.into_body();
+        let mut chunks = vec![];
+        while let Some(chunk) = body.data().await {
+            chunks.push(chunk.unwrap());
+        }
+        assert_eq!(chunks, ["aa", "b", "ccc"]);
+
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["x-checksum"], "abc");
+        assert_eq!(trailers["x-n"], "3");
+    }
+
+    #[tokio::test]
+    async fn test_chunked_bad_trailer() {
+        let res = request()
+            .path("/?trailer=no-colon")
+            .handle(extract(chunked))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
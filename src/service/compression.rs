@@ -0,0 +1,68 @@
+use crate::http::compression::{compress, Encoding};
+use crate::http::{Request, Result};
+use crate::service::method::echo;
+
+async fn compressed(req: Request, encoding: Encoding) -> Result {
+    let res = echo(req).await?;
+    Ok(compress(res, encoding))
+}
+
+pub async fn gzip(req: Request) -> Result {
+    compressed(req, Encoding::Gzip).await
+}
+
+pub async fn deflate(req: Request) -> Result {
+    compressed(req, Encoding::Deflate).await
+}
+
+pub async fn brotli(req: Request) -> Result {
+    compressed(req, Encoding::Brotli).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::compression::test::decompress;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_gzip() {
+        let res = request().path("/?key=val").handle(gzip).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-encoding").unwrap(), "gzip");
+        let body = res.read_body().await.unwrap();
+        let body: Value =
+            serde_json::from_slice(&decompress(&body, Encoding::Gzip).await)
+                .unwrap();
+        assert_eq!(body["args"], json!({"key": "val"}));
+    }
+
+    #[tokio::test]
+    async fn test_deflate() {
+        let res = request().path("/?key=val").handle(deflate).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-encoding").unwrap(), "deflate");
+        let body = res.read_body().await.unwrap();
+        let body: Value =
+            serde_json::from_slice(&decompress(&body, Encoding::Deflate).await)
+                .unwrap();
+        assert_eq!(body["args"], json!({"key": "val"}));
+    }
+
+    #[tokio::test]
+    async fn test_brotli() {
+        let res = request().path("/?key=val").handle(brotli).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-encoding").unwrap(), "br");
+        let body = res.read_body().await.unwrap();
+        let body: Value =
+            serde_json::from_slice(&decompress(&body, Encoding::Brotli).await)
+                .unwrap();
+        assert_eq!(body["args"], json!({"key": "val"}));
+    }
+}
@@ -0,0 +1,198 @@
+//! What is known about the connection of a request, and responses
+//! deliberately carrying hop-by-hop headers, for testing how intermediaries
+//! handle them
+use crate::headers::{Connection, KeepAlive};
+use crate::http::{bad_request, json, response, Request, Result, StatusCode};
+use hyper::header::HeaderName;
+use hyper::Version;
+use serde_derive::{Deserialize, Serialize};
+use std::iter;
+use std::time::Duration;
+
+/// Connection headers only exist up to HTTP/1.1, HTTP/2 and HTTP/3 refusing
+/// them
+fn http1(req: &Request) -> Option<Result> {
+    match req.version() {
+        Version::HTTP_10 | Version::HTTP_11 => None,
+        _ => Some(
+            response()
+                .status(StatusCode::HTTP_VERSION_NOT_SUPPORTED)
+                .body("Only served over HTTP/1.x"),
+        ),
+    }
+}
+
+#[derive(Serialize)]
+struct Tls<'a> {
+    version: Option<&'static str>,
+    cipher: Option<&'static str>,
+    alpn: Option<&'a str>,
+    server_name: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Info<'a> {
+    /// The number of the connection since the server started
+    id: Option<u64>,
+    /// The number of the request on the connection, from 1
+    request: Option<u64>,
+    reused: bool,
+    version: String,
+    /// The address, or the process over a Unix socket
+    peer: Option<String>,
+    local: Option<String>,
+    tls: Option<Tls<'a>>,
+}
+
+/// Reports the connection the request came in on, over any version
+pub async fn info(req: Request) -> Result {
+    let info = req.connection_info();
+    let peer = info.and_then(|info| match (info.peer_addr, info.credentials) {
+        (Some(addr), _) => Some(addr.to_string()),
+        (None, credentials) => credentials.map(|c| c.to_string()),
+    });
+    json(&Info {
+        id: info.map(|info| info.id),
+        request: info.map(|info| info.request),
+        reused: info.is_some_and(|info| info.request > 1),
+        version: format!("{:?}", req.version()),
+        peer,
+        local: info
+            .and_then(|info| info.local_addr)
+            .map(|addr| addr.to_string()),
+        tls: info.and_then(|info| info.tls.as_ref()).map(|tls| Tls {
+            version: tls.version,
+            cipher: tls.cipher,
+            alpn: tls.alpn.as_deref(),
+            server_name: tls.server_name.as_deref(),
+        }),
+    })
+}
+
+/// hyper sees the header and closes the connection after the response
+pub async fn close(req: Request) -> Result {
+    if let Some(res) = http1(&req) {
+        return res;
+    }
+    response()
+        .typed_header(Connection::close())
+        .body("The connection closes after this response\n")
+}
+
+#[derive(Deserialize)]
+pub struct KeepAliveQueryParams {
+    /// In seconds
+    timeout: Option<u64>,
+    max: Option<u64>,
+}
+
+/// Announces `timeout` and `max` and holds the connection to them, closing
+/// it once idle for `timeout` seconds or after `max` more requests
+pub async fn keep_alive(req: Request) -> Result {
+    if let Some(res) = http1(&req) {
+        return res;
+    }
+    let query = req
+        .query::<KeepAliveQueryParams>()
+        .map_err(|_| bad_request())?;
+    let keep_alive = KeepAlive {
+        timeout: Duration::from_secs(query.timeout.unwrap_or(5)),
+        max: Some(query.max.unwrap_or(100)),
+    };
+    if keep_alive.max == Some(0) {
+        return close(req).await;
+    }
+    req.keep_alive(keep_alive.timeout, keep_alive.max);
+    response()
+        .typed_header(Connection::keep_alive())
+        .typed_header(keep_alive)
+        .body("The connection stays open after this response\n")
+}
+
+/// `X-Hop` is only meant for the next hop, so a client behind a proxy
+/// should never see it
+pub async fn hop_by_hop(req: Request) -> Result {
+    if let Some(res) = http1(&req) {
+        return res;
+    }
+    let x_hop = HeaderName::from_static("x-hop");
+    response()
+        .typed_header(iter::once(x_hop.clone()).collect::<Connection>())
+        .header(x_hop, "dropped by intermediaries")
+        .body("A proxy should have dropped X-Hop from this response\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{ConnectionInfo, Lifecycle};
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_info() {
+        let mut connection = ConnectionInfo::new();
+        connection.peer_addr = Some("127.0.0.1:1234".parse().unwrap());
+        connection.next_request();
+        let res = request()
+            .extension(connection.next_request())
+            .handle(info)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["id"], connection.id);
+        assert_eq!(body["request"], 2);
+        assert_eq!(body["reused"], true);
+        assert_eq!(body["version"], "HTTP/1.1");
+        assert_eq!(body["peer"], "127.0.0.1:1234");
+        assert_eq!(body["tls"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive() {
+        let res = request()
+            .path("/connection/keep-alive?timeout=2")
+            .handle(keep_alive)
+            .await
+            .unwrap();
+
+        assert_eq!(res.headers()["connection"], "keep-alive");
+        assert_eq!(res.headers()["keep-alive"], "timeout=2, max=100");
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_applied() {
+        let lifecycle = Lifecycle::new(None);
+        request()
+            .path("/connection/keep-alive?timeout=2&max=1")
+            .extension(lifecycle.clone())
+            .handle(keep_alive)
+            .await
+            .unwrap();
+
+        assert_eq!(lifecycle.idle_timeout(), Some(Duration::from_secs(2)));
+        assert!(lifecycle.begin());
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_max_zero() {
+        let res = request()
+            .path("/connection/keep-alive?max=0")
+            .handle(keep_alive)
+            .await
+            .unwrap();
+
+        assert_eq!(res.headers()["connection"], "close");
+    }
+
+    #[tokio::test]
+    async fn test_close_http2() {
+        let res = request()
+            .version(Version::HTTP_2)
+            .handle(close)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::HTTP_VERSION_NOT_SUPPORTED);
+    }
+}
@@ -1,14 +1,35 @@
-use crate::headers::{Cookie, SetCookie};
-use crate::http::{bad_request, ok, response, Request, Result};
+use crate::headers::Location;
+use crate::http::{
+    bad_request, ok, response, Error, Request, Result, StatusCode,
+};
 use cookie::Cookie as HTTPCookie;
+use hyper::Uri;
 use itertools::Itertools;
 
-pub async fn cookies(req: Request) -> Result {
-    let cookies = req.typed_header::<Cookie>();
+fn redirect_to_cookies<'a, I>(cookies: I) -> Result
+where
+    I: IntoIterator<Item = HTTPCookie<'a>>,
+{
+    let mut res = response()
+        .status(StatusCode::FOUND)
+        .typed_header(Location::from(Uri::from_static("/cookies")));
+    for cookie in cookies {
+        res = res.cookie(cookie);
+    }
+    res.into()
+}
 
-    let body = cookies
+fn names_and_values(
+    req: &Request,
+) -> std::result::Result<Vec<(String, String)>, Error> {
+    req.query::<Vec<(String, String)>>()
+        .map_err(|_| bad_request())
+}
+
+pub async fn cookies(req: Request) -> Result {
+    let body = req
+        .cookies()
         .iter()
-        .flat_map(|cookie| cookie.iter())
         .format_with("\n", |cookie, f| {
             f(&format_args!("{} = {}", cookie.name(), cookie.value()))
         })
@@ -17,22 +38,31 @@ pub async fn cookies(req: Request) -> Result {
     ok(body)
 }
 
+/// Sets a cookie per query parameter, then redirects to `/cookies`
 pub async fn set_cookies(req: Request) -> Result {
-    let mut res = response();
-    for (k, v) in req
-        .query::<Vec<(String, String)>>()
-        .map_err(|_| bad_request())?
-    {
-        res = res.typed_header(SetCookie(HTTPCookie::new(k, v)));
-    }
+    let cookies = names_and_values(&req)?
+        .into_iter()
+        .map(|(name, value)| HTTPCookie::build(name, value).path("/").finish());
 
-    res.into()
+    redirect_to_cookies(cookies)
+}
+
+/// Expires the cookie named by each query parameter, then redirects to
+/// `/cookies`
+pub async fn delete_cookies(req: Request) -> Result {
+    let cookies = names_and_values(&req)?.into_iter().map(|(name, _)| {
+        let mut cookie = HTTPCookie::build(name, "").path("/").finish();
+        cookie.make_removal();
+        cookie
+    });
+
+    redirect_to_cookies(cookies)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::headers::HeaderMapExt;
+    use crate::headers::{Cookie, HeaderMapExt, SetCookie};
     use crate::test::*;
     use hyper::http::StatusCode;
 
@@ -82,10 +112,44 @@ mod test {
             .await
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert_eq!(res.headers().get("location").unwrap(), "/cookies");
         assert_eq!(
             res.headers().typed_get::<SetCookie>().unwrap(),
-            SetCookie(HTTPCookie::new("test", "value"))
+            SetCookie(HTTPCookie::build("test", "value").path("/").finish())
         )
     }
+
+    #[tokio::test]
+    async fn test_set_multiple_cookies() {
+        let res = request()
+            .path("/?first=value&second=another")
+            .handle(set_cookies)
+            .await
+            .unwrap();
+
+        let cookies = res
+            .headers()
+            .get_all("set-cookie")
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(cookies, ["first=value; Path=/", "second=another; Path=/"]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_cookies() {
+        let res = request()
+            .path("/?test")
+            .handle(delete_cookies)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert_eq!(res.headers().get("location").unwrap(), "/cookies");
+        let cookie = res.headers().typed_get::<SetCookie>().unwrap().0;
+        assert_eq!(cookie.name(), "test");
+        assert_eq!(cookie.value(), "");
+        assert_eq!(cookie.max_age(), Some(cookie::time::Duration::ZERO));
+    }
 }
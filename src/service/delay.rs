@@ -1,49 +1,71 @@
-use crate::http::{bad_request, ok, Request, Result};
-use futures_timer::Delay;
+use crate::http::{bad_request, Request, Result};
+use crate::service::method::echo;
 use std::cmp::min;
 use std::time::Duration;
 
-macro_rules! substitute_in_test {
-    ($value:expr => $substitute:expr) => {{
-        if cfg!(test) {
-            $substitute
-        } else {
-            $value
-        }
-    }};
+fn delay_duration(n: u64, max: Duration) -> Duration {
+    min(Duration::from_secs(n), max)
 }
 
 pub async fn delay(req: Request) -> Result {
     let n = req.param::<u64>("n").ok_or_else(bad_request)?;
-    let delay = min(n, 10);
 
-    let duration = Duration::from_secs(substitute_in_test!(delay => 0));
-    let _ = Delay::new(duration).await;
-    ok(delay.to_string())
+    let duration = delay_duration(n, req.config().max_delay());
+    let duration = substitute_in_test!(duration => Duration::ZERO);
+    req.unless_disconnected(tokio::time::sleep(duration))
+        .await?;
+    echo(req).await
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::headers::ContentType;
     use crate::test::*;
     use hyper::http::StatusCode;
+    use hyper::Method;
+    use serde_json::json;
+
+    const MAX: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn test_delay_duration() {
+        assert_eq!(delay_duration(3, MAX), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_delay_duration_too_long() {
+        assert_eq!(delay_duration(33, MAX), MAX);
+    }
 
     #[tokio::test]
     async fn test_sleep() {
-        let res = request().param("n", "3").handle(delay).await.unwrap();
+        let res = request()
+            .path("/?key=val")
+            .param("n", "3")
+            .handle(delay)
+            .await
+            .unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "3");
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(body["args"], json!({"key": "val"}));
     }
 
     #[tokio::test]
-    async fn test_sleep_too_long() {
-        let res = request().param("n", "33").handle(delay).await.unwrap();
+    async fn test_sleep_post() {
+        let res = request()
+            .method(Method::POST)
+            .typed_header(ContentType::form_url_encoded())
+            .body("key=val")
+            .param("n", "3")
+            .handle(delay)
+            .await
+            .unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "10");
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(body["form"], json!({"key": "val"}));
     }
 
     #[tokio::test]
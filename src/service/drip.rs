@@ -0,0 +1,198 @@
+use crate::headers::{ContentLength, ContentType};
+use crate::http::{
+    bad_request, body_from_stream, response, Bytes, Request, Result, StatusCode,
+};
+use crate::random::rng;
+use futures::prelude::*;
+use serde_derive::Deserialize;
+use std::cmp::{max, min};
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+
+const MAX_DURATION: Duration = Duration::from_secs(60);
+const MAX_BYTES: usize = 10 * 1024 * 1024;
+const TICK: Duration = Duration::from_millis(10);
+
+#[derive(Deserialize)]
+pub struct DripQueryParams {
+    duration: Option<f64>,
+    numbytes: Option<usize>,
+    code: Option<u16>,
+    delay: Option<f64>,
+    /// Random bytes from this seed instead of asterisks
+    seed: Option<u32>,
+}
+
+fn seconds(
+    value: Option<f64>,
+    default: f64,
+    limit: Duration,
+) -> Option<Duration> {
+    let duration =
+        Duration::try_from_secs_f64(value.unwrap_or(default)).ok()?;
+    Some(min(duration, limit))
+}
+
+fn chunk_sizes(numbytes: usize, ticks: usize) -> impl Iterator<Item = usize> {
+    let (size, remainder) = (numbytes / ticks, numbytes % ticks);
+    (0..ticks).map(move |i| size + usize::from(i < remainder))
+}
+
+fn drip_stream(
+    numbytes: usize,
+    duration: Duration,
+    seed: Option<u32>,
+) -> impl Stream<Item = Bytes> {
+    let ticks = (duration.as_nanos() / TICK.as_nanos()) as usize;
+    let ticks = min(max(ticks, 1), numbytes);
+    let period = duration / ticks as u32;
+    let start = Instant::now();
+
+    let mut rng = seed.map(|seed| rng(Some(seed)));
+    let chunks = chunk_sizes(numbytes, ticks).map(move |size| match &mut rng {
+        Some(rng) => (0..size).map(|_| rng.byte()).collect::<Vec<_>>(),
+        None => vec![b'*'; size],
+    });
+    stream::iter(chunks.zip(1..)).then(move |(chunk, tick)| async move {
+        sleep_until(start + period * tick).await;
+        Bytes::from(chunk)
+    })
+}
+
+pub async fn drip(req: Request) -> Result {
+    let query = req.query::<DripQueryParams>().map_err(|_| bad_request())?;
+
+    let duration =
+        seconds(query.duration, 2.0, MAX_DURATION).ok_or_else(bad_request)?;
+    let delay = seconds(query.delay, 2.0, req.config().max_delay())
+        .ok_or_else(bad_request)?;
+    let numbytes = match query.numbytes.unwrap_or(10) {
+        0 => return Err(bad_request()),
+        n => min(n, MAX_BYTES),
+    };
+    // Interim responses can't carry the body
+    let status = StatusCode::from_u16(query.code.unwrap_or(200))
+        .ok()
+        .filter(|status| !status.is_informational())
+        .ok_or_else(bad_request)?;
+
+    let delay = substitute_in_test!(delay => Duration::ZERO);
+    req.unless_disconnected(tokio::time::sleep(delay)).await?;
+
+    if status == StatusCode::NO_CONTENT || status == StatusCode::NOT_MODIFIED {
+        return response().status(status).into();
+    }
+    let duration = substitute_in_test!(duration => Duration::ZERO);
+    response()
+        .status(status)
+        .typed_header(ContentType::octet_stream())
+        .typed_header(ContentLength(numbytes as u64))
+        .body(body_from_stream(Box::pin(req.until_disconnect(
+            req.until_draining(drip_stream(numbytes, duration, query.seed)),
+        ))))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+
+    #[test]
+    fn test_chunk_sizes() {
+        assert_eq!(chunk_sizes(10, 3).collect::<Vec<_>>(), [4, 3, 3]);
+        assert_eq!(chunk_sizes(2, 2).collect::<Vec<_>>(), [1, 1]);
+    }
+
+    #[test]
+    fn test_seconds() {
+        let limit = Duration::from_secs(10);
+        assert_eq!(
+            seconds(Some(1.5), 2.0, limit),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(seconds(None, 2.0, limit), Some(Duration::from_secs(2)));
+        assert_eq!(seconds(Some(60.0), 2.0, limit), Some(limit));
+        assert_eq!(seconds(Some(-1.0), 2.0, limit), None);
+    }
+
+    #[tokio::test]
+    async fn test_drip_stream_chunks() {
+        let chunks = drip_stream(5, Duration::from_millis(30), None)
+            .map(|chunk| chunk.len())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(chunks, [2, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_drip() {
+        let res = request().handle(drip).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.read_body().await.unwrap(), b"**********");
+    }
+
+    #[tokio::test]
+    async fn test_drip_with_params() {
+        let res = request()
+            .path("/?duration=1&numbytes=4&code=201&delay=1")
+            .handle(drip)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.read_body().await.unwrap(), b"****");
+    }
+
+    #[tokio::test]
+    async fn test_drip_seeded() {
+        let res = request()
+            .path("/?numbytes=4&seed=1234")
+            .handle(drip)
+            .await
+            .unwrap();
+
+        assert_eq!(res.read_body().await.unwrap(), [214, 212, 32, 32]);
+    }
+
+    #[tokio::test]
+    async fn test_drip_with_bad_code() {
+        let res = request().path("/?code=1000").handle(drip).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_drip_with_informational_code() {
+        let res = request().path("/?code=101").handle(drip).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_drip_without_body() {
+        for (code, status) in [
+            (204, StatusCode::NO_CONTENT),
+            (304, StatusCode::NOT_MODIFIED),
+        ] {
+            let res = request()
+                .path(&format!("/?code={}", code))
+                .handle(drip)
+                .await
+                .unwrap();
+
+            assert_eq!(res.status(), status);
+            assert!(res.headers().get("content-length").is_none());
+            assert!(res.read_body().await.unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drip_with_no_bytes() {
+        let res = request().path("/?numbytes=0").handle(drip).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
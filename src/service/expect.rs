@@ -0,0 +1,98 @@
+//! Answering `Expect: 100-continue` as asked, for testing how clients wait
+//! for the interim response
+use crate::headers::Expect;
+use crate::http::{bad_request, json, Request, Result};
+use crate::middleware::{expect_continue, ContinueMode};
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Deserialize)]
+pub struct ExpectQueryParams {
+    #[serde(default)]
+    mode: ContinueMode,
+    /// In seconds, up to the maximum delay
+    delay: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Expectation {
+    expected: bool,
+    /// `sent`, or `withheld` with the body left unread
+    #[serde(rename = "continue")]
+    continued: Option<&'static str>,
+    /// The length of the body read
+    size: usize,
+}
+
+/// Holds back `100 Continue` for `delay` seconds, then sends it, rejects the
+/// request with 417 or answers without reading the body as `mode` says
+pub async fn expect(mut req: Request) -> Result {
+    let query = req
+        .query::<ExpectQueryParams>()
+        .map_err(|_| bad_request())?;
+    let delay = query.delay.unwrap_or_default();
+    if !(delay >= 0.0 && delay.is_finite()) {
+        return Err(bad_request());
+    }
+    let delay = Duration::from_secs_f64(delay).min(req.config().max_delay());
+
+    let expected = req.typed_header::<Expect>().is_some();
+    if let Some(res) = expect_continue(&mut req, query.mode, delay).await {
+        return res;
+    }
+    let size = req.bytes().await?.len();
+    json(&Expectation {
+        expected,
+        continued: expected.then(|| query.mode.as_str()),
+        size,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::StatusCode;
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_expect_reject() {
+        let res = request()
+            .path("/expect?mode=reject")
+            .header("expect", "100-continue")
+            .body("abc")
+            .handle(expect)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::EXPECTATION_FAILED);
+    }
+
+    #[tokio::test]
+    async fn test_expect_never() {
+        let res = request()
+            .path("/expect?mode=never")
+            .header("expect", "100-continue")
+            .body("abc")
+            .handle(expect)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["continue"], "withheld");
+        assert_eq!(body["size"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_expect_without_expectation() {
+        let res = request()
+            .path("/expect?mode=reject")
+            .body("abc")
+            .handle(expect)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["expected"], false);
+        assert_eq!(body["size"], 3);
+    }
+}
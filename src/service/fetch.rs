@@ -0,0 +1,185 @@
+//! A GET made by the server on behalf of the client, for testing egress
+//! and protections against server-side request forgery
+use super::reflection::{data, fields, Fields};
+use crate::client::{self, NotPublic};
+use crate::http::{
+    bad_request, json, response, Body, Error, Request, Result, StatusCode,
+};
+use hyper::Method;
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+use url::Url;
+
+/// How long the other server gets to send its response, body and all
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// The most of the body told, the rest being left unread
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+pub struct FetchQueryParams {
+    url: String,
+}
+
+#[derive(Serialize)]
+struct Fetched {
+    url: String,
+    status: u16,
+    headers: Fields,
+    body: String,
+    /// Whether the body went on past what is told of it
+    truncated: bool,
+}
+
+fn forbidden(message: String) -> Result {
+    response().status(StatusCode::FORBIDDEN).body(message)
+}
+
+fn bad_gateway(url: &Url, e: anyhow::Error) -> Result {
+    response()
+        .status(StatusCode::BAD_GATEWAY)
+        .body(format!("Fetching {} failed: {}", url, e))
+}
+
+/// Reads at most `MAX_BODY_SIZE` bytes of `body`, and whether there was more
+async fn read(mut body: Body) -> anyhow::Result<(Vec<u8>, bool)> {
+    let mut bytes = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| anyhow::anyhow!(e))?;
+        if bytes.len() + chunk.len() > MAX_BODY_SIZE {
+            bytes.extend_from_slice(&chunk[..MAX_BODY_SIZE - bytes.len()]);
+            return Ok((bytes, true));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok((bytes, false))
+}
+
+/// Gets `url`, which has to be allowed and on a public address unless
+/// configured otherwise, and tells its status, headers and body
+///
+/// Redirects are told and not followed, since following one could lead
+/// anywhere.
+pub async fn fetch(req: Request) -> Result {
+    let query = req.query::<FetchQueryParams>().map_err(|_| bad_request())?;
+    let url = Url::parse(&query.url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| Error::bad_request("url has to be http or https"))?;
+    let host = client::host(&url)
+        .ok_or_else(|| Error::bad_request("url has no host"))?;
+    let config = req.config();
+    if !config.fetch_allowed(host) {
+        return forbidden(format!("Fetching {} is not allowed", host));
+    }
+
+    let get = client::request(&url)
+        .and_then(|get| Ok(get.method(Method::GET).body(Body::empty())?))
+        .map_err(Error::internal)?;
+    let fetched = async {
+        let res = client::send(&url, get, config.outbound_private()).await?;
+        let (parts, body) = res.into_parts();
+        let (body, truncated) = read(body).await?;
+        Ok::<_, anyhow::Error>((parts, body, truncated))
+    };
+    let (parts, body, truncated) =
+        match tokio::time::timeout(FETCH_TIMEOUT, fetched).await {
+            Ok(Ok(fetched)) => fetched,
+            Ok(Err(e)) if e.is::<NotPublic>() => {
+                return forbidden(e.to_string())
+            }
+            Ok(Err(e)) => return bad_gateway(&url, e),
+            Err(_) => return Err(Error::Timeout),
+        };
+
+    let headers = parts.headers.iter().map(|(name, value)| {
+        (
+            name.as_str().to_owned(),
+            String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        )
+    });
+    json(&Fetched {
+        url: url.to_string(),
+        status: parts.status.as_u16(),
+        headers: fields(headers),
+        body: data(&body),
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+    use crate::test::*;
+    use hyper::service::service_fn;
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    /// A server answering every request with `body`
+    fn serve(body: &'static [u8]) -> SocketAddr {
+        crate::test::serve(service_fn(move |_| async move {
+            let res = hyper::Response::builder()
+                .header("x-served-by", "test")
+                .body(Body::from(body))
+                .unwrap();
+            Ok::<_, Infallible>(res)
+        }))
+    }
+
+    async fn get(url: &str, config: Config) -> crate::http::Response {
+        request()
+            .path(&format!("/fetch?url={}", url))
+            .extension(Arc::new(config))
+            .handle(fetch)
+            .await
+            .unwrap()
+    }
+
+    fn private() -> Config {
+        Config {
+            outbound_private: Some(true),
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let addr = serve(b"hello");
+        let res = get(&format!("http://{}/a", addr), private()).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(body["status"], 200);
+        assert_eq!(body["headers"]["x-served-by"], "test");
+        assert_eq!(body["body"], "hello");
+        assert_eq!(body["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_truncates() {
+        let addr = serve(&[b'*'; MAX_BODY_SIZE + 1]);
+        let res = get(&format!("http://{}/", addr), private()).await;
+
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(body["body"].as_str().unwrap().len(), MAX_BODY_SIZE);
+        assert_eq!(body["truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_refuses() {
+        let addr = serve(b"hello");
+        let res = get(&format!("http://{}/", addr), Config::default()).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let config = Config {
+            fetch_deny: vec!["localhost".to_owned()],
+            ..private()
+        };
+        let url = format!("http://localhost:{}/", addr.port());
+        assert_eq!(get(&url, config).await.status(), StatusCode::FORBIDDEN);
+
+        let res = get("file:///etc/passwd", private()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,78 @@
+//! Fixed documents in the formats clients commonly parse
+use crate::handler::Handler;
+use crate::http::{response, Request, Result};
+use async_trait::async_trait;
+use hyper::header::CONTENT_TYPE;
+
+pub struct Fixture {
+    /// Textual types name their charset, the documents being UTF-8
+    content_type: &'static str,
+    data: &'static str,
+}
+
+#[async_trait]
+impl Handler for Fixture {
+    async fn handle(&self, _: Request) -> Result {
+        response()
+            .header(CONTENT_TYPE, self.content_type)
+            .body(self.data)
+    }
+}
+
+pub const HTML: Fixture = Fixture {
+    content_type: "text/html; charset=utf-8",
+    data: include_str!("../../assets/fixtures/sample.html"),
+};
+
+/// XML declares its own encoding
+pub const XML: Fixture = Fixture {
+    content_type: "application/xml",
+    data: include_str!("../../assets/fixtures/sample.xml"),
+};
+
+/// JSON is always UTF-8, so takes no charset
+pub const JSON: Fixture = Fixture {
+    content_type: "application/json",
+    data: include_str!("../../assets/fixtures/sample.json"),
+};
+
+pub const ROBOTS_TXT: Fixture = Fixture {
+    content_type: "text/plain; charset=utf-8",
+    data: include_str!("../../assets/fixtures/robots.txt"),
+};
+
+pub const DENY: Fixture = Fixture {
+    content_type: "text/plain; charset=utf-8",
+    data: include_str!("../../assets/fixtures/deny.txt"),
+};
+
+pub const UTF8: Fixture = Fixture {
+    content_type: "text/html; charset=utf-8",
+    data: include_str!("../../assets/fixtures/utf8.html"),
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_fixture() {
+        let res = request().handle(UTF8).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["content-type"], "text/html; charset=utf-8");
+        assert!(res.read_body_utf8().await.unwrap().contains("🦀"));
+    }
+
+    #[test]
+    fn test_json_valid() {
+        serde_json::from_str::<serde_json::Value>(JSON.data).unwrap();
+    }
+
+    #[test]
+    fn test_robots_deny() {
+        assert!(ROBOTS_TXT.data.contains("Disallow: /deny"));
+    }
+}
@@ -0,0 +1,744 @@
+//! A GraphQL endpoint without a schema of its own, answering every
+//! operation with the operation itself, its variables and the headers it
+//! came with
+//!
+//! Introspection is answered as well, describing an `Echo` type with those
+//! three fields, so clients that introspect before sending anything work.
+use crate::graphql::{self, Document, Field, Operation, Selection, Value};
+use crate::headers::ContentType;
+use crate::http::{json, response, Error, Request, Result, StatusCode};
+use crate::service::reflection::Reflection;
+use hyper::header::ALLOW;
+use hyper::Method;
+use lazy_static::lazy_static;
+use serde_derive::Deserialize;
+use serde_json::{json, Map, Value as Json};
+
+lazy_static! {
+    static ref SCHEMA: Json = schema();
+}
+
+#[derive(Deserialize)]
+struct GraphQLRequest {
+    query: Option<String>,
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+    variables: Option<Json>,
+}
+
+/// The GET form, with the variables as JSON
+#[derive(Deserialize)]
+struct GraphQLQuery {
+    query: Option<String>,
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+    variables: Option<String>,
+}
+
+fn scalar(name: &str, description: &str) -> Json {
+    json!({
+        "__typename": "__Type",
+        "kind": "SCALAR",
+        "name": name,
+        "description": description,
+        "fields": null,
+        "inputFields": null,
+        "interfaces": null,
+        "enumValues": null,
+        "possibleTypes": null,
+        "ofType": null,
+        "specifiedByURL": null,
+    })
+}
+
+fn non_null(name: &str, kind: &str) -> Json {
+    json!({
+        "__typename": "__Type",
+        "kind": "NON_NULL",
+        "name": null,
+        "ofType": {
+            "__typename": "__Type",
+            "kind": kind,
+            "name": name,
+            "ofType": null,
+        },
+    })
+}
+
+/// The introspection of a schema whose query and mutation type is `Echo`
+fn schema() -> Json {
+    let field = |name: &str, description: &str| {
+        json!({
+            "__typename": "__Field",
+            "name": name,
+            "description": description,
+            "args": [],
+            "type": non_null("JSON", "SCALAR"),
+            "isDeprecated": false,
+            "deprecationReason": null,
+        })
+    };
+    let echo = json!({
+        "__typename": "__Type",
+        "kind": "OBJECT",
+        "name": "Echo",
+        "description": "What the operation was sent with",
+        "fields": [
+            field("operation", "The parsed operation"),
+            field("variables", "The variables, their defaults filled in"),
+            field("headers", "The headers of the request"),
+        ],
+        "inputFields": null,
+        "interfaces": [],
+        "enumValues": null,
+        "possibleTypes": null,
+        "ofType": null,
+        "specifiedByURL": null,
+    });
+    let directive = |name: &str, description: &str| {
+        json!({
+            "__typename": "__Directive",
+            "name": name,
+            "description": description,
+            "locations": ["FIELD", "FRAGMENT_SPREAD", "INLINE_FRAGMENT"],
+            "args": [{
+                "__typename": "__InputValue",
+                "name": "if",
+                "description": null,
+                "type": non_null("Boolean", "SCALAR"),
+                "defaultValue": null,
+                "isDeprecated": false,
+                "deprecationReason": null,
+            }],
+            "isRepeatable": false,
+        })
+    };
+
+    json!({
+        "__typename": "__Schema",
+        "description": null,
+        "queryType": echo,
+        "mutationType": echo,
+        "subscriptionType": null,
+        "types": [
+            echo,
+            scalar("JSON", "Any JSON value"),
+            scalar("String", "UTF-8 text"),
+            scalar("Int", "A signed 32-bit integer"),
+            scalar("Float", "A double-precision floating point number"),
+            scalar("Boolean", "true or false"),
+            scalar("ID", "A unique identifier, serialized as a string"),
+        ],
+        "directives": [
+            directive("include", "Only includes the selection if true"),
+            directive("skip", "Skips the selection if true"),
+        ],
+    })
+}
+
+/// A GraphQL error response, as GraphQL over HTTP has them
+fn graphql_error(
+    status: StatusCode,
+    message: String,
+    locations: Json,
+) -> Error {
+    let mut error = json!({ "message": message });
+    if !locations.is_null() {
+        error["locations"] = locations;
+    }
+    response()
+        .status(status)
+        .json(&json!({ "errors": [error] }))
+        .map_or_else(|e| e, |res| Error::Failure(Box::new(res)))
+}
+
+fn invalid(message: String) -> Error {
+    graphql_error(StatusCode::BAD_REQUEST, message, Json::Null)
+}
+
+fn arguments(
+    arguments: &[(String, Value)],
+    variables: &Map<String, Json>,
+) -> Json {
+    Json::Object(
+        arguments
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_json(variables)))
+            .collect(),
+    )
+}
+
+/// Adds the directives of a selection to its echo, if it has any
+fn with_directives(
+    mut echo: Map<String, Json>,
+    directives: &[graphql::Directive],
+    variables: &Map<String, Json>,
+) -> Json {
+    if !directives.is_empty() {
+        let directives = directives
+            .iter()
+            .map(|directive| {
+                json!({
+                    "name": directive.name,
+                    "arguments": arguments(&directive.arguments, variables),
+                })
+            })
+            .collect();
+        echo.insert("directives".to_owned(), Json::Array(directives));
+    }
+    Json::Object(echo)
+}
+
+fn echo_selections(
+    selections: &[Selection],
+    variables: &Map<String, Json>,
+) -> Json {
+    let echo = |selection: &Selection| match selection {
+        Selection::Field(field) => {
+            let mut echo = Map::new();
+            echo.insert("field".to_owned(), field.name.clone().into());
+            if let Some(alias) = &field.alias {
+                echo.insert("alias".to_owned(), alias.clone().into());
+            }
+            if !field.arguments.is_empty() {
+                echo.insert(
+                    "arguments".to_owned(),
+                    arguments(&field.arguments, variables),
+                );
+            }
+            if !field.selections.is_empty() {
+                echo.insert(
+                    "selections".to_owned(),
+                    echo_selections(&field.selections, variables),
+                );
+            }
+            with_directives(echo, &field.directives, variables)
+        }
+        Selection::FragmentSpread { name, directives } => {
+            let mut echo = Map::new();
+            echo.insert("fragment".to_owned(), name.clone().into());
+            with_directives(echo, directives, variables)
+        }
+        Selection::InlineFragment {
+            type_condition,
+            directives,
+            selections,
+        } => {
+            let mut echo = Map::new();
+            echo.insert("on".to_owned(), type_condition.clone().into());
+            echo.insert(
+                "selections".to_owned(),
+                echo_selections(selections, variables),
+            );
+            with_directives(echo, directives, variables)
+        }
+    };
+    selections.iter().map(echo).collect()
+}
+
+fn echo_operation(
+    document: &Document,
+    operation: &Operation,
+    variables: &Map<String, Json>,
+) -> Json {
+    let definitions = operation
+        .variables
+        .iter()
+        .map(|definition| {
+            json!({
+                "name": definition.name,
+                "type": definition.ty,
+                "defaultValue": definition
+                    .default
+                    .as_ref()
+                    .map(|value| value.to_json(variables)),
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut echo = Map::new();
+    echo.insert("type".to_owned(), operation.kind.as_str().into());
+    echo.insert("name".to_owned(), operation.name.clone().into());
+    echo.insert("variables".to_owned(), definitions.into());
+    echo.insert(
+        "selections".to_owned(),
+        echo_selections(&operation.selections, variables),
+    );
+    if !document.fragments.is_empty() {
+        let fragments = document
+            .fragments
+            .iter()
+            .map(|fragment| {
+                let echo = json!({
+                    "on": fragment.type_condition,
+                    "selections":
+                        echo_selections(&fragment.selections, variables),
+                });
+                (fragment.name.clone(), echo)
+            })
+            .collect();
+        echo.insert("fragments".to_owned(), Json::Object(fragments));
+    }
+    with_directives(echo, &operation.directives, variables)
+}
+
+/// Checks every fragment spread of `selections` is to a fragment of the
+/// document, and none spreads itself or nests too deep to resolve
+fn check_spreads<'a>(
+    document: &'a Document,
+    selections: &'a [Selection],
+    spreading: &mut Vec<&'a str>,
+) -> std::result::Result<(), Error> {
+    for selection in selections {
+        match selection {
+            Selection::Field(Field { selections, .. })
+            | Selection::InlineFragment { selections, .. } => {
+                check_spreads(document, selections, spreading)?
+            }
+            Selection::FragmentSpread { name, .. } => {
+                if spreading.contains(&name.as_str()) {
+                    return Err(invalid(format!(
+                        "Cannot spread fragment \"{}\" within itself.",
+                        name
+                    )));
+                }
+                let fragment = document.fragment(name).ok_or_else(|| {
+                    invalid(format!("Unknown fragment \"{}\".", name))
+                })?;
+                if spreading.len() >= graphql::MAX_DEPTH {
+                    return Err(invalid(format!(
+                        "Fragment spreads nest over {} deep.",
+                        graphql::MAX_DEPTH
+                    )));
+                }
+                spreading.push(name);
+                check_spreads(document, &fragment.selections, spreading)?;
+                spreading.pop();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether the top level of `selections` asks for `__schema` or `__type`
+fn introspects(document: &Document, selections: &[Selection]) -> bool {
+    selections.iter().any(|selection| match selection {
+        Selection::Field(field) => {
+            field.name == "__schema" || field.name == "__type"
+        }
+        Selection::FragmentSpread { name, .. } => document
+            .fragment(name)
+            .is_some_and(|f| introspects(document, &f.selections)),
+        Selection::InlineFragment { selections, .. } => {
+            introspects(document, selections)
+        }
+    })
+}
+
+/// Resolves selections against JSON, which is all introspection takes
+struct Executor<'a> {
+    document: &'a Document,
+    variables: &'a Map<String, Json>,
+}
+
+impl Executor<'_> {
+    /// Whether `@skip` and `@include` leave the selection in
+    fn included(&self, directives: &[graphql::Directive]) -> bool {
+        directives.iter().all(|directive| {
+            let condition = directive
+                .arguments
+                .iter()
+                .find(|(name, _)| name == "if")
+                .map(|(_, value)| value.to_json(self.variables));
+            match directive.name.as_str() {
+                "skip" => condition != Some(Json::Bool(true)),
+                "include" => condition != Some(Json::Bool(false)),
+                _ => true,
+            }
+        })
+    }
+
+    fn applies(type_condition: &str, value: &Json) -> bool {
+        value["__typename"] == type_condition
+    }
+
+    fn resolve(&self, field: &Field, parent: &Json) -> Json {
+        if field.name != "__type" {
+            return parent.get(&field.name).cloned().unwrap_or(Json::Null);
+        }
+        let name = field
+            .arguments
+            .iter()
+            .find(|(name, _)| name == "name")
+            .map(|(_, value)| value.to_json(self.variables));
+        SCHEMA["types"]
+            .as_array()
+            .and_then(|types| {
+                types.iter().find(|ty| Some(&ty["name"]) == name.as_ref())
+            })
+            .cloned()
+            .unwrap_or(Json::Null)
+    }
+
+    fn complete(&self, selections: &[Selection], value: Json) -> Json {
+        match value {
+            Json::Array(values) => values
+                .into_iter()
+                .map(|value| self.complete(selections, value))
+                .collect(),
+            value @ Json::Object(_) => {
+                let mut result = Map::new();
+                self.select(selections, &value, &mut result);
+                Json::Object(result)
+            }
+            value => value,
+        }
+    }
+
+    fn select(
+        &self,
+        selections: &[Selection],
+        value: &Json,
+        result: &mut Map<String, Json>,
+    ) {
+        for selection in selections {
+            match selection {
+                Selection::Field(field) if self.included(&field.directives) => {
+                    let resolved = match field.selections.is_empty() {
+                        true => self.resolve(field, value),
+                        false => self.complete(
+                            &field.selections,
+                            self.resolve(field, value),
+                        ),
+                    };
+                    match (result.get_mut(field.response_key()), resolved) {
+                        (Some(Json::Object(existing)), Json::Object(more)) => {
+                            existing.extend(more)
+                        }
+                        (_, resolved) => {
+                            result.insert(
+                                field.response_key().to_owned(),
+                                resolved,
+                            );
+                        }
+                    }
+                }
+                Selection::FragmentSpread { name, directives }
+                    if self.included(directives) =>
+                {
+                    if let Some(fragment) = self
+                        .document
+                        .fragment(name)
+                        .filter(|f| Self::applies(&f.type_condition, value))
+                    {
+                        self.select(&fragment.selections, value, result);
+                    }
+                }
+                Selection::InlineFragment {
+                    type_condition,
+                    directives,
+                    selections,
+                } if self.included(directives)
+                    && type_condition
+                        .as_deref()
+                        .is_none_or(|ty| Self::applies(ty, value)) =>
+                {
+                    self.select(selections, value, result)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The query, operation name and variables of a GET or POST request
+async fn graphql_request(
+    req: &mut Request,
+) -> std::result::Result<GraphQLRequest, Error> {
+    if req.method() != Method::POST {
+        let query = req
+            .query::<GraphQLQuery>()
+            .map_err(|_| invalid("Invalid query string.".to_owned()))?;
+        let variables = query
+            .variables
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(|_| invalid("Variables are invalid JSON.".to_owned()))?;
+        return Ok(GraphQLRequest {
+            query: query.query,
+            operation_name: query.operation_name,
+            variables,
+        });
+    }
+
+    let content_type = req.typed_header::<ContentType>().map(mime::Mime::from);
+    if content_type
+        .is_some_and(|mime| mime.essence_str() == "application/graphql")
+    {
+        let body = req.bytes().await?;
+        return Ok(GraphQLRequest {
+            query: Some(String::from_utf8_lossy(&body).into_owned()),
+            operation_name: None,
+            variables: None,
+        });
+    }
+    req.json::<GraphQLRequest>().await
+}
+
+/// Parses the operation and answers with it, its variables and the request
+/// headers, or with the introspection of the echo for `__schema` and
+/// `__type`
+pub async fn graphql(mut req: Request) -> Result {
+    let request = graphql_request(&mut req).await?;
+    let query = request
+        .query
+        .filter(|query| !query.trim().is_empty())
+        .ok_or_else(|| invalid("Must provide query string.".to_owned()))?;
+    let document = graphql::parse(&query).map_err(|error| {
+        graphql_error(
+            StatusCode::BAD_REQUEST,
+            format!("Syntax Error: {}", error.message),
+            json!([{ "line": error.line, "column": error.column }]),
+        )
+    })?;
+
+    let operation =
+        match (request.operation_name.as_deref(), &document.operations[..]) {
+            (Some(name), operations) => operations
+                .iter()
+                .find(|operation| operation.name.as_deref() == Some(name))
+                .ok_or_else(|| {
+                    invalid(format!("Unknown operation named \"{}\".", name))
+                })?,
+            (None, [operation]) => operation,
+            (None, _) => {
+                return Err(invalid(
+                    "Must provide operation name if query contains multiple \
+                 operations."
+                        .to_owned(),
+                ))
+            }
+        };
+    if req.method() != Method::POST
+        && operation.kind != graphql::OperationKind::Query
+    {
+        let error = graphql_error(
+            StatusCode::METHOD_NOT_ALLOWED,
+            format!(
+                "Can only perform a {} operation from a POST request.",
+                operation.kind.as_str()
+            ),
+            Json::Null,
+        );
+        return Err(match error {
+            Error::Failure(mut res) => {
+                res.headers_mut().insert(ALLOW, "POST".parse().unwrap());
+                Error::Failure(res)
+            }
+            error => error,
+        });
+    }
+    check_spreads(&document, &operation.selections, &mut vec![])?;
+
+    let mut variables = match request.variables {
+        Some(Json::Object(variables)) => variables,
+        None | Some(Json::Null) => Map::new(),
+        Some(_) => {
+            return Err(invalid("Variables must be an object.".to_owned()))
+        }
+    };
+    for definition in &operation.variables {
+        if let (false, Some(default)) = (
+            variables.contains_key(&definition.name),
+            &definition.default,
+        ) {
+            let default = default.to_json(&Map::new());
+            variables.insert(definition.name.clone(), default);
+        }
+    }
+    let headers = serde_json::to_value(Reflection::new().headers(&req))
+        .map_err(Error::internal)?["headers"]
+        .take();
+
+    let mut echo = Map::new();
+    echo.insert(
+        "operation".to_owned(),
+        echo_operation(&document, operation, &variables),
+    );
+    echo.insert("variables".to_owned(), Json::Object(variables.clone()));
+    echo.insert("headers".to_owned(), headers);
+    if !introspects(&document, &operation.selections) {
+        return json(&json!({ "data": echo }));
+    }
+
+    echo.insert("__typename".to_owned(), "Echo".into());
+    echo.insert("__schema".to_owned(), SCHEMA.clone());
+    let executor = Executor {
+        document: &document,
+        variables: &variables,
+    };
+    let data = executor.complete(&operation.selections, Json::Object(echo));
+    json(&json!({ "data": data }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    async fn post(body: Json) -> (StatusCode, Json) {
+        let res = request()
+            .method(Method::POST)
+            .header("content-type", "application/json")
+            .header("x-client", "test")
+            .body(body.to_string())
+            .handle(graphql)
+            .await
+            .unwrap();
+        let status = res.status();
+        (status, res.read_body_json().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_echo() {
+        let (status, body) = post(json!({
+            "query": "query User($id: ID!, $full: Boolean = true) { \
+                      user(id: $id) { name ...Details @include(if: $full) } }\n\
+                      fragment Details on User { email }",
+            "operationName": "User",
+            "variables": {"id": "42"},
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let data = &body["data"];
+        assert_eq!(data["variables"], json!({"id": "42", "full": true}));
+        assert_eq!(data["headers"]["X-Client"], "test");
+        let operation = &data["operation"];
+        assert_eq!(operation["type"], "query");
+        assert_eq!(operation["name"], "User");
+        assert_eq!(operation["variables"][0]["type"], "ID!");
+        assert_eq!(
+            operation["selections"][0],
+            json!({
+                "field": "user",
+                "arguments": {"id": "42"},
+                "selections": [
+                    {"field": "name"},
+                    {
+                        "fragment": "Details",
+                        "directives": [
+                            {"name": "include", "arguments": {"if": true}}
+                        ],
+                    },
+                ],
+            })
+        );
+        assert_eq!(operation["fragments"]["Details"]["on"], "User");
+    }
+
+    #[tokio::test]
+    async fn test_introspection() {
+        let (status, body) = post(json!({
+            "query": "query IntrospectionQuery { __schema { \
+                      queryType { name } types { ...T } } \
+                      echo: __type(name: \"Echo\") { fields { name } } }\n\
+                      fragment T on __Type { kind name }",
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let data = &body["data"];
+        assert_eq!(data["__schema"]["queryType"], json!({"name": "Echo"}));
+        assert_eq!(
+            data["__schema"]["types"][1],
+            json!({"kind": "SCALAR", "name": "JSON"})
+        );
+        assert_eq!(
+            data["echo"]["fields"],
+            json!([
+                {"name": "operation"},
+                {"name": "variables"},
+                {"name": "headers"}
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let res = request()
+            .path("/graphql?query=%7Ba%7D&variables=%7B%22x%22%3A1%7D")
+            .handle(graphql)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(body["data"]["variables"], json!({"x": 1}));
+
+        let res = request()
+            .path("/graphql?query=mutation%7Ba%7D")
+            .handle(graphql)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.headers()[ALLOW], "POST");
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let (status, body) = post(json!({"query": "{ a(b: ) }"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["errors"][0],
+            json!({
+                "message": "Syntax Error: Unexpected \")\"",
+                "locations": [{"line": 1, "column": 8}],
+            })
+        );
+
+        let (_, body) = post(json!({"query": "{ a } { b }"})).await;
+        assert_eq!(
+            body["errors"][0]["message"],
+            "Must provide operation name if query contains multiple \
+             operations."
+        );
+
+        let (_, body) = post(json!({
+            "query": "{ ...A } fragment A on Echo { ...A }"
+        }))
+        .await;
+        assert_eq!(
+            body["errors"][0]["message"],
+            "Cannot spread fragment \"A\" within itself."
+        );
+
+        let fragments = (0..=graphql::MAX_DEPTH)
+            .map(|i| format!("fragment F{} on Echo {{ ...F{} }}", i, i + 1))
+            .collect::<String>();
+        let (_, body) = post(json!({
+            "query": format!(
+                "{{ ...F0 }} {} fragment F{} on Echo {{ a }}",
+                fragments,
+                graphql::MAX_DEPTH + 1
+            )
+        }))
+        .await;
+        assert_eq!(
+            body["errors"][0]["message"],
+            format!("Fragment spreads nest over {} deep.", graphql::MAX_DEPTH)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_graphql_body() {
+        let res = request()
+            .method(Method::POST)
+            .header("content-type", "application/graphql")
+            .body("mutation Add { add(n: 1) }")
+            .handle(graphql)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["data"]["operation"]["type"], "mutation");
+    }
+}
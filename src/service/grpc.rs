@@ -0,0 +1,283 @@
+//! gRPC echo and server reflection services, served by the router like any
+//! other endpoint so that they share its middleware, logging and metrics
+//! among them
+use crate::config::DEFAULT_MAX_DELAY;
+use crate::handler::Handler;
+use crate::http::{Body, Request, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::{Stream, StreamExt};
+use http_body_util::BodyExt;
+use hyper::http::{Request as HTTPRequest, Response as HTTPResponse};
+use std::cmp::min;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::body::BoxBody;
+use tonic::metadata::KeyAndValueRef;
+use tonic::{Code, Status, Streaming};
+use tonic_reflection::server::v1alpha::{
+    ServerReflection, ServerReflectionServer,
+};
+use tower::{Service, ServiceExt};
+
+mod proto {
+    tonic::include_proto!("httpbox.echo.v1");
+
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("echo");
+}
+
+use self::proto::echo_server::EchoServer;
+use self::proto::{EchoRequest, EchoResponse};
+
+/// What is echoed of a call along with each message
+#[derive(Clone, Debug)]
+struct Call {
+    metadata: HashMap<String, String>,
+    peer: String,
+    max_delay: Duration,
+}
+
+impl Call {
+    fn new<T>(request: &tonic::Request<T>) -> Self {
+        let metadata = request
+            .metadata()
+            .iter()
+            .map(|entry| match entry {
+                KeyAndValueRef::Ascii(key, value) => (
+                    key.to_string(),
+                    String::from_utf8_lossy(value.as_encoded_bytes())
+                        .into_owned(),
+                ),
+                KeyAndValueRef::Binary(key, value) => (
+                    key.to_string(),
+                    STANDARD.encode(value.to_bytes().unwrap_or_default()),
+                ),
+            })
+            .collect();
+        let extensions = request.extensions();
+        Self {
+            metadata,
+            peer: extensions
+                .get::<SocketAddr>()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            max_delay: extensions
+                .get::<Arc<crate::config::Config>>()
+                .map_or(DEFAULT_MAX_DELAY, |config| config.max_delay()),
+        }
+    }
+
+    async fn answer(
+        &self,
+        message: EchoRequest,
+    ) -> std::result::Result<EchoResponse, Status> {
+        let delay = Duration::from_millis(message.delay_ms.into());
+        tokio::time::sleep(min(delay, self.max_delay)).await;
+        if message.code != 0 {
+            let code = Code::from_i32(message.code as i32);
+            return Err(Status::new(code, message.message));
+        }
+        Ok(EchoResponse {
+            message: message.message,
+            metadata: self.metadata.clone(),
+            peer: self.peer.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Echo;
+
+type EchoStream = Pin<
+    Box<dyn Stream<Item = std::result::Result<EchoResponse, Status>> + Send>,
+>;
+
+#[tonic::async_trait]
+impl proto::echo_server::Echo for Echo {
+    async fn echo(
+        &self,
+        request: tonic::Request<EchoRequest>,
+    ) -> std::result::Result<tonic::Response<EchoResponse>, Status> {
+        let call = Call::new(&request);
+        call.answer(request.into_inner())
+            .await
+            .map(tonic::Response::new)
+    }
+
+    type EchoStreamStream = EchoStream;
+
+    async fn echo_stream(
+        &self,
+        request: tonic::Request<Streaming<EchoRequest>>,
+    ) -> std::result::Result<tonic::Response<EchoStream>, Status> {
+        let call = Call::new(&request);
+        let answers = request.into_inner().then(move |message| {
+            let call = call.clone();
+            async move { call.answer(message?).await }
+        });
+        Ok(tonic::Response::new(Box::pin(answers)))
+    }
+}
+
+/// A tonic service as the handler of its methods
+///
+/// The response body is handed over through a channel, as tonic's body
+/// isn't `Sync` like a `Body` has to be, trailers carrying the gRPC status
+/// and all.
+#[derive(Clone, Debug)]
+pub struct Grpc<S>(S);
+
+impl Grpc<EchoServer<Echo>> {
+    pub fn echo() -> Self {
+        Self(EchoServer::new(Echo))
+    }
+}
+
+/// The server reflection service, describing the echo service and itself
+pub fn reflection() -> Grpc<ServerReflectionServer<impl ServerReflection>> {
+    let service = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
+        .build_v1alpha()
+        .expect("valid file descriptors");
+    Grpc(service)
+}
+
+fn forward(mut body: BoxBody) -> Body {
+    let (mut sender, forwarded) = Body::channel();
+    tokio::spawn(async move {
+        while let Some(frame) = body.frame().await {
+            let sent = match frame.map(|frame| frame.into_data()) {
+                Ok(Ok(data)) => sender.send_data(data).await.is_ok(),
+                Ok(Err(frame)) => match frame.into_trailers() {
+                    Ok(trailers) => {
+                        let _ = sender.send_trailers(trailers).await;
+                        return;
+                    }
+                    Err(_) => true,
+                },
+                Err(_) => false,
+            };
+            if !sent {
+                return sender.abort();
+            }
+        }
+    });
+    forwarded
+}
+
+#[async_trait]
+impl<S> Handler for Grpc<S>
+where
+    S: Service<
+            HTTPRequest<Body>,
+            Response = HTTPResponse<BoxBody>,
+            Error = Infallible,
+        > + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send,
+{
+    async fn handle(&self, mut req: Request) -> Result {
+        let mut call = HTTPRequest::new(req.body());
+        *call.method_mut() = req.method().clone();
+        *call.uri_mut() = req.uri().clone();
+        *call.version_mut() = req.version();
+        *call.headers_mut() = req.headers().clone();
+        *call.extensions_mut() = std::mem::take(req.extensions_mut());
+
+        let res = match self.0.clone().oneshot(call).await {
+            Ok(res) => res,
+            Err(never) => match never {},
+        };
+        Ok(res.map(forward))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::Method;
+    use prost::Message;
+
+    /// A gRPC message, length-prefixed
+    fn frame(message: &impl Message) -> Vec<u8> {
+        let encoded = message.encode_to_vec();
+        let mut frame = vec![0];
+        frame.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&encoded);
+        frame
+    }
+
+    async fn call(path: &str, body: Vec<u8>) -> crate::http::Response {
+        request()
+            .method(Method::POST)
+            .path(path)
+            .header("content-type", "application/grpc")
+            .header("te", "trailers")
+            .header("x-client", "test")
+            .body(body)
+            .handle(Grpc::echo())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_echo() {
+        let message = EchoRequest {
+            message: "hello".to_owned(),
+            ..EchoRequest::default()
+        };
+        let res = call("/httpbox.echo.v1.Echo/Echo", frame(&message)).await;
+        let body = BodyExt::collect(res.into_body()).await.unwrap();
+        assert_eq!(body.trailers().unwrap()["grpc-status"], "0");
+
+        let data = body.to_bytes();
+        let answer = EchoResponse::decode(&data[5..]).unwrap();
+        assert_eq!(answer.message, "hello");
+        assert_eq!(answer.metadata["x-client"], "test");
+    }
+
+    #[tokio::test]
+    async fn test_echo_status() {
+        let message = EchoRequest {
+            message: "not here".to_owned(),
+            code: Code::NotFound as u32,
+            ..EchoRequest::default()
+        };
+        let res = call("/httpbox.echo.v1.Echo/Echo", frame(&message)).await;
+
+        let status = Status::from_header_map(res.headers()).unwrap();
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message(), "not here");
+    }
+
+    #[tokio::test]
+    async fn test_echo_stream() {
+        let mut body = vec![];
+        for message in ["one", "two"] {
+            body.extend(frame(&EchoRequest {
+                message: message.to_owned(),
+                ..EchoRequest::default()
+            }));
+        }
+        let res = call("/httpbox.echo.v1.Echo/EchoStream", body).await;
+        let mut data = &res.read_body().await.unwrap()[..];
+
+        let mut answers = vec![];
+        while data.len() >= 5 {
+            let len = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+            let (message, rest) = data[5..].split_at(len as usize);
+            answers.push(EchoResponse::decode(message).unwrap().message);
+            data = rest;
+        }
+        assert_eq!(answers, ["one", "two"]);
+    }
+}
@@ -1,38 +1,63 @@
-use crate::http::{bad_request, ok, response, Request, Result};
+use crate::headers::ContentLength;
+use crate::http::{bad_request, json, response, Error, Request, Result};
+use crate::service::reflection::{fields, Reflection};
 use hyper::header::{HeaderName, HeaderValue};
-use itertools::{process_results, Itertools};
 
 pub async fn headers(req: Request) -> Result {
-    let request_headers = req
-        .headers()
-        .iter()
-        .map(|(n, v)| v.to_str().map(|v| (n, v)));
+    json(&Reflection::new().headers(&req))
+}
 
-    ok(process_results(request_headers, |iter| {
-        iter.format_with("\n", |(n, v), f| {
-            f(&format_args!("{}: {}", n, v.trim()))
-        })
-        .to_string()
-    })
-    .map_err(|_| bad_request())?)
+/// The JSON listing of the response headers, which has to include its own
+/// `Content-Length`
+///
+/// The length is recomputed until it stops changing, since writing it down
+/// can itself make the body longer.
+fn describe_headers(
+    pairs: &[(String, String)],
+) -> std::result::Result<Vec<u8>, Error> {
+    let mut length = 0;
+    loop {
+        let mut pairs = pairs.to_vec();
+        pairs.push(("Content-Length".to_owned(), length.to_string()));
+
+        let body = serde_json::to_vec_pretty(&fields(pairs))
+            .map_err(Error::internal)?;
+        if body.len() == length {
+            return Ok(body);
+        }
+        length = body.len();
+    }
 }
 
 pub async fn response_headers(req: Request) -> Result {
-    let output_headers = req
+    let mut pairs = req
         .query::<Vec<(String, String)>>()
-        .map_err(|_| bad_request())?
+        .map_err(|_| bad_request())?;
+    pairs.retain(|(name, _)| !name.eq_ignore_ascii_case("content-length"));
+    if !pairs
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+    {
+        pairs.push((
+            "Content-Type".to_owned(),
+            mime::APPLICATION_JSON.to_string(),
+        ));
+    }
+
+    let output_headers = pairs
         .iter()
         .map(|(name, value)| {
             Ok((name.parse::<HeaderName>()?, value.parse::<HeaderValue>()?))
         })
         .collect::<anyhow::Result<Vec<_>>>()
         .map_err(|_| bad_request())?;
+    let body = describe_headers(&pairs)?;
 
-    let mut res = response();
+    let mut res = response().typed_header(ContentLength(body.len() as u64));
     for (key, value) in output_headers {
         res = res.header(key, value);
     }
-    res.into()
+    res.body(body)
 }
 
 #[cfg(test)]
@@ -51,8 +76,14 @@ mod test {
             .unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "x-request-id: 1234\nuser-agent: ExampleBot")
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"headers": {
+                "User-Agent": "ExampleBot",
+                "X-Request-Id": "1234",
+            }})
+        );
     }
 
     #[tokio::test]
@@ -66,4 +97,50 @@ mod test {
         assert_eq!(res.status(), StatusCode::OK);
         assert_eq!(res.headers().get("X-Request-ID").unwrap(), "1234")
     }
+
+    #[tokio::test]
+    async fn test_response_headers_body() {
+        let res = request()
+            .path("/?X-Key=a&X-Key=b")
+            .handle(response_headers)
+            .await
+            .unwrap();
+
+        let values = res.headers().get_all("x-key").iter().collect::<Vec<_>>();
+        assert_eq!(values, ["a", "b"]);
+        assert_eq!(
+            res.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+
+        let length = res.headers().get("content-length").unwrap().clone();
+        let body = res.read_body().await.unwrap();
+        assert_eq!(length, body.len().to_string().as_str());
+
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "Content-Length": length.to_str().unwrap(),
+                "Content-Type": "application/json",
+                "X-Key": ["a", "b"],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_response_headers_content_type() {
+        let res = request()
+            .path("/?Content-Type=text/plain")
+            .handle(response_headers)
+            .await
+            .unwrap();
+
+        let values = res
+            .headers()
+            .get_all("content-type")
+            .iter()
+            .collect::<Vec<_>>();
+        assert_eq!(values, ["text/plain"]);
+    }
 }
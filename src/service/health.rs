@@ -0,0 +1,174 @@
+//! Health and readiness probes whose answers are set at runtime, to test
+//! how orchestrators and load balancers react to an instance going down
+use crate::handler::Handler;
+use crate::http::{bad_request, response, Error, Request, Result, StatusCode};
+use async_trait::async_trait;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+
+/// What a probe answers when set down without a status
+const DEFAULT_DOWN_STATUS: StatusCode = StatusCode::SERVICE_UNAVAILABLE;
+
+#[derive(Deserialize)]
+struct AdminQueryParams {
+    set: Option<String>,
+    /// For the probe to fail with, a 4xx or 5xx
+    status: Option<u16>,
+}
+
+#[derive(Serialize)]
+struct State {
+    status: &'static str,
+    code: u16,
+}
+
+/// A probe, shared by the endpoint answering it and the one setting it
+///
+/// Holds the status it fails with, or 0 while up.
+#[derive(Clone, Debug, Default)]
+pub struct Health(Arc<AtomicU16>);
+
+impl Health {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The endpoint setting the probe up or down
+    pub fn admin(&self) -> Admin {
+        Admin(self.clone())
+    }
+
+    fn set(&self, code: u16) {
+        self.0.store(code, Ordering::Relaxed);
+    }
+
+    fn status(&self) -> StatusCode {
+        match self.0.load(Ordering::Relaxed) {
+            0 => StatusCode::OK,
+            code => StatusCode::from_u16(code).unwrap_or(DEFAULT_DOWN_STATUS),
+        }
+    }
+
+    fn answer(&self) -> Result {
+        let status = self.status();
+        response().status(status).json(&State {
+            status: match status.is_success() {
+                true => "up",
+                false => "down",
+            },
+            code: status.as_u16(),
+        })
+    }
+}
+
+#[async_trait]
+impl Handler for Health {
+    async fn handle(&self, _req: Request) -> Result {
+        self.answer()
+    }
+}
+
+/// Sets a probe with `?set=up` or `?set=down&status=...`, answering with
+/// what the probe answers from then on
+#[derive(Clone, Debug)]
+pub struct Admin(Health);
+
+#[async_trait]
+impl Handler for Admin {
+    async fn handle(&self, req: Request) -> Result {
+        let query =
+            req.query::<AdminQueryParams>().map_err(|_| bad_request())?;
+        let code = match (query.set.as_deref(), query.status) {
+            (None, None) => return self.0.answer().map(into_ok),
+            (Some("up"), None) => 0,
+            (Some("down"), None) => DEFAULT_DOWN_STATUS.as_u16(),
+            (Some("down"), Some(code)) => StatusCode::from_u16(code)
+                .ok()
+                .filter(|status| {
+                    status.is_client_error() || status.is_server_error()
+                })
+                .map(|status| status.as_u16())
+                .ok_or_else(|| {
+                    Error::bad_request("status must be a 4xx or 5xx")
+                })?,
+            _ => return Err(Error::bad_request("set must be up or down")),
+        };
+        self.0.set(code);
+        self.0.answer().map(into_ok)
+    }
+}
+
+/// The admin endpoints succeed whatever the probe answers
+fn into_ok(mut res: crate::http::Response) -> crate::http::Response {
+    *res.status_mut() = StatusCode::OK;
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use serde_json::Value;
+
+    async fn probe(health: &Health) -> (StatusCode, Value) {
+        let res = request().handle(health.clone()).await.unwrap();
+        let status = res.status();
+        (status, res.read_body_json().await.unwrap())
+    }
+
+    async fn set(health: &Health, query: &str) -> crate::http::Response {
+        request()
+            .path(&format!("/admin/health?{}", query))
+            .handle(health.admin())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_up_by_default() {
+        let (status, body) = probe(&Health::new()).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "up");
+    }
+
+    #[tokio::test]
+    async fn test_set_down_and_up() {
+        let health = Health::new();
+
+        let res = set(&health, "set=down").await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let (status, body) = probe(&health).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "down");
+
+        set(&health, "set=down&status=500").await;
+        assert_eq!(probe(&health).await.0, StatusCode::INTERNAL_SERVER_ERROR);
+
+        set(&health, "set=up").await;
+        assert_eq!(probe(&health).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_admin_reads_state() {
+        let health = Health::new();
+        set(&health, "set=down").await;
+
+        let res = set(&health, "").await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.read_body_utf8().await.unwrap().contains("down"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_settings() {
+        let health = Health::new();
+        for query in
+            ["set=sideways", "set=down&status=200", "set=up&status=503"]
+        {
+            let res = set(&health, query).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(probe(&health).await.0, StatusCode::OK);
+    }
+}
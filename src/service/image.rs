@@ -0,0 +1,131 @@
+use crate::headers::ContentType;
+use crate::http::{not_acceptable, response, Request, Result};
+use hyper::header::{HeaderValue, VARY};
+
+struct Image {
+    content_type: &'static str,
+    data: &'static [u8],
+}
+
+const PNG: Image = Image {
+    content_type: "image/png",
+    data: include_bytes!("../../assets/images/sample.png"),
+};
+
+const JPEG: Image = Image {
+    content_type: "image/jpeg",
+    data: include_bytes!("../../assets/images/sample.jpeg"),
+};
+
+const WEBP: Image = Image {
+    content_type: "image/webp",
+    data: include_bytes!("../../assets/images/sample.webp"),
+};
+
+const SVG: Image = Image {
+    content_type: "image/svg+xml",
+    data: include_bytes!("../../assets/images/sample.svg"),
+};
+
+/// In order of preference when the client doesn't mind
+const IMAGES: [&Image; 4] = [&PNG, &JPEG, &WEBP, &SVG];
+
+fn send(image: &Image) -> Result {
+    response()
+        .typed_header(ContentType::from(
+            image.content_type.parse::<mime::Mime>().unwrap(),
+        ))
+        .body(image.data)
+}
+
+/// Picks a format from the `Accept` header
+pub async fn image(req: Request) -> Result {
+    let offered = IMAGES.map(|image| image.content_type);
+    let content_type = req.negotiate(&offered).ok_or_else(not_acceptable)?;
+    let image = IMAGES
+        .iter()
+        .find(|image| image.content_type == content_type)
+        .unwrap();
+
+    let mut res = send(image)?;
+    res.headers_mut()
+        .insert(VARY, HeaderValue::from_static("accept"));
+    Ok(res)
+}
+
+pub async fn png(_req: Request) -> Result {
+    send(&PNG)
+}
+
+pub async fn jpeg(_req: Request) -> Result {
+    send(&JPEG)
+}
+
+pub async fn webp(_req: Request) -> Result {
+    send(&WEBP)
+}
+
+pub async fn svg(_req: Request) -> Result {
+    send(&SVG)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_image_default() {
+        let res = request().handle(image).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-type").unwrap(), "image/png");
+        assert_eq!(res.headers().get("vary").unwrap(), "accept");
+        assert!(res.read_body().await.unwrap().starts_with(b"\x89PNG"));
+    }
+
+    #[tokio::test]
+    async fn test_image_negotiated() {
+        let res = request()
+            .header("accept", "image/webp,image/*;q=0.8")
+            .handle(image)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-type").unwrap(), "image/webp");
+        assert!(res.read_body().await.unwrap().starts_with(b"RIFF"));
+    }
+
+    #[tokio::test]
+    async fn test_image_not_acceptable() {
+        let res = request()
+            .header("accept", "text/html")
+            .handle(image)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[tokio::test]
+    async fn test_jpeg() {
+        let res = request().handle(jpeg).await.unwrap();
+
+        assert_eq!(res.headers().get("content-type").unwrap(), "image/jpeg");
+        assert!(res.read_body().await.unwrap().starts_with(b"\xff\xd8\xff"));
+    }
+
+    #[tokio::test]
+    async fn test_svg() {
+        let res = request().handle(svg).await.unwrap();
+
+        assert_eq!(res.headers().get("content-type").unwrap(), "image/svg+xml");
+        assert!(res
+            .read_body_utf8()
+            .await
+            .unwrap()
+            .contains("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    }
+}
@@ -1,8 +1,14 @@
+//! The index page, listing the endpoints from the route metadata, as HTML
+//! or as JSON for clients that ask for it
 use crate::handler::Handler;
-use crate::http::{html, Bytes, Request, Result};
+use crate::http::{html, not_acceptable, response, Bytes, Request, Result};
 use crate::router::Route;
 use askama::Template;
 use async_trait::async_trait;
+use hyper::header::{HeaderValue, CONTENT_TYPE, VARY};
+use serde_derive::Serialize;
+
+const MEDIA_TYPES: [&str; 2] = ["text/html", "application/json"];
 
 #[derive(Template)]
 #[template(path = "index.html")]
@@ -10,13 +16,35 @@ struct IndexTemplate<'a> {
     routes: Vec<&'a Route>,
 }
 
-#[derive(Debug, Clone)]
-pub struct Index(Bytes);
+#[derive(Serialize)]
+struct Endpoint<'a> {
+    path: String,
+    methods: Vec<&'a str>,
+    tags: &'a [&'static str],
+    description: Option<&'static str>,
+    /// A path to try the endpoint at, with its parameters filled in
+    example: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    html: Bytes,
+    json: Bytes,
+}
 
 #[async_trait]
 impl Handler for Index {
-    async fn handle(&self, _: Request) -> Result {
-        html(self.0.clone())
+    async fn handle(&self, req: Request) -> Result {
+        let negotiated = req.negotiate(&MEDIA_TYPES).ok_or_else(not_acceptable);
+        let mut res = match negotiated? {
+            "application/json" => response()
+                .header(CONTENT_TYPE, "application/json")
+                .body(self.json.clone()),
+            _ => html(self.html.clone()),
+        }?;
+        res.headers_mut()
+            .insert(VARY, HeaderValue::from_static("accept"));
+        Ok(res)
     }
 }
 
@@ -27,8 +55,91 @@ pub fn render_index<'a>(routes: impl IntoIterator<Item = &'a Route>) -> String {
     template.render().unwrap()
 }
 
+fn render_json<'a>(routes: impl IntoIterator<Item = &'a Route>) -> Vec<u8> {
+    let endpoints = routes
+        .into_iter()
+        .map(|route| Endpoint {
+            path: route.path().to_string(),
+            methods: route
+                .methods()
+                .iter()
+                .map(|method| method.as_str())
+                .collect(),
+            tags: route.tags(),
+            description: route.description(),
+            // The root's example is the empty relative reference
+            example: route.example_path().map(|example| match example {
+                "" => "/",
+                example => example,
+            }),
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_vec_pretty(&endpoints).unwrap_or_default()
+}
+
 impl<'a, IT: IntoIterator<Item = &'a Route>> From<IT> for Index {
     fn from(routes: IT) -> Self {
-        Index(render_index(routes).into())
+        let routes = routes.into_iter().collect::<Vec<_>>();
+        Index {
+            html: render_index(routes.iter().copied()).into(),
+            json: render_json(routes).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::router::route;
+    use crate::test::*;
+    use hyper::StatusCode;
+    use uri_path::path;
+
+    fn index() -> Index {
+        let routes: Vec<Route> = vec![
+            route(path!()).description("This page").into(),
+            route(path!("status" / [code: u16]))
+                .description("Returns the status code")
+                .add_example_param("code", "418")
+                .into(),
+        ];
+        Index::from(&routes)
+    }
+
+    #[tokio::test]
+    async fn test_html() {
+        let res = request().handle(index()).await.unwrap();
+
+        assert_eq!(res.headers()["vary"], "accept");
+        let body = res.read_body_utf8().await.unwrap();
+        assert!(body.contains(r#"<a href="/status/418">"#));
+        assert!(body.contains("Returns the status code"));
+    }
+
+    #[tokio::test]
+    async fn test_json() {
+        let res = request()
+            .header("accept", "application/json")
+            .handle(index())
+            .await
+            .unwrap();
+
+        assert_eq!(res.headers()["content-type"], "application/json");
+        let endpoints = res.read_body_json().await.unwrap();
+        assert_eq!(endpoints[1]["path"], "/status/:code");
+        assert_eq!(endpoints[1]["methods"][0], "GET");
+        assert_eq!(endpoints[1]["example"], "/status/418");
+        assert!(endpoints[0]["description"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_not_acceptable() {
+        let res = request()
+            .header("accept", "image/png")
+            .handle(index())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
     }
 }
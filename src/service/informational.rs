@@ -0,0 +1,122 @@
+//! Informational responses ahead of the final one, for testing how clients
+//! handle 103 Early Hints and friends
+use crate::http::{bad_request, json, Request, Result, StatusCode};
+use hyper::header::LINK;
+use hyper::HeaderMap;
+use serde_derive::Serialize;
+use std::time::Duration;
+
+const DEFAULT_STATUS: u16 = 103;
+/// Enough for any test, few enough to keep the response head bounded
+const MAX_INFORMATIONAL: usize = 10;
+
+/// Statuses such as `103,102`, which have to be 1xx but not 101
+fn parse_statuses(statuses: &str) -> Option<Vec<StatusCode>> {
+    let statuses = statuses
+        .split(',')
+        .map(|status| StatusCode::from_bytes(status.trim().as_bytes()).ok())
+        .collect::<Option<Vec<_>>>()?;
+    let valid = statuses.len() <= MAX_INFORMATIONAL
+        && statuses.iter().all(|status| {
+            status.is_informational()
+                && *status != StatusCode::SWITCHING_PROTOCOLS
+        });
+    valid.then_some(statuses)
+}
+
+#[derive(Serialize)]
+struct Informational {
+    /// The statuses sent ahead, in order
+    sent: Vec<u16>,
+}
+
+/// Sends each informational `status`, `103` by default, `delay` seconds
+/// apart and before the final response, the 103s with the `link` headers
+pub async fn informational(req: Request) -> Result {
+    let query = req
+        .query::<Vec<(String, String)>>()
+        .map_err(|_| bad_request())?;
+    let mut statuses = vec![StatusCode::from_u16(DEFAULT_STATUS).unwrap()];
+    let mut links = HeaderMap::new();
+    let mut delay = Duration::ZERO;
+    for (key, value) in &query {
+        match key.as_str() {
+            "status" => {
+                statuses = parse_statuses(value).ok_or_else(bad_request)?
+            }
+            "link" => {
+                links.append(LINK, value.parse().map_err(|_| bad_request())?);
+            }
+            "delay" => {
+                let seconds =
+                    value.parse::<f64>().map_err(|_| bad_request())?;
+                if !(seconds >= 0.0 && seconds.is_finite()) {
+                    return Err(bad_request());
+                }
+                delay = Duration::from_secs_f64(seconds)
+                    .min(req.config().max_delay());
+            }
+            _ => {}
+        }
+    }
+
+    let none = HeaderMap::new();
+    for status in &statuses {
+        let headers = match status.as_u16() {
+            103 => &links,
+            _ => &none,
+        };
+        req.send_informational(*status, headers)?;
+        req.unless_disconnected(tokio::time::sleep(delay)).await?;
+    }
+    json(&Informational {
+        sent: statuses.iter().map(StatusCode::as_u16).collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::Interim;
+    use crate::test::*;
+    use hyper::Version;
+
+    #[test]
+    fn test_parse_statuses() {
+        assert_eq!(
+            parse_statuses("103, 102"),
+            Some(vec![
+                StatusCode::from_u16(103).unwrap(),
+                StatusCode::PROCESSING
+            ])
+        );
+        assert_eq!(parse_statuses("101"), None);
+        assert_eq!(parse_statuses("200"), None);
+        assert_eq!(parse_statuses("103,"), None);
+    }
+
+    #[tokio::test]
+    async fn test_informational() {
+        let res = request()
+            .path("/informational?status=102,103")
+            .extension(Interim::new())
+            .handle(informational)
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+
+        assert_eq!(body["sent"], serde_json::json!([102, 103]));
+    }
+
+    #[tokio::test]
+    async fn test_informational_http2() {
+        let res = request()
+            .version(Version::HTTP_2)
+            .extension(Interim::new())
+            .handle(informational)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::HTTP_VERSION_NOT_SUPPORTED);
+    }
+}
@@ -1,34 +1,53 @@
-use crate::headers::XForwardedFor;
-use crate::http::{bad_request, ok, Request, Result};
+use crate::http::{bad_request, json, Request, Result};
+use crate::service::reflection::Reflection;
 
 pub async fn ip(req: Request) -> Result {
-    let ip = req
-        .typed_header::<XForwardedFor>()
-        .map(|header| header.ip_addr())
-        .or_else(|| Some(req.client_addr()?.ip()))
-        .ok_or_else(bad_request)?;
+    req.client_addr().ok_or_else(bad_request)?;
 
-    ok(format!("{}", ip))
+    json(&Reflection::new().origin(&req))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::headers::XForwardedFor;
+    use crate::http::TrustedProxies;
     use crate::test::*;
     use hyper::http::StatusCode;
+    use serde_json::Value;
+
+    async fn origin_of(res: crate::http::Response) -> Value {
+        let body = res.read_body_json().await.unwrap();
+        body["origin"].clone()
+    }
 
     #[tokio::test]
     async fn test_ip_x_forwarded_for() {
         let res = request()
             .typed_header(XForwardedFor::client("1.2.3.4".parse().unwrap()))
+            .client_addr("10.0.0.1:1234".parse().unwrap())
+            .trusted_proxies(TrustedProxies::new(vec!["10.0.0.0/8"
+                .parse()
+                .unwrap()]))
+            .handle(ip)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(origin_of(res).await, "1.2.3.4");
+    }
+
+    #[tokio::test]
+    async fn test_ip_x_forwarded_for_untrusted() {
+        let res = request()
+            .typed_header(XForwardedFor::client("1.2.3.4".parse().unwrap()))
+            .client_addr("10.0.0.1:1234".parse().unwrap())
             .handle(ip)
             .await
             .unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "1.2.3.4");
+        assert_eq!(origin_of(res).await, "10.0.0.1");
     }
 
     #[tokio::test]
@@ -40,8 +59,7 @@ mod test {
             .unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "127.0.0.1");
+        assert_eq!(origin_of(res).await, "127.0.0.1");
     }
 
     #[tokio::test]
@@ -61,7 +79,6 @@ mod test {
             .unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "127.0.0.1");
+        assert_eq!(origin_of(res).await, "127.0.0.1");
     }
 }
@@ -0,0 +1,88 @@
+use crate::http::{bad_request, redirect_to, Request, Result};
+use crate::service::template::render;
+use askama::Template;
+use std::cmp::min;
+
+const MAX_LINKS: u16 = 200;
+
+struct Link {
+    index: u16,
+    current: bool,
+}
+
+#[derive(Template)]
+#[template(path = "links.html")]
+struct LinksTemplate {
+    n: u16,
+    links: Vec<Link>,
+}
+
+pub async fn links(req: Request) -> Result {
+    let n = req.param::<u16>("n").ok_or_else(bad_request)?;
+    let uri = format!("/links/{}/0", n)
+        .parse()
+        .map_err(|_| bad_request())?;
+
+    redirect_to(uri)
+}
+
+/// A page of `n` links to its siblings, `offset` being the current one
+pub async fn page(req: Request) -> Result {
+    let n = min(req.param::<u16>("n").ok_or_else(bad_request)?, MAX_LINKS);
+    let offset = req.param::<u16>("offset").ok_or_else(bad_request)?;
+
+    let links = (0..n)
+        .map(|index| Link {
+            index,
+            current: index == offset,
+        })
+        .collect();
+
+    render(&LinksTemplate { n, links })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_links() {
+        let res = request().param("n", "10").handle(links).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert_eq!(res.headers().get("location").unwrap(), "/links/10/0");
+    }
+
+    #[tokio::test]
+    async fn test_page() {
+        let res = request()
+            .param("n", "3")
+            .param("offset", "1")
+            .handle(page)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-type").unwrap(), "text/html");
+        let body = res.read_body_utf8().await.unwrap();
+        assert!(body.contains(r#"<a href="/links/3/0">0</a>"#));
+        assert!(!body.contains(r#"<a href="/links/3/1">"#));
+        assert!(body.contains(r#"<a href="/links/3/2">2</a>"#));
+        assert!(!body.contains("/links/3/3"));
+    }
+
+    #[tokio::test]
+    async fn test_page_capped() {
+        let res = request()
+            .param("n", "1000")
+            .param("offset", "0")
+            .handle(page)
+            .await
+            .unwrap();
+
+        let body = res.read_body_utf8().await.unwrap();
+        assert_eq!(body.matches("<a ").count(), MAX_LINKS as usize - 1);
+    }
+}
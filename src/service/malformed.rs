@@ -0,0 +1,114 @@
+//! Responses breaking HTTP/1.1 in ways hyper refuses to, written straight
+//! to the connection
+use crate::handler::Handler;
+use crate::http::{response, Bytes, Replacement, Request, Result, StatusCode};
+use async_trait::async_trait;
+
+/// A whole response, head and body, sent as it is before the connection is
+/// closed
+pub struct Malformed(&'static [u8]);
+
+#[async_trait]
+impl Handler for Malformed {
+    async fn handle(&self, req: Request) -> Result {
+        let replacement = Replacement::new(Bytes::from_static(self.0));
+        if !req.replace_response(replacement) {
+            return response()
+                .status(StatusCode::HTTP_VERSION_NOT_SUPPORTED)
+                .body("Only served over HTTP/1.x");
+        }
+        response().into()
+    }
+}
+
+/// Announces more of a body than is sent before the connection closes
+pub const CONTENT_LENGTH: Malformed = Malformed(
+    b"HTTP/1.1 200 OK\r\n\
+      Content-Type: text/plain\r\n\
+      Content-Length: 100\r\n\
+      Connection: close\r\n\
+      \r\n\
+      This body is shorter than announced\n",
+);
+
+/// Contradicts itself on the type and length of the body
+pub const DUPLICATE_HEADERS: Malformed = Malformed(
+    b"HTTP/1.1 200 OK\r\n\
+      Content-Type: text/plain\r\n\
+      Content-Type: application/json\r\n\
+      Content-Length: 6\r\n\
+      Content-Length: 7\r\n\
+      Connection: close\r\n\
+      \r\n\
+      hello\n",
+);
+
+/// A space in a header name, control characters and Latin-1 in values, and
+/// a line ended by a bare LF
+pub const HEADER_BYTES: Malformed = Malformed(
+    b"HTTP/1.1 200 OK\r\n\
+      Content-Type: text/plain\r\n\
+      X-Bad Name: a space in the name\r\n\
+      X-Control: \x01\x7f\r\n\
+      X-Latin-1: caf\xe9\r\n\
+      X-Bare-LF: value\n\
+      Content-Length: 6\r\n\
+      Connection: close\r\n\
+      \r\n\
+      hello\n",
+);
+
+/// Frames the body both by length and as chunks, which read differently
+pub const LENGTH_AND_CHUNKED: Malformed = Malformed(
+    b"HTTP/1.1 200 OK\r\n\
+      Content-Type: text/plain\r\n\
+      Content-Length: 5\r\n\
+      Transfer-Encoding: chunked\r\n\
+      Connection: close\r\n\
+      \r\n\
+      b\r\n\
+      hello world\r\n\
+      0\r\n\
+      \r\n",
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{Abort, Aborting};
+    use crate::test::*;
+    use futures::prelude::*;
+    use hyper::Version;
+
+    #[tokio::test]
+    async fn test_malformed() {
+        let aborting = Aborting::new();
+        request()
+            .extension(aborting.clone())
+            .handle(LENGTH_AND_CHUNKED)
+            .await
+            .unwrap();
+
+        let replaced = match aborting.get() {
+            Some(Abort::Replace(replacement)) => replacement.take().unwrap(),
+            other => panic!("not replaced: {:?}", other),
+        };
+        let replaced = replaced.collect::<Vec<_>>().await.concat();
+        assert!(replaced.starts_with(b"HTTP/1.1 200 OK\r\n"));
+        assert!(replaced.ends_with(b"0\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_http2() {
+        let aborting = Aborting::new();
+        let res = request()
+            .version(Version::HTTP_2)
+            .extension(aborting.clone())
+            .handle(CONTENT_LENGTH)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::HTTP_VERSION_NOT_SUPPORTED);
+        assert!(aborting.get().is_none());
+    }
+}
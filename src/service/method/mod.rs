@@ -1,36 +1,47 @@
-mod body;
-use self::body::body;
-use crate::http::{bad_request, ok, Request, Result};
-use itertools::Itertools;
+//! Echoing requests made with a given method
+use crate::http::{json, Error, Request, Result};
+use crate::service::reflection::Reflection;
+use hyper::Method;
+
+fn reflection(req: &Request) -> std::result::Result<Reflection, Error> {
+    Ok(Reflection::new()
+        .args(req)?
+        .headers(req)
+        .origin(req)
+        .url(req))
+}
 
 pub async fn get(req: Request) -> Result {
-    let body = {
-        req.query::<Vec<(String, String)>>()
-            .map_err(|_| bad_request())?
-            .into_iter()
-            .format_with("\n", |(key, value), f| {
-                f(&format_args!("{} = {}", key, value))
-            })
-            .to_string()
-    };
+    json(&reflection(&req)?)
+}
 
-    ok(body)
+/// Also describes the body, parsed as a form or JSON
+async fn with_body(mut req: Request) -> Result {
+    let reflection = reflection(&req)?.body(&mut req).await?;
+    json(&reflection)
 }
 
 pub async fn post(req: Request) -> Result {
-    body(req).await
+    with_body(req).await
 }
 
 pub async fn put(req: Request) -> Result {
-    body(req).await
+    with_body(req).await
 }
 
 pub async fn patch(req: Request) -> Result {
-    body(req).await
+    with_body(req).await
 }
 
 pub async fn delete(req: Request) -> Result {
-    body(req).await
+    with_body(req).await
+}
+
+pub async fn echo(req: Request) -> Result {
+    match *req.method() {
+        Method::GET => get(req).await,
+        _ => with_body(req).await,
+    }
 }
 
 #[cfg(test)]
@@ -39,54 +50,37 @@ mod test {
     use crate::headers::ContentType;
     use crate::test::*;
     use hyper::{Method, StatusCode};
-    use std::collections::HashSet;
-    use std::iter::FromIterator;
-
-    #[tokio::test]
-    async fn test_get() {
-        let res = request().path("/?key=val").handle(get).await.unwrap();
+    use serde_json::{json, Value};
 
+    async fn reflected(res: crate::http::Response) -> Value {
         assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "key = val");
+        assert_eq!(res.headers()["content-type"], "application/json");
+        res.read_body_json().await.unwrap()
     }
 
     #[tokio::test]
-    async fn test_multi_get() {
+    async fn test_get() {
         let res = request()
-            .path("/?key=val&other=something&key=another")
+            .path("/get?key=val&other=something&key=another")
+            .header("x-request-id", "1234")
+            .client_addr("127.0.0.1:1234".parse().unwrap())
             .handle(get)
             .await
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        let result: HashSet<&str> = HashSet::from_iter(body.split("\n"));
-        let expected = HashSet::from_iter(vec![
-            "key = val",
-            "other = something",
-            "key = another",
-        ]);
-        assert_eq!(expected, result)
-    }
-
-    #[tokio::test]
-    async fn test_post() {
-        let res = request()
-            .method(Method::POST)
-            .typed_header(ContentType::form_url_encoded())
-            .body("key=val")
-            .handle(post)
-            .await
-            .unwrap();
-
-        assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "key = val");
+        assert_eq!(
+            reflected(res).await,
+            json!({
+                "args": {"key": ["val", "another"], "other": "something"},
+                "headers": {"X-Request-Id": "1234"},
+                "origin": "127.0.0.1",
+                "url": "/get?key=val&other=something&key=another",
+            })
+        );
     }
 
     #[tokio::test]
-    async fn test_multi_post() {
+    async fn test_post_form() {
         let res = request()
             .method(Method::POST)
             .typed_header(ContentType::form_url_encoded())
@@ -95,96 +89,86 @@ mod test {
             .await
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        let result: HashSet<&str> = HashSet::from_iter(body.split("\n"));
-        let expected = HashSet::from_iter(vec![
-            "key = val",
-            "other = something",
-            "key = another",
-        ]);
-        assert_eq!(expected, result)
+        let body = reflected(res).await;
+        assert_eq!(
+            body["form"],
+            json!({"key": ["val", "another"], "other": "something"})
+        );
+        assert_eq!(body["data"], "");
+        assert_eq!(body["json"], Value::Null);
     }
 
     #[tokio::test]
-    async fn test_put() {
+    async fn test_post_form_with_charset() {
         let res = request()
-            .method(Method::PUT)
-            .typed_header(ContentType::form_url_encoded())
+            .method(Method::POST)
+            .header(
+                "content-type",
+                "application/x-www-form-urlencoded; charset=UTF-8",
+            )
             .body("key=val")
-            .handle(put)
+            .handle(post)
             .await
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "key = val");
+        let body = reflected(res).await;
+        assert_eq!(body["form"], json!({"key": "val"}));
+        assert_eq!(body["data"], "");
     }
 
     #[tokio::test]
-    async fn test_multi_put() {
+    async fn test_put_json() {
         let res = request()
             .method(Method::PUT)
-            .typed_header(ContentType::form_url_encoded())
-            .body("key=val&other=something&key=another")
+            .typed_header(ContentType::json())
+            .body(r#"{"key": "val"}"#)
             .handle(put)
             .await
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        let result: HashSet<&str> = HashSet::from_iter(body.split("\n"));
-        let expected = HashSet::from_iter(vec![
-            "key = val",
-            "other = something",
-            "key = another",
-        ]);
-        assert_eq!(expected, result)
+        let body = reflected(res).await;
+        assert_eq!(body["json"], json!({"key": "val"}));
+        assert_eq!(body["data"], r#"{"key": "val"}"#);
+        assert_eq!(body["form"], json!({}));
     }
 
     #[tokio::test]
     async fn test_patch() {
         let res = request()
             .method(Method::PATCH)
-            .typed_header(ContentType::form_url_encoded())
-            .body("key=val")
+            .path("/patch?key=val")
+            .body("raw")
             .handle(patch)
             .await
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "key = val");
+        let body = reflected(res).await;
+        assert_eq!(body["args"], json!({"key": "val"}));
+        assert_eq!(body["data"], "raw");
     }
 
     #[tokio::test]
-    async fn test_multi_patch() {
+    async fn test_delete() {
         let res = request()
-            .method(Method::PATCH)
-            .typed_header(ContentType::form_url_encoded())
-            .body("key=val&other=something&key=another")
-            .handle(patch)
+            .method(Method::DELETE)
+            .handle(delete)
             .await
             .unwrap();
 
-        assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        let result: HashSet<&str> = HashSet::from_iter(body.split("\n"));
-        let expected = HashSet::from_iter(vec![
-            "key = val",
-            "other = something",
-            "key = another",
-        ]);
-        assert_eq!(expected, result)
+        assert_eq!(reflected(res).await["data"], "");
     }
 
     #[tokio::test]
-    async fn test_delete() {
+    async fn test_echo() {
+        let res = request().handle(echo).await.unwrap();
+        assert!(reflected(res).await.get("data").is_none());
+
         let res = request()
-            .method(Method::DELETE)
-            .handle(delete)
+            .method(Method::POST)
+            .body("raw")
+            .handle(echo)
             .await
             .unwrap();
-        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(reflected(res).await["data"], "raw");
     }
 }
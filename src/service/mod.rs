@@ -1,26 +1,105 @@
-use crate::router::{route, Route, Router};
+use crate::config::{Config, Group};
+use crate::handler::extract;
+use crate::middleware::{ConcurrencyLimit, Metrics};
+use crate::router::{route, Route, Router, RouterBuilder, Timeout};
+use crate::service::admin::{Admin, Controls};
+use crate::service::health::Health;
+use crate::service::index::Index;
+use crate::service::openapi::OpenApi;
+use crate::service::routes::Routes;
 use hyper::http::Method;
 use uri_path::path;
 
-mod auth;
-mod bytes;
-mod cache;
-mod cookies;
-mod delay;
-mod headers;
-mod index;
-mod ip;
-mod method;
-mod redirect;
-mod status_code;
-mod user_agent;
-
-pub fn router() -> Router {
-    let builder = Router::builder()
+macro_rules! substitute_in_test {
+    ($value:expr => $substitute:expr) => {{
+        if cfg!(test) {
+            $substitute
+        } else {
+            $value
+        }
+    }};
+}
+
+pub mod abort;
+pub mod admin;
+pub mod anything;
+pub mod auth;
+pub mod base64;
+pub mod bins;
+pub mod bytes;
+pub mod cache;
+pub mod callback;
+pub mod certificate;
+pub mod chunked;
+pub mod compression;
+pub mod connection;
+pub mod cookies;
+pub mod delay;
+pub mod drip;
+pub mod expect;
+pub mod fetch;
+pub mod fixtures;
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod headers;
+pub mod health;
+pub mod image;
+pub mod index;
+pub mod informational;
+pub mod ip;
+pub mod links;
+pub mod malformed;
+pub mod method;
+pub mod openapi;
+pub mod poll;
+pub mod range;
+pub mod rate_limited;
+pub mod redirect;
+pub mod reflection;
+pub mod respond;
+pub mod routes;
+pub mod slow;
+pub mod sse;
+pub mod static_files;
+pub mod status_code;
+pub mod stream;
+pub mod template;
+pub mod templating;
+pub mod upload;
+pub mod user_agent;
+pub mod uuid;
+pub mod websocket;
+
+fn inspection(builder: RouterBuilder) -> RouterBuilder {
+    builder
         .install(
             crate::service::ip::ip,
             route(path!("ip")).description("Returns Origin IP"),
         )
+        .install(
+            crate::service::connection::info,
+            route(path!("connection")).description(
+                "Returns the protocol, addresses and TLS parameters of the \
+                 connection, and how many requests it has carried",
+            ),
+        )
+        .install(
+            crate::service::certificate::cert,
+            route(path!("cert")).description(
+                "Returns the subject, issuer, validity and fingerprint of \
+                 the client certificate, over TLS with tls-client-ca",
+            ),
+        )
+        .install(
+            crate::service::graphql::graphql,
+            route(path!("graphql"))
+                .methods(vec![Method::GET, Method::POST])
+                .description(
+                    "Echoes the parsed GraphQL operation, its variables and \
+                     the headers as the data, answering introspection too",
+                ),
+        )
         .install(
             crate::service::user_agent::user_agent,
             route(path!("user-agent")).description("Returns user-agent"),
@@ -29,6 +108,10 @@ pub fn router() -> Router {
             crate::service::headers::headers,
             route(path!("headers")).description("Returns headers"),
         )
+}
+
+fn methods(builder: RouterBuilder) -> RouterBuilder {
+    builder
         .install(
             crate::service::method::get,
             route(path!("get"))
@@ -45,7 +128,7 @@ pub fn router() -> Router {
             crate::service::method::patch,
             route(path!("patch"))
                 .method(Method::PATCH)
-                .description("Returns PUT data"),
+                .description("Returns PATCH data"),
         )
         .install(
             crate::service::method::put,
@@ -59,12 +142,39 @@ pub fn router() -> Router {
                 .method(Method::DELETE)
                 .description("Returns DELETE data"),
         )
-        .install(
-            crate::service::status_code::status_code,
-            route(path!("status" / code))
-                .description("Returns given HTTP Status code")
-                .add_example_param("code", "418"),
-        )
+}
+
+fn anything(builder: RouterBuilder) -> RouterBuilder {
+    builder.install(
+        crate::service::anything::anything,
+        route(path!("anything" / [*rest]))
+            .any_method()
+            .description(
+                "Returns request data, including method used, at any path \
+                 below /anything",
+            )
+            .add_example_param("rest", ""),
+    )
+}
+
+fn status(builder: RouterBuilder) -> RouterBuilder {
+    builder.install(
+        crate::service::status_code::status_code,
+        route(path!("status" / code))
+            .description(
+                "Returns given HTTP Status code, or a random one from a \
+                     weighted list like 200:0.7,500:0.3",
+            )
+            .add_example_param("code", "418"),
+    )
+}
+
+fn auth(builder: RouterBuilder) -> RouterBuilder {
+    use crate::service::auth::{DigestAuth, Endpoint, Provider};
+
+    let digest = DigestAuth::new();
+    let provider = Provider::new();
+    builder
         .install(
             crate::service::auth::basic,
             route(path!("basic-auth" / user / passwd))
@@ -72,42 +182,242 @@ pub fn router() -> Router {
                 .add_example_param("user", "user")
                 .add_example_param("passwd", "passwd"),
         )
+        .install(
+            digest.clone(),
+            route(path!("digest-auth" / qop / user / passwd))
+                .description(
+                    "HTTP Digest Auth Challenge (qop is auth or auth-int)",
+                )
+                .add_example_param("qop", "auth")
+                .add_example_param("user", "user")
+                .add_example_param("passwd", "passwd"),
+        )
+        .install(
+            digest,
+            route(path!("digest-auth" / qop / user / passwd / algorithm))
+                .description("HTTP Digest Auth Challenge with MD5 or SHA-256")
+                .add_example_param("qop", "auth")
+                .add_example_param("user", "user")
+                .add_example_param("passwd", "passwd")
+                .add_example_param("algorithm", "SHA-256"),
+        )
+        .install(
+            crate::service::auth::token,
+            route(path!("bearer")).description("Bearer Auth with any token"),
+        )
         .install(
             crate::service::auth::bearer,
             route(path!("bearer-auth" / token))
                 .description("Bearer Auth Challenge")
                 .add_example_param("token", "random-token"),
         )
+        .install(
+            crate::service::auth::hmac,
+            route(path!("hmac")).any_method().description(
+                "Checks the AWS SigV4 or sha256=<hex> HMAC signature of \
+                 the request with hmac-secret, reporting the step that \
+                 mismatched",
+            ),
+        )
+        .install(
+            crate::service::auth::jwt,
+            route(path!("jwt"))
+                .methods(vec![Method::GET, Method::POST])
+                .description(
+                    "Decodes the JWT of the Authorization header or body, \
+                     checking its expiry and, with jwt-keys, its signature",
+                ),
+        )
+        .install(
+            crate::service::auth::configuration,
+            route(path!(".well-known" / "openid-configuration")).description(
+                "OpenID Connect discovery for the stand-in provider",
+            ),
+        )
+        .install(
+            provider.endpoint(Endpoint::Jwks),
+            route(path!("jwks.json")).description(
+                "The JSON Web Key Set of the key /token signs with, \
+                 generated at startup",
+            ),
+        )
+        .install(
+            provider.endpoint(Endpoint::Token),
+            route(path!("token"))
+                .methods(vec![Method::GET, Method::POST])
+                .description(
+                    "Mints an ES256 token with the claims of the query, form \
+                     or JSON body, valid for expires_in seconds",
+                )
+                .add_example_param("sub", "user")
+                .add_example_param("expires_in", "3600"),
+        )
+        .install(
+            provider.endpoint(Endpoint::Authorize),
+            route(path!("oauth" / "authorize")).description(
+                "Approves an OAuth 2.0 authorization code request, with \
+                     PKCE, redirecting back with the code",
+            ),
+        )
+        .install(
+            provider.endpoint(Endpoint::ExchangeToken),
+            route(path!("oauth" / "token"))
+                .methods(vec![Method::POST])
+                .description(
+                    "Exchanges a code from /oauth/authorize for tokens, or \
+                     client credentials",
+                ),
+        )
+}
+
+#[cfg(feature = "grpc")]
+fn grpc(builder: RouterBuilder) -> RouterBuilder {
+    builder
+        .install(
+            crate::service::grpc::Grpc::echo(),
+            route(path!("httpbox.echo.v1.Echo" / method))
+                .methods(vec![Method::POST])
+                .compress(false)
+                .description(
+                    "gRPC echo service, answering Echo and EchoStream calls \
+                     with their messages and metadata",
+                ),
+        )
+        .install(
+            crate::service::grpc::reflection(),
+            route(path!("grpc.reflection.v1alpha.ServerReflection" / method))
+                .methods(vec![Method::POST])
+                .compress(false)
+                .description("gRPC server reflection, describing the echo"),
+        )
+}
+
+fn response(builder: RouterBuilder) -> RouterBuilder {
+    builder
         .install(
             crate::service::headers::response_headers,
             route(path!("response-headers"))
                 .description("Returns given response headers")
                 .add_example_param("key", "val"),
         )
+        .install(
+            crate::service::cache::cache,
+            route(path!("cache")).description(
+                "Returns 200 unless an If-Modified-Since or If-None-Match \
+                 header is provided, then it returns a 304",
+            ),
+        )
+        .install(
+            crate::service::cache::etag,
+            route(path!("etag" / etag))
+                .description(
+                    "Assumes the resource has the given etag, answering \
+                     If-None-Match with 304 and If-Match with 412",
+                )
+                .add_example_param("etag", "etag"),
+        )
+        .install(
+            extract(crate::service::cache::set_cache),
+            route(path!("cache" / [n: u64]))
+                .description("Sets a Cache-Control header for n seconds")
+                .add_example_param("n", "10"),
+        )
+        .install(
+            crate::service::cache::vary,
+            route(path!("cache" / "vary"))
+                .description(
+                    "Varies on the given request headers, with an ETag for \
+                     each variant",
+                )
+                .add_example_param("vary", "Accept-Language"),
+        )
+        .install(
+            crate::service::cache::stale,
+            route(path!("cache" / "stale"))
+                .description(
+                    "Allows serving stale while revalidating and on errors, \
+                     answering with the given status and Surrogate-Control",
+                )
+                .add_example_param("max_age", "10")
+                .add_example_param("stale_while_revalidate", "30")
+                .add_example_param("stale_if_error", "60"),
+        )
+        .install(
+            crate::service::connection::close,
+            route(path!("connection" / "close"))
+                .hop_by_hop(true)
+                .description(
+                    "Answers with Connection: close and closes the \
+                     connection, over HTTP/1.x",
+                ),
+        )
+        .install(
+            crate::service::connection::keep_alive,
+            route(path!("connection" / "keep-alive"))
+                .hop_by_hop(true)
+                .description(
+                    "Answers with Connection: keep-alive and a Keep-Alive \
+                     header, then keeps the connection open for the given \
+                     timeout between requests and max more of them, over \
+                     HTTP/1.x",
+                )
+                .add_example_param("timeout", "5")
+                .add_example_param("max", "100"),
+        )
+        .install(
+            crate::service::connection::hop_by_hop,
+            route(path!("connection" / "hop-by-hop"))
+                .hop_by_hop(true)
+                .description(
+                    "Names an X-Hop header in Connection, which \
+                     intermediaries have to drop, over HTTP/1.x",
+                ),
+        )
+}
+
+fn redirects(builder: RouterBuilder) -> RouterBuilder {
+    builder
         .install(
             crate::service::redirect::redirect,
-            route(path!("redirect" / n))
-                .description("302 Redirects n times")
+            route(path!("redirect" / [n: u16]))
+                .description(
+                    "302 Redirects n times before landing on /get, \
+                     absolutely with absolute=true",
+                )
                 .add_example_param("n", "5"),
         )
         .install(
             crate::service::redirect::to,
             route(path!("redirect-to"))
-                .description("302 Redirects to the url= URL")
+                .methods(vec![
+                    Method::GET,
+                    Method::POST,
+                    Method::PUT,
+                    Method::PATCH,
+                    Method::DELETE,
+                ])
+                .description(
+                    "Redirects to the url= URL, with a 302 or the given \
+                     3xx status_code=",
+                )
                 .add_example_param("url", "http://example.com"),
         )
         .install(
             crate::service::redirect::absolute,
-            route(path!("absolute-redirect" / n))
+            route(path!("absolute-redirect" / [n: u16]))
                 .description("302 Absolute redirects n times")
                 .add_example_param("n", "5"),
         )
         .install(
             crate::service::redirect::relative,
-            route(path!("relative-redirect" / n))
+            route(path!("relative-redirect" / [n: u16]))
                 .description("302 Relative redirects n times")
                 .add_example_param("n", "5"),
         )
+}
+
+fn cookies(builder: RouterBuilder) -> RouterBuilder {
+    builder
         .install(
             crate::service::cookies::cookies,
             route(path!("cookies")).description("Returns cookie data"),
@@ -118,48 +428,945 @@ pub fn router() -> Router {
                 .description("Sets one or more simple cookies")
                 .add_example_param("key", "val"),
         )
+        .install(
+            crate::service::cookies::delete_cookies,
+            route(path!("cookies" / "delete"))
+                .description("Deletes one or more simple cookies")
+                .add_example_param("key", ""),
+        )
+}
+
+fn dynamic(builder: RouterBuilder) -> RouterBuilder {
+    use crate::service::poll::{Polls, MAX_BODY_SIZE as POLL_BODY_SIZE};
+
+    let polls = Polls::new();
+    builder
+        .install(
+            crate::service::upload::upload,
+            route(path!("upload"))
+                .methods(vec![Method::POST, Method::PUT])
+                // The body is never buffered, so any size will do
+                .max_body_size(usize::MAX)
+                .timeout(Timeout::Idle)
+                .description(
+                    "Reads a body of any size without keeping it, then \
+                     returns its size and SHA-256",
+                ),
+        )
+        .install(
+            crate::service::informational::informational,
+            route(path!("informational"))
+                .any_method()
+                .description(
+                    "Sends each 1xx status, 103 Early Hints by default with \
+                     any link headers, delay seconds apart before the final \
+                     response, over HTTP/1.1",
+                )
+                .add_example_param(
+                    "link",
+                    "</style.css>; rel=preload; as=style",
+                ),
+        )
+        .install(
+            crate::service::expect::expect,
+            route(path!("expect"))
+                .methods(vec![Method::POST, Method::PUT])
+                .description(
+                    "Holds back 100 Continue for delay seconds, then sends \
+                     it, rejects with 417 or answers without reading the \
+                     body, as mode (send, reject or never) says",
+                )
+                .add_example_param("mode", "reject"),
+        )
+        .install(
+            crate::service::uuid::uuid,
+            route(path!("uuid"))
+                .description(
+                    "Returns a UUID of the given version, 4 or 7, or count \
+                     of them, version 4 accepting a seed",
+                )
+                .add_example_param("version", "4"),
+        )
         .install(
             crate::service::delay::delay,
-            route(path!("delay" / n))
-                .description("Delays responding for min(n, 10) seconds")
+            route(path!("delay" / [n: u64]))
+                .any_method()
+                .timeout(Timeout::Idle)
+                .description(
+                    "Delays responding for n seconds, up to the maximum \
+                     delay, then echoes the request",
+                )
                 .add_example_param("n", "3"),
         )
         .install(
-            crate::service::cache::cache,
-            route(path!("cache")).description(
-                "Returns 200 unless an If-Modified-Since or If-None-Match \
-                 header is provided, then it returns a 304",
-            ),
+            crate::service::drip::drip,
+            route(path!("drip"))
+                .compress(false)
+                .timeout(Timeout::Idle)
+                .description(
+                    "Drips numbytes bytes over duration seconds after an \
+                     initial delay, responding with the given status code, \
+                     random ones if given a seed",
+                )
+                .add_example_param("duration", "2")
+                .add_example_param("numbytes", "10")
+                .add_example_param("code", "200")
+                .add_example_param("delay", "2"),
         )
         .install(
-            crate::service::cache::set_cache,
-            route(path!("cache" / n))
-                .description("Sets a Cache-Control header for n seconds")
-                .add_example_param("n", "10"),
+            crate::service::sse::sse,
+            route(path!("sse"))
+                .compress(false)
+                .timeout(Timeout::Idle)
+                .description(
+                    "Streams count Server-Sent Events every interval seconds",
+                )
+                .add_example_param("count", "10")
+                .add_example_param("interval", "1"),
+        )
+        .install(
+            polls.clone(),
+            route(path!("poll"))
+                .timeout(Timeout::Idle)
+                .description(
+                    "Waits on id until notified or timeout seconds pass, \
+                     answering 200 with the notification or 204",
+                )
+                .add_example_param("id", "job")
+                .add_example_param("timeout", "5"),
+        )
+        .install(
+            polls,
+            route(path!("poll" / [id: String] / "notify"))
+                .method(Method::POST)
+                .max_body_size(POLL_BODY_SIZE)
+                .description(
+                    "Hands the posted body to every request waiting on id",
+                ),
+        )
+        .install(
+            crate::service::rate_limited::RateLimited::new(),
+            route(path!("rate-limited" / [n: u32]))
+                .description(
+                    "Allows each client n requests every per seconds, \
+                     default 1, then answers 429 with a Retry-After",
+                )
+                .add_example_param("n", "3")
+                .add_example_param("per", "10"),
+        )
+        .install(
+            crate::service::base64::encode_base64,
+            route(path!("base64" / "encode" / value))
+                .description("Encodes value with the URL-safe base64 alphabet")
+                .add_example_param("value", "httpbox"),
+        )
+        .install(
+            crate::service::base64::decode_base64,
+            route(path!("base64" / value))
+                .description("Decodes base64, URL-safe or not, into text")
+                .add_example_param("value", "SFRUUEJPWCBpcyBhd2Vzb21l"),
         )
         .install(
-            crate::service::bytes::bytes,
-            route(path!("bytes" / n))
+            extract(crate::service::bytes::bytes),
+            route(path!("bytes" / [n: u64]))
+                .compress(false)
                 .description(
                     "Generates n random bytes of binary data, accepts \
-                        optional seed integer parameter",
+                        optional seed and rate (bytes per second) integer \
+                        parameters, a pattern of zeros, incrementing or \
+                        text to repeat instead, and a sha256 or crc32c digest \
+                        sent in the Repr-Digest header or trailer \
+                        (digest_in)",
                 )
                 .add_example_param("n", "256"),
         )
         .install(
-            crate::service::bytes::stream_bytes,
-            route(path!("stream-bytes" / n))
+            crate::service::range::range,
+            route(path!("range" / [n: usize]))
+                .compress(false)
+                .description(
+                    "Returns n bytes of the alphabet, honoring Range \
+                     requests for up to 100KiB",
+                )
+                .add_example_param("n", "1024"),
+        )
+        .install(
+            crate::service::links::links,
+            route(path!("links" / [n: u16]))
+                .description("Redirects to the first page of n links")
+                .add_example_param("n", "10"),
+        )
+        .install(
+            crate::service::links::page,
+            route(path!("links" / [n: u16] / [offset: u16]))
+                .description(
+                    "Returns a page of n links to each other, up to 200, \
+                     the offset one not being a link",
+                )
+                .add_example_param("n", "10")
+                .add_example_param("offset", "0"),
+        )
+        .install(
+            extract(crate::service::chunked::chunked),
+            route(path!("chunked"))
+                .compress(false)
+                .description(
+                    "Sends chunks of the comma-separated sizes, followed by \
+                     any name:value trailers over HTTP/2",
+                )
+                .add_example_param("chunks", "4,8,16")
+                .add_example_param("trailer", "x-checksum:abc"),
+        )
+        .install(
+            crate::service::stream::stream,
+            route(path!("stream" / [n: usize]))
+                .compress(false)
+                .timeout(Timeout::Idle)
+                .description(
+                    "Streams min(n, 100) lines of JSON describing the request",
+                )
+                .add_example_param("n", "10"),
+        )
+        .install(
+            crate::service::respond::respond,
+            route(path!("respond"))
+                .any_method()
+                .compress(false)
+                .timeout(Timeout::Idle)
+                .description(
+                    "Responds with the given status, repeated \
+                     header=name:value, body or body_base64, after delay \
+                     seconds and in chunks of chunk_size bytes",
+                )
+                .add_example_param("status", "200")
+                .add_example_param("header", "x-test:1")
+                .add_example_param("body", "hello"),
+        )
+        .install(
+            extract(crate::service::templating::template),
+            route(path!("template")).method(Method::POST).description(
+                "Renders the posted template with its JSON data, using \
+                     {{value}}, {{#each}}, {{#if}} and {{#repeat n}} from \
+                     Handlebars",
+            ),
+        )
+        .install(
+            extract(crate::service::bytes::stream_bytes),
+            route(path!("stream-bytes" / [n: u64]))
+                .compress(false)
+                .timeout(Timeout::Idle)
                 .description(
                     "Streams n random bytes of binary data, accepts \
-                        optional seed and chunk_size integer parameters",
+                        optional seed, chunk_size and rate (bytes per \
+                        second) integer parameters, and a pattern and digest \
+                        like /bytes",
                 )
                 .add_example_param("n", "256"),
+        )
+}
+
+fn chaos(builder: RouterBuilder) -> RouterBuilder {
+    builder
+        .install(
+            crate::service::abort::abort,
+            route(path!("abort"))
+                .any_method()
+                .compress(false)
+                .description(
+                    "Closes the connection before the headers, after them \
+                     or mid-body, as when is before-headers, after-headers \
+                     or mid-body",
+                )
+                .add_example_param("when", "mid-body"),
+        )
+        .install(
+            crate::service::abort::reset,
+            route(path!("reset"))
+                .any_method()
+                .compress(false)
+                .description(
+                    "Resets the connection before the headers, after them \
+                     or mid-body, as when is before-headers, after-headers \
+                     or mid-body",
+                )
+                .add_example_param("when", "after-headers"),
+        )
+        .install(
+            crate::service::malformed::CONTENT_LENGTH,
+            route(path!("malformed" / "content-length")).description(
+                "Announces a longer body than it sends, over HTTP/1.x",
+            ),
+        )
+        .install(
+            crate::service::malformed::DUPLICATE_HEADERS,
+            route(path!("malformed" / "duplicate-headers")).description(
+                "Sends conflicting Content-Type and Content-Length \
+                     headers, over HTTP/1.x",
+            ),
+        )
+        .install(
+            crate::service::malformed::HEADER_BYTES,
+            route(path!("malformed" / "header-bytes")).description(
+                "Sends headers with invalid bytes and a bare LF, over \
+                     HTTP/1.x",
+            ),
+        )
+        .install(
+            crate::service::malformed::LENGTH_AND_CHUNKED,
+            route(path!("malformed" / "length-and-chunked")).description(
+                "Sends both Content-Length and Transfer-Encoding: chunked, \
+                     over HTTP/1.x",
+            ),
+        )
+        .install(
+            crate::service::slow::slow_headers,
+            route(path!("slow-headers"))
+                .compress(false)
+                .timeout(Timeout::Idle)
+                .description(
+                    "Sends the status line and headers at rate bytes per \
+                     second, over HTTP/1.x",
+                )
+                .add_example_param("rate", "10"),
+        )
+        .install(
+            crate::service::slow::slow_body,
+            route(path!("slow-body"))
+                .compress(false)
+                .timeout(Timeout::Idle)
+                .description(
+                    "Sends the headers at once and numbytes bytes of body at \
+                     rate bytes per second",
+                )
+                .add_example_param("numbytes", "100")
+                .add_example_param("rate", "10"),
+        )
+}
+
+fn compression(builder: RouterBuilder) -> RouterBuilder {
+    builder
+        .install(
+            crate::service::compression::gzip,
+            route(path!("gzip")).description("Returns gzip-encoded data"),
+        )
+        .install(
+            crate::service::compression::deflate,
+            route(path!("deflate")).description("Returns deflate-encoded data"),
+        )
+        .install(
+            crate::service::compression::brotli,
+            route(path!("brotli")).description("Returns brotli-encoded data"),
+        )
+}
+
+fn images(builder: RouterBuilder) -> RouterBuilder {
+    builder
+        .install(
+            crate::service::image::image,
+            route(path!("image")).compress(false).description(
+                "Returns a PNG, JPEG, WebP or SVG image, as preferred by \
+                     the Accept header",
+            ),
+        )
+        .install(
+            crate::service::image::png,
+            route(path!("image" / "png"))
+                .compress(false)
+                .description("Returns a PNG image"),
+        )
+        .install(
+            crate::service::image::jpeg,
+            route(path!("image" / "jpeg"))
+                .compress(false)
+                .description("Returns a JPEG image"),
+        )
+        .install(
+            crate::service::image::webp,
+            route(path!("image" / "webp"))
+                .compress(false)
+                .description("Returns a WebP image"),
+        )
+        .install(
+            crate::service::image::svg,
+            route(path!("image" / "svg")).description("Returns an SVG image"),
+        )
+}
+
+fn fixtures(builder: RouterBuilder) -> RouterBuilder {
+    builder
+        .install(
+            crate::service::fixtures::HTML,
+            route(path!("html")).description("Returns a simple HTML document"),
+        )
+        .install(
+            crate::service::fixtures::XML,
+            route(path!("xml")).description("Returns a simple XML document"),
+        )
+        .install(
+            crate::service::fixtures::JSON,
+            route(path!("json")).description("Returns a simple JSON document"),
+        )
+        .install(
+            crate::service::fixtures::ROBOTS_TXT,
+            route(path!("robots.txt"))
+                .description("Returns some robots.txt rules"),
+        )
+        .install(
+            crate::service::fixtures::DENY,
+            route(path!("deny"))
+                .description("Returns a page denied by robots.txt rules"),
+        )
+        .install(
+            crate::service::fixtures::UTF8,
+            route(path!("encoding" / "utf8"))
+                .description("Returns a UTF-8 encoded body"),
+        )
+}
+
+fn bins(builder: RouterBuilder) -> RouterBuilder {
+    use crate::service::bins::{Bins, MAX_BODY_SIZE};
+
+    let bins = Bins::new();
+    builder
+        .install(
+            bins.clone(),
+            route(path!("bin"))
+                .method(Method::POST)
+                .description("Creates a request bin, which lives for an hour"),
+        )
+        .install(
+            bins.clone(),
+            route(path!("bin" / [id: String] / "requests"))
+                .description("Lists the requests captured by the bin"),
+        )
+        .install(
+            bins,
+            route(path!("bin" / [id: String] / [*rest]))
+                .any_method()
+                .max_body_size(MAX_BODY_SIZE)
+                .description(
+                    "Captures any request, keeping the latest hundred, with \
+                     bodies of up to 64 KiB",
+                ),
+        )
+}
+
+fn outbound(builder: RouterBuilder) -> RouterBuilder {
+    use crate::service::callback::{Callbacks, MAX_BODY_SIZE};
+
+    let callbacks = Callbacks::new();
+    builder
+        .install(
+            callbacks.clone(),
+            route(path!("callback"))
+                .method(Method::POST)
+                .max_body_size(MAX_BODY_SIZE)
+                .description(
+                    "Calls back the posted url after delay seconds, with the \
+                     given method and body, signed with HMAC-SHA256 if \
+                     given a secret",
+                ),
+        )
+        .install(
+            callbacks,
+            route(path!("callback" / [id: String]))
+                .description("Tells whether the callback was delivered"),
+        )
+        .install(
+            crate::service::fetch::fetch,
+            route(path!("fetch"))
+                .description(
+                    "Gets the given url from the server and returns its \
+                     status, headers and body",
+                )
+                .add_example_param("url", "https://example.com/"),
+        )
+}
+
+fn websocket(builder: RouterBuilder) -> RouterBuilder {
+    builder.install(
+        crate::service::websocket::echo,
+        route(path!("ws" / "echo"))
+            .compress(false)
+            .description("Echoes WebSocket messages back to the client"),
+    )
+}
+
+fn metrics(builder: RouterBuilder, metrics: &Metrics) -> RouterBuilder {
+    builder.install(
+        metrics.clone(),
+        route(path!("metrics"))
+            .compress(false)
+            .description("Returns request metrics in the Prometheus format"),
+    )
+}
+
+fn health(
+    builder: RouterBuilder,
+    live: &Health,
+    ready: &Health,
+) -> RouterBuilder {
+    builder
+        .install(
+            live.clone(),
+            route(path!("healthz")).compress(false).description(
+                "Answers 200 while up, or the status it is set down with",
+            ),
+        )
+        .install(
+            ready.clone(),
+            route(path!("readyz")).compress(false).description(
+                "Answers 200 while ready, or the status it is set down with",
+            ),
+        )
+}
+
+fn admin(
+    builder: RouterBuilder,
+    live: &Health,
+    ready: &Health,
+) -> RouterBuilder {
+    builder
+        .install(
+            live.admin(),
+            route(path!("admin" / "health"))
+                .methods(vec![Method::GET, Method::POST])
+                .compress(false)
+                .description(
+                    "Sets /healthz with set=up or set=down, and an optional \
+                     status to fail with",
+                ),
+        )
+        .install(
+            ready.admin(),
+            route(path!("admin" / "ready"))
+                .methods(vec![Method::GET, Method::POST])
+                .compress(false)
+                .description(
+                    "Sets /readyz with set=up or set=down, and an optional \
+                     status to fail with",
+                ),
+        )
+}
+
+fn admin_listener(
+    config: &Config,
+    controls: &Controls,
+    routes: Routes,
+) -> Router {
+    use crate::service::admin::{
+        ChaosControl, RateLimitControl, ShutdownControl,
+    };
+
+    let builder = Router::builder()
+        .install(
+            ChaosControl(controls.chaos.clone()),
+            route(path!("chaos"))
+                .methods(vec![Method::GET, Method::PUT, Method::DELETE])
+                .description(
+                    "Reads the injected faults, replaces them with the PUT \
+                     body, like --chaos, or turns them off with DELETE",
+                ),
+        )
+        .install(
+            RateLimitControl(controls.rate_limit.clone()),
+            route(path!("rate-limit"))
+                .methods(vec![Method::GET, Method::PUT, Method::DELETE])
+                .description(
+                    "Reads the rate limit, replaces it with PUT ?rate=2.5&\
+                     burst=3, or lifts it with DELETE",
+                ),
+        )
+        .install(
+            controls.live.admin(),
+            route(path!("health"))
+                .methods(vec![Method::GET, Method::POST])
+                .description(
+                    "Sets /healthz with set=up or set=down, and an optional \
+                     status to fail with",
+                ),
+        )
+        .install(
+            controls.ready.admin(),
+            route(path!("ready"))
+                .methods(vec![Method::GET, Method::POST])
+                .description(
+                    "Sets /readyz with set=up or set=down, and an optional \
+                     status to fail with",
+                ),
+        )
+        .install(
+            routes,
+            route(path!("routes"))
+                .description("Lists the routes of the public listener"),
+        )
+        .install(
+            ShutdownControl(controls.shutdown.clone()),
+            route(path!("shutdown")).method(Method::POST).description(
+                "Shuts the server down gracefully, draining connections \
+                     as on SIGTERM",
+            ),
         );
 
-    let index_route: Route = route(path!()).description("This page").into();
+    let index_route = Route::from(route(path!()).description("This page"))
+        .with_handler(&Index::default());
+    let index: Index =
+        std::iter::once(&index_route).chain(builder.routes()).into();
+
+    builder
+        .install(index, index_route)
+        .layer(crate::middleware::RequestIds)
+        .layer(crate::middleware::AccessLog)
+        .max_body_size(config.max_body_size())
+        .config(config.clone())
+        .errors(config.error_format())
+        .build()
+}
+
+/// The router of the public listener, and that of the admin listener
+/// controlling it if there is one
+pub fn routers(config: &Config) -> (Router, Option<Admin>) {
+    let concurrency = config.max_concurrency().map(ConcurrencyLimit::new);
+    let recorder = match &concurrency {
+        Some(limit) => Metrics::new().concurrency_limit(limit.clone()),
+        None => Metrics::new(),
+    };
+    let controls = config.admin().then(|| Controls::new(config));
+    let (live, ready) = match &controls {
+        Some(controls) => (controls.live.clone(), controls.ready.clone()),
+        None => (Health::new(), Health::new()),
+    };
+    let builder = Group::ALL
+        .iter()
+        .filter(|group| config.enabled(**group))
+        .fold(Router::builder(), |builder, group| {
+            builder.group(group.name(), |builder| match group {
+                Group::Inspection => inspection(builder),
+                Group::Methods => methods(builder),
+                Group::Anything => anything(builder),
+                Group::Status => status(builder),
+                Group::Auth => auth(builder),
+                Group::Response => response(builder),
+                Group::Redirects => redirects(builder),
+                Group::Cookies => cookies(builder),
+                Group::Dynamic => dynamic(builder),
+                Group::Chaos => chaos(builder),
+                Group::Compression => compression(builder),
+                Group::Images => images(builder),
+                Group::Fixtures => fixtures(builder),
+                Group::Bins => bins(builder),
+                Group::Outbound => outbound(builder),
+                Group::Websocket => websocket(builder),
+                Group::Metrics => metrics(builder, &recorder),
+                Group::Health => health(builder, &live, &ready),
+                // Kept off the public listener when there is an admin one
+                Group::Admin if controls.is_some() => builder,
+                Group::Admin => admin(builder, &live, &ready),
+            })
+        });
+
+    #[cfg(feature = "grpc")]
+    let builder = match config.grpc() {
+        true => grpc(builder),
+        false => builder,
+    };
+
+    let builder = match config.swagger_ui() {
+        true => builder.install(
+            crate::service::openapi::swagger_ui,
+            route(path!("docs"))
+                .description("Browses /openapi.json with Swagger UI"),
+        ),
+        false => builder,
+    };
+
+    let builder = match config.static_dir() {
+        Some(dir) => builder.install(
+            crate::service::static_files::StaticFiles::new(dir)
+                .listing(config.static_listing()),
+            route(path!("static" / [*rest]))
+                .compress(false)
+                .description("Serves the files of the static directory")
+                .add_example_param("rest", ""),
+        ),
+        None => builder,
+    };
+
+    let index_route = Route::from(route(path!()).description("This page"))
+        .with_handler(&Index::default());
+    let routes_route =
+        Route::from(route(path!("routes"))).with_handler(&Routes::default());
+    let openapi_route = Route::from(route(path!("openapi.json")))
+        .with_handler(&OpenApi::default());
+
+    let own = [&index_route, &routes_route, &openapi_route];
+    let index: Index = own.iter().copied().chain(builder.routes()).into();
+    let table: Routes = own.iter().copied().chain(builder.routes()).into();
+    let openapi: OpenApi = own.iter().copied().chain(builder.routes()).into();
+
+    let mut builder = builder
+        .install(index, index_route)
+        .install(table.clone(), routes_route)
+        .install(openapi, openapi_route)
+        .layer(crate::middleware::RequestIds)
+        .layer(crate::middleware::AccessLog)
+        .layer(crate::middleware::HopByHop);
+    if let Some(expect) = config.expect_continue() {
+        builder = builder.layer(expect);
+    }
+    if config.enabled(Group::Metrics) {
+        builder = builder.layer(recorder);
+    }
+    let chaos = match &controls {
+        Some(controls) => Some(controls.chaos.clone()),
+        None => config.chaos(),
+    };
+    if let Some(chaos) = chaos {
+        builder = builder.layer(chaos);
+    }
+    if let Some(cors) = config.cors() {
+        builder = builder.layer(cors);
+    }
+    if let Some(limit) = concurrency {
+        builder = builder.layer(limit);
+    }
+    let rate_limit = match &controls {
+        Some(controls) => Some(controls.rate_limit.clone()),
+        None => config.rate_limit().map(|limit| {
+            crate::middleware::RateLimit::new(limit, config.rate_limit_key())
+        }),
+    };
+    if let Some(rate_limit) = rate_limit {
+        builder = builder.layer(rate_limit);
+    }
+    builder = builder.layer(crate::middleware::Timeouts::new(
+        config.request_timeout(),
+        config.idle_timeout(),
+    ));
+    builder = builder.layer(crate::middleware::Compression);
+    if let Some(port) = config.http3_port() {
+        builder = builder.layer(crate::middleware::AltSvc::http3(port));
+    }
+
+    let public = builder
+        .max_body_size(config.max_body_size())
+        .trusted_proxies(config.trusted_proxies())
+        .config(config.clone())
+        .errors(config.error_format())
+        .build();
+    let admin = controls.map(|controls| Admin {
+        router: admin_listener(config, &controls, table),
+        shutdown: controls.shutdown,
+    });
+    (public, admin)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+
+    fn router(config: &Config) -> Router {
+        routers(config).0
+    }
+
+    async fn status(router: &mut Router, path: &str) -> StatusCode {
+        request().path(path).call(router).await.status()
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_groups() {
+        let config = Config {
+            endpoints: vec![Group::Methods],
+            ..Config::default()
+        };
+        let mut router = router(&config);
+
+        assert_eq!(status(&mut router, "/get").await, StatusCode::OK);
+        assert_eq!(status(&mut router, "/ip").await, StatusCode::NOT_FOUND);
+        assert_eq!(status(&mut router, "/").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_endpoint_groups() {
+        let config = Config {
+            disable_endpoints: vec![Group::Chaos, Group::Outbound],
+            ..Config::default()
+        };
+        let mut router = router(&config);
+
+        assert_eq!(status(&mut router, "/abort").await, StatusCode::NOT_FOUND);
+        assert_eq!(status(&mut router, "/fetch").await, StatusCode::NOT_FOUND);
+        assert_eq!(status(&mut router, "/get").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_routers_keep_their_own_state() {
+        let jwks = |mut router: Router| async move {
+            let res = request().path("/jwks.json").call(&mut router).await;
+            res.read_body().await.unwrap()
+        };
+
+        let config = Config::default();
+        assert_ne!(jwks(router(&config)).await, jwks(router(&config)).await);
+    }
+
+    #[tokio::test]
+    async fn test_routes_tagged_by_group() {
+        let mut router = router(&Config::default());
+        let res = request().path("/routes").call(&mut router).await;
+        let table = res.read_body_json().await.unwrap();
+
+        let tags = |path: &str| {
+            let entry = table
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|entry| entry["path"] == path);
+            entry.unwrap()["tags"].clone()
+        };
+        assert_eq!(tags("/get"), serde_json::json!(["methods"]));
+        assert_eq!(tags("/status/:code"), serde_json::json!(["status"]));
+        assert_eq!(tags("/routes"), serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_typed_params() {
+        let mut router = router(&Config::default());
+
+        assert_eq!(status(&mut router, "/bytes/16").await, StatusCode::OK);
+        assert_eq!(
+            status(&mut router, "/bytes/many").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bytes_digest_uncompressed() {
+        let mut router = router(&Config::default());
+        let res = request()
+            .path("/bytes/9?pattern=123456789&digest=crc32c")
+            .header("accept-encoding", "gzip")
+            .call(&mut router)
+            .await;
+
+        assert!(!res.headers().contains_key("content-encoding"));
+        assert_eq!(res.headers()["repr-digest"], "crc32c=:4waSgw==:");
+        assert_eq!(res.read_body_utf8().await.unwrap(), "123456789");
+    }
+
+    #[tokio::test]
+    async fn test_stream_bytes_digest_trailer() {
+        let mut router = router(&Config::default());
+        let res = request()
+            .path("/stream-bytes/9?pattern=123456789&digest=crc32c&digest_in=trailer")
+            .call(&mut router)
+            .await;
+
+        assert_eq!(res.headers()["trailer"], "repr-digest");
+        let mut body = res.into_body();
+        while let Some(chunk) = body.data().await {
+            chunk.unwrap();
+        }
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["repr-digest"], "crc32c=:4waSgw==:");
+    }
+
+    #[tokio::test]
+    async fn test_method_endpoints() {
+        let mut router = router(&Config::default());
+        let res = request()
+            .method(Method::POST)
+            .path("/get")
+            .call(&mut router)
+            .await;
+
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            status(&mut router, "/post").await,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fixtures() {
+        let mut router = router(&Config::default());
+
+        assert_eq!(status(&mut router, "/robots.txt").await, StatusCode::OK);
+        assert_eq!(status(&mut router, "/encoding/utf8").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_anything_below() {
+        let mut router = router(&Config::default());
+
+        assert_eq!(status(&mut router, "/anything").await, StatusCode::OK);
+        assert_eq!(status(&mut router, "/anything/a/b").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_admin_sets_probes() {
+        let mut router = router(&Config::default());
+        request()
+            .method(Method::POST)
+            .path("/admin/ready?set=down")
+            .call(&mut router)
+            .await;
+
+        assert_eq!(
+            status(&mut router, "/readyz").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(status(&mut router, "/healthz").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_admin_listener() {
+        let config = Config {
+            admin_port: Some(3001),
+            ..Config::default()
+        };
+        let (mut public, admin) = routers(&config);
+        let mut admin = admin.unwrap().router;
+
+        assert_eq!(
+            status(&mut public, "/admin/health").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(status(&mut admin, "/routes").await, StatusCode::OK);
+        assert_eq!(status(&mut public, "/routes").await, StatusCode::OK);
+        assert_eq!(status(&mut public, "/openapi.json").await, StatusCode::OK);
+        assert_eq!(status(&mut public, "/docs").await, StatusCode::NOT_FOUND);
+
+        request()
+            .method(Method::PUT)
+            .path("/chaos")
+            .body("error=1")
+            .call(&mut admin)
+            .await;
+        assert!(status(&mut public, "/get").await.is_server_error());
+
+        request()
+            .method(Method::POST)
+            .path("/ready?set=down")
+            .call(&mut admin)
+            .await;
+        assert!(status(&mut public, "/readyz").await.is_server_error());
+    }
 
-    let routes = std::iter::once(&index_route).chain(builder.routes());
-    let index: crate::service::index::Index = routes.into();
+    #[tokio::test]
+    async fn test_cors_preflight() {
+        let mut router = router(&Config::default());
+        let res = request()
+            .method(Method::OPTIONS)
+            .path("/get")
+            .header("origin", "https://example.com")
+            .header("access-control-request-method", "GET")
+            .call(&mut router)
+            .await;
 
-    builder.install(index, index_route).build()
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(res.headers()["access-control-allow-origin"], "*");
+    }
 }
@@ -0,0 +1,74 @@
+//! The OpenAPI document of the endpoints, for client generators and API
+//! browsers
+use crate::handler::Handler;
+use crate::http::{html, response, Bytes, Request, Result};
+use crate::router::Route;
+use async_trait::async_trait;
+
+const SWAGGER_UI: &str = include_str!("../../assets/openapi/swagger-ui.html");
+
+/// The document of a router's routes, rendered once they are all known
+#[derive(Clone, Debug, Default)]
+pub struct OpenApi(Bytes);
+
+impl<'a, IT: IntoIterator<Item = &'a Route>> From<IT> for OpenApi {
+    fn from(routes: IT) -> Self {
+        let document = crate::openapi::document(routes);
+        OpenApi(
+            serde_json::to_vec_pretty(&document)
+                .unwrap_or_default()
+                .into(),
+        )
+    }
+}
+
+#[async_trait]
+impl Handler for OpenApi {
+    async fn handle(&self, _: Request) -> Result {
+        response()
+            .header("content-type", "application/json")
+            .body(self.0.clone())
+    }
+
+    fn describe(&self) -> Option<&'static str> {
+        Some("Returns the OpenAPI 3.1 document of the endpoints")
+    }
+}
+
+pub async fn swagger_ui(_: Request) -> Result {
+    html(SWAGGER_UI)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::handler::extract;
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use uri_path::path;
+
+    #[tokio::test]
+    async fn test_query_parameters() {
+        let builder = Router::builder().install(
+            extract(crate::service::bytes::bytes),
+            route(path!("bytes" / [n: u64])),
+        );
+        let res = request()
+            .handle(OpenApi::from(builder.routes()))
+            .await
+            .unwrap();
+
+        let document = res.read_body_json().await.unwrap();
+        let parameters = document["paths"]["/bytes/{n}"]["get"]["parameters"]
+            .as_array()
+            .unwrap()
+            .clone();
+        assert_eq!(parameters[0]["name"], "n");
+        let seed = parameters.iter().find(|p| p["name"] == "seed").unwrap();
+        assert_eq!(seed["in"], "query");
+        assert_eq!(seed["schema"]["type"], "integer");
+        let pattern =
+            parameters.iter().find(|p| p["name"] == "pattern").unwrap();
+        assert_eq!(pattern["schema"]["type"], "string");
+    }
+}
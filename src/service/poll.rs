@@ -0,0 +1,226 @@
+//! Long polling, where a request waits until another one notifies it
+use crate::handler::Handler;
+use crate::http::{
+    bad_request, json, response, Bytes, Error, Request, Result, StatusCode,
+};
+use async_trait::async_trait;
+use hyper::header::{self, HeaderValue};
+use serde_derive::{Deserialize, Serialize};
+use std::cmp::min;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError, Receiver, Sender};
+
+/// The largest body of a notification
+pub const MAX_BODY_SIZE: usize = 64 * 1024;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize)]
+pub struct PollQueryParams {
+    id: String,
+    /// In seconds, up to the maximum delay
+    timeout: Option<f64>,
+}
+
+/// Handed by a notifying request to each one waiting
+#[derive(Clone, Debug)]
+struct Notification {
+    content_type: Option<HeaderValue>,
+    body: Bytes,
+}
+
+#[derive(Serialize)]
+struct Notified<'a> {
+    id: &'a str,
+    /// How many requests were waiting
+    notified: usize,
+}
+
+type Waiters = Arc<Mutex<HashMap<String, Sender<Notification>>>>;
+
+/// A request waiting on `id`, which stops waiting there once dropped
+///
+/// hyper drops the handler of a request whose client went away, so this is
+/// also what cleans up after a client that stopped waiting.
+struct Waiting {
+    waiters: Waiters,
+    id: String,
+    receiver: Option<Receiver<Notification>>,
+}
+
+impl Waiting {
+    async fn notified(&mut self) -> Option<Notification> {
+        let receiver = self.receiver.as_mut()?;
+        loop {
+            match receiver.recv().await {
+                Ok(notification) => return Some(notification),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        // Not to be counted as waiting when deciding to clean up
+        drop(self.receiver.take());
+        let mut waiters = self.waiters.lock().unwrap();
+        if let Some(sender) = waiters.get(&self.id) {
+            if sender.receiver_count() == 0 {
+                waiters.remove(&self.id);
+            }
+        }
+    }
+}
+
+/// The requests waiting at `GET /poll?id=`, shared with `POST
+/// /poll/:id/notify`, which wakes them up
+#[derive(Clone, Default)]
+pub struct Polls(Waiters);
+
+impl Polls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn wait(&self, id: &str) -> Waiting {
+        let mut waiters = self.0.lock().unwrap();
+        let receiver = waiters
+            .entry(id.to_owned())
+            .or_insert_with(|| broadcast::channel(1).0)
+            .subscribe();
+        Waiting {
+            waiters: self.0.clone(),
+            id: id.to_owned(),
+            receiver: Some(receiver),
+        }
+    }
+
+    /// Waits for a notification, answering 200 with its body, until the
+    /// timeout passes, answering 204
+    async fn poll(&self, req: Request) -> Result {
+        let query =
+            req.query::<PollQueryParams>().map_err(|_| bad_request())?;
+        let timeout = query.timeout.map_or(Ok(DEFAULT_TIMEOUT), |timeout| {
+            Duration::try_from_secs_f64(timeout)
+                .map_err(|_| Error::bad_request("invalid timeout"))
+        })?;
+        let timeout = min(timeout, req.config().max_delay());
+
+        let mut waiting = self.wait(&query.id);
+        let notified = tokio::time::timeout(timeout, waiting.notified());
+        match req.unless_disconnected(notified).await? {
+            Ok(Some(notification)) => {
+                let mut res = response();
+                if let Some(content_type) = notification.content_type {
+                    res = res.header(header::CONTENT_TYPE, content_type);
+                }
+                res.body(notification.body)
+            }
+            Ok(None) | Err(_) => {
+                response().status(StatusCode::NO_CONTENT).into()
+            }
+        }
+    }
+
+    async fn notify(&self, id: &str, mut req: Request) -> Result {
+        let notification = Notification {
+            content_type: req.headers().get(header::CONTENT_TYPE).cloned(),
+            body: req.bytes().await?,
+        };
+        let notified = match self.0.lock().unwrap().get(id) {
+            Some(sender) => sender.send(notification).unwrap_or_default(),
+            None => 0,
+        };
+        json(&Notified { id, notified })
+    }
+}
+
+#[async_trait]
+impl Handler for Polls {
+    async fn handle(&self, req: Request) -> Result {
+        match req.param::<String>("id") {
+            Some(id) => self.notify(&id, req).await,
+            None => self.poll(req).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::Method;
+    use serde_json::Value;
+
+    async fn notify(polls: &Polls, id: &str) -> Value {
+        let res = request()
+            .method(Method::POST)
+            .param("id", id)
+            .header("content-type", "text/plain")
+            .body("done")
+            .handle(polls.clone())
+            .await
+            .unwrap();
+        res.read_body_json().await.unwrap()
+    }
+
+    fn waiters(polls: &Polls) -> usize {
+        polls.0.lock().unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn test_notified() {
+        let polls = Polls::new();
+        let poll = tokio::spawn(
+            request()
+                .path("/poll?id=job&timeout=5")
+                .handle(polls.clone()),
+        );
+        while waiters(&polls) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let notified = notify(&polls, "job").await;
+        assert_eq!(notified["notified"], 1);
+
+        let res = poll.await.unwrap().unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["content-type"], "text/plain");
+        assert_eq!(res.read_body_utf8().await.unwrap(), "done");
+        assert_eq!(waiters(&polls), 0);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out() {
+        let polls = Polls::new();
+        let res = request()
+            .path("/poll?id=job&timeout=0.01")
+            .handle(polls.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(waiters(&polls), 0);
+        assert_eq!(notify(&polls, "job").await["notified"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_cleans_up_after_disconnect() {
+        let polls = Polls::new();
+        let poll = tokio::spawn(
+            request()
+                .path("/poll?id=job&timeout=5")
+                .handle(polls.clone()),
+        );
+        while waiters(&polls) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        poll.abort();
+        assert!(poll.await.unwrap_err().is_cancelled());
+        assert_eq!(waiters(&polls), 0);
+    }
+}
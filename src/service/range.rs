@@ -0,0 +1,72 @@
+use crate::headers::Range;
+use crate::http::range::ranged;
+use crate::http::{bad_request, Bytes, Request, Result};
+
+const MAX_LENGTH: usize = 100 * 1024;
+
+/// `n` bytes of the alphabet, over and over
+fn alphabet(n: usize) -> Bytes {
+    (0..n)
+        .map(|i| b'a' + (i % 26) as u8)
+        .collect::<Vec<_>>()
+        .into()
+}
+
+pub async fn range(req: Request) -> Result {
+    let n = req
+        .param::<usize>("n")
+        .filter(|n| (1..=MAX_LENGTH).contains(n))
+        .ok_or_else(bad_request)?;
+
+    ranged(
+        alphabet(n),
+        mime::APPLICATION_OCTET_STREAM,
+        req.typed_header::<Range>(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_range_full() {
+        let res = request().param("n", "30").handle(range).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.read_body_utf8().await.unwrap(),
+            "abcdefghijklmnopqrstuvwxyzabcd"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_range_partial() {
+        let res = request()
+            .param("n", "30")
+            .header("range", "bytes=24-27")
+            .handle(range)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            res.headers().get("content-range").unwrap(),
+            "bytes 24-27/30"
+        );
+        assert_eq!(res.read_body_utf8().await.unwrap(), "yzab");
+    }
+
+    #[tokio::test]
+    async fn test_range_too_large() {
+        let res = request()
+            .param("n", &(MAX_LENGTH + 1).to_string())
+            .handle(range)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
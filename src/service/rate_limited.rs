@@ -0,0 +1,111 @@
+use crate::handler::Handler;
+use crate::http::{bad_request, json, Buckets, Limit, Request, Result};
+use async_trait::async_trait;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// The longest window, so buckets can't be kept alive for ages
+const MAX_PER: u32 = 3600;
+
+#[derive(Deserialize)]
+struct RateLimitedQueryParams {
+    per: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct Allowed {
+    limit: u32,
+    per: u32,
+    remaining: u32,
+}
+
+/// Allows each client `n` requests every `per` seconds, the buckets
+/// counting them being shared by clones
+#[derive(Clone, Default)]
+pub struct RateLimited(Arc<Buckets>);
+
+impl RateLimited {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Handler for RateLimited {
+    async fn handle(&self, req: Request) -> Result {
+        rate_limited(req, &self.0)
+    }
+}
+
+fn rate_limited(req: Request, buckets: &Buckets) -> Result {
+    let n = req
+        .param::<u32>("n")
+        .filter(|n| *n > 0)
+        .ok_or_else(bad_request)?;
+    let per = req
+        .query::<RateLimitedQueryParams>()
+        .map_err(|_| bad_request())?
+        .per
+        .unwrap_or(1);
+    if per == 0 || per > MAX_PER {
+        return Err(bad_request());
+    }
+
+    let key = format!(
+        "{}/{}:{}",
+        n,
+        per,
+        req.client_key(&req.config().rate_limit_key())
+    );
+    let limit = Limit::new(f64::from(n) / f64::from(per), n);
+    let remaining = buckets.take(&key, limit)?;
+    json(&Allowed {
+        limit: n,
+        per,
+        remaining,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+    use serde_json::json;
+
+    fn rate_limited_request(client: &str, n: &str) -> RequestBuilder {
+        request()
+            .path(&format!("/rate-limited/{}?per=60", n))
+            .param("n", n)
+            .client_addr(client.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited() {
+        let limited = RateLimited::new();
+        let req = || rate_limited_request("10.0.0.1:1234", "2");
+
+        let res = req().handle(limited.clone()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = res.read_body_utf8().await.unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"limit": 2, "per": 60, "remaining": 1})
+        );
+        let res = req().handle(limited.clone()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = req().handle(limited.clone()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()["retry-after"], "30");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_bad_limit() {
+        let res = rate_limited_request("10.0.0.2:1234", "0")
+            .handle(RateLimited::new())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,56 +1,94 @@
-mod uri;
-
-use self::uri::absolute_url;
-use crate::http::{bad_request, redirect_to, Request, Result};
+use crate::http::{
+    bad_request, redirect_to, redirect_with_status, Request, Result, StatusCode,
+};
 use hyper::Uri;
 use serde_derive::Deserialize;
 use std::cmp::min;
 
-#[derive(Deserialize)]
-pub struct RedirectUrlParams {
-    url: String,
-}
+const MAX_REDIRECTS: u16 = 100;
 
-pub async fn to(req: Request) -> Result {
-    let query = req
-        .query::<RedirectUrlParams>()
-        .map_err(|_| bad_request())?;
+#[derive(Default, Deserialize)]
+struct RedirectToParams {
+    url: Option<String>,
+    status_code: Option<u16>,
+}
 
-    let uri = query.url.parse::<Uri>().map_err(|_| bad_request())?;
+impl RedirectToParams {
+    fn or(self, other: Self) -> Self {
+        Self {
+            url: self.url.or(other.url),
+            status_code: self.status_code.or(other.status_code),
+        }
+    }
+}
 
-    redirect_to(uri)
+#[derive(Default, Deserialize)]
+struct RedirectParams {
+    #[serde(default)]
+    absolute: bool,
 }
 
-pub async fn redirect(req: Request) -> Result {
-    relative(req).await
+/// Redirects to `url`, taken from the query or a form body
+pub async fn to(mut req: Request) -> Result {
+    let mut params =
+        req.query::<RedirectToParams>().map_err(|_| bad_request())?;
+    if req.has_content_type(mime::APPLICATION, mime::WWW_FORM_URLENCODED) {
+        params = params.or(req.form().await?);
+    }
+
+    let url = params.url.ok_or_else(bad_request)?;
+    // Control characters would let the caller inject headers
+    if url.chars().any(char::is_control) {
+        return Err(bad_request());
+    }
+    let uri = url.parse::<Uri>().map_err(|_| bad_request())?;
+
+    let status = params
+        .status_code
+        .map(StatusCode::from_u16)
+        .transpose()
+        .map_err(|_| bad_request())?
+        .unwrap_or(StatusCode::FOUND);
+    if !status.is_redirection() {
+        return Err(bad_request());
+    }
+
+    redirect_with_status(uri, status)
 }
 
-pub async fn relative(req: Request) -> Result {
-    let n = req.param::<u16>("n").ok_or_else(bad_request)?;
-    let n = min(n - 1, 100);
+/// The next hop of a chain of `n` redirects, landing on `/get`
+fn next_hop(req: &Request, prefix: &str) -> std::result::Result<Uri, ()> {
+    let n = req.param::<u16>("n").ok_or(())?;
+    let remaining = min(n.checked_sub(1).ok_or(())?, MAX_REDIRECTS);
 
-    let url = if n > 0 {
-        format!("/relative-redirect/{}", n)
+    let url = if remaining > 0 {
+        format!("/{}/{}", prefix, remaining)
     } else {
-        String::from("/")
+        String::from("/get")
     };
+    url.parse().map_err(|_| ())
+}
 
-    let uri = url.parse::<Uri>().map_err(|_| bad_request())?;
+pub async fn redirect(req: Request) -> Result {
+    let query = req.query::<RedirectParams>().map_err(|_| bad_request())?;
+
+    if query.absolute {
+        absolute(req).await
+    } else {
+        relative(req).await
+    }
+}
+
+pub async fn relative(req: Request) -> Result {
+    let uri = next_hop(&req, "relative-redirect").map_err(|_| bad_request())?;
     redirect_to(uri)
 }
 
 pub async fn absolute(req: Request) -> Result {
-    let n = req.param::<u16>("n").ok_or_else(bad_request)?;
-    let n = min(n - 1, 100);
-    let url = if n > 0 {
-        format!("/absolute-redirect/{}", n)
-    } else {
-        String::from("/")
-    };
+    let uri = next_hop(&req, "absolute-redirect").map_err(|_| bad_request())?;
 
-    let request_uri = req.uri();
-    let response_uri = absolute_url(&req, request_uri)
-        .and_then(|base| Ok(base.join(&url)?))
+    let response_uri = req
+        .absolute_url(&uri)
         .and_then(|url| Ok(url.to_string().parse::<Uri>()?))
         .map_err(|_| bad_request())?;
 
@@ -61,12 +99,13 @@ pub async fn absolute(req: Request) -> Result {
 mod test {
 
     use super::*;
+    use crate::headers::ContentType;
     use crate::headers::HeaderMapExt;
     use crate::headers::Host;
     use crate::headers::Location;
     use crate::test::*;
-    use hyper::http::StatusCode;
     use hyper::http::{uri::Authority, Uri};
+    use hyper::Method;
 
     #[tokio::test]
     async fn test_redirect_to() {
@@ -83,6 +122,67 @@ mod test {
         )
     }
 
+    #[tokio::test]
+    async fn test_redirect_to_status_code() {
+        let res = request()
+            .path("/?url=/get&status_code=307")
+            .handle(to)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(
+            res.headers().typed_get::<Location>().unwrap().uri(),
+            &Uri::from_static("/get")
+        )
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_invalid_status_code() {
+        for path in &["/?url=/get&status_code=200", "/?url=/get&status_code=x"]
+        {
+            let res = request().path(path).handle(to).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_form() {
+        let res = request()
+            .method(Method::POST)
+            .typed_header(ContentType::form_url_encoded())
+            .body("url=http%3A%2F%2Fexample.com&status_code=303")
+            .handle(to)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            res.headers().typed_get::<Location>().unwrap().uri(),
+            &Uri::from_static("http://example.com/")
+        )
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_header_injection() {
+        let res = request()
+            .path("/?url=/get%0D%0ASet-Cookie:%20a=b")
+            .handle(to)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(res.headers().get("set-cookie").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_missing_url() {
+        let res = request().handle(to).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_redirect() {
         let res = request().param("n", "5").handle(redirect).await.unwrap();
@@ -101,7 +201,7 @@ mod test {
         assert_eq!(res.status(), StatusCode::FOUND);
         assert_eq!(
             res.headers().typed_get::<Location>().unwrap().uri(),
-            &Uri::from_static("/")
+            &Uri::from_static("/get")
         )
     }
 
@@ -123,7 +223,7 @@ mod test {
         assert_eq!(res.status(), StatusCode::FOUND);
         assert_eq!(
             res.headers().typed_get::<Location>().unwrap().uri(),
-            &Uri::from_static("/")
+            &Uri::from_static("/get")
         )
     }
 
@@ -155,7 +255,31 @@ mod test {
         assert_eq!(res.status(), StatusCode::FOUND);
         assert_eq!(
             res.headers().typed_get::<Location>().unwrap().uri(),
-            &Uri::from_static("http://example.com/")
+            &Uri::from_static("http://example.com/get")
+        )
+    }
+
+    #[tokio::test]
+    async fn test_redirect_absolute_query() {
+        let res = request()
+            .path("/redirect/3?absolute=true")
+            .typed_header(Host::from(Authority::from_static("example.com")))
+            .param("n", "3")
+            .handle(redirect)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert_eq!(
+            res.headers().typed_get::<Location>().unwrap().uri(),
+            &Uri::from_static("http://example.com/absolute-redirect/2")
         )
     }
+
+    #[tokio::test]
+    async fn test_redirect_zero() {
+        let res = request().param("n", "0").handle(relative).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,341 @@
+//! The JSON description of a request shared by the echo endpoints
+use crate::headers::{ContentType, UserAgent};
+use crate::http::{bad_request, multipart_error, Error, Field, Request};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_derive::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Named values where a repeated name collects its values into an array
+pub type Fields = BTreeMap<String, Value>;
+
+pub fn fields<I: IntoIterator<Item = (String, String)>>(pairs: I) -> Fields {
+    let mut fields = Fields::new();
+    for (key, value) in pairs {
+        match fields.get_mut(&key) {
+            Some(Value::Array(values)) => values.push(value.into()),
+            Some(existing) => {
+                let first = existing.take();
+                *existing = Value::Array(vec![first, value.into()]);
+            }
+            None => {
+                fields.insert(key, value.into());
+            }
+        }
+    }
+    fields
+}
+
+fn title_case(name: &str) -> String {
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string())
+                .unwrap_or_default()
+                + chars.as_str()
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Text as is and anything else under the `data` URL scheme
+pub fn data(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(data) => data.to_owned(),
+        Err(_) => format!(
+            "data:application/octet-stream;base64,{}",
+            STANDARD.encode(bytes)
+        ),
+    }
+}
+
+/// A textual part as is and anything else under the `data` encoding
+async fn part(field: Field<'_>) -> Result<String, Error> {
+    let bytes = field.bytes().await.map_err(multipart_error)?;
+    Ok(data(&bytes))
+}
+
+/// Splits a multipart body into its form fields and uploaded files
+async fn multipart(req: &mut Request) -> Result<(Fields, Fields), Error> {
+    let mut multipart = req.multipart()?;
+    let mut form = vec![];
+    let mut files = vec![];
+
+    while let Some(field) =
+        multipart.next_field().await.map_err(multipart_error)?
+    {
+        let name = field.name().unwrap_or_default().to_owned();
+        if field.file_name().is_some() {
+            files.push((name, part(field).await?));
+        } else {
+            form.push((name, part(field).await?));
+        }
+    }
+    Ok((fields(form), fields(files)))
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Reflection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<Fields>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<Fields>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    form: Option<Fields>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headers: Option<BTreeMap<String, String>>,
+    /// The position of the reflection among those streamed by `/stream`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(rename = "user-agent", skip_serializing_if = "Option::is_none")]
+    user_agent: Option<String>,
+}
+
+impl Reflection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything about the request, as reported by `/anything`
+    pub async fn request(req: &mut Request) -> Result<Self, Error> {
+        Self::new()
+            .args(req)?
+            .headers(req)
+            .method(req)
+            .origin(req)
+            .url(req)
+            .body(req)
+            .await
+    }
+
+    pub fn args(mut self, req: &Request) -> Result<Self, Error> {
+        let pairs = req
+            .query::<Vec<(String, String)>>()
+            .map_err(|_| bad_request())?;
+        self.args = Some(fields(pairs));
+        Ok(self)
+    }
+
+    pub fn headers(mut self, req: &Request) -> Self {
+        let mut headers = BTreeMap::<String, String>::new();
+        for (name, value) in req.headers() {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            headers
+                .entry(title_case(name.as_str()))
+                .and_modify(|existing| {
+                    existing.push(',');
+                    existing.push_str(&value);
+                })
+                .or_insert_with(|| value.into_owned());
+        }
+        self.headers = Some(headers);
+        self
+    }
+
+    pub fn id(mut self, id: usize) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn method(mut self, req: &Request) -> Self {
+        self.method = Some(req.method().to_string());
+        self
+    }
+
+    pub fn origin(mut self, req: &Request) -> Self {
+        self.origin = req.client_addr().map(|ip| ip.to_string());
+        self
+    }
+
+    pub fn url(mut self, req: &Request) -> Self {
+        let url = req
+            .absolute_url(req.uri())
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| req.uri().to_string());
+        self.url = Some(url);
+        self
+    }
+
+    pub fn user_agent(mut self, req: &Request) -> Self {
+        self.user_agent = req
+            .typed_header::<UserAgent>()
+            .map(|agent| agent.to_string());
+        self
+    }
+
+    pub async fn body(mut self, req: &mut Request) -> Result<Self, Error> {
+        let content_type =
+            req.typed_header::<ContentType>().map(mime::Mime::from);
+
+        let mut files = Fields::new();
+        let mut form = Fields::new();
+        let mut data = String::new();
+        let mut json = Value::Null;
+
+        match content_type
+            .as_ref()
+            .map(|content_type| (content_type.type_(), content_type.subtype()))
+        {
+            Some((mime::MULTIPART, mime::FORM_DATA)) => {
+                (form, files) = multipart(req).await?;
+            }
+            Some((mime::APPLICATION, mime::WWW_FORM_URLENCODED)) => {
+                form = fields(req.form::<Vec<(String, String)>>().await?);
+            }
+            _ => {
+                let bytes = req.bytes().await?;
+                data = self::data(&bytes);
+                json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+            }
+        }
+
+        self.data = Some(data);
+        self.files = Some(files);
+        self.form = Some(form);
+        self.json = Some(json);
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headers::Host;
+    use crate::http::multipart::test::{
+        multipart_body, multipart_content_type,
+    };
+    use crate::test::*;
+    use hyper::http::uri::Authority;
+    use serde_json::json;
+
+    #[test]
+    fn test_fields() {
+        let pairs = vec![
+            ("a".to_owned(), "1".to_owned()),
+            ("b".to_owned(), "2".to_owned()),
+            ("a".to_owned(), "3".to_owned()),
+            ("a".to_owned(), "4".to_owned()),
+        ];
+        assert_eq!(
+            serde_json::to_value(fields(pairs)).unwrap(),
+            json!({"a": ["1", "3", "4"], "b": "2"})
+        );
+    }
+
+    #[test]
+    fn test_title_case() {
+        assert_eq!(title_case("x-request-id"), "X-Request-Id");
+        assert_eq!(title_case("host"), "Host");
+    }
+
+    #[test]
+    fn test_data_binary() {
+        assert_eq!(
+            data(&[0xff, 0xfe]),
+            "data:application/octet-stream;base64,//4="
+        );
+    }
+
+    #[test]
+    fn test_headers() {
+        let req = request()
+            .header("x-request-id", "1234")
+            .header("user-agent", "ExampleBot")
+            .build();
+
+        assert_eq!(
+            serde_json::to_value(Reflection::new().headers(&req)).unwrap(),
+            json!({"headers": {
+                "User-Agent": "ExampleBot",
+                "X-Request-Id": "1234",
+            }})
+        );
+    }
+
+    #[test]
+    fn test_url() {
+        let req = request()
+            .path("/anything?a=1")
+            .typed_header(Host::from(Authority::from_static("example.com")))
+            .build();
+
+        assert_eq!(
+            serde_json::to_value(Reflection::new().url(&req)).unwrap(),
+            json!({"url": "http://example.com/anything?a=1"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_form_body() {
+        let mut req = request()
+            .typed_header(ContentType::form_url_encoded())
+            .body("key=val&key=other")
+            .build();
+
+        let reflection = Reflection::new().body(&mut req).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(reflection).unwrap(),
+            json!({
+                "data": "",
+                "files": {},
+                "form": {"key": ["val", "other"]},
+                "json": null,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_body() {
+        let mut req = request()
+            .typed_header(ContentType::json())
+            .body(r#"{"key": "val"}"#)
+            .build();
+
+        let reflection = Reflection::new().body(&mut req).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(reflection).unwrap(),
+            json!({
+                "data": r#"{"key": "val"}"#,
+                "files": {},
+                "form": {},
+                "json": {"key": "val"},
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multipart_body() {
+        let mut req = request()
+            .header("content-type", &multipart_content_type())
+            .body(multipart_body(&[
+                ("key", None, "val"),
+                ("key", None, "other"),
+                ("upload", Some("a.txt"), "contents"),
+            ]))
+            .build();
+
+        let reflection = Reflection::new().body(&mut req).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(reflection).unwrap(),
+            json!({
+                "data": "",
+                "files": {"upload": "contents"},
+                "form": {"key": ["val", "other"]},
+                "json": null,
+            })
+        );
+    }
+}
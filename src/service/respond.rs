@@ -0,0 +1,214 @@
+//! A response built from the parameters of the request
+use crate::headers::{ContentType, HeaderMapExt};
+use crate::http::StatusCode;
+use crate::http::{body_from_stream, response, Bytes, Error, Request, Result};
+use crate::middleware::HOP_BY_HOP;
+use futures::prelude::*;
+use hyper::header::{self, HeaderName, HeaderValue};
+use std::cmp::min;
+use std::time::Duration;
+
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Headers that frame the message, which hyper has to be left to write,
+/// on top of the hop-by-hop ones
+const RESERVED: [HeaderName; 3] = [
+    header::CONTENT_LENGTH,
+    header::TRAILER,
+    header::PROXY_AUTHENTICATE,
+];
+
+#[derive(Debug, Default)]
+struct Script {
+    status: Option<StatusCode>,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    body: Option<Bytes>,
+    delay: Option<Duration>,
+    chunk_size: Option<usize>,
+}
+
+/// A header such as `x-test:1`, refusing the reserved ones
+///
+/// `HeaderValue` refuses control characters, so a value can't smuggle in
+/// further headers.
+fn parse_header(
+    header: &str,
+) -> std::result::Result<(HeaderName, HeaderValue), Error> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| Error::bad_request("header has to be name:value"))?;
+    let name = name
+        .trim()
+        .parse::<HeaderName>()
+        .map_err(|_| Error::bad_request("invalid header name"))?;
+    if HOP_BY_HOP.contains(&name) || RESERVED.contains(&name) {
+        return Err(Error::bad_request(format!("{} can't be set", name)));
+    }
+    let value = value
+        .trim()
+        .parse::<HeaderValue>()
+        .map_err(|_| Error::bad_request("invalid header value"))?;
+    Ok((name, value))
+}
+
+fn parse_script(
+    pairs: &[(String, String)],
+    max_delay: Duration,
+) -> std::result::Result<Script, Error> {
+    let mut script = Script::default();
+    for (key, value) in pairs {
+        match key.as_str() {
+            "status" => {
+                let status = value
+                    .parse::<u16>()
+                    .ok()
+                    // Informational responses don't end the exchange
+                    .filter(|code| (200..600).contains(code))
+                    .and_then(|code| StatusCode::from_u16(code).ok())
+                    .ok_or_else(|| Error::bad_request("invalid status"))?;
+                script.status = Some(status);
+            }
+            "header" => script.headers.push(parse_header(value)?),
+            "body" | "body_base64" if script.body.is_some() => {
+                return Err(Error::bad_request("only one body can be given"))
+            }
+            "body" => script.body = Some(value.clone().into()),
+            "body_base64" => {
+                let body = super::base64::decode(value)
+                    .ok_or_else(|| Error::bad_request("invalid body_base64"))?;
+                script.body = Some(body.into());
+            }
+            "delay" => {
+                let delay = value
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| Error::bad_request("invalid delay"))?;
+                script.delay = Some(min(delay, max_delay));
+            }
+            "chunk_size" => {
+                let size = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|size| *size > 0)
+                    .ok_or_else(|| Error::bad_request("invalid chunk_size"))?;
+                script.chunk_size = Some(size);
+            }
+            _ => {}
+        }
+    }
+    if script.body.as_ref().map_or(0, Bytes::len) > MAX_BODY_SIZE {
+        return Err(Error::bad_request("body is too large"));
+    }
+    Ok(script)
+}
+
+/// Answers with the `status`, repeated `header=name:value`s and `body` (or
+/// `body_base64`) given in the query or a form body, after `delay` seconds,
+/// sending the body in chunks of `chunk_size` bytes if given
+pub async fn respond(mut req: Request) -> Result {
+    let mut pairs = req
+        .query::<Vec<(String, String)>>()
+        .map_err(|_| Error::bad_request("invalid query"))?;
+    if req.has_content_type(mime::APPLICATION, mime::WWW_FORM_URLENCODED) {
+        pairs.extend(req.form::<Vec<(String, String)>>().await?);
+    }
+    let script = parse_script(&pairs, req.config().max_delay())?;
+
+    if let Some(delay) = script.delay {
+        tokio::time::sleep(substitute_in_test!(delay => Duration::ZERO)).await;
+    }
+
+    let body = script.body.unwrap_or_default();
+    let mut res = match script.chunk_size {
+        Some(size) => {
+            let chunks = (0..body.len())
+                .step_by(size)
+                .map(|start| body.slice(start..min(start + size, body.len())))
+                .collect::<Vec<_>>();
+            response().body(body_from_stream(stream::iter(chunks)))?
+        }
+        None => response().body(body)?,
+    };
+    *res.status_mut() = script.status.unwrap_or(StatusCode::OK);
+    let headers = res.headers_mut();
+    if !script
+        .headers
+        .iter()
+        .any(|(name, _)| name == header::CONTENT_TYPE)
+    {
+        headers.typed_insert(ContentType::text());
+    }
+    for (name, value) in script.headers {
+        headers.append(name, value);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::Method;
+
+    #[test]
+    fn test_parse_header() {
+        let (name, value) = parse_header("X-Test: a b").unwrap();
+        assert_eq!(name, "x-test");
+        assert_eq!(value, "a b");
+
+        assert!(parse_header("x-test").is_err());
+        assert!(parse_header("x-test:a\r\nx-injected:b").is_err());
+        assert!(parse_header("content-length:1").is_err());
+        assert!(parse_header("Transfer-Encoding:chunked").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_respond() {
+        let res = request()
+            .path("/?status=201&header=x-a:1&header=x-a:2&body=hello")
+            .handle(respond)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let values = res.headers().get_all("x-a").iter().collect::<Vec<_>>();
+        assert_eq!(values, ["1", "2"]);
+        assert_eq!(res.headers()["content-type"], "text/plain");
+        assert_eq!(res.read_body_utf8().await.unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_respond_form_base64_chunks() {
+        let res = request()
+            .method(Method::POST)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("body_base64=AAECAwQ%3D&chunk_size=2&header=content-type:x/y")
+            .handle(respond)
+            .await
+            .unwrap();
+
+        assert_eq!(res.headers()["content-type"], "x/y");
+        let mut body = res.into_body();
+        let mut chunks = vec![];
+        while let Some(chunk) = body.data().await {
+            chunks.push(chunk.unwrap().to_vec());
+        }
+        assert_eq!(chunks, [vec![0, 1], vec![2, 3], vec![4]]);
+    }
+
+    #[tokio::test]
+    async fn test_respond_invalid() {
+        for query in [
+            "/?status=99",
+            "/?status=101",
+            "/?body=a&body_base64=YQ",
+            "/?header=connection:close",
+            "/?delay=-1",
+            "/?chunk_size=0",
+        ] {
+            let res = request().path(query).handle(respond).await.unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", query);
+        }
+    }
+}
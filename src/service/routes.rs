@@ -0,0 +1,105 @@
+//! The route table, for clients to discover the endpoints without scraping
+//! the index page
+use crate::handler::Handler;
+use crate::http::{response, Bytes, Request, Result};
+use crate::router::Route;
+use async_trait::async_trait;
+use hyper::Method;
+use serde_derive::Serialize;
+
+#[derive(Serialize)]
+struct RouteEntry<'a> {
+    /// The pattern, like `/status/:code`
+    path: String,
+    /// The name the route goes by in logs and metrics, if not its path
+    name: Option<&'a str>,
+    methods: Vec<&'a str>,
+    tags: &'a [&'static str],
+    handler: Option<&'a str>,
+    description: Option<&'a str>,
+}
+
+/// The routes of a router, rendered as JSON once they are all known, like
+/// the index page
+#[derive(Clone, Debug, Default)]
+pub struct Routes(Bytes);
+
+impl<'a, IT: IntoIterator<Item = &'a Route>> From<IT> for Routes {
+    fn from(routes: IT) -> Self {
+        let entries = routes
+            .into_iter()
+            .map(|route| RouteEntry {
+                path: route.path().to_string(),
+                name: route.explicit_name(),
+                methods: route.methods().iter().map(Method::as_str).collect(),
+                tags: route.tags(),
+                handler: route.handler(),
+                description: route.description(),
+            })
+            .collect::<Vec<_>>();
+        Routes(
+            serde_json::to_vec_pretty(&entries)
+                .unwrap_or_default()
+                .into(),
+        )
+    }
+}
+
+#[async_trait]
+impl Handler for Routes {
+    async fn handle(&self, _: Request) -> Result {
+        response()
+            .header("content-type", "application/json")
+            .body(self.0.clone())
+    }
+
+    fn describe(&self) -> Option<&'static str> {
+        Some(
+            "Lists the routes with their path pattern, name, methods, tags, \
+             handler and description, as JSON",
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use uri_path::path;
+
+    async fn get(_: Request) -> Result {
+        crate::http::ok("")
+    }
+
+    #[tokio::test]
+    async fn test_routes() {
+        let builder = Router::builder()
+            .group("methods", |builder| {
+                builder.install(
+                    get,
+                    route(path!("get"))
+                        .name("get")
+                        .description("Returns GET data"),
+                )
+            })
+            .install(Routes::default(), route(path!("routes")));
+        let res = request()
+            .handle(Routes::from(builder.routes()))
+            .await
+            .unwrap();
+
+        let table = res.read_body_json().await.unwrap();
+        assert_eq!(table[0]["path"], "/get");
+        assert_eq!(table[0]["name"], "get");
+        assert_eq!(table[0]["methods"][0], "GET");
+        assert_eq!(table[0]["tags"][0], "methods");
+        assert_eq!(table[0]["description"], "Returns GET data");
+        assert!(table[0]["handler"].as_str().unwrap().ends_with("::get"));
+        assert!(table[1]["name"].is_null());
+        assert!(table[1]["description"]
+            .as_str()
+            .unwrap()
+            .starts_with("Lists the routes"));
+    }
+}
@@ -0,0 +1,133 @@
+//! Responses sent at a crawl, for testing how long clients wait on the head
+//! of a response apart from its body
+use crate::headers::{ContentLength, ContentType};
+use crate::http::{
+    bad_request, body_from_stream, response, Bytes, Replacement, Request,
+    Result, StatusCode, ThrottledStream,
+};
+use futures::prelude::*;
+use serde_derive::Deserialize;
+use std::time::Duration;
+
+const DEFAULT_RATE: u64 = 10;
+const DEFAULT_BYTES: usize = 100;
+const MAX_BYTES: usize = 1024 * 1024;
+const BODY: &[u8] = b"The headers were slow, this body is not\n";
+
+#[derive(Deserialize)]
+pub struct SlowQueryParams {
+    /// In bytes per second
+    rate: Option<u64>,
+    numbytes: Option<usize>,
+}
+
+/// The rate of the query, as long as sending `len` bytes at it takes no
+/// longer than the maximum delay
+fn rate(req: &Request, query: &SlowQueryParams, len: usize) -> Option<u64> {
+    let rate = query.rate.unwrap_or(DEFAULT_RATE);
+    let takes = Duration::try_from_secs_f64(len as f64 / rate as f64).ok()?;
+    (takes <= req.config().max_delay()).then_some(rate)
+}
+
+/// Sends the status line and headers at `rate` bytes per second, and the
+/// body all at once after them
+///
+/// hyper writes heads whole, so this one is written straight to the
+/// connection, which only works over HTTP/1.x.
+pub async fn slow_headers(req: Request) -> Result {
+    let query = req.query::<SlowQueryParams>().map_err(|_| bad_request())?;
+    let head = format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/plain\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n",
+        BODY.len()
+    );
+    let rate = rate(&req, &query, head.len()).ok_or_else(bad_request)?;
+
+    let head = ThrottledStream::new(stream::iter([Bytes::from(head)]), rate);
+    let replacement = head.chain(stream::iter([Bytes::from_static(BODY)]));
+    if !req.replace_response(Replacement::stream(replacement)) {
+        return response()
+            .status(StatusCode::HTTP_VERSION_NOT_SUPPORTED)
+            .body("Only served over HTTP/1.x");
+    }
+    response().into()
+}
+
+/// Sends the headers at once, and a body of `numbytes` bytes after them at
+/// `rate` bytes per second
+pub async fn slow_body(req: Request) -> Result {
+    let query = req.query::<SlowQueryParams>().map_err(|_| bad_request())?;
+    let numbytes = query.numbytes.unwrap_or(DEFAULT_BYTES);
+    if numbytes > MAX_BYTES {
+        return Err(bad_request());
+    }
+    let rate = rate(&req, &query, numbytes).ok_or_else(bad_request)?;
+
+    let body = stream::iter([Bytes::from(vec![b'*'; numbytes])]);
+    response()
+        .typed_header(ContentType::text())
+        .typed_header(ContentLength(numbytes as u64))
+        .body(body_from_stream(ThrottledStream::new(body, rate)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::http::{Abort, Aborting};
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_slow_headers() {
+        let aborting = Aborting::new();
+        request()
+            .path("/?rate=500")
+            .extension(aborting.clone())
+            .handle(slow_headers)
+            .await
+            .unwrap();
+
+        let replacement = match aborting.get() {
+            Some(Abort::Replace(replacement)) => replacement.take().unwrap(),
+            other => panic!("not replaced: {:?}", other),
+        };
+        let chunks = replacement.collect::<Vec<_>>().await;
+        // A tenth of a second's worth at a time
+        assert_eq!(chunks[0].len(), 50);
+        assert_eq!(chunks.last().unwrap(), BODY);
+        let head = chunks[..chunks.len() - 1].concat();
+        assert!(head.ends_with(b"Connection: close\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_slow_body() {
+        let start = std::time::Instant::now();
+        let res = request()
+            .path("/?numbytes=20&rate=100")
+            .handle(slow_body)
+            .await
+            .unwrap();
+
+        assert_eq!(res.headers()["content-length"], "20");
+        let mut body = res.into_body();
+        let mut len = 0;
+        while let Some(chunk) = body.data().await {
+            len += chunk.unwrap().len();
+        }
+        assert_eq!(len, 20);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_slower_than_max_delay() {
+        let res = request()
+            .path("/?numbytes=1000&rate=1")
+            .handle(slow_body)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::headers::{CacheControl, ContentType};
+use crate::http::sse::{comment, Event};
+use crate::http::{
+    bad_request, body_from_stream, response, Bytes, Request, Result,
+};
+use futures::prelude::*;
+use serde_derive::Deserialize;
+use std::cmp::min;
+use std::time::Duration;
+
+const MAX_EVENTS: u32 = 100;
+
+#[derive(Deserialize)]
+pub struct SseQueryParams {
+    count: Option<u32>,
+    interval: Option<f64>,
+    retry: Option<u64>,
+    heartbeat: Option<f64>,
+}
+
+fn events(count: u32, interval: Duration) -> impl Stream<Item = Bytes> {
+    stream::iter(1..=count).then(move |n| async move {
+        tokio::time::sleep(interval).await;
+        Event::new()
+            .id(n.to_string())
+            .event("tick")
+            .data(n.to_string())
+            .into()
+    })
+}
+
+fn heartbeats(period: Duration) -> impl Stream<Item = Bytes> {
+    stream::repeat(()).then(move |_| async move {
+        tokio::time::sleep(period).await;
+        comment("heartbeat")
+    })
+}
+
+fn event_stream(
+    retry: Option<Duration>,
+    count: u32,
+    interval: Duration,
+    heartbeat: Duration,
+) -> impl Stream<Item = Bytes> {
+    let preamble = retry.map(|retry| Event::new().retry(retry).into());
+
+    // Heartbeats never end, so mark the end of the events to stop them
+    let events = events(count, interval)
+        .map(Some)
+        .chain(stream::once(future::ready(None)));
+
+    stream::iter(preamble).chain(
+        stream::select(events, heartbeats(heartbeat).map(Some))
+            .take_while(|item| future::ready(item.is_some()))
+            .filter_map(future::ready),
+    )
+}
+
+pub async fn sse(req: Request) -> Result {
+    let query = req.query::<SseQueryParams>().map_err(|_| bad_request())?;
+
+    let count = min(query.count.unwrap_or(10), MAX_EVENTS);
+    let interval = Duration::try_from_secs_f64(query.interval.unwrap_or(1.0))
+        .map_err(|_| bad_request())?;
+    let heartbeat =
+        Duration::try_from_secs_f64(query.heartbeat.unwrap_or(15.0))
+            .ok()
+            .filter(|heartbeat| !heartbeat.is_zero())
+            .ok_or_else(bad_request)?;
+    let retry = query.retry.map(Duration::from_millis);
+
+    let interval = substitute_in_test!(interval => Duration::ZERO);
+    response()
+        .typed_header(ContentType::from(mime::TEXT_EVENT_STREAM))
+        .typed_header(CacheControl::new().with_no_cache())
+        .body(body_from_stream(Box::pin(req.until_disconnect(
+            req.until_draining(event_stream(retry, count, interval, heartbeat)),
+        ))))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_event_stream_heartbeat() {
+        let chunks = event_stream(
+            None,
+            1,
+            Duration::from_millis(100),
+            Duration::from_millis(30),
+        )
+        .collect::<Vec<_>>()
+        .await;
+
+        assert_eq!(chunks.first(), Some(&comment("heartbeat")));
+        assert_eq!(
+            chunks.last(),
+            Some(&Event::new().id("1").event("tick").data("1").into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sse() {
+        let res = request().path("/?count=2").handle(sse).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+        let body = res.read_body_utf8().await.unwrap();
+        assert_eq!(
+            body,
+            "id: 1\nevent: tick\ndata: 1\n\nid: 2\nevent: tick\ndata: 2\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sse_retry() {
+        let res = request()
+            .path("/?count=1&retry=2500")
+            .handle(sse)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = res.read_body_utf8().await.unwrap();
+        assert_eq!(body, "retry: 2500\n\nid: 1\nevent: tick\ndata: 1\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_sse_bad_interval() {
+        let res = request().path("/?interval=-1").handle(sse).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_sse_bad_heartbeat() {
+        let res = request().path("/?heartbeat=0").handle(sse).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
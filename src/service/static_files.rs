@@ -0,0 +1,386 @@
+//! Serving the files of a directory below `/static`
+use crate::handler::Handler;
+use crate::headers::{
+    evaluate_preconditions, ETag, HeaderMapExt, LastModified, Precondition,
+    Range,
+};
+use crate::http::range::ranged_file;
+use crate::http::{
+    bad_request, json, not_acceptable, not_found, redirect_with_status,
+    response, Error, Request, Response, Result, StatusCode,
+};
+use crate::service::template::render;
+use askama::Template;
+use async_trait::async_trait;
+use httpdate::fmt_http_date;
+use hyper::header::{HeaderValue, VARY};
+use percent_encoding::{
+    percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC,
+};
+use serde_derive::Serialize;
+use std::fs::Metadata;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Serves the files below `root`, streaming each or just the ranges of it
+/// asked for
+///
+/// A directory is served by its `index.html`, if it has one, or else by a
+/// listing of its entries when those are switched on.
+#[derive(Clone, Debug)]
+pub struct StaticFiles {
+    root: PathBuf,
+    listing: bool,
+}
+
+enum Target {
+    File(PathBuf),
+    Directory(PathBuf),
+}
+
+impl StaticFiles {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            listing: false,
+        }
+    }
+
+    pub fn listing(mut self, listing: bool) -> Self {
+        self.listing = listing;
+        self
+    }
+
+    /// `path` with any symbolic links followed, if that stays below the root
+    fn contained(&self, path: &Path) -> Option<PathBuf> {
+        let root = self.root.canonicalize().ok()?;
+        let path = path.canonicalize().ok()?;
+        path.starts_with(root).then_some(path)
+    }
+
+    /// What `rest` names below the root, refusing anything that leads out
+    /// of it
+    fn resolve(&self, rest: &str) -> Option<Target> {
+        let rest = percent_decode_str(rest).decode_utf8().ok()?;
+        let mut path = self.root.clone();
+        for component in Path::new(rest.as_ref()).components() {
+            match component {
+                Component::Normal(segment) => path.push(segment),
+                Component::CurDir => {}
+                _ => return None,
+            }
+        }
+
+        let path = self.contained(&path)?;
+        if path.is_dir() {
+            Some(Target::Directory(path))
+        } else {
+            path.is_file().then_some(Target::File(path))
+        }
+    }
+
+    /// Answers for a directory, whose path has to end with a slash for the
+    /// relative links in it to work
+    async fn directory(&self, req: &Request, dir: &Path) -> Result {
+        if !req.uri().path().ends_with('/') {
+            let uri = format!("{}/", req.uri().path())
+                .parse()
+                .map_err(|_| bad_request())?;
+            return redirect_with_status(uri, StatusCode::MOVED_PERMANENTLY);
+        }
+        if let Some(index) = self.contained(&dir.join("index.html")) {
+            return self.file(req, &index).await;
+        }
+        if !self.listing {
+            return Err(not_found());
+        }
+
+        let listing = Listing::read(req.uri().path(), dir)
+            .await
+            .map_err(Error::internal)?;
+        let mut res = match req.negotiate(&["text/html", "application/json"]) {
+            Some("application/json") => json(&listing)?,
+            Some(_) => render(&listing)?,
+            None => return Err(not_acceptable()),
+        };
+        res.headers_mut()
+            .insert(VARY, HeaderValue::from_static("accept"));
+        Ok(res)
+    }
+
+    async fn file(&self, req: &Request, path: &Path) -> Result {
+        let metadata =
+            tokio::fs::metadata(path).await.map_err(Error::internal)?;
+        let etag = etag(&metadata);
+        let last_modified = metadata.modified().ok();
+
+        let res = match evaluate_preconditions(
+            req.headers(),
+            req.method(),
+            etag.as_ref(),
+            last_modified,
+        ) {
+            Precondition::Passed => {
+                let content_type =
+                    mime_guess::from_path(path).first_or_octet_stream();
+                ranged_file(path, content_type, req.typed_header::<Range>())
+                    .await?
+            }
+            Precondition::NotModified => {
+                Result::from(response().status(StatusCode::NOT_MODIFIED))?
+            }
+            Precondition::Failed => {
+                return response()
+                    .status(StatusCode::PRECONDITION_FAILED)
+                    .into()
+            }
+        };
+        Ok(with_validators(res, etag, last_modified))
+    }
+}
+
+#[derive(Serialize)]
+struct Entry {
+    name: String,
+    directory: bool,
+    /// In bytes, for files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    /// In seconds since the Unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<u64>,
+}
+
+impl Entry {
+    fn href(&self) -> String {
+        let name = utf8_percent_encode(&self.name, NON_ALPHANUMERIC);
+        if self.directory {
+            format!("{}/", name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn modified_date(&self) -> String {
+        self.modified
+            .map(|secs| fmt_http_date(UNIX_EPOCH + Duration::from_secs(secs)))
+            .unwrap_or_default()
+    }
+}
+
+/// The entries of a directory, sorted by name
+#[derive(Serialize, Template)]
+#[template(path = "listing.html")]
+struct Listing {
+    path: String,
+    entries: Vec<Entry>,
+}
+
+impl Listing {
+    async fn read(path: &str, dir: &Path) -> std::io::Result<Self> {
+        let mut entries = vec![];
+        let mut read_dir = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            entries.push(Entry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                directory: metadata.is_dir(),
+                size: metadata.is_file().then_some(metadata.len()),
+                modified: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|modified| modified.as_secs()),
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Self {
+            path: percent_decode_str(path).decode_utf8_lossy().into_owned(),
+            entries,
+        })
+    }
+}
+
+/// A weak validator from the size and modification time of the file
+fn etag(metadata: &Metadata) -> Option<ETag> {
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    format!(
+        "W/\"{:x}-{:x}.{:x}\"",
+        metadata.len(),
+        modified.as_secs(),
+        modified.subsec_nanos()
+    )
+    .parse()
+    .ok()
+}
+
+fn with_validators(
+    mut res: Response,
+    etag: Option<ETag>,
+    last_modified: Option<SystemTime>,
+) -> Response {
+    if let Some(etag) = etag {
+        res.headers_mut().typed_insert(etag);
+    }
+    if let Some(last_modified) = last_modified {
+        res.headers_mut()
+            .typed_insert(LastModified::from(last_modified));
+    }
+    res
+}
+
+#[async_trait]
+impl Handler for StaticFiles {
+    async fn handle(&self, req: Request) -> Result {
+        let rest = req.param::<String>("rest").unwrap_or_default();
+        match self.resolve(&rest).ok_or_else(not_found)? {
+            Target::File(path) => self.file(&req, &path).await,
+            Target::Directory(dir) => self.directory(&req, &dir).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    fn root() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "httpbox-{}-{}",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        std::fs::create_dir_all(dir.join("public/docs")).unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+        std::fs::write(dir.join("public/hello.txt"), "hello world").unwrap();
+        std::fs::write(dir.join("public/docs/index.html"), "<p>docs</p>")
+            .unwrap();
+        dir
+    }
+
+    async fn get(files: &StaticFiles, rest: &str) -> Response {
+        request()
+            .path(&format!("/static/{}", rest))
+            .param("rest", rest)
+            .handle(files.clone())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serves_file() {
+        let root = root();
+        let files = StaticFiles::new(root.join("public"));
+        let res = get(&files, "hello.txt").await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["content-type"], "text/plain");
+        assert_eq!(res.headers()["content-length"], "11");
+        assert!(res.headers().contains_key("etag"));
+        assert!(res.headers().contains_key("last-modified"));
+        assert_eq!(res.read_body_utf8().await.unwrap(), "hello world");
+
+        let res = get(&files, "docs/").await;
+        assert_eq!(res.headers()["content-type"], "text/html");
+        assert_eq!(res.read_body_utf8().await.unwrap(), "<p>docs</p>");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_directory_redirect() {
+        let root = root();
+        let files = StaticFiles::new(root.join("public"));
+        let res = get(&files, "docs").await;
+
+        assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(res.headers()["location"], "/static/docs/");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_listing() {
+        let root = root();
+        let files = StaticFiles::new(&root);
+        assert_eq!(get(&files, "").await.status(), StatusCode::NOT_FOUND);
+
+        let files = files.listing(true);
+        let res = request()
+            .path("/static/public/")
+            .param("rest", "public/")
+            .header("accept", "application/json")
+            .handle(files.clone())
+            .await
+            .unwrap();
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(body["path"], "/static/public/");
+        assert_eq!(body["entries"][0]["name"], "docs");
+        assert_eq!(body["entries"][0]["directory"], true);
+        assert_eq!(body["entries"][1]["name"], "hello.txt");
+        assert_eq!(body["entries"][1]["size"], 11);
+        assert!(body["entries"][1]["modified"].is_u64());
+
+        let res = get(&files, "public/").await;
+        assert_eq!(res.headers()["content-type"], "text/html");
+        let body = res.read_body_utf8().await.unwrap();
+        assert!(body.contains(r#"<a href="docs/">docs/</a>"#));
+        assert!(body.contains(r#"<a href="hello%2Etxt">hello.txt</a>"#));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_path_traversal() {
+        let root = root();
+        let files = StaticFiles::new(root.join("public"));
+
+        for rest in ["../secret.txt", "%2e%2e/secret.txt", "/etc/passwd"] {
+            let res = get(&files, rest).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", rest);
+        }
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_conditional_and_range() {
+        let root = root();
+        let files = StaticFiles::new(root.join("public"));
+        let etag = get(&files, "hello.txt").await.headers()["etag"].clone();
+
+        let res = request()
+            .param("rest", "hello.txt")
+            .header("if-none-match", etag.to_str().unwrap())
+            .handle(files.clone())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let res = request()
+            .param("rest", "hello.txt")
+            .header("range", "bytes=6-")
+            .handle(files.clone())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.headers()["content-range"], "bytes 6-10/11");
+        assert_eq!(res.headers()["content-length"], "5");
+        assert_eq!(res.read_body_utf8().await.unwrap(), "world");
+
+        let res = request()
+            .param("rest", "hello.txt")
+            .header("range", "bytes=0-1,-2")
+            .handle(files.clone())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        let length: usize = res.headers()["content-length"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let body = res.read_body_utf8().await.unwrap();
+        assert_eq!(body.len(), length);
+        assert!(body.contains("bytes 0-1/11\r\n\r\nhe\r\n"));
+        assert!(body.contains("bytes 9-10/11\r\n\r\nld\r\n"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
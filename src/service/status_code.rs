@@ -1,20 +1,158 @@
-use crate::http::{bad_request, response, Request, Result, StatusCode};
+use crate::headers::{ContentType, Location, WWWAuthenticate};
+use crate::http::{bad_request, response, Request, Result, StatusCode, Uri};
+use crate::random::rng;
+use crate::service::auth::REALM;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+
+const TEAPOT: &str = r#"
+    -=[ teapot ]=-
+
+       _...._
+     .'  _ _ `.
+    | ."` ^ `". _,
+    \_;`"---"`|//
+      |       ;/
+      \_     _/
+        `"""`
+"#;
+
+#[derive(Debug, PartialEq)]
+struct WeightedStatus {
+    status: StatusCode,
+    weight: f64,
+}
+
+fn parse_status(s: &str) -> Option<WeightedStatus> {
+    let (code, weight) = match s.split_once(':') {
+        Some((code, weight)) => (code, weight.trim().parse::<f64>().ok()?),
+        None => (s, 1.0),
+    };
+
+    let status = code.trim().parse::<StatusCode>().ok()?;
+    if status.is_informational() || !weight.is_finite() || weight < 0.0 {
+        return None;
+    }
+
+    Some(WeightedStatus { status, weight })
+}
+
+fn parse_statuses(s: &str) -> Option<Vec<WeightedStatus>> {
+    s.split(',').map(parse_status).collect()
+}
+
+fn choose_status<R: Rng>(
+    statuses: &[WeightedStatus],
+    rng: &mut R,
+) -> Option<StatusCode> {
+    let index = WeightedIndex::new(statuses.iter().map(|s| s.weight)).ok()?;
+    Some(statuses[index.sample(rng)].status)
+}
+
+fn status_response(status: StatusCode) -> Result {
+    let res = response().status(status);
+
+    match status {
+        StatusCode::IM_A_TEAPOT => {
+            res.typed_header(ContentType::text()).body(TEAPOT)
+        }
+        StatusCode::UNAUTHORIZED => {
+            res.typed_header(WWWAuthenticate::basic_realm(REALM)).into()
+        }
+        StatusCode::MOVED_PERMANENTLY
+        | StatusCode::FOUND
+        | StatusCode::SEE_OTHER
+        | StatusCode::USE_PROXY
+        | StatusCode::TEMPORARY_REDIRECT => res
+            .typed_header(Location::from(Uri::from_static("/redirect/1")))
+            .into(),
+        // Bodiless statuses (204, 304) fall through to an empty response
+        _ => res.into(),
+    }
+}
 
 pub async fn status_code(req: Request) -> Result {
-    let status = req.param::<StatusCode>("code").ok_or_else(bad_request)?;
+    let statuses = req
+        .param::<String>("code")
+        .as_deref()
+        .and_then(parse_statuses)
+        .ok_or_else(bad_request)?;
+
+    let status =
+        choose_status(&statuses, &mut rng(None)).ok_or_else(bad_request)?;
 
-    response().status(status).into()
+    status_response(status)
 }
 
 #[cfg(test)]
 mod test {
 
     use super::*;
+    use crate::headers::HeaderMapExt;
     use crate::test::*;
     use hyper::http::StatusCode;
 
-    #[tokio::test]
+    #[test]
+    fn test_parse_statuses() {
+        assert_eq!(
+            parse_statuses("200:0.7,500:0.3").unwrap(),
+            vec![
+                WeightedStatus {
+                    status: StatusCode::OK,
+                    weight: 0.7
+                },
+                WeightedStatus {
+                    status: StatusCode::INTERNAL_SERVER_ERROR,
+                    weight: 0.3
+                },
+            ]
+        )
+    }
+
+    #[test]
+    fn test_parse_statuses_default_weight() {
+        assert_eq!(
+            parse_statuses("200,404").unwrap(),
+            vec![
+                WeightedStatus {
+                    status: StatusCode::OK,
+                    weight: 1.0
+                },
+                WeightedStatus {
+                    status: StatusCode::NOT_FOUND,
+                    weight: 1.0
+                },
+            ]
+        )
+    }
+
+    #[test]
+    fn test_parse_statuses_invalid() {
+        assert!(parse_statuses("200:abc").is_none());
+        assert!(parse_statuses("200:-1").is_none());
+        assert!(parse_statuses("200,").is_none());
+        assert!(parse_statuses("100").is_none());
+    }
 
+    #[test]
+    fn test_choose_status_weighted() {
+        let statuses = parse_statuses("200:0,500:1").unwrap();
+        let mut rng = rng(Some(1234));
+        for _ in 0..10 {
+            assert_eq!(
+                choose_status(&statuses, &mut rng),
+                Some(StatusCode::INTERNAL_SERVER_ERROR)
+            );
+        }
+    }
+
+    #[test]
+    fn test_choose_status_no_weight() {
+        let statuses = parse_statuses("200:0,500:0").unwrap();
+        assert_eq!(choose_status(&statuses, &mut rng(None)), None);
+    }
+
+    #[tokio::test]
     async fn test_status_code() {
         let res = request()
             .param("code", "429")
@@ -35,4 +173,55 @@ mod test {
 
         assert_eq!(res.status(), StatusCode::BAD_REQUEST);
     }
+
+    #[tokio::test]
+    async fn test_weighted_status_code() {
+        let res = request()
+            .param("code", "201:1,500:0")
+            .handle(status_code)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_no_content_status_code() {
+        let res = request()
+            .param("code", "204")
+            .handle(status_code)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(res.read_body().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_teapot_status_code() {
+        let res = request()
+            .param("code", "418")
+            .handle(status_code)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::IM_A_TEAPOT);
+        let body = res.read_body_utf8().await.unwrap();
+        assert!(body.contains("teapot"));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_status_code() {
+        let res = request()
+            .param("code", "302")
+            .handle(status_code)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert_eq!(
+            res.headers().typed_get::<Location>().unwrap().uri(),
+            &Uri::from_static("/redirect/1")
+        );
+    }
 }
@@ -0,0 +1,81 @@
+use crate::headers::ContentType;
+use crate::http::{
+    bad_request, body_from_stream, response, Bytes, Request, Result,
+};
+use crate::service::reflection::Reflection;
+use futures::prelude::*;
+use std::cmp::min;
+
+const MAX_LINES: usize = 100;
+
+/// The reflection with its id as one line of JSON
+fn line(reflection: &Reflection, id: usize) -> Bytes {
+    let mut line = serde_json::to_vec(&reflection.clone().id(id))
+        .expect("reflections serialize");
+    line.push(b'\n');
+    line.into()
+}
+
+/// Streams `n` lines of JSON, one chunk each, describing the request like
+/// `/get` does
+pub async fn stream(req: Request) -> Result {
+    let n = min(req.param::<usize>("n").ok_or_else(bad_request)?, MAX_LINES);
+    let reflection = Reflection::new()
+        .args(&req)?
+        .headers(&req)
+        .origin(&req)
+        .url(&req);
+
+    let lines = stream::iter(0..n).map(move |id| line(&reflection, id));
+    response()
+        .typed_header(ContentType::json())
+        .body(body_from_stream(Box::pin(req.until_draining(lines))))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_stream() {
+        let res = request()
+            .path("/stream/3?key=val")
+            .param("n", "3")
+            .handle(stream)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let mut body = res.into_body();
+        let mut lines = vec![];
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.unwrap();
+            assert_eq!(chunk.last(), Some(&b'\n'));
+            lines.push(serde_json::from_slice::<Value>(&chunk).unwrap());
+        }
+        assert_eq!(lines.len(), 3);
+        for (id, line) in lines.iter().enumerate() {
+            assert_eq!(line["id"], id);
+            assert_eq!(line["args"], json!({"key": "val"}));
+            assert_eq!(line["url"], "/stream/3?key=val");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_limit() {
+        let res = request().param("n", "1000").handle(stream).await.unwrap();
+        let body = res.read_body_utf8().await.unwrap();
+
+        assert_eq!(body.lines().count(), MAX_LINES);
+    }
+
+    #[tokio::test]
+    async fn test_stream_bad_n() {
+        let res = request().param("n", "x").handle(stream).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
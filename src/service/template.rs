@@ -0,0 +1,8 @@
+//! Rendering askama templates into HTML responses
+use crate::http::{html, Error, Result};
+use askama::Template;
+
+pub fn render<T: Template>(template: &T) -> Result {
+    let body = template.render().map_err(Error::internal)?;
+    html(body)
+}
@@ -0,0 +1,506 @@
+//! Rendering client-supplied templates, a small subset of Handlebars
+//!
+//! - `{{path}}` inserts a value, looked up in the current context and then
+//!   in the enclosing ones. Paths are dot-separated keys or array indices,
+//!   `this` is the current context itself and `@index` the position in the
+//!   enclosing `each`. Strings are inserted as they are, anything else as
+//!   JSON, nothing for `null` or a missing value.
+//! - `{{#each path}}...{{/each}}` renders its body for every element of an
+//!   array, or value of an object.
+//! - `{{#if path}}...{{else}}...{{/if}}` renders the first body unless the
+//!   value is missing, `null`, `false`, `0`, empty or an empty collection.
+//! - `{{#repeat n}}...{{/repeat}}` renders its body `n` times.
+//!
+//! Nothing is escaped. The template size, output size, nesting depth and
+//! amount of work are all limited, so a template can't tie up the server.
+use crate::handler::Json;
+use crate::http::{response, Error, Result};
+use hyper::header::CONTENT_TYPE;
+use serde_derive::Deserialize;
+use serde_json::Value;
+use std::fmt;
+
+const MAX_TEMPLATE_SIZE: usize = 64 * 1024;
+const MAX_OUTPUT_SIZE: usize = 1024 * 1024;
+const MAX_DEPTH: usize = 16;
+const MAX_REPEAT: u64 = 10_000;
+/// Nodes rendered, bounding templates that loop without output
+const MAX_STEPS: usize = 1_000_000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    TooLarge,
+    OutputTooLarge,
+    TooDeep,
+    TooMuchWork,
+    Unclosed(&'static str),
+    Unexpected(String),
+    BadRepeat(String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooLarge => {
+                write!(f, "template is over {} bytes", MAX_TEMPLATE_SIZE)
+            }
+            Self::OutputTooLarge => {
+                write!(f, "output is over {} bytes", MAX_OUTPUT_SIZE)
+            }
+            Self::TooDeep => {
+                write!(f, "blocks are nested over {} deep", MAX_DEPTH)
+            }
+            Self::TooMuchWork => write!(f, "template takes too long to render"),
+            Self::Unclosed(block) => write!(f, "unclosed {{{{#{}}}}}", block),
+            Self::Unexpected(tag) => write!(f, "unexpected {{{{{}}}}}", tag),
+            Self::BadRepeat(count) => write!(
+                f,
+                "repeat count {} is not a number up to {}",
+                count, MAX_REPEAT
+            ),
+        }
+    }
+}
+
+type Path = Vec<String>;
+
+#[derive(Debug, PartialEq)]
+enum Node {
+    Text(String),
+    Value(Path),
+    Each(Path, Vec<Node>),
+    If(Path, Vec<Node>, Vec<Node>),
+    Repeat(u64, Vec<Node>),
+}
+
+fn path(expression: &str) -> Path {
+    match expression {
+        "this" | "." => vec![],
+        _ => expression
+            .strip_prefix("this.")
+            .unwrap_or(expression)
+            .split('.')
+            .map(str::to_owned)
+            .collect(),
+    }
+}
+
+/// What ended a run of nodes
+enum End<'a> {
+    Eof,
+    Else,
+    Close(&'a str),
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    /// Nodes up to the end of the template or of the enclosing block
+    fn nodes(
+        &mut self,
+        depth: usize,
+    ) -> std::result::Result<(Vec<Node>, End<'a>), TemplateError> {
+        if depth > MAX_DEPTH {
+            return Err(TemplateError::TooDeep);
+        }
+        let mut nodes = vec![];
+        loop {
+            let (text, tag) = match self.rest.find("{{") {
+                Some(start) => {
+                    let end = self.rest[start..]
+                        .find("}}")
+                        .ok_or(TemplateError::Unclosed("{{"))?;
+                    let tag = &self.rest[start + 2..start + end];
+                    let text = &self.rest[..start];
+                    self.rest = &self.rest[start + end + 2..];
+                    (text, Some(tag.trim()))
+                }
+                None => (std::mem::take(&mut self.rest), None),
+            };
+            if !text.is_empty() {
+                nodes.push(Node::Text(text.to_owned()));
+            }
+
+            let tag = match tag {
+                Some(tag) => tag,
+                None => return Ok((nodes, End::Eof)),
+            };
+            if tag == "else" {
+                return Ok((nodes, End::Else));
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                return Ok((nodes, End::Close(name.trim())));
+            }
+            match tag.strip_prefix('#') {
+                Some(block) => nodes.push(self.block(block, depth)?),
+                None => nodes.push(Node::Value(path(tag))),
+            }
+        }
+    }
+
+    fn block(
+        &mut self,
+        block: &str,
+        depth: usize,
+    ) -> std::result::Result<Node, TemplateError> {
+        let (name, argument) =
+            block.split_once(char::is_whitespace).unwrap_or((block, ""));
+        let argument = argument.trim();
+        let name: &'static str = match name {
+            "each" => "each",
+            "if" => "if",
+            "repeat" => "repeat",
+            _ => return Err(TemplateError::Unexpected(format!("#{}", block))),
+        };
+
+        let (body, end) = self.nodes(depth + 1)?;
+        let (otherwise, end) = match end {
+            End::Else if name == "if" => self.nodes(depth + 1)?,
+            end => (vec![], end),
+        };
+        match end {
+            End::Close(closed) if closed == name => {}
+            End::Close(closed) => {
+                return Err(TemplateError::Unexpected(format!("/{}", closed)))
+            }
+            End::Else => {
+                return Err(TemplateError::Unexpected("else".to_owned()))
+            }
+            End::Eof => return Err(TemplateError::Unclosed(name)),
+        }
+
+        Ok(match name {
+            "each" => Node::Each(path(argument), body),
+            "if" => Node::If(path(argument), body, otherwise),
+            _ => {
+                let count = argument
+                    .parse()
+                    .ok()
+                    .filter(|count| *count <= MAX_REPEAT)
+                    .ok_or_else(|| {
+                        TemplateError::BadRepeat(argument.to_owned())
+                    })?;
+                Node::Repeat(count, body)
+            }
+        })
+    }
+}
+
+fn parse(template: &str) -> std::result::Result<Vec<Node>, TemplateError> {
+    if template.len() > MAX_TEMPLATE_SIZE {
+        return Err(TemplateError::TooLarge);
+    }
+    let mut parser = Parser { rest: template };
+    match parser.nodes(0)? {
+        (nodes, End::Eof) => Ok(nodes),
+        (_, End::Else) => Err(TemplateError::Unexpected("else".to_owned())),
+        (_, End::Close(name)) => {
+            Err(TemplateError::Unexpected(format!("/{}", name)))
+        }
+    }
+}
+
+fn lookup<'v>(value: &'v Value, path: &[String]) -> Option<&'v Value> {
+    path.iter().try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(values) => values.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn truthy(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+        Some(Value::Number(n)) => n.as_f64() != Some(0.0),
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(Value::Array(values)) => !values.is_empty(),
+        Some(Value::Object(map)) => !map.is_empty(),
+        Some(Value::Bool(true)) => true,
+    }
+}
+
+struct Frame<'v> {
+    value: &'v Value,
+    index: Option<usize>,
+}
+
+struct Renderer<'v> {
+    stack: Vec<Frame<'v>>,
+    output: String,
+    steps: usize,
+}
+
+impl<'v> Renderer<'v> {
+    fn resolve(&self, path: &[String]) -> Option<&'v Value> {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|frame| lookup(frame.value, path))
+    }
+
+    fn index(&self) -> Option<usize> {
+        self.stack.iter().rev().find_map(|frame| frame.index)
+    }
+
+    fn write(&mut self, text: &str) -> std::result::Result<(), TemplateError> {
+        if self.output.len() + text.len() > MAX_OUTPUT_SIZE {
+            return Err(TemplateError::OutputTooLarge);
+        }
+        self.output.push_str(text);
+        Ok(())
+    }
+
+    fn step(&mut self) -> std::result::Result<(), TemplateError> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(TemplateError::TooMuchWork);
+        }
+        Ok(())
+    }
+
+    /// Renders `nodes` once more within `frame`
+    fn nested(
+        &mut self,
+        frame: Frame<'v>,
+        nodes: &[Node],
+    ) -> std::result::Result<(), TemplateError> {
+        self.step()?;
+        self.stack.push(frame);
+        let result = self.render(nodes);
+        self.stack.pop();
+        result
+    }
+
+    fn render(
+        &mut self,
+        nodes: &[Node],
+    ) -> std::result::Result<(), TemplateError> {
+        for node in nodes {
+            self.step()?;
+            match node {
+                Node::Text(text) => self.write(text)?,
+                Node::Value(path) if path.len() == 1 && path[0] == "@index" => {
+                    let index =
+                        self.index().map(|i| i.to_string()).unwrap_or_default();
+                    self.write(&index)?;
+                }
+                Node::Value(path) => match self.resolve(path) {
+                    None | Some(Value::Null) => {}
+                    Some(Value::String(s)) => self.write(s)?,
+                    Some(value) => self.write(&value.to_string())?,
+                },
+                Node::Each(path, body) => {
+                    let values: Vec<&Value> = match self.resolve(path) {
+                        Some(Value::Array(values)) => values.iter().collect(),
+                        Some(Value::Object(map)) => map.values().collect(),
+                        _ => vec![],
+                    };
+                    for (index, value) in values.into_iter().enumerate() {
+                        let frame = Frame {
+                            value,
+                            index: Some(index),
+                        };
+                        self.nested(frame, body)?;
+                    }
+                }
+                Node::If(path, body, otherwise) => {
+                    if truthy(self.resolve(path)) {
+                        self.render(body)?;
+                    } else {
+                        self.render(otherwise)?;
+                    }
+                }
+                Node::Repeat(count, body) => {
+                    let value = self.stack.last().map(|frame| frame.value);
+                    for index in 0..*count as usize {
+                        let frame = Frame {
+                            value: value.unwrap_or(&Value::Null),
+                            index: Some(index),
+                        };
+                        self.nested(frame, body)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn render(
+    template: &str,
+    data: &Value,
+) -> std::result::Result<String, TemplateError> {
+    let nodes = parse(template)?;
+    let mut renderer = Renderer {
+        stack: vec![Frame {
+            value: data,
+            index: None,
+        }],
+        output: String::new(),
+        steps: 0,
+    };
+    renderer.render(&nodes)?;
+    Ok(renderer.output)
+}
+
+fn default_content_type() -> String {
+    "text/plain; charset=utf-8".to_owned()
+}
+
+#[derive(Deserialize)]
+pub struct TemplateRequest {
+    template: String,
+    #[serde(default)]
+    data: Value,
+    #[serde(default = "default_content_type")]
+    content_type: String,
+}
+
+/// Renders the posted template with the posted data
+pub async fn template(Json(body): Json<TemplateRequest>) -> Result {
+    let output = render(&body.template, &body.data)
+        .map_err(|e| Error::bad_request(e.to_string()))?;
+    let content_type = body
+        .content_type
+        .parse::<hyper::header::HeaderValue>()
+        .map_err(|_| Error::bad_request("invalid content_type"))?;
+    response().header(CONTENT_TYPE, content_type).body(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::handler::extract;
+    use crate::headers::ContentType;
+    use crate::test::*;
+    use hyper::{Method, StatusCode};
+    use serde_json::json;
+
+    #[test]
+    fn test_values() {
+        let data =
+            json!({"name": "box", "n": 3, "tags": ["a", "b"], "x": null});
+
+        assert_eq!(
+            render(
+                "{{ name }} {{n}} {{tags.1}} {{tags}} [{{x}}{{missing}}]",
+                &data
+            )
+            .unwrap(),
+            r#"box 3 b ["a","b"] []"#
+        );
+    }
+
+    #[test]
+    fn test_each() {
+        let data = json!({"sep": ",", "items": [{"id": 1}, {"id": 2}]});
+
+        assert_eq!(
+            render("{{#each items}}{{@index}}:{{id}}{{sep}}{{/each}}", &data)
+                .unwrap(),
+            "0:1,1:2,"
+        );
+        assert_eq!(
+            render(
+                "{{#each this}}{{this}} {{/each}}",
+                &json!({"a": 1, "b": 2})
+            )
+            .unwrap(),
+            "1 2 "
+        );
+    }
+
+    #[test]
+    fn test_if() {
+        let template = "{{#if on}}yes{{else}}no{{/if}}";
+
+        assert_eq!(render(template, &json!({"on": true})).unwrap(), "yes");
+        assert_eq!(render(template, &json!({"on": []})).unwrap(), "no");
+        assert_eq!(render(template, &json!({})).unwrap(), "no");
+    }
+
+    #[test]
+    fn test_repeat() {
+        assert_eq!(
+            render("{{#repeat 3}}{{@index}}{{/repeat}}", &Value::Null).unwrap(),
+            "012"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let data = Value::Null;
+
+        assert_eq!(
+            render("{{#each items}}", &data),
+            Err(TemplateError::Unclosed("each"))
+        );
+        assert_eq!(
+            render("{{#if a}}{{/each}}", &data),
+            Err(TemplateError::Unexpected("/each".to_owned()))
+        );
+        assert_eq!(
+            render("{{#repeat many}}{{/repeat}}", &data),
+            Err(TemplateError::BadRepeat("many".to_owned()))
+        );
+        assert_eq!(render("{{name", &data), Err(TemplateError::Unclosed("{{")));
+    }
+
+    #[test]
+    fn test_limits() {
+        let data = Value::Null;
+
+        assert_eq!(
+            render(&"{{#if a}}".repeat(MAX_DEPTH + 1), &data),
+            Err(TemplateError::TooDeep)
+        );
+        let template =
+            format!("{{{{#repeat 2000}}}}{}{{{{/repeat}}}}", "x".repeat(1000));
+        assert_eq!(
+            render(&template, &data),
+            Err(TemplateError::OutputTooLarge)
+        );
+        assert_eq!(
+            render(
+                "{{#repeat 10000}}{{#repeat 10000}}{{/repeat}}{{/repeat}}",
+                &data
+            ),
+            Err(TemplateError::TooMuchWork)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_template() {
+        let res = request()
+            .method(Method::POST)
+            .typed_header(ContentType::json())
+            .body(
+                r#"{"template": "<p>{{greeting}}</p>",
+                    "data": {"greeting": "hi"},
+                    "content_type": "text/html"}"#,
+            )
+            .handle(extract(template))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["content-type"], "text/html");
+        assert_eq!(res.read_body_utf8().await.unwrap(), "<p>hi</p>");
+    }
+
+    #[tokio::test]
+    async fn test_template_error() {
+        let res = request()
+            .method(Method::POST)
+            .typed_header(ContentType::json())
+            .body(r#"{"template": "{{#each items}}"}"#)
+            .handle(extract(template))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.read_body_utf8().await.unwrap(), "unclosed {{#each}}");
+    }
+}
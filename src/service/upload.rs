@@ -0,0 +1,89 @@
+//! A sink for request bodies of any size, for testing how clients upload
+use crate::checksum::Algorithm;
+use crate::headers::{ContentLength, Expect, HeaderMapExt};
+use crate::hex::hex;
+use crate::http::{bad_request, json, Request, Result};
+use hyper::header::TRANSFER_ENCODING;
+use serde_derive::Serialize;
+use std::time::Instant;
+
+#[derive(Serialize)]
+struct Upload {
+    /// In bytes, as received
+    size: u64,
+    /// Hex encoded
+    sha256: String,
+    /// How many pieces the body arrived in, which need not match the chunks
+    /// sent
+    chunks: u64,
+    content_length: Option<u64>,
+    chunked: bool,
+    expect_continue: bool,
+    /// In seconds, from the first poll of the body to its end
+    duration: f64,
+}
+
+/// Reads the body to its end, one piece at a time, and reports how big it
+/// was and its SHA-256
+///
+/// Nothing is kept of the body, so it can be arbitrarily large. A client
+/// expecting `100 Continue` gets it once the body is first read.
+pub async fn upload(mut req: Request) -> Result {
+    let content_length = req.headers().typed_get::<ContentLength>();
+    let chunked = req
+        .headers()
+        .get_all(TRANSFER_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.to_ascii_lowercase().contains("chunked"));
+    let expect_continue = req.headers().typed_get::<Expect>().is_some();
+
+    let mut body = req.body();
+    let mut hasher = Algorithm::Sha256.hasher();
+    let (mut size, mut chunks) = (0, 0);
+    let start = Instant::now();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| bad_request())?;
+        hasher.update(&chunk);
+        size += chunk.len() as u64;
+        chunks += 1;
+    }
+
+    let digest = hasher.finish();
+    json(&Upload {
+        size,
+        sha256: hex(&digest),
+        chunks,
+        content_length: content_length.map(|length| length.0),
+        chunked,
+        expect_continue,
+        duration: start.elapsed().as_secs_f64(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+
+    #[tokio::test]
+    async fn test_upload() {
+        let res = request()
+            .header("expect", "100-continue")
+            .header("content-length", "3")
+            .body("abc")
+            .handle(upload)
+            .await
+            .unwrap();
+        let upload = res.read_body_json().await.unwrap();
+
+        assert_eq!(upload["size"], 3);
+        assert_eq!(
+            upload["sha256"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(upload["content_length"], 3);
+        assert_eq!(upload["chunked"], false);
+        assert_eq!(upload["expect_continue"], true);
+    }
+}
@@ -1,10 +1,11 @@
 use crate::headers::UserAgent;
-use crate::http::{bad_request, ok, Request, Result};
+use crate::http::{bad_request, json, Request, Result};
+use crate::service::reflection::Reflection;
 
 pub async fn user_agent(req: Request) -> Result {
-    let agent = req.typed_header::<UserAgent>().ok_or_else(bad_request)?;
+    req.typed_header::<UserAgent>().ok_or_else(bad_request)?;
 
-    ok(agent.to_string())
+    json(&Reflection::new().user_agent(&req))
 }
 
 #[cfg(test)]
@@ -29,7 +30,7 @@ mod test {
             .unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
-        let body = res.read_body_utf8().await.unwrap();
-        assert_eq!(body, "HTTPBoxBot/1.0");
+        let body = res.read_body_json().await.unwrap();
+        assert_eq!(body, serde_json::json!({"user-agent": "HTTPBoxBot/1.0"}));
     }
 }
@@ -0,0 +1,134 @@
+use crate::http::{bad_request, json, Request, Result};
+use crate::random::{rng, Rng};
+use serde_derive::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const MAX_COUNT: usize = 1000;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+enum Version {
+    /// Random
+    #[default]
+    #[serde(rename = "4")]
+    V4,
+    /// Time-ordered
+    #[serde(rename = "7")]
+    V7,
+}
+
+impl Version {
+    fn generate(self, rng: &mut Rng) -> String {
+        match self {
+            Self::V4 => rng.uuid(),
+            Self::V7 => Uuid::now_v7(),
+        }
+        .hyphenated()
+        .to_string()
+    }
+}
+
+#[derive(Deserialize)]
+struct UuidQueryParams {
+    #[serde(default)]
+    version: Version,
+    count: Option<usize>,
+    /// Only for version 4, as version 7 depends on the time
+    seed: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct Uuids {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uuids: Option<Vec<String>>,
+}
+
+pub async fn uuid(req: Request) -> Result {
+    let query = req.query::<UuidQueryParams>().map_err(|_| bad_request())?;
+    if query.seed.is_some() && query.version != Version::V4 {
+        return Err(bad_request());
+    }
+    let mut rng = rng(query.seed);
+
+    let uuids = match query.count {
+        None => Uuids {
+            uuid: Some(query.version.generate(&mut rng)),
+            uuids: None,
+        },
+        Some(count) if (1..=MAX_COUNT).contains(&count) => Uuids {
+            uuid: None,
+            uuids: Some(
+                (0..count)
+                    .map(|_| query.version.generate(&mut rng))
+                    .collect(),
+            ),
+        },
+        Some(_) => return Err(bad_request()),
+    };
+
+    json(&uuids)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::*;
+    use hyper::http::StatusCode;
+    use serde_json::Value;
+
+    async fn body(path: &str) -> Value {
+        let res = request().path(path).handle(uuid).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        res.read_body_json().await.unwrap()
+    }
+
+    fn parse(value: &Value) -> Uuid {
+        value.as_str().unwrap().parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_uuid() {
+        let body = body("/uuid").await;
+
+        assert_eq!(parse(&body["uuid"]).get_version_num(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_uuid_v7() {
+        let body = body("/uuid?version=7&count=3").await;
+
+        let uuids = body["uuids"].as_array().unwrap();
+        assert_eq!(uuids.len(), 3);
+        assert!(uuids.iter().all(|uuid| parse(uuid).get_version_num() == 7));
+        assert!(uuids.windows(2).all(|w| parse(&w[0]) < parse(&w[1])));
+    }
+
+    #[tokio::test]
+    async fn test_uuid_seeded() {
+        let body = body("/uuid?seed=1234&count=2").await;
+
+        assert_eq!(
+            body["uuids"],
+            serde_json::json!([
+                "d60400d2-d604-40d2-9404-0069d4040069",
+                "204d0000-204d-4000-a0df-269d20df269d",
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_uuid_bad_params() {
+        for path in &[
+            "/uuid?version=1",
+            "/uuid?count=0",
+            "/uuid?count=1001",
+            "/uuid?version=7&seed=1",
+        ] {
+            let res = request().path(path).handle(uuid).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::headers::{
+    Connection, SecWebsocketAccept, SecWebsocketKey, SecWebsocketVersion,
+    Upgrade,
+};
+use crate::http::{bad_request, response, Request, Result, StatusCode};
+use futures::prelude::*;
+use hyper::header::UPGRADE;
+use hyper::upgrade::Upgraded;
+use hyper_util::rt::TokioIo;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Error as WebSocketError;
+use tokio_tungstenite::WebSocketStream;
+
+fn handshake_key(req: &Request) -> Option<SecWebsocketKey> {
+    let upgrade = req.headers().get(UPGRADE)?.to_str().ok()?;
+    if !upgrade.eq_ignore_ascii_case("websocket") {
+        return None;
+    }
+    if !req.typed_header::<Connection>()?.contains("upgrade") {
+        return None;
+    }
+    if req.typed_header::<SecWebsocketVersion>()? != SecWebsocketVersion::V13 {
+        return None;
+    }
+
+    req.typed_header::<SecWebsocketKey>()
+}
+
+/// Echoes frames until the client or `until` ends the session, closing
+/// the socket on the way out
+async fn echo_frames(
+    ws: WebSocketStream<TokioIo<Upgraded>>,
+    until: impl Future<Output = ()>,
+) -> std::result::Result<(), WebSocketError> {
+    let (sink, stream) = ws.split();
+    stream
+        .take_until(until)
+        .try_filter(|message| {
+            future::ready(message.is_text() || message.is_binary())
+        })
+        .forward(sink)
+        .await
+}
+
+pub async fn echo(mut req: Request) -> Result {
+    let key = handshake_key(&req).ok_or_else(bad_request)?;
+
+    let on_upgrade = req.upgrade();
+    let draining = req.draining();
+    tokio::spawn(async move {
+        let upgraded = match on_upgrade.await {
+            Ok(upgraded) => upgraded,
+            Err(e) => {
+                tracing::debug!("WebSocket upgrade failed: {}", e);
+                return;
+            }
+        };
+
+        let upgraded = TokioIo::new(upgraded);
+        let ws = WebSocketStream::from_raw_socket(upgraded, Role::Server, None)
+            .await;
+        if let Err(e) = echo_frames(ws, draining).await {
+            tracing::debug!("WebSocket connection closed: {}", e);
+        }
+    });
+
+    response()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .typed_header(Upgrade::websocket())
+        .typed_header(Connection::upgrade())
+        .typed_header(SecWebsocketAccept::from(key))
+        .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headers::HeaderMapExt;
+    use crate::router::{route, Router};
+    use crate::test::*;
+    use hyper::http::StatusCode;
+    use hyper_util::service::TowerToHyperService;
+    use tokio_tungstenite::tungstenite::Message;
+    use uri_path::path;
+
+    const KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+
+    fn handshake() -> RequestBuilder {
+        request()
+            .header("upgrade", "websocket")
+            .header("connection", "Upgrade")
+            .header("sec-websocket-version", "13")
+            .header("sec-websocket-key", KEY)
+    }
+
+    #[tokio::test]
+    async fn test_handshake() {
+        let res = handshake().handle(echo).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(
+            res.headers().get("sec-websocket-accept").unwrap(),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(
+            res.headers().typed_get::<Upgrade>().unwrap(),
+            Upgrade::websocket()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_upgrade() {
+        let res = request()
+            .header("sec-websocket-version", "13")
+            .header("sec-websocket-key", KEY)
+            .handle(echo)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_version() {
+        let res = handshake()
+            .header("sec-websocket-version", "8")
+            .handle(echo)
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_echo() {
+        let router = Router::builder()
+            .install(echo, route(path!("ws" / "echo")))
+            .build();
+        let addr = serve(TowerToHyperService::new(router));
+
+        let (mut ws, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}/ws/echo", addr))
+                .await
+                .unwrap();
+
+        ws.send(Message::text("hello")).await.unwrap();
+        assert_eq!(ws.next().await.unwrap().unwrap(), Message::text("hello"));
+
+        ws.send(Message::binary(vec![1, 2, 3])).await.unwrap();
+        assert_eq!(
+            ws.next().await.unwrap().unwrap(),
+            Message::binary(vec![1, 2, 3])
+        );
+    }
+}
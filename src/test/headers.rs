@@ -2,7 +2,7 @@
 
 use crate::headers::{Header, HeaderMapExt, HeaderValue};
 
-pub fn encode<'a, H: Header>(header: H) -> HeaderValue {
+pub fn encode<H: Header>(header: H) -> HeaderValue {
     let mut map = hyper::http::HeaderMap::new();
     map.typed_insert(header);
     map.get(H::name()).unwrap().clone()
@@ -0,0 +1,20 @@
+use crate::http::{Error, Request, Result};
+use crate::router::{Middleware, Next};
+use async_trait::async_trait;
+use hyper::header::HeaderValue;
+
+/// Appends its `x-tag` to every response that passes it, errors included
+pub struct Tag(pub &'static str);
+
+#[async_trait]
+impl Middleware for Tag {
+    async fn handle(&self, req: Request, next: Next<'_>) -> Result {
+        let mut res = next.run(req).await.or_else(|e| match e {
+            Error::Failure(res) => Ok(*res),
+            e => Err(e),
+        })?;
+        res.headers_mut()
+            .append("x-tag", HeaderValue::from_static(self.0));
+        Ok(res)
+    }
+}
@@ -1,8 +1,12 @@
 #![cfg(test)]
 
 pub(crate) mod headers;
+mod middleware;
 mod request;
 mod response;
+mod server;
 
-pub use request::request;
+pub use middleware::Tag;
+pub use request::{request, RequestBuilder};
 pub use response::TestResponseExt;
+pub use server::serve;
@@ -3,19 +3,22 @@
 use crate::handler::Handler;
 use crate::headers::ContentLength;
 use crate::headers::{Header, HeaderMapExt};
-use crate::http::Request;
+use crate::http::{Body, Request, TrustedProxies};
+use crate::router::Router;
 use futures::prelude::*;
 use hyper::header::{HeaderName, HeaderValue};
 use hyper::http::{Request as HTTPRequest, Response as HTTPResponse};
-use hyper::Body;
-use hyper::Method;
+use hyper::{Method, Version};
 use std::convert::TryFrom;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::Service;
 use uri_path::PathMatch;
 
 pub struct RequestBuilder {
     req: HTTPRequest<Body>,
     client_addr: Option<SocketAddr>,
+    trusted_proxies: Option<TrustedProxies>,
     params: PathMatch,
 }
 
@@ -25,6 +28,11 @@ impl RequestBuilder {
         self
     }
 
+    pub fn version(mut self, version: Version) -> Self {
+        *self.req.version_mut() = version;
+        self
+    }
+
     pub fn path(mut self, p: &str) -> Self {
         let uri = p.parse().expect("test request path invalid");
         *self.req.uri_mut() = uri;
@@ -63,18 +71,43 @@ impl RequestBuilder {
         self.typed_header(ContentLength(len as u64))
     }
 
+    /// A body sent as it comes, without a `Content-Length`
+    pub fn streamed_body(mut self, body: Body) -> Self {
+        *self.req.body_mut() = body;
+        self
+    }
+
     pub fn client_addr(mut self, addr: SocketAddr) -> Self {
         self.client_addr = Some(addr);
         self
     }
 
-    pub fn build(self) -> Request {
-        let mut req = self.req;
+    pub fn extension<T: Clone + Send + Sync + 'static>(
+        mut self,
+        value: T,
+    ) -> Self {
+        self.req.extensions_mut().insert(value);
+        self
+    }
 
+    pub fn trusted_proxies(mut self, trusted_proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = Some(trusted_proxies);
+        self
+    }
+
+    fn into_http(mut self) -> (HTTPRequest<Body>, PathMatch) {
         if let Some(client_addr) = self.client_addr {
-            req.extensions_mut().insert(client_addr);
+            self.req.extensions_mut().insert(client_addr);
         }
-        Request::new(req, self.params)
+        if let Some(trusted_proxies) = self.trusted_proxies {
+            self.req.extensions_mut().insert(Arc::new(trusted_proxies));
+        }
+        (self.req, self.params)
+    }
+
+    pub fn build(self) -> Request {
+        let (req, params) = self.into_http();
+        Request::new(req, params)
     }
 
     pub async fn handle<H: Handler>(
@@ -84,12 +117,20 @@ impl RequestBuilder {
         let req = self.build();
         handler.handle(req).or_else(|e| e.into_result()).await
     }
+
+    /// Sends the request through the whole router, which matches the path
+    /// and runs the middleware itself
+    pub async fn call(self, router: &mut Router) -> HTTPResponse<Body> {
+        let (req, _) = self.into_http();
+        router.call(req).await.unwrap()
+    }
 }
 
 pub fn request() -> RequestBuilder {
     RequestBuilder {
         req: HTTPRequest::default(),
         client_addr: None,
+        trusted_proxies: None,
         params: PathMatch::default(),
     }
 }
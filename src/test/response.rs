@@ -1,3 +1,4 @@
+use crate::http::Body;
 use async_trait::async_trait;
 
 #[async_trait]
@@ -6,12 +7,17 @@ pub trait TestResponseExt: Sized {
     async fn read_body_utf8(self) -> anyhow::Result<String> {
         Ok(String::from_utf8(self.read_body().await?)?)
     }
+    async fn read_body_json(self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::from_slice(&self.read_body().await?)?)
+    }
 }
 
 #[async_trait]
-impl TestResponseExt for hyper::Response<hyper::Body> {
+impl TestResponseExt for hyper::Response<Body> {
     async fn read_body(self) -> anyhow::Result<Vec<u8>> {
-        let bytes = hyper::body::to_bytes(self.into_body()).await?;
+        let bytes = crate::http::to_bytes(self.into_body())
+            .await
+            .map_err(|e| anyhow::anyhow!(e))?;
         Ok(bytes.to_vec())
     }
 }
@@ -0,0 +1,253 @@
+//! Reading the names and validity out of X.509 certificates, for reporting
+//! the client certificate of a connection
+//!
+//! Only as much DER is decoded here as that takes, checking the
+//! certificates being up to rustls.
+use crate::hex::hex;
+use std::fmt::Write;
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const INTEGER: u8 = 0x02;
+const OID: u8 = 0x06;
+const UTF8_STRING: u8 = 0x0c;
+const PRINTABLE_STRING: u8 = 0x13;
+const T61_STRING: u8 = 0x14;
+const IA5_STRING: u8 = 0x16;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const BMP_STRING: u8 = 0x1e;
+/// The explicitly tagged version of the TBS certificate
+const VERSION: u8 = 0xa0;
+
+/// What a certificate says about itself
+#[derive(Debug, PartialEq, Eq)]
+pub struct Certificate {
+    /// Such as `C=DE, O=httpbox, CN=client`, in the order of the
+    /// certificate
+    pub subject: String,
+    pub issuer: String,
+    /// In hex
+    pub serial: String,
+    /// Such as `2026-10-14T16:06:38Z`
+    pub not_before: String,
+    pub not_after: String,
+}
+
+/// DER elements, one after the other
+struct Der<'a>(&'a [u8]);
+
+impl<'a> Der<'a> {
+    /// The tag and contents of the next element
+    fn next(&mut self) -> Option<(u8, &'a [u8])> {
+        let (&tag, rest) = self.0.split_first()?;
+        let (&first, rest) = rest.split_first()?;
+        let (len, rest) = match first {
+            0..=0x7f => (first as usize, rest),
+            0x81..=0x84 => {
+                let octets = (first & 0x7f) as usize;
+                let (len, rest) = (rest.get(..octets)?, &rest[octets..]);
+                let len = len.iter().fold(0, |len, &b| len << 8 | b as usize);
+                (len, rest)
+            }
+            _ => return None,
+        };
+        let contents = rest.get(..len)?;
+        self.0 = &rest[len..];
+        Some((tag, contents))
+    }
+
+    /// The contents of the next element, which has to be tagged `tag`
+    fn expect(&mut self, tag: u8) -> Option<&'a [u8]> {
+        self.next()
+            .filter(|(found, _)| *found == tag)
+            .map(|(_, c)| c)
+    }
+}
+
+/// Such as `2.5.4.3`
+fn oid(contents: &[u8]) -> String {
+    let (&first, rest) = match contents.split_first() {
+        Some(split) => split,
+        None => return String::new(),
+    };
+    let mut oid = format!("{}.{}", first / 40, first % 40);
+    let mut arc = 0u64;
+    for &b in rest {
+        arc = arc << 7 | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            let _ = write!(oid, ".{}", arc);
+            arc = 0;
+        }
+    }
+    oid
+}
+
+/// The short names OpenSSL uses for the common attributes
+fn attribute_name(oid: &str) -> Option<&'static str> {
+    Some(match oid {
+        "2.5.4.3" => "CN",
+        "2.5.4.5" => "serialNumber",
+        "2.5.4.6" => "C",
+        "2.5.4.7" => "L",
+        "2.5.4.8" => "ST",
+        "2.5.4.10" => "O",
+        "2.5.4.11" => "OU",
+        "1.2.840.113549.1.9.1" => "emailAddress",
+        _ => return None,
+    })
+}
+
+/// A string attribute as text, anything else as `#` and its DER in hex
+fn attribute_value(tag: u8, contents: &[u8]) -> String {
+    match tag {
+        UTF8_STRING | PRINTABLE_STRING | T61_STRING | IA5_STRING => {
+            String::from_utf8_lossy(contents).into_owned()
+        }
+        BMP_STRING => char::decode_utf16(
+            contents
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]])),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect(),
+        _ => format!("#{:02x}{:02x}{}", tag, contents.len(), hex(contents)),
+    }
+}
+
+/// A distinguished name such as `C=DE, O=httpbox, CN=client`
+fn name(contents: &[u8]) -> Option<String> {
+    let mut attributes = vec![];
+    let mut rdns = Der(contents);
+    while !rdns.0.is_empty() {
+        let mut rdn = Der(rdns.expect(SET)?);
+        while !rdn.0.is_empty() {
+            let mut attribute = Der(rdn.expect(SEQUENCE)?);
+            let oid = oid(attribute.expect(OID)?);
+            let (tag, value) = attribute.next()?;
+            attributes.push(format!(
+                "{}={}",
+                attribute_name(&oid).unwrap_or(&oid),
+                attribute_value(tag, value)
+            ));
+        }
+    }
+    Some(attributes.join(", "))
+}
+
+/// A UTCTime or a GeneralizedTime in RFC 3339
+fn time((tag, contents): (u8, &[u8])) -> Option<String> {
+    let time = std::str::from_utf8(contents).ok()?.strip_suffix('Z')?;
+    if !time.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let time = match tag {
+        // Two digit years are from 1950 to 2049
+        UTC_TIME if time.len() == 12 => {
+            let century = if time[..2].parse::<u8>().ok()? < 50 {
+                "20"
+            } else {
+                "19"
+            };
+            format!("{}{}", century, time)
+        }
+        GENERALIZED_TIME if time.len() == 14 => time.to_owned(),
+        _ => return None,
+    };
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &time[..4],
+        &time[4..6],
+        &time[6..8],
+        &time[8..10],
+        &time[10..12],
+        &time[12..14]
+    ))
+}
+
+/// Reads the certificate in `der`, `None` if it is malformed
+pub fn parse(der: &[u8]) -> Option<Certificate> {
+    let mut certificate = Der(Der(der).expect(SEQUENCE)?);
+    let mut tbs = Der(certificate.expect(SEQUENCE)?);
+    if tbs.0.first() == Some(&VERSION) {
+        tbs.next()?;
+    }
+    let serial = tbs.expect(INTEGER)?;
+    let serial = match serial {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        serial => serial,
+    };
+    tbs.expect(SEQUENCE)?;
+    let issuer = name(tbs.expect(SEQUENCE)?)?;
+    let mut validity = Der(tbs.expect(SEQUENCE)?);
+    let not_before = time(validity.next()?)?;
+    let not_after = time(validity.next()?)?;
+    let subject = name(tbs.expect(SEQUENCE)?)?;
+
+    Some(Certificate {
+        subject,
+        issuer,
+        serial: hex(serial),
+        not_before,
+        not_after,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rustls_pemfile::Item;
+    use std::io::BufReader;
+
+    const CLIENT: &[u8] = include_bytes!("server/testdata/client.pem");
+
+    fn der(pem: &[u8]) -> Vec<u8> {
+        match rustls_pemfile::read_one(&mut BufReader::new(pem)) {
+            Ok(Some(Item::X509Certificate(der))) => der,
+            _ => panic!("not a certificate"),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse(&der(CLIENT)),
+            Some(Certificate {
+                subject: "C=DE, O=httpbox, CN=client".to_owned(),
+                issuer: "O=httpbox, CN=httpbox test CA".to_owned(),
+                serial: "1234".to_owned(),
+                not_before: "2026-10-14T16:06:38Z".to_owned(),
+                not_after: "2126-09-20T16:06:38Z".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let der = der(CLIENT);
+        assert_eq!(parse(&der[..der.len() / 2]), None);
+        assert_eq!(parse(b""), None);
+    }
+
+    #[test]
+    fn test_time() {
+        assert_eq!(
+            time((UTC_TIME, b"491231235959Z")),
+            Some("2049-12-31T23:59:59Z".to_owned())
+        );
+        assert_eq!(
+            time((UTC_TIME, b"500101000000Z")),
+            Some("1950-01-01T00:00:00Z".to_owned())
+        );
+        assert_eq!(time((UTC_TIME, "1\u{e9}010100000Z".as_bytes())), None);
+        assert_eq!(time((GENERALIZED_TIME, b"2026101412000Z")), None);
+    }
+
+    #[test]
+    fn test_oid() {
+        assert_eq!(
+            oid(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01]),
+            "1.2.840.113549.1.9.1"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Compares looking up a path in a `PathTrie` with trying every path in turn,
+//! run with `cargo bench`
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use uri_path::{Path, PathParam, PathSegment, PathToken, PathTrie};
+
+const ROUTES: usize = 500;
+const ITERATIONS: u32 = 10_000;
+
+/// Routes like `/resource-42/:id/items/:item`, leaked as paths only take
+/// static segments
+fn routes() -> Vec<Path> {
+    (0..ROUTES)
+        .map(|i| {
+            let resource: &'static str =
+                Box::leak(format!("resource-{}", i).into_boxed_str());
+            let mut segments = vec![
+                PathSegment::Literal(resource),
+                PathSegment::Dynamic(PathParam::new(
+                    "id",
+                    PathToken::parses::<u64>("u64"),
+                )),
+            ];
+            if i % 2 == 1 {
+                segments.push(PathSegment::Literal("items"));
+                segments.push(PathSegment::Dynamic(PathParam::new(
+                    "item",
+                    PathToken::Any,
+                )));
+            }
+            Path(segments)
+        })
+        .collect()
+}
+
+fn time(name: &str, mut f: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per: Duration = start.elapsed() / ITERATIONS;
+    println!("{:<12} {:>10?} per lookup", name, per);
+}
+
+fn main() {
+    let routes = routes();
+    let trie = routes
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(i, path)| (path, i))
+        .collect::<PathTrie<_>>();
+
+    for request in ["/resource-0/1", "/resource-499/1/items/2", "/missing"] {
+        println!("{}", request);
+        time("linear", || {
+            black_box(routes.iter().find_map(|route| route.matches(request)));
+        });
+        time("trie", || {
+            black_box(trie.matches(request).next());
+        });
+    }
+}
@@ -1,7 +1,9 @@
 #[macro_use]
 mod macros;
+mod trie;
+
+pub use trie::PathTrie;
 
-use itertools::EitherOrBoth;
 use itertools::Itertools;
 #[cfg(feature = "regex")]
 pub use regex;
@@ -9,19 +11,15 @@ use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::iter::FromIterator;
 use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
 
 fn segmented(str: &str) -> impl Iterator<Item = &str> {
     str.split('/').filter(|seg| !seg.is_empty())
 }
 
+#[derive(Debug, Default, PartialEq)]
 pub struct PathMatch(HashMap<&'static str, String>);
 
-impl Default for PathMatch {
-    fn default() -> Self {
-        Self(HashMap::new())
-    }
-}
-
 impl Deref for PathMatch {
     type Target = HashMap<&'static str, String>;
 
@@ -30,38 +28,67 @@ impl Deref for PathMatch {
     }
 }
 
+impl FromIterator<(&'static str, String)> for PathMatch {
+    fn from_iter<I: IntoIterator<Item = (&'static str, String)>>(
+        iter: I,
+    ) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
 impl DerefMut for PathMatch {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
 }
 
+/// How narrowly a path matches, where a more specific path should be tried
+/// before a less specific one matching the same request
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Specificity(Vec<u8>);
+
 #[derive(Debug, Clone)]
 pub struct Path(pub Vec<PathSegment>);
 
 impl Path {
+    /// Compares segment by segment, with literals over constrained
+    /// parameters over any parameter over the rest of the path
+    pub fn specificity(&self) -> Specificity {
+        const END: u8 = 1;
+        let ranks = self.iter().map(|segment| match segment {
+            PathSegment::Literal(_) => 4,
+            PathSegment::Dynamic(param) => match param.token {
+                PathToken::Any => 2,
+                _ => 3,
+            },
+            PathSegment::Rest(_) => 0,
+        });
+        Specificity(ranks.chain(std::iter::once(END)).collect())
+    }
+
     pub fn matches(&self, path: &str) -> Option<PathMatch> {
         let mut params = PathMatch::default();
-        for el in self.iter().zip_longest(segmented(path)) {
-            match el {
-                EitherOrBoth::Both(expected, actual) => {
-                    if !expected.matches(actual) {
-                        return None;
-                    }
-                    if let PathSegment::Dynamic(param) = expected {
-                        params.insert(param.name, actual.to_owned());
-                    }
-                }
-                _ => return None,
+        let mut actual = segmented(path);
+        for expected in self.iter() {
+            if let PathSegment::Rest(name) = expected {
+                params.insert(name, actual.join("/"));
+                return Some(params);
+            }
+            let segment = actual.next()?;
+            if !expected.matches(segment) {
+                return None;
+            }
+            if let PathSegment::Dynamic(param) = expected {
+                params.insert(param.name, segment.to_owned());
             }
         }
-        Some(params)
+        actual.next().is_none().then_some(params)
     }
 
     pub fn replace(
         &self,
         params: &BTreeMap<&'static str, &'static str>,
-    ) -> Option<PathAndQuery> {
+    ) -> Option<PathAndQuery<'_>> {
         let mut segments = vec![];
         let mut params = params.clone();
 
@@ -72,7 +99,13 @@ impl Path {
                 PathSegment::Literal(str) => segments.push(str),
                 PathSegment::Dynamic(param) => {
                     let value = params.remove(param.name)?;
-                    segments.push(&value)
+                    segments.push(value)
+                }
+                PathSegment::Rest(name) => {
+                    let value = params.remove(name)?;
+                    if !value.is_empty() {
+                        segments.push(value)
+                    }
                 }
             }
         }
@@ -105,17 +138,38 @@ impl From<&'static str> for Path {
     }
 }
 
+fn is_uuid(segment: &str) -> bool {
+    segment.len() == 36
+        && segment.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
 #[derive(Debug, Clone)]
 pub enum PathToken {
     Any,
+    /// Segments accepted by the check, named after what they have to be
+    Typed(&'static str, fn(&str) -> bool),
     #[cfg(feature = "regex")]
     Regex(regex::Regex),
 }
 
 impl PathToken {
+    /// Segments that parse as `T`
+    pub fn parses<T: FromStr>(name: &'static str) -> Self {
+        Self::Typed(name, |segment| segment.parse::<T>().is_ok())
+    }
+
+    /// Segments that are a hyphenated UUID
+    pub fn uuid() -> Self {
+        Self::Typed("uuid", is_uuid)
+    }
+
     pub fn matches(&self, path: &str) -> bool {
         match self {
             Self::Any => true,
+            Self::Typed(_, check) => check(path),
             #[cfg(feature = "regex")]
             Self::Regex(re) => re.is_match(path),
         }
@@ -126,6 +180,7 @@ impl fmt::Display for PathToken {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Any => write!(f, "*"),
+            Self::Typed(name, _) => write!(f, "{}", name),
             #[cfg(feature = "regex")]
             Self::Regex(re) => write!(f, "{}", re),
         }
@@ -142,6 +197,14 @@ impl PathParam {
     pub fn new(name: &'static str, token: PathToken) -> Self {
         Self { name, token }
     }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn token(&self) -> &PathToken {
+        &self.token
+    }
 }
 
 impl fmt::Display for PathParam {
@@ -154,6 +217,8 @@ impl fmt::Display for PathParam {
 pub enum PathSegment {
     Literal(&'static str),
     Dynamic(PathParam),
+    /// The remaining segments, if any, joined by `/`; only valid last
+    Rest(&'static str),
 }
 
 impl PathSegment {
@@ -161,6 +226,7 @@ impl PathSegment {
         match self {
             Self::Literal(str) => str == &path,
             Self::Dynamic(param) => param.token.matches(path),
+            Self::Rest(_) => true,
         }
     }
 }
@@ -170,6 +236,7 @@ impl fmt::Display for PathSegment {
         match self {
             Self::Literal(str) => write!(f, "{}", str),
             Self::Dynamic(param) => write!(f, "{}", param),
+            Self::Rest(name) => write!(f, "*{}", name),
         }
     }
 }
@@ -9,6 +9,21 @@ macro_rules! path {
             $crate::PathToken::Any,
         ))
     };
+    (@segment [* $i:ident]) => {
+        $crate::PathSegment::Rest(stringify!($i))
+    };
+    (@segment [$i:ident : uuid]) => {
+        $crate::PathSegment::Dynamic($crate::PathParam::new(
+            stringify!($i),
+            $crate::PathToken::uuid(),
+        ))
+    };
+    (@segment [$i:ident : $t:ty]) => {
+        $crate::PathSegment::Dynamic($crate::PathParam::new(
+            stringify!($i),
+            $crate::PathToken::parses::<$t>(stringify!($t)),
+        ))
+    };
     (@segment [$i:ident ~ $re:literal]) => {{
         $crate::PathSegment::Dynamic($crate::PathParam::new(
             stringify!($i),
@@ -0,0 +1,118 @@
+use super::{segmented, Path, PathMatch, PathSegment, Specificity};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    literals: HashMap<&'static str, Node>,
+    /// Any parameter, its token being checked once a path is a candidate
+    dynamic: Option<Box<Node>>,
+    /// The paths ending at this node
+    ends: Vec<usize>,
+    /// The paths with a rest segment at this node
+    rest: Vec<usize>,
+}
+
+impl Node {
+    fn insert(&mut self, segments: &[PathSegment], index: usize) {
+        match segments.split_first() {
+            None => self.ends.push(index),
+            Some((PathSegment::Rest(_), _)) => self.rest.push(index),
+            Some((PathSegment::Literal(literal), rest)) => self
+                .literals
+                .entry(literal)
+                .or_default()
+                .insert(rest, index),
+            Some((PathSegment::Dynamic(_), rest)) => self
+                .dynamic
+                .get_or_insert_with(Default::default)
+                .insert(rest, index),
+        }
+    }
+
+    fn candidates(&self, segments: &[&str], found: &mut Vec<usize>) {
+        found.extend(&self.rest);
+        match segments.split_first() {
+            None => found.extend(&self.ends),
+            Some((segment, rest)) => {
+                if let Some(node) = self.literals.get(segment) {
+                    node.candidates(rest, found);
+                }
+                if let Some(node) = &self.dynamic {
+                    node.candidates(rest, found);
+                }
+            }
+        }
+    }
+}
+
+/// Paths indexed by their segments, so looking up the ones matching a
+/// request takes time in the length of the request path rather than the
+/// number of paths
+#[derive(Debug, Clone)]
+pub struct PathTrie<T> {
+    root: Node,
+    values: Vec<(Path, Specificity, T)>,
+}
+
+impl<T> Default for PathTrie<T> {
+    fn default() -> Self {
+        Self {
+            root: Node::default(),
+            values: vec![],
+        }
+    }
+}
+
+impl<T> PathTrie<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, path: Path, value: T) {
+        let index = self.values.len();
+        self.root.insert(&path.0, index);
+        let specificity = path.specificity();
+        self.values.push((path, specificity, value));
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The values of the paths matching `path`, the most specific first and
+    /// those as specific as each other in the order they were inserted
+    pub fn matches<'a: 'p, 'p>(
+        &'a self,
+        path: &'p str,
+    ) -> impl Iterator<Item = (&'a T, PathMatch)> + 'p {
+        let segments = segmented(path).collect::<Vec<_>>();
+        let mut found = vec![];
+        self.root.candidates(&segments, &mut found);
+        found.sort_by_key(|&index| (Reverse(&self.values[index].1), index));
+
+        found.into_iter().filter_map(move |index| {
+            let (candidate, _, value) = &self.values[index];
+            candidate.matches(path).map(|params| (value, params))
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &T)> {
+        self.values.iter().map(|(path, _, value)| (path, value))
+    }
+}
+
+impl<T> FromIterator<(Path, T)> for PathTrie<T> {
+    fn from_iter<I: IntoIterator<Item = (Path, T)>>(iter: I) -> Self {
+        let mut trie = Self::new();
+        for (path, value) in iter {
+            trie.insert(path, value);
+        }
+        trie
+    }
+}
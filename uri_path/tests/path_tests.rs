@@ -1,4 +1,60 @@
-use uri_path::{path, Path};
+use uri_path::{path, Path, PathTrie};
+
+#[test]
+fn test_specificity() {
+    let mut paths = [
+        path!("test" / [*rest]),
+        path!("test" / param),
+        path!("test"),
+        path!("test" / [param: u32]),
+        path!("test" / "literal"),
+    ];
+    paths.sort_by_key(|path| std::cmp::Reverse(path.specificity()));
+
+    assert_eq!(
+        paths.iter().map(Path::to_string).collect::<Vec<_>>(),
+        vec![
+            "/test/literal",
+            "/test/:param",
+            "/test/:param",
+            "/test",
+            "/test/*rest"
+        ]
+    );
+    assert!(
+        path!("test" / [param: u32]).specificity()
+            > path!("test" / param).specificity()
+    );
+}
+
+#[test]
+fn test_trie() {
+    let trie = vec![
+        (path!("test" / [*rest]), "rest"),
+        (path!("test" / param), "param"),
+        (path!("test" / [param: u32]), "number"),
+        (path!("test" / "literal"), "literal"),
+        (path!("other"), "other"),
+    ]
+    .into_iter()
+    .collect::<PathTrie<_>>();
+    let matches = |path| {
+        trie.matches(path)
+            .map(|(value, _)| *value)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(matches("/test/literal"), ["literal", "param", "rest"]);
+    assert_eq!(matches("/test/123"), ["number", "param", "rest"]);
+    assert_eq!(matches("/test/abc"), ["param", "rest"]);
+    assert_eq!(matches("/test/a/b"), ["rest"]);
+    assert_eq!(matches("/test"), ["rest"]);
+    assert_eq!(matches("/other/"), ["other"]);
+    assert!(matches("/missing").is_empty());
+
+    let (_, params) = trie.matches("/test/a/b").next().unwrap();
+    assert_eq!(params["rest"], "a/b");
+}
 
 macro_rules! path_test {
     (@assertion $path:ident, matches, $expected:literal) => {
@@ -107,7 +163,46 @@ path_test! {
             {param: "value"} => "/test/value",
             {param: "value", first: "other", second: "another"} => "/test/value?first=other&second=another",
         },
-    }
+    },
+    rest(path!("test" / [*rest])) {
+        matches: ["/test", "/test/", "/test/abc", "/test/abc/whatever/"],
+        non_matches: ["/", "/other", "/other/test"],
+        params: {
+            "/test" => {rest: ""},
+            "/test/abc" => {rest: "abc"},
+            "/test/abc//whatever/" => {rest: "abc/whatever"},
+        },
+        replace: {
+            {} => None,
+            {rest: ""} => "/test",
+            {rest: "abc/whatever"} => "/test/abc/whatever",
+        },
+    },
+    segmented_rest(path!("test" / param / [*rest])) {
+        matches: ["/test/abc", "/test/abc/whatever"],
+        non_matches: ["/test"],
+        params: {
+            "/test/abc/whatever/else" => {param: "abc", rest: "whatever/else"},
+        },
+    },
+    typed(path!("test" / [n: u32])) {
+        matches: ["/test/0", "/test/123"],
+        non_matches: ["/test", "/test/-1", "/test/abc", "/test/4294967296"],
+        params: {
+            "/test/123" => {n: "123"},
+        },
+        replace: {
+            {n: "123"} => "/test/123",
+        },
+    },
+    uuid(path!("test" / [id: uuid])) {
+        matches: ["/test/67e55044-10b1-426f-9247-bb680e5fe0c8"],
+        non_matches: [
+            "/test/67e55044-10b1-426f-9247-bb680e5fe0c",
+            "/test/67e55044x10b1-426f-9247-bb680e5fe0c8",
+            "/test/67e55044-10b1-426f-9247-bb680e5fe0cg",
+        ],
+    },
 }
 
 #[cfg(feature = "regex")]